@@ -0,0 +1,66 @@
+// the tests of the modules below are left out without the test harness, not their imports
+#![allow(dead_code, unused_imports)]
+// the modules of the aggregation, the server is a bin without a lib to link against
+#[path = "../src/clock.rs"]
+mod clock;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/fixed.rs"]
+mod fixed;
+#[path = "../src/health.rs"]
+mod health;
+#[path = "../src/orderbook.rs"]
+mod orderbook;
+#[path = "../src/proto/mod.rs"]
+mod proto;
+#[path = "../src/strategy.rs"]
+mod strategy;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fixed::Fixed;
+use orderbook::{AggregatedOrderbook, Orderbook, Side};
+use std::str::FromStr;
+use std::sync::Arc;
+use strategy::Consolidate;
+
+// 5 exchanges x 20 levels per side, the prices apart by a few cents
+fn books() -> Vec<Orderbook> {
+    (0..5)
+        .map(|i| {
+            let mut ob = Orderbook::new(&format!("ex{}", i));
+            for level in 0..20 {
+                let bid = format!("{}.{:02}", 29990 - level, 17 + i);
+                let ask = format!("{}.{:02}", 30010 + level, 17 + i);
+                let amount = format!("0.{:04}", 1234 + level);
+                ob.insert(
+                    Side::Bid,
+                    Fixed::from_str(&bid).unwrap(),
+                    Fixed::from_str(&amount).unwrap(),
+                );
+                ob.insert(
+                    Side::Ask,
+                    Fixed::from_str(&ask).unwrap(),
+                    Fixed::from_str(&amount).unwrap(),
+                );
+            }
+            ob
+        })
+        .collect()
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let books = books();
+    c.bench_function("merge 5 books x 20 levels + finalize", |b| {
+        b.iter(|| {
+            let mut agg = AggregatedOrderbook::new();
+            agg.strategy = Arc::new(Consolidate);
+            for ob in books.iter() {
+                agg.merge(ob);
+            }
+            agg.finalize(10).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_merge);
+criterion_main!(benches);
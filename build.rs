@@ -1,7 +1,89 @@
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// (cargo feature, exchange, constructor) of the websocket adapters, in src/apitree/wsapi
+const WS_ADAPTERS: [(&str, &str, &str); 12] = [
+    ("exchange-binance", "binance", "binance::spot"),
+    (
+        "exchange-binance-futures",
+        "binance_futures",
+        "binance_futures::new",
+    ),
+    ("exchange-bitfinex", "bitfinex", "bitfinex::new"),
+    ("exchange-bitmex", "bitmex", "bitmex::new"),
+    ("exchange-bitstamp", "bitstamp", "bitstamp::new"),
+    ("exchange-cryptocom", "cryptocom", "cryptocom::new"),
+    ("exchange-deribit", "deribit", "deribit::new"),
+    ("exchange-gateio", "gateio", "gateio::new"),
+    ("exchange-huobi", "huobi", "huobi::new"),
+    ("exchange-kraken", "kraken", "kraken::new"),
+    ("exchange-kucoin", "kucoin", "kucoin::new"),
+    ("exchange-mexc", "mexc", "mexc::new"),
+];
+
+// (cargo feature, exchange, api) of the rest apis, in src/apitree/restapi
+const REST_APIS: [(&str, &str, &str); 6] = [
+    ("exchange-binance", "binance", "binance::API"),
+    ("exchange-bitstamp", "bitstamp", "bitstamp::API"),
+    ("exchange-btcmarkets", "btcmarkets", "btcmarkets::API"),
+    ("exchange-coinbase", "coinbase", "coinbase::API"),
+    (
+        "exchange-independentreserve",
+        "independentreserve",
+        "independentreserve::API",
+    ),
+    ("exchange-kraken", "kraken", "kraken::API"),
+];
+
+fn enabled(feature: &str) -> bool {
+    let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+    env::var_os(var).is_some()
+}
+
+// write the phf map of the enabled exchanges as `pub static {name}: phf::Map<&str, {ty}>`
+fn write_map(
+    file: &str,
+    name: &str,
+    ty: &str,
+    entries: &[(&str, &str, &str)],
+    value: impl Fn(&str) -> String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut map = phf_codegen::Map::new();
+    for (_, exchange, item) in entries.iter().filter(|(feature, ..)| enabled(feature)) {
+        map.entry(*exchange, &value(item));
+    }
+    let code = format!(
+        "pub static {}: phf::Map<&'static str, {}> = {};\n",
+        name,
+        ty,
+        map.build()
+    );
+    fs::write(Path::new(&env::var("OUT_DIR")?).join(file), code)?;
+    Ok(())
+}
+
+fn compile_protos() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .build_server(true)
         .out_dir("src/proto")
+        .file_descriptor_set_path("src/proto/aggregator_descriptor.bin")
+        .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         .compile(&["proto/aggregator.proto"], &["proto"])?;
     Ok(())
 }
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    compile_protos()?;
+    write_map(
+        "ws_apimap.rs",
+        "WS_APIMAP",
+        "NewFunc",
+        &WS_ADAPTERS,
+        |new| format!("({} as NewFunc)", new),
+    )?;
+    write_map("rest_apimap.rs", "REST_APIMAP", "Api", &REST_APIS, |api| {
+        api.to_string()
+    })?;
+    Ok(())
+}
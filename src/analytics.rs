@@ -0,0 +1,77 @@
+use crate::proto::{Level, Summary};
+
+// total notional and amount of the first `levels` distinct prices.
+// Without consolidate the same price shows up once per exchange.
+fn top(side: &[Level], levels: u32) -> (f64, f64) {
+    let (mut notional, mut amount) = (0.0, 0.0);
+    let mut prices = 0;
+    let mut last_price = None;
+    for level in side {
+        if last_price != Some(level.price) {
+            if prices == levels {
+                break;
+            }
+            prices += 1;
+            last_price = Some(level.price);
+        }
+        notional += level.price * level.amount;
+        amount += level.amount;
+    }
+    (notional, amount)
+}
+
+// fills the imbalance and the microprice of the summary from its published levels.
+pub fn apply(summary: &mut Summary, levels: u32) {
+    let (bid_notional, bid_amount) = top(&summary.bids, levels);
+    let (ask_notional, ask_amount) = top(&summary.asks, levels);
+    let total = bid_amount + ask_amount;
+    summary.imbalance = if total > 0.0 {
+        (bid_amount - ask_amount) / total
+    } else {
+        0.0
+    };
+    // heavier bids push the price towards the ask
+    summary.microprice = if bid_amount > 0.0 && ask_amount > 0.0 {
+        let bid_vwap = bid_notional / bid_amount;
+        let ask_vwap = ask_notional / ask_amount;
+        (bid_vwap * ask_amount + ask_vwap * bid_amount) / total
+    } else {
+        0.0
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(exchange: &str, price: f64, amount: f64) -> Level {
+        Level {
+            exchange: exchange.to_string(),
+            price,
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut summary = Summary {
+            bids: vec![
+                level("a", 100.0, 3.0),
+                level("b", 100.0, 1.0),
+                level("a", 99.0, 10.0),
+            ],
+            asks: vec![level("a", 101.0, 1.0), level("b", 102.0, 10.0)],
+            ..Default::default()
+        };
+        // 4 bid at 100, 1 ask at 101
+        apply(&mut summary, 1);
+        assert_eq!(summary.imbalance, 0.6);
+        assert_eq!(summary.microprice, (100.0 * 1.0 + 101.0 * 4.0) / 5.0);
+
+        summary.asks.clear();
+        apply(&mut summary, 2);
+        assert_eq!(summary.imbalance, 1.0);
+        assert_eq!(summary.microprice, 0.0);
+    }
+}
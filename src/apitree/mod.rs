@@ -1,15 +1,41 @@
 pub mod restapi;
+// before wsapi, for exchange_adapter!
+#[macro_use]
+pub mod sdk;
+pub mod seq;
 pub mod wsapi;
-use anyhow::{Context as _, Result};
+use crate::config::NetworkSetting;
+use crate::error::{Error, Result};
+use crate::ratelimit;
 
-pub fn ws(name: &str) -> Result<&'static wsapi::Api> {
+// create a new adapter for one websocket connection
+pub fn ws(name: &str) -> Result<Box<dyn wsapi::ExchangeAdapter>> {
     wsapi::WS_APIMAP
         .get(name)
-        .with_context(|| format!("Exchange {} not supported", name))
+        .map(|new| new())
+        .ok_or_else(|| Error::Unsupported(format!("Exchange {}", name)))
 }
 
 pub fn rest(name: &str) -> Result<&'static restapi::Api> {
     restapi::REST_APIMAP
         .get(name)
-        .with_context(|| format!("Exchange {} not supported", name))
+        .ok_or_else(|| Error::Unsupported(format!("Exchange {}", name)))
+}
+
+// the pairs the exchange offers, from its rest api
+pub async fn pairs(name: &str, network: &NetworkSetting) -> Result<Vec<String>> {
+    let api = rest(name)?;
+    let list = api
+        .pairs
+        .ok_or_else(|| Error::Unsupported(format!("{} doesn't list its pairs", name)))?;
+    ratelimit::acquire(name, api.rate_limit).await;
+    list(network.clone()).await
+}
+
+// the names of the exchanges with a websocket api and of those with a rest api
+pub fn supported() -> (Vec<&'static str>, Vec<&'static str>) {
+    (
+        wsapi::WS_APIMAP.keys().copied().collect(),
+        restapi::REST_APIMAP.keys().copied().collect(),
+    )
 }
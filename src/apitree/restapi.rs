@@ -1,27 +1,58 @@
-use crate::orderbook::{Orderbook, Side};
-use anyhow::{anyhow, Result};
-use bigdecimal::BigDecimal;
+#[cfg(feature = "exchange-binance")]
+mod binance;
+#[cfg(feature = "exchange-bitstamp")]
+mod bitstamp;
+#[cfg(feature = "exchange-btcmarkets")]
+mod btcmarkets;
+#[cfg(feature = "exchange-coinbase")]
+mod coinbase;
+#[cfg(feature = "exchange-independentreserve")]
+mod independentreserve;
+#[cfg(feature = "exchange-kraken")]
+mod kraken;
+
+use crate::config::NetworkSetting;
+use crate::error::Result;
+use crate::orderbook::Orderbook;
+use crate::ratelimit::RateLimit;
 use futures_util::future::Future;
-use log::info;
-use phf::phf_map;
-use serde::Deserialize;
 use std::pin::Pin;
-use std::str::FromStr;
 
 type BoxFuture = Pin<Box<dyn Future<Output = Result<Orderbook>> + Send>>;
+type PairsFuture = Pin<Box<dyn Future<Output = Result<Vec<String>>> + Send>>;
 
 pub struct Api {
     pub endpoint: &'static str,
-    pub orderbook: fn(String) -> BoxFuture,
+    // pair, network options => the book of the pair
+    pub orderbook: fn(String, NetworkSetting) -> BoxFuture,
+    // network options => the pairs offered, named as in exchange_pair_map. None => not listed.
+    pub pairs: Option<fn(NetworkSetting) -> PairsFuture>,
+    // limit of the polling requests
+    pub rate_limit: RateLimit,
 }
 
-pub static REST_APIMAP: phf::Map<&'static str, Api> = phf_map! {
-    "btcmarkets" => Api {
-        endpoint: "https://api.btcmarkets.net",
-        orderbook: |s| Box::pin(btcmarkets_orderbook(s)),
-    }
-};
+// the apis of the exchanges enabled by their cargo feature, see build.rs
+include!(concat!(env!("OUT_DIR"), "/rest_apimap.rs"));
+
+// the quote currencies, to split the pairs written without separator. ex: btcaud
+#[cfg(any(feature = "exchange-coinbase", feature = "exchange-independentreserve"))]
+const QUOTES: [&str; 9] = [
+    "usdt", "usdc", "usd", "aud", "nzd", "sgd", "eur", "gbp", "btc",
+];
 
-async fn btcmarkets_orderbook(pair: String) -> Result<Orderbook> {
-    return Err(anyhow!("not implemented"));
+// the lowercase (base, quote) of a pair: btcaud, btc/aud, BTC-AUD => (btc, aud)
+#[cfg(any(feature = "exchange-coinbase", feature = "exchange-independentreserve"))]
+fn split_pair(exchange: &str, pair: &str) -> Result<(String, String)> {
+    let pair = pair.to_lowercase();
+    let (base, quote) = match pair.split_once(['/', '-', '_']) {
+        Some(split) => split,
+        None => QUOTES
+            .iter()
+            .filter_map(|quote| pair.strip_suffix(quote).map(|base| (base, *quote)))
+            .find(|(base, _)| !base.is_empty())
+            .ok_or_else(|| {
+                crate::error::Error::Unsupported(format!("{} pair {}", exchange, pair))
+            })?,
+    };
+    Ok((base.to_string(), quote.to_string()))
 }
@@ -0,0 +1,65 @@
+use super::Api;
+use crate::config::NetworkSetting;
+use crate::error::Result;
+use crate::fixed::Fixed;
+use crate::net;
+use crate::orderbook::{Orderbook, Side};
+use crate::ratelimit::RateLimit;
+use serde::Deserialize;
+use std::str::FromStr;
+
+pub const API: Api = Api {
+    endpoint: "https://api.binance.com",
+    orderbook: |s, n| Box::pin(orderbook(s, n)),
+    pairs: None,
+    // 6000 request weight per minute, 5 for 100 levels
+    rate_limit: RateLimit {
+        burst: 10,
+        per_sec: 10.0,
+    },
+};
+
+fn book(raw: &str) -> Result<Orderbook> {
+    #[derive(Deserialize, Debug)]
+    struct Book<'a> {
+        // [price, quantity]
+        #[serde(borrow)]
+        bids: Vec<[&'a str; 2]>,
+        #[serde(borrow)]
+        asks: Vec<[&'a str; 2]>,
+    }
+    let book: Book = serde_json::from_str(raw)?;
+    let mut ob = Orderbook::new("binance");
+    for (side, levels) in [(Side::Bid, book.bids), (Side::Ask, book.asks)] {
+        for [price, quantity] in levels {
+            ob.insert(side, Fixed::from_str(price)?, Fixed::from_str(quantity)?);
+        }
+    }
+    Ok(ob)
+}
+
+// the symbols are uppercase: btcusdt => BTCUSDT
+async fn orderbook(pair: String, network: NetworkSetting) -> Result<Orderbook> {
+    let url = format!(
+        "https://api.binance.com/api/v3/depth?symbol={}&limit=100",
+        pair.to_uppercase()
+    );
+    let raw = net::http_get(&url, &network).await?;
+    book(&raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binance() {
+        let raw = r#"{"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],
+            "asks":[["4.00000200","12.00000000"],["4.00000300","1.00000000"]]}"#;
+        let ob = book(raw).unwrap();
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        assert_eq!(ob.bid.get(&fixed("4")), Some(&fixed("431")));
+        assert_eq!(ob.ask.len(), 2);
+        assert_eq!(ob.ask.get(&fixed("4.000002")), Some(&fixed("12")));
+    }
+}
@@ -0,0 +1,98 @@
+use super::Api;
+use crate::config::NetworkSetting;
+use crate::error::Result;
+use crate::fixed::Fixed;
+use crate::net;
+use crate::orderbook::{Orderbook, Side};
+use crate::ratelimit::RateLimit;
+use serde::Deserialize;
+use std::str::FromStr;
+
+pub const API: Api = Api {
+    endpoint: "https://www.bitstamp.net",
+    orderbook: |s, n| Box::pin(orderbook(s, n)),
+    pairs: Some(|n| Box::pin(pairs(n))),
+    // 400 requests per second, shared with the websocket snapshots
+    rate_limit: RateLimit {
+        burst: 10,
+        per_sec: 10.0,
+    },
+};
+
+fn book(raw: &str) -> Result<Orderbook> {
+    #[derive(Deserialize, Debug)]
+    struct Book<'a> {
+        microtimestamp: &'a str,
+        // [price, amount]
+        #[serde(borrow)]
+        bids: Vec<[&'a str; 2]>,
+        #[serde(borrow)]
+        asks: Vec<[&'a str; 2]>,
+    }
+    let book: Book = serde_json::from_str(raw)?;
+    let mut ob = Orderbook::new("bitstamp");
+    for (side, levels) in [(Side::Bid, book.bids), (Side::Ask, book.asks)] {
+        for [price, amount] in levels {
+            ob.insert(side, Fixed::from_str(price)?, Fixed::from_str(amount)?);
+        }
+    }
+    ob.exchange_ts = book
+        .microtimestamp
+        .parse::<u128>()
+        .ok()
+        .map(|micros| micros / 1000);
+    Ok(ob)
+}
+
+// the url symbols of the pairs trading, ex: btcusd
+fn symbols(raw: &str) -> Result<Vec<String>> {
+    #[derive(Deserialize, Debug)]
+    struct Info<'a> {
+        url_symbol: &'a str,
+        trading: &'a str,
+    }
+    let infos: Vec<Info> = serde_json::from_str(raw)?;
+    Ok(infos
+        .iter()
+        .filter(|info| info.trading == "Enabled")
+        .map(|info| info.url_symbol.to_string())
+        .collect())
+}
+
+async fn pairs(network: NetworkSetting) -> Result<Vec<String>> {
+    let raw = net::http_get(
+        "https://www.bitstamp.net/api/v2/trading-pairs-info/",
+        &network,
+    )
+    .await?;
+    symbols(&raw)
+}
+
+async fn orderbook(pair: String, network: NetworkSetting) -> Result<Orderbook> {
+    let url = format!("https://www.bitstamp.net/api/v2/order_book/{}/", pair);
+    let raw = net::http_get(&url, &network).await?;
+    book(&raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitstamp() {
+        let raw = r#"{"timestamp":"1700000000","microtimestamp":"1700000000123456",
+            "bids":[["37000","0.5"],["36999.5","1"]],"asks":[["37001","0.25"]]}"#;
+        let ob = book(raw).unwrap();
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        assert_eq!(ob.bid.len(), 2);
+        assert_eq!(ob.ask.get(&fixed("37001")), Some(&fixed("0.25")));
+        assert_eq!(ob.exchange_ts, Some(1700000000123));
+    }
+
+    #[test]
+    fn test_symbols() {
+        let raw = r#"[{"name":"BTC/USD","url_symbol":"btcusd","base_decimals":8,"trading":"Enabled"},
+            {"name":"XYZ/USD","url_symbol":"xyzusd","base_decimals":8,"trading":"Disabled"}]"#;
+        assert_eq!(symbols(raw).unwrap(), vec!["btcusd"]);
+    }
+}
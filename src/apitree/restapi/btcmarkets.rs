@@ -0,0 +1,20 @@
+use super::Api;
+use crate::config::NetworkSetting;
+use crate::error::{Error, Result};
+use crate::orderbook::Orderbook;
+use crate::ratelimit::RateLimit;
+
+pub const API: Api = Api {
+    endpoint: "https://api.btcmarkets.net",
+    orderbook: |s, n| Box::pin(orderbook(s, n)),
+    pairs: None,
+    // 50 requests per 10 seconds
+    rate_limit: RateLimit {
+        burst: 5,
+        per_sec: 5.0,
+    },
+};
+
+async fn orderbook(_pair: String, _network: NetworkSetting) -> Result<Orderbook> {
+    Err(Error::Unsupported("btcmarkets orderbook".to_string()))
+}
@@ -0,0 +1,78 @@
+use super::Api;
+use crate::config::NetworkSetting;
+use crate::error::Result;
+use crate::fixed::Fixed;
+use crate::net;
+use crate::orderbook::{Orderbook, Side};
+use crate::ratelimit::RateLimit;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::str::FromStr;
+
+pub const API: Api = Api {
+    endpoint: "https://api.exchange.coinbase.com",
+    orderbook: |s, n| Box::pin(orderbook(s, n)),
+    pairs: None,
+    // 10 requests per second on the public endpoints
+    rate_limit: RateLimit {
+        burst: 10,
+        per_sec: 10.0,
+    },
+};
+
+// the product id of a pair: btcusd, btc/usd => BTC-USD
+fn product(pair: &str) -> Result<String> {
+    let (base, quote) = super::split_pair("coinbase", pair)?;
+    Ok(format!("{}-{}", base, quote).to_uppercase())
+}
+
+fn book(raw: &str) -> Result<Orderbook> {
+    // [price, size, number of orders]
+    type Entry<'a> = (&'a str, &'a str, IgnoredAny);
+    #[derive(Deserialize, Debug)]
+    struct Book<'a> {
+        #[serde(borrow)]
+        bids: Vec<Entry<'a>>,
+        #[serde(borrow)]
+        asks: Vec<Entry<'a>>,
+    }
+    let book: Book = serde_json::from_str(raw)?;
+    let mut ob = Orderbook::new("coinbase");
+    for (side, entries) in [(Side::Bid, book.bids), (Side::Ask, book.asks)] {
+        for (price, size, _) in entries {
+            ob.insert(side, Fixed::from_str(price)?, Fixed::from_str(size)?);
+        }
+    }
+    Ok(ob)
+}
+
+// the level 2 book, aggregated by price
+async fn orderbook(pair: String, network: NetworkSetting) -> Result<Orderbook> {
+    let url = format!(
+        "https://api.exchange.coinbase.com/products/{}/book?level=2",
+        product(&pair)?
+    );
+    let raw = net::http_get(&url, &network).await?;
+    book(&raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coinbase() {
+        assert_eq!(product("btcusd").unwrap(), "BTC-USD");
+        assert_eq!(product("eth/btc").unwrap(), "ETH-BTC");
+        assert_eq!(product("SOL-USDC").unwrap(), "SOL-USDC");
+
+        let raw = r#"{"bids":[["30000.01","0.5",3],["29999.5","1.25",1]],
+            "asks":[["30000.02","0.1",2]],"sequence":3,"auction_mode":false,"auction":null,
+            "time":"2023-10-01T00:00:00.000000Z"}"#;
+        let ob = book(raw).unwrap();
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        assert_eq!(ob.bid.len(), 2);
+        assert_eq!(ob.bid.get(&fixed("30000.01")), Some(&fixed("0.5")));
+        assert_eq!(ob.ask.get(&fixed("30000.02")), Some(&fixed("0.1")));
+    }
+}
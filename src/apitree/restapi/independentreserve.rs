@@ -0,0 +1,109 @@
+use super::Api;
+use crate::config::NetworkSetting;
+use crate::error::Result;
+use crate::fixed::Fixed;
+use crate::net;
+use crate::orderbook::{Orderbook, Side};
+use crate::ratelimit::RateLimit;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+pub const API: Api = Api {
+    endpoint: "https://api.independentreserve.com",
+    orderbook: |s, n| Box::pin(orderbook(s, n)),
+    pairs: None,
+    // 1 request per second on the public methods
+    rate_limit: RateLimit {
+        burst: 1,
+        per_sec: 1.0,
+    },
+};
+
+// the (primary, secondary) currency codes of a pair: btcaud, xbt/aud, XBT-AUD => (Xbt, Aud)
+fn codes(pair: &str) -> Result<(String, String)> {
+    let (primary, secondary) = super::split_pair("independentreserve", pair)?;
+    // the bitcoin is xbt
+    let primary = if primary == "btc" { "xbt" } else { &primary };
+    let capitalize = |code: &str| {
+        let mut chars = code.chars();
+        chars.next().map_or(String::new(), |first| {
+            first.to_uppercase().chain(chars).collect()
+        })
+    };
+    Ok((capitalize(primary), capitalize(&secondary)))
+}
+
+fn book(raw: &str) -> Result<Orderbook> {
+    #[derive(Deserialize, Debug)]
+    struct Order<'a> {
+        // the numbers are read as written, not through f64
+        #[serde(rename = "Price", borrow)]
+        price: &'a RawValue,
+        #[serde(rename = "Volume", borrow)]
+        volume: &'a RawValue,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Book<'a> {
+        #[serde(rename = "BuyOrders", borrow)]
+        buy_orders: Vec<Order<'a>>,
+        #[serde(rename = "SellOrders", borrow)]
+        sell_orders: Vec<Order<'a>>,
+    }
+    let book: Book = serde_json::from_str(raw)?;
+    let mut ob = Orderbook::new("independentreserve");
+    for (side, orders) in [(Side::Bid, book.buy_orders), (Side::Ask, book.sell_orders)] {
+        // the orders of a price are listed one by one
+        let mut levels = BTreeMap::<Fixed, Fixed>::new();
+        for order in orders {
+            let price = Fixed::from_str(order.price.get())?;
+            let volume = Fixed::from_str(order.volume.get())?;
+            *levels.entry(price).or_insert(Fixed::ZERO) += &volume;
+        }
+        for (price, volume) in levels {
+            ob.insert(side, price, volume);
+        }
+    }
+    Ok(ob)
+}
+
+async fn orderbook(pair: String, network: NetworkSetting) -> Result<Orderbook> {
+    let (primary, secondary) = codes(&pair)?;
+    let url = format!(
+        "https://api.independentreserve.com/Public/GetOrderBook?primaryCurrencyCode={}&secondaryCurrencyCode={}",
+        primary, secondary
+    );
+    let raw = net::http_get(&url, &network).await?;
+    book(&raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_independentreserve() {
+        let codes_of = |pair: &str| codes(pair).unwrap();
+        assert_eq!(codes_of("btcaud"), ("Xbt".to_string(), "Aud".to_string()));
+        assert_eq!(codes_of("Xbt/Usd"), ("Xbt".to_string(), "Usd".to_string()));
+        assert_eq!(
+            codes_of("ETH-USDT"),
+            ("Eth".to_string(), "Usdt".to_string())
+        );
+        assert!(codes("aud").is_err());
+
+        let raw = r#"{"BuyOrders":[{"OrderType":"LimitBid","Price":497.02,"Volume":0.01},
+            {"OrderType":"LimitBid","Price":497.02,"Volume":0.02},
+            {"OrderType":"LimitBid","Price":490,"Volume":1E-05}],
+            "SellOrders":[{"OrderType":"LimitOffer","Price":500.1,"Volume":1.5}],
+            "CreatedTimestampUtc":"2014-08-05T06:42:11.3032208Z",
+            "PrimaryCurrencyCode":"Xbt","SecondaryCurrencyCode":"Usd"}"#;
+        let ob = book(raw).unwrap();
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        assert_eq!(ob.bid.len(), 2);
+        assert_eq!(ob.bid.get(&fixed("497.02")), Some(&fixed("0.03")));
+        assert_eq!(ob.bid.get(&fixed("490")), Some(&fixed("0.00001")));
+        assert_eq!(ob.ask.get(&fixed("500.1")), Some(&fixed("1.5")));
+    }
+}
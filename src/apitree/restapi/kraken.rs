@@ -0,0 +1,132 @@
+use super::Api;
+use crate::config::NetworkSetting;
+use crate::error::{Error, Result};
+use crate::fixed::Fixed;
+use crate::net;
+use crate::orderbook::{Orderbook, Side};
+use crate::ratelimit::RateLimit;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub const API: Api = Api {
+    endpoint: "https://api.kraken.com",
+    orderbook: |s, n| Box::pin(orderbook(s, n)),
+    pairs: Some(|n| Box::pin(pairs(n))),
+    // the public endpoints allow about 1 request per second
+    rate_limit: RateLimit {
+        burst: 1,
+        per_sec: 1.0,
+    },
+};
+
+fn book(raw: &str) -> Result<Orderbook> {
+    // [price, volume, timestamp]
+    type Entry<'a> = (&'a str, &'a str, IgnoredAny);
+    #[derive(Deserialize, Debug)]
+    struct Book<'a> {
+        #[serde(borrow)]
+        bids: Vec<Entry<'a>>,
+        #[serde(borrow)]
+        asks: Vec<Entry<'a>>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Response<'a> {
+        error: Vec<String>,
+        // keyed by the kraken name of the pair, ex: XXBTZUSD for XBT/USD
+        #[serde(borrow, default)]
+        result: HashMap<&'a str, Book<'a>>,
+    }
+    let response: Response = serde_json::from_str(raw)?;
+    if !response.error.is_empty() {
+        return Err(Error::ParseError(format!(
+            "kraken depth: {}",
+            response.error.join(", ")
+        )));
+    }
+    let Some(book) = response.result.into_values().next() else {
+        return Err(Error::ParseError(format!(
+            "kraken depth without book: {}",
+            raw
+        )));
+    };
+    let mut ob = Orderbook::new("kraken");
+    for (side, entries) in [(Side::Bid, book.bids), (Side::Ask, book.asks)] {
+        for (price, volume, _) in entries {
+            ob.insert(side, Fixed::from_str(price)?, Fixed::from_str(volume)?);
+        }
+    }
+    Ok(ob)
+}
+
+// the websocket names of the pairs, ex: XBT/USD. The dark pool pairs have none.
+fn wsnames(raw: &str) -> Result<Vec<String>> {
+    #[derive(Deserialize, Debug)]
+    struct Pair {
+        wsname: Option<String>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Response {
+        error: Vec<String>,
+        #[serde(default)]
+        result: HashMap<String, Pair>,
+    }
+    let response: Response = serde_json::from_str(raw)?;
+    if !response.error.is_empty() {
+        return Err(Error::ParseError(format!(
+            "kraken asset pairs: {}",
+            response.error.join(", ")
+        )));
+    }
+    let mut names: Vec<String> = response
+        .result
+        .into_values()
+        .filter_map(|pair| pair.wsname)
+        .collect();
+    names.sort_unstable();
+    Ok(names)
+}
+
+async fn pairs(network: NetworkSetting) -> Result<Vec<String>> {
+    let raw = net::http_get("https://api.kraken.com/0/public/AssetPairs", &network).await?;
+    wsnames(&raw)
+}
+
+// the websocket names of the pairs, XBT/USD, are accepted without the slash
+async fn orderbook(pair: String, network: NetworkSetting) -> Result<Orderbook> {
+    let url = format!(
+        "https://api.kraken.com/0/public/Depth?pair={}&count=100",
+        pair.replace('/', "")
+    );
+    let raw = net::http_get(&url, &network).await?;
+    book(&raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kraken() {
+        let raw = r#"{"error":[],"result":{"XXBTZUSD":{
+            "asks":[["37001.10000","0.500",1700000000]],
+            "bids":[["37000.00000","1.250",1700000000],["36999.90000","2.000",1699999999]]}}}"#;
+        let ob = book(raw).unwrap();
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        assert_eq!(ob.bid.len(), 2);
+        assert_eq!(ob.bid.get(&fixed("37000")), Some(&fixed("1.25")));
+        assert_eq!(ob.ask.get(&fixed("37001.1")), Some(&fixed("0.5")));
+
+        assert!(book(r#"{"error":["EQuery:Unknown asset pair"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_wsnames() {
+        let raw = r#"{"error":[],"result":{
+            "XXBTZUSD":{"altname":"XBTUSD","wsname":"XBT/USD","base":"XXBT","quote":"ZUSD"},
+            "XETHZUSD":{"altname":"ETHUSD","wsname":"ETH/USD","base":"XETH","quote":"ZUSD"},
+            "XXBTZUSD.d":{"altname":"XBTUSD.d","base":"XXBT","quote":"ZUSD"}}}"#;
+        assert_eq!(wsnames(raw).unwrap(), vec!["ETH/USD", "XBT/USD"]);
+    }
+}
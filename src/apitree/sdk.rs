@@ -0,0 +1,348 @@
+// The pieces a websocket adapter is made of: the parsing of the price levels, the books of
+// the incremental feeds, and exchange_adapter! writing the ExchangeAdapter around a parse
+// function. A new exchange with a plain protocol is its messages and its parse function,
+// see wsapi/cryptocom.rs.
+use super::wsapi::ParsedEvent;
+use crate::error::{Error, Result};
+use crate::fixed::Fixed;
+use crate::orderbook::{Orderbook, Side};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::str::FromStr;
+
+// one level of a message, as the exchange writes it: [price, amount], or longer arrays
+// starting with them, ex: [price, amount, number of orders]. The numbers are strings, or
+// json numbers, ex: huobi.
+pub trait Level: Debug {
+    // (price, amount). None => malformed
+    fn parts(&self) -> Option<(Cow<'_, str>, Cow<'_, str>)>;
+}
+
+impl Level for [&str; 2] {
+    fn parts(&self) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+        Some((self[0].into(), self[1].into()))
+    }
+}
+
+impl Level for Vec<&str> {
+    fn parts(&self) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+        match &self[..] {
+            [price, amount, ..] => Some(((*price).into(), (*amount).into())),
+            _ => None,
+        }
+    }
+}
+
+impl Level for (&str, &str) {
+    fn parts(&self) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+        Some((self.0.into(), self.1.into()))
+    }
+}
+
+impl Level for [serde_json::Number; 2] {
+    fn parts(&self) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+        Some((self[0].to_string().into(), self[1].to_string().into()))
+    }
+}
+
+// insert the levels on a side of the book. An amount of 0 removes the level.
+pub fn apply<L: Level>(ob: &mut Orderbook, side: Side, levels: Vec<L>) -> Result<()> {
+    for level in levels {
+        let (price, amount) = level
+            .parts()
+            .ok_or_else(|| Error::ParseError(format!("{} malformed level {:?}", ob.name, level)))?;
+        ob.insert(side, Fixed::from_str(&price)?, Fixed::from_str(&amount)?);
+    }
+    Ok(())
+}
+
+// replace the side of the book with the levels of a snapshot, see Orderbook::replace
+pub fn replace<L: Level>(ob: &mut Orderbook, side: Side, levels: Vec<L>) -> Result<()> {
+    let mut snapshot = BTreeMap::new();
+    for level in levels {
+        let (price, amount) = level
+            .parts()
+            .ok_or_else(|| Error::ParseError(format!("{} malformed level {:?}", ob.name, level)))?;
+        let amount = Fixed::from_str(&amount)?;
+        if !amount.is_zero() {
+            snapshot.insert(Fixed::from_str(&price)?, amount);
+        }
+    }
+    ob.replace(side, snapshot);
+    Ok(())
+}
+
+// the book of a message holding the whole top of the book
+pub fn book<L: Level>(exchange: &str, pair: &str, bids: Vec<L>, asks: Vec<L>) -> Result<Orderbook> {
+    let mut ob = Orderbook::with_pair(exchange, pair);
+    apply(&mut ob, Side::Bid, bids)?;
+    apply(&mut ob, Side::Ask, asks)?;
+    Ok(ob)
+}
+
+// the books of the pairs of an incremental feed: a snapshot replaces the book of the pair,
+// a delta is applied on it.
+pub struct Books {
+    exchange: &'static str,
+    books: HashMap<String, Orderbook>,
+}
+
+impl Books {
+    pub fn new(exchange: &'static str) -> Books {
+        Books {
+            exchange,
+            books: HashMap::new(),
+        }
+    }
+
+    pub fn snapshot<L: Level>(
+        &mut self,
+        pair: &str,
+        bids: Vec<L>,
+        asks: Vec<L>,
+    ) -> Result<ParsedEvent> {
+        let ob = book(self.exchange, pair, bids, asks)?;
+        self.books.insert(pair.to_string(), ob.clone());
+        Ok(ParsedEvent::Book(ob))
+    }
+
+    // a delta before the snapshot, or half applied, leaves a hole in the book: Desync
+    pub fn delta<L: Level>(
+        &mut self,
+        pair: &str,
+        bids: Vec<L>,
+        asks: Vec<L>,
+    ) -> Result<ParsedEvent> {
+        let exchange = self.exchange;
+        let ob = self
+            .books
+            .get_mut(pair)
+            .ok_or_else(|| Error::Desync(format!("{} has no snapshot of {}", exchange, pair)))?;
+        apply(ob, Side::Bid, bids)
+            .and_then(|_| apply(ob, Side::Ask, asks))
+            .map_err(|e| Error::Desync(format!("{} {}: {}", exchange, pair, e)))?;
+        Ok(ParsedEvent::Book(ob.clone()))
+    }
+
+    pub fn pairs(&self) -> Vec<String> {
+        self.books.keys().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.books.clear();
+    }
+}
+
+// the ExchangeAdapter of an exchange subscribing each pair with templated messages, see
+// render, and turning the frames into books with
+// fn parse(books: &mut Books, raw: &str) -> Result<ParsedEvent>.
+// The messages are rendered with the pair, passed through fn pair(&str) -> String if given,
+// and the level rounded up to one of the depths, see supported_depth. The frames are decoded
+// with fn decode(&[u8]) -> Result<String> if given, as text otherwise. depths, pair,
+// heartbeat and decode are optional, in this order. A protocol needing more, ex: a token
+// before connecting or a snapshot after subscribing, implements ExchangeAdapter itself.
+//
+// exchange_adapter! {
+//     adapter: Okx,
+//     exchange: "okx",
+//     endpoint: "wss://ws.okx.com:8443/ws/v5/public",
+//     depths: [5, 400],
+//     subscribe: [r#"{{"op":"subscribe","args":[{{"channel":"books","instId":"{}"}}]}}"#],
+//     unsubscribe: [r#"{{"op":"unsubscribe","args":[{{"channel":"books","instId":"{}"}}]}}"#],
+//     heartbeat: (25, "ping"),
+//     parse: parse,
+// }
+// unused by the builds without the exchanges using it
+#[allow(unused_macros)]
+macro_rules! exchange_adapter {
+    (
+        adapter: $adapter:ident,
+        exchange: $exchange:literal,
+        endpoint: $endpoint:literal,
+        $(depths: [$($depth:literal),* $(,)?],)?
+        $(pair: $pair:path,)?
+        subscribe: [$($subscribe:literal),* $(,)?],
+        unsubscribe: [$($unsubscribe:literal),* $(,)?],
+        $(heartbeat: ($every:literal, $ping:literal),)?
+        $(decode: $decode:path,)?
+        parse: $parse:path $(,)?
+    ) => {
+        pub struct $adapter {
+            books: $crate::apitree::sdk::Books,
+        }
+
+        pub fn new() -> Box<dyn $crate::apitree::wsapi::ExchangeAdapter> {
+            Box::new($adapter {
+                books: $crate::apitree::sdk::Books::new($exchange),
+            })
+        }
+
+        // the depths the subscriptions accept
+        const DEPTHS: &[u32] = &[$($($depth),*)?];
+
+        impl $crate::apitree::wsapi::ExchangeAdapter for $adapter {
+            fn endpoint(&self) -> &'static str {
+                $endpoint
+            }
+
+            fn supported_depths(&self) -> &'static [u32] {
+                DEPTHS
+            }
+
+            fn subscribe_messages(
+                &self,
+                pair: &str,
+                level: u32,
+            ) -> $crate::error::Result<Vec<String>> {
+                $crate::apitree::wsapi::render(
+                    &[$($subscribe),*],
+                    &exchange_adapter!(@pair pair $(, $pair)?),
+                    $crate::apitree::wsapi::supported_depth(DEPTHS, level),
+                )
+            }
+
+            fn unsubscribe_messages(
+                &self,
+                pair: &str,
+                level: u32,
+            ) -> $crate::error::Result<Vec<String>> {
+                $crate::apitree::wsapi::render(
+                    &[$($unsubscribe),*],
+                    &exchange_adapter!(@pair pair $(, $pair)?),
+                    $crate::apitree::wsapi::supported_depth(DEPTHS, level),
+                )
+            }
+
+            fn heartbeat(&self) -> Option<(u64, String)> {
+                exchange_adapter!(@heartbeat $($every, $ping)?)
+            }
+
+            fn decode(&self, raw: &[u8]) -> $crate::error::Result<String> {
+                exchange_adapter!(@decode raw $(, $decode)?)
+            }
+
+            fn parse(&mut self, raw: &str) -> $crate::error::Result<$crate::apitree::wsapi::ParsedEvent> {
+                $parse(&mut self.books, raw)
+            }
+
+            fn reset(&mut self) {
+                self.books.clear();
+            }
+        }
+    };
+    (@pair $pair:ident) => {
+        std::borrow::Cow::Borrowed($pair)
+    };
+    (@pair $pair:ident, $f:path) => {
+        std::borrow::Cow::<str>::Owned($f($pair))
+    };
+    (@decode $raw:ident) => {
+        Ok(std::str::from_utf8($raw)?.to_string())
+    };
+    (@decode $raw:ident, $f:path) => {
+        $f($raw)
+    };
+    (@heartbeat) => {
+        None
+    };
+    (@heartbeat $every:literal, $ping:literal) => {
+        Some(($every, $ping.to_string()))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(books: &mut Books, raw: &str) -> Result<ParsedEvent> {
+        let (kind, pair) = raw.split_once(' ').unwrap_or((raw, ""));
+        match kind {
+            "snapshot" => books.snapshot(pair, vec![["100", "1"], ["99", "2"]], vec![["101", "1"]]),
+            "delta" => books.delta(pair, vec![["100", "0"]], vec![["102", "3"]]),
+            "bad" => books.delta(pair, vec![vec!["100"]], vec![]),
+            _ => Ok(ParsedEvent::Ignore),
+        }
+    }
+
+    exchange_adapter! {
+        adapter: Sample,
+        exchange: "sample",
+        endpoint: "wss://sample.test/ws",
+        depths: [10, 50],
+        subscribe: [r#"{{"sub":"{}","depth":{}}}"#],
+        unsubscribe: [r#"{{"unsub":"{}"}}"#],
+        heartbeat: (20, "ping"),
+        parse: parse,
+    }
+
+    #[test]
+    fn test_levels() {
+        let ob = book("A", "X", vec![vec!["100", "1", "3"]], vec![]).unwrap();
+        assert_eq!(ob.bid.get(&Fixed::from(100)), Some(&Fixed::from(1)));
+        assert!(book("A", "X", vec![vec!["100"]], vec![]).is_err());
+        assert!(book("A", "X", vec![("100", "x")], vec![]).is_err());
+    }
+
+    #[test]
+    fn test_exchange_adapter() {
+        let mut api = new();
+        assert_eq!(api.endpoint(), "wss://sample.test/ws");
+        assert_eq!(
+            api.subscribe_messages("BTCUSD", 20).unwrap(),
+            vec![r#"{"sub":"BTCUSD","depth":50}"#]
+        );
+        assert_eq!(
+            api.unsubscribe_messages("BTCUSD", 20).unwrap(),
+            vec![r#"{"unsub":"BTCUSD"}"#]
+        );
+        assert_eq!(api.heartbeat(), Some((20, "ping".to_string())));
+        // the deltas wait for the snapshot
+        assert!(matches!(api.parse("delta BTCUSD"), Err(Error::Desync(_))));
+        let ob = api.parse("snapshot BTCUSD").unwrap().book().unwrap();
+        assert_eq!((ob.name.as_str(), ob.bid.len()), ("sample", 2));
+        let ob = api.parse("delta BTCUSD").unwrap().book().unwrap();
+        assert_eq!((ob.bid.len(), ob.ask.len()), (1, 2));
+        assert!(matches!(api.parse("bad BTCUSD"), Err(Error::Desync(_))));
+        api.reset();
+        assert!(api.parse("delta BTCUSD").is_err());
+        assert_eq!(api.parse("pong").unwrap(), ParsedEvent::Ignore);
+    }
+
+    // the optional pair and decode
+    mod upper {
+        use super::*;
+
+        fn upper(pair: &str) -> String {
+            pair.to_uppercase()
+        }
+
+        fn decode(raw: &[u8]) -> Result<String> {
+            Ok(raw.iter().rev().map(|b| *b as char).collect())
+        }
+
+        exchange_adapter! {
+            adapter: Upper,
+            exchange: "upper",
+            endpoint: "wss://upper.test/ws",
+            pair: upper,
+            subscribe: [r#"{{"sub":"{}","depth":{}}}"#],
+            unsubscribe: [r#"{{"unsub":"{}"}}"#],
+            decode: decode,
+            parse: parse,
+        }
+
+        #[test]
+        fn test_exchange_adapter_options() {
+            let api = new();
+            assert_eq!(
+                api.subscribe_messages("btcusd", 20).unwrap(),
+                vec![r#"{"sub":"BTCUSD","depth":20}"#]
+            );
+            assert_eq!(api.heartbeat(), None);
+            assert_eq!(api.decode(b"gnop").unwrap(), "pong");
+            // without decode, the frames are text
+            assert!(super::new().decode(&[0xff, 0xfe]).is_err());
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+
+// the update ids of the incremental feeds of one exchange, per pair. An update not
+// following the last applied one is a Desync: the executor clears the books and
+// resubscribes instead of applying it on a book with a hole.
+pub struct SeqTracker {
+    exchange: &'static str,
+    // pair => id of the last applied update, or of the snapshot
+    last: HashMap<String, u64>,
+}
+
+impl SeqTracker {
+    pub fn new(exchange: &'static str) -> SeqTracker {
+        SeqTracker {
+            exchange,
+            last: HashMap::new(),
+        }
+    }
+
+    // the id of the snapshot the updates are applied on.
+    pub fn baseline(&mut self, pair: &str, id: u64) {
+        self.last.insert(pair.to_string(), id);
+    }
+
+    fn last(&self, pair: &str) -> Result<u64> {
+        self.last
+            .get(pair)
+            .copied()
+            .ok_or_else(|| Error::Desync(format!("{} has no snapshot of {}", self.exchange, pair)))
+    }
+
+    // an update covering the ids first..=last. false => already in the book, skip it.
+    pub fn range(&mut self, pair: &str, first: u64, last: u64) -> Result<bool> {
+        let id = self.last(pair)?;
+        if last <= id {
+            return Ok(false);
+        }
+        if first > id + 1 {
+            return Err(Error::Desync(format!(
+                "{} {} missed updates {}..{}",
+                self.exchange,
+                pair,
+                id + 1,
+                first
+            )));
+        }
+        self.last.insert(pair.to_string(), last);
+        Ok(true)
+    }
+
+    // an update carrying the id of the update before it.
+    pub fn chain(&mut self, pair: &str, prev: Option<u64>, id: u64) -> Result<()> {
+        let last = self.last(pair)?;
+        if prev != Some(last) {
+            return Err(Error::Desync(format!(
+                "{} {} expects {}, got {:?}",
+                self.exchange, pair, last, prev
+            )));
+        }
+        self.last.insert(pair.to_string(), id);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.last.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range() {
+        let mut seq = SeqTracker::new("gateio");
+        assert!(matches!(seq.range("BTC_USDT", 1, 2), Err(Error::Desync(_))));
+        seq.baseline("BTC_USDT", 10);
+        assert!(!seq.range("BTC_USDT", 5, 10).unwrap());
+        assert!(seq.range("BTC_USDT", 9, 12).unwrap());
+        assert!(seq.range("BTC_USDT", 13, 13).unwrap());
+        assert!(matches!(
+            seq.range("BTC_USDT", 15, 16),
+            Err(Error::Desync(_))
+        ));
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut seq = SeqTracker::new("deribit");
+        seq.baseline("BTC-PERPETUAL", 1);
+        seq.chain("BTC-PERPETUAL", Some(1), 5).unwrap();
+        assert!(matches!(
+            seq.chain("BTC-PERPETUAL", Some(4), 6),
+            Err(Error::Desync(_))
+        ));
+        seq.reset();
+        assert!(matches!(
+            seq.chain("BTC-PERPETUAL", Some(5), 6),
+            Err(Error::Desync(_))
+        ));
+    }
+}
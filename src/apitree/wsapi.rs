@@ -1,375 +1,152 @@
-use crate::orderbook::{Orderbook, Side};
-use anyhow::bail;
-use anyhow::{anyhow, Result};
-use bigdecimal::BigDecimal;
+#[cfg(feature = "exchange-binance")]
+mod binance;
+#[cfg(feature = "exchange-binance-futures")]
+mod binance_futures;
+#[cfg(feature = "exchange-bitfinex")]
+mod bitfinex;
+#[cfg(feature = "exchange-bitmex")]
+mod bitmex;
+#[cfg(feature = "exchange-bitstamp")]
+mod bitstamp;
+#[cfg(feature = "exchange-cryptocom")]
+mod cryptocom;
+#[cfg(feature = "exchange-deribit")]
+mod deribit;
+#[cfg(feature = "exchange-gateio")]
+mod gateio;
+#[cfg(feature = "exchange-huobi")]
+mod huobi;
+#[cfg(feature = "exchange-kraken")]
+mod kraken;
+#[cfg(feature = "exchange-kucoin")]
+mod kucoin;
+#[cfg(feature = "exchange-mexc")]
+mod mexc;
+
+use crate::config::NetworkSetting;
+use crate::error::{Error, Result};
+use crate::orderbook::Orderbook;
+use crate::ratelimit::RateLimit;
+use async_trait::async_trait;
 use formatx::formatx;
-use once_cell::sync::Lazy;
-use phf::phf_map;
-use serde::Deserialize;
-use serde_json::Value;
-use std::collections::HashMap;
-use std::str::FromStr;
-use std::sync::Mutex;
 
-type ParseFunc = fn(String) -> Result<Option<Orderbook>>;
-#[derive(Clone)]
-pub struct Api {
-    pub endpoint: &'static str,
-    // (pair, level)
-    pub subscribe_template: &'static [&'static str],
-    // raw String as input
-    pub parse: ParseFunc,
-    // render url with data
-    pub render_url: bool,
-    // wait second, heartbeat message. None means no need to send heartbeat
-    pub heartbeat: Option<(u64, &'static str)>,
-    // cleanup function when error happens
-    pub clear: fn() -> (),
+// what a message of the exchange turns into.
+// Almost every message is a book, boxing it would only add an allocation.
+#[derive(Debug, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum ParsedEvent {
+    Book(Orderbook),
+    // sent back on the connection, ex: the answer to an application level ping
+    Reply(String),
+    // subscription responses, heartbeats, updates not changing a book yet
+    Ignore,
 }
 
-impl Api {
-    // utility to render the subscription text
-    pub fn subscribe_text(&self, pair: &str, level: u32) -> Result<Vec<String>> {
-        let mut result = vec![];
-        for template in self.subscribe_template.iter() {
-            result.push(formatx!(template.to_string(), pair, level)?);
+impl ParsedEvent {
+    pub fn book(self) -> Option<Orderbook> {
+        match self {
+            ParsedEvent::Book(book) => Some(book),
+            _ => None,
         }
-        Ok(result)
     }
 }
 
-static BINANCE: Lazy<Mutex<HashMap<String, Orderbook>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
-fn binance_parser(raw: String) -> Result<Option<Orderbook>> {
-    // TODO: the PartialBookDepth doesn't contain symbol.
-    // Use PartialDiff packet to replace it.
-    #[derive(Default, Deserialize, Debug)]
-    #[serde(rename_all = "camelCase", default)]
-    struct PartialBookDepth {
-        last_update_id: u64,
-        bids: Vec<[String; 2]>,
-        asks: Vec<[String; 2]>,
-        result: Value,
-        id: u64,
-    }
-    #[derive(Default, Deserialize, Debug)]
-    struct Ticker {
-        #[serde(rename = "e")]
-        event_type: String,
-        #[serde(rename = "c")]
-        close: String,
-        #[serde(rename = "v")]
-        volume: String,
-        #[serde(rename = "s")]
-        symbol: String,
+// One instance per websocket connection.
+// Any state the exchange protocol needs is kept inside the adapter.
+#[async_trait]
+pub trait ExchangeAdapter: Send {
+    fn endpoint(&self) -> &'static str;
+    // render url with the pairs
+    fn render_url(&self) -> bool {
+        false
+    }
+    // resolve the real endpoint right before connecting
+    async fn prepare(&mut self, _network: &NetworkSetting) -> Result<String> {
+        Ok(self.endpoint().to_string())
+    }
+    // the depths the subscriptions accept, ascending: the level is rounded up to one of
+    // them, see supported_depth, and the books are trimmed locally. Empty => any level, or
+    // a depth fixed by the adapter.
+    fn supported_depths(&self) -> &'static [u32] {
+        &[]
+    }
+    // the streams a connection carries, each subscribe message of a pair is one. The pairs
+    // beyond are spread over more connections. 0 => no limit
+    fn max_streams(&self) -> usize {
+        0
     }
-    let result: Value = serde_json::from_str(&raw)?;
-    let mut tmp = BINANCE.lock().unwrap();
-
-    // Since PartialBookDepth doesn't contain any key information,
-    // Use a dummy one here
-    let key: String = "dummy".to_string();
-    let ob = if let Some(ob) = tmp.get_mut(&key) {
-        ob
-    } else {
-        tmp.insert(key.clone(), Orderbook::new("binance"));
-        tmp.get_mut(&key).unwrap()
-    };
-
-    if result["e"].as_str() == Some("24hrTicker") {
-        let result: Ticker = serde_json::from_value(result)?;
-        ob.last_price = BigDecimal::from_str(&result.close)?;
-        ob.volume = BigDecimal::from_str(&result.volume)?;
-        return Ok(Some(ob.clone()));
-    } else {
-        let result: PartialBookDepth = serde_json::from_value(result)?;
-        // this is a subscription response
-        if result.last_update_id == 0 && result.bids.is_empty() && result.asks.is_empty() {
-            return Ok(None);
-        }
-        if result.result != Value::Null {
-            bail!("result not empty");
-        }
-        ob.ask.clear();
-        ob.bid.clear();
-
-        for [price_str, quantity_str] in result.bids {
-            let price = BigDecimal::from_str(&price_str)?;
-            let quantity = BigDecimal::from_str(&quantity_str)?;
-            ob.insert(Side::Bid, price, quantity);
-        }
-        for [price_str, quantity_str] in result.asks {
-            let price = BigDecimal::from_str(&price_str)?;
-            let quantity = BigDecimal::from_str(&quantity_str)?;
-            ob.insert(Side::Ask, price, quantity);
-        }
-        Ok(Some(ob.clone()))
-    }
-}
-
-fn bitstamp_parser(raw: String) -> Result<Option<Orderbook>> {
-    #[derive(Deserialize, Debug)]
-    struct LiveDetailOrderbook {
-        bids: Vec<[String; 2]>,
-        asks: Vec<[String; 2]>,
-        #[serde(rename = "timestamp")]
-        _timestamp: String,
-        #[serde(rename = "microtimestamp")]
-        _microtimestamp: String,
-    }
-    #[derive(Deserialize, Debug)]
-    struct WsEvent {
-        data: Value,
-        event: String,
-        channel: String,
-    }
-    let result: WsEvent = serde_json::from_str(&raw).map_err(|e| anyhow!("{:?}", e))?;
-    if result.event != "data" {
-        // reconnect
-        return Ok(None);
-    }
-    if !result.channel.starts_with("order_book_") {
-        bail!("non-orderbook signal passed it");
+    // (pair, level)
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>>;
+    // (pair, level)
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>>;
+    // fetch the baseline book of the pair after subscribing.
+    // Only needed when the stream sends incremental updates
+    async fn snapshot(&mut self, _pair: &str, _network: &NetworkSetting) -> Result<()> {
+        Ok(())
+    }
+    // load the book of the pair fetched from the rest api, in hybrid mode. The websocket
+    // updates parsed afterwards are applied on it.
+    fn seed(&mut self, pair: &str, _ob: Orderbook) -> Result<()> {
+        Err(Error::Unsupported(format!("seeding {}", pair)))
+    }
+    // switch the pair to the incremental feed of the exchange, called before subscribing.
+    // The adapter loads the baseline book in snapshot.
+    fn incremental(&mut self, pair: &str) -> Result<()> {
+        Err(Error::Unsupported(format!("incremental {}", pair)))
+    }
+    // connect to the sandbox of the exchange, called before connecting: the endpoint and
+    // the snapshots switch to its testnet
+    fn testnet(&mut self) -> Result<()> {
+        Err(Error::Unsupported("testnet".to_string()))
+    }
+    // the text of one frame. The typed messages borrow their fields from it.
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent>;
+    // limit of the messages sent and the REST calls made to the exchange
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit::default()
+    }
+    // after a dropped connection, the pairs whose books continue from the updates of the
+    // new one without a snapshot: the adapter keeps their book and the id of their last
+    // update. An update not following it is a Desync, and the executor starts over with a
+    // new adapter. Empty => a new adapter is made.
+    fn resume(&mut self) -> Vec<String> {
+        vec![]
     }
-    // LiveDetailOrderbook is the only subscription type
-    // others should be categorized as error
-    let result: LiveDetailOrderbook = serde_json::from_value(result.data)?;
-    let mut ob = Orderbook::new("bitstamp");
-    for [price_str, quantity_str] in result.bids {
-        let price = BigDecimal::from_str(&price_str)?;
-        let quantity = BigDecimal::from_str(&quantity_str)?;
-        ob.insert(Side::Bid, price, quantity);
+    // cleanup function when error happens
+    fn reset(&mut self) {}
+    // wait second, heartbeat message. None means no need to send heartbeat
+    fn heartbeat(&self) -> Option<(u64, String)> {
+        None
     }
-    for [price_str, quantity_str] in result.asks {
-        let price = BigDecimal::from_str(&price_str)?;
-        let quantity = BigDecimal::from_str(&quantity_str)?;
-        ob.insert(Side::Ask, price, quantity);
+    // convert binary frames to text
+    fn decode(&self, raw: &[u8]) -> Result<String> {
+        Ok(std::str::from_utf8(raw)?.to_string())
     }
-    Ok(Some(ob))
 }
 
-static KRAKEN: Lazy<Mutex<HashMap<String, Orderbook>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
-fn kraken_clear() {
-    let mut tmp = KRAKEN.lock().unwrap();
-    tmp.clear();
+// the smallest of the depths covering the level, the largest if none does. Empty => the level
+pub fn supported_depth(depths: &[u32], level: u32) -> u32 {
+    depths
+        .iter()
+        .copied()
+        .find(|d| *d >= level)
+        .or(depths.last().copied())
+        .unwrap_or(level)
 }
 
-fn kraken_parser(raw: String) -> Result<Option<Orderbook>> {
-    if raw.as_bytes()[0] as char == '{' {
-        return Ok(None);
-    }
-    let result: Vec<Value> = serde_json::from_str(&raw).map_err(|e| anyhow!("{:?}", e))?;
-    let channel_name: String =
-        serde_json::from_value(result[2].clone()).map_err(|e| anyhow!("{:?}", e))?;
-    let pair: String = serde_json::from_value(result[3].clone()).map_err(|e| anyhow!("{:?}", e))?;
-    let key = &pair;
-    if channel_name.starts_with("book") {
-        #[derive(Deserialize, Debug)]
-        struct Data {
-            #[serde(default)]
-            r#as: Vec<[String; 3]>,
-            #[serde(default)]
-            bs: Vec<[String; 3]>,
-            #[serde(default)]
-            a: Vec<Vec<String>>,
-            #[serde(default)]
-            b: Vec<Vec<String>>,
-        }
-        // channel_id: u64
-        // data: object
-        // - as: Vec<[String; 3]>
-        // - bs: Vec<[String; 3]>
-        // channel_name: String
-        // pair: String
-        let data: Data =
-            serde_json::from_value(result[1].clone()).map_err(|e| anyhow!("{:?}", e))?;
-        let mut tmp = KRAKEN.lock().unwrap();
-        let ob = if let Some(ob) = tmp.get_mut(key) {
-            ob
-        } else {
-            tmp.insert(key.clone(), Orderbook::new("kraken"));
-            tmp.get_mut(key).unwrap()
-        };
-        if data.bs.len() > 0 || data.r#as.len() > 0 {
-            ob.bid.clear();
-            ob.ask.clear();
-        }
-        for [price_str, quantity_str, _timestamp] in data.bs {
-            let price = BigDecimal::from_str(&price_str).map_err(|e| anyhow!("{:?}", e))?;
-            let quantity = BigDecimal::from_str(&quantity_str).map_err(|e| anyhow!("{:?}", e))?;
-            ob.insert(Side::Bid, price, quantity);
-        }
-        for v in data.b {
-            let price_str: &str = &v[0];
-            let quantity_str: &str = &v[1];
-            let price = BigDecimal::from_str(price_str).map_err(|e| anyhow!("{:?}", e))?;
-            let quantity = BigDecimal::from_str(quantity_str).map_err(|e| anyhow!("{:?}", e))?;
-            ob.insert(Side::Bid, price, quantity);
-        }
-        for [price_str, quantity_str, _timestamp] in data.r#as {
-            let price = BigDecimal::from_str(&price_str).map_err(|e| anyhow!("{:?}", e))?;
-            let quantity = BigDecimal::from_str(&quantity_str).map_err(|e| anyhow!("{:?}", e))?;
-            ob.insert(Side::Ask, price, quantity);
-        }
-        for v in data.a {
-            let price_str: &str = &v[0];
-            let quantity_str: &str = &v[1];
-            let price = BigDecimal::from_str(price_str).map_err(|e| anyhow!("{:?}", e))?;
-            let quantity = BigDecimal::from_str(quantity_str).map_err(|e| anyhow!("{:?}", e))?;
-            ob.insert(Side::Ask, price, quantity);
-        }
-        // we're subscribing to book-25, so do cleanup here
-        // the exchange/mod.rs side could only get the cloned item,
-        // so the orderbook didn't explicitly trim the orderbook.
-        ob.trim(25);
-        return Ok(Some(ob.clone()));
-    } else if channel_name == "ticker".to_string() {
-        // data:
-        // - a: best ask [3]
-        // - b: best bid [3]
-        // - c: close [2]
-        // - v: volume [2] (today, last24hr)
-        #[derive(Deserialize, Debug)]
-        struct Data {
-            #[serde(default)]
-            c: [String; 2],
-            #[serde(default)]
-            v: [String; 2],
-        }
-        let data: Data =
-            serde_json::from_value(result[1].clone()).map_err(|e| anyhow!("{:?}", e))?;
-        let mut tmp = KRAKEN.lock().unwrap();
-        let ob = if let Some(ob) = tmp.get_mut(key) {
-            ob
-        } else {
-            tmp.insert(key.clone(), Orderbook::new("kraken"));
-            tmp.get_mut(key).unwrap()
-        };
-        ob.volume = BigDecimal::from_str(&data.v[1]).map_err(|e| anyhow!("{:?}", e))?;
-        ob.last_price = BigDecimal::from_str(&data.c[0]).map_err(|e| anyhow!("{:?}", e))?;
-        return Ok(Some(ob.clone()));
-    }
-    Ok(None)
+// utility to render the (un)subscription text
+pub fn render(templates: &[&str], pair: &str, level: u32) -> Result<Vec<String>> {
+    // formatx! expands to the unqualified Result with two arguments
+    use std::result::Result;
+    let mut result = vec![];
+    for template in templates.iter() {
+        result.push(formatx!(template.to_string(), pair, level).map_err(anyhow::Error::from)?);
+    }
+    Ok(result)
 }
 
-// The API Map compile-time static map that handles depth orderbook subscription and parsing
-pub static WS_APIMAP: phf::Map<&'static str, Api> = phf_map! {
-    "binance" => Api {
-        endpoint: "wss://stream.binance.com:9443/ws",
-        subscribe_template: &[
-            r#"{{"id": 1, "method": "SUBSCRIBE", "params": ["{}@depth{}@100ms"]}}"#,
-            r#"{{"id": 2, "method": "SUBSCRIBE", "params": ["{}@ticker"]}}"#,
-        ],
-        parse: (binance_parser as ParseFunc),
-        render_url: false,
-        heartbeat: None,
-        clear: || {},
-    },
-    "binance_futures" => Api {
-        endpoint: "wss://fstream.binance.com:9443/ws",
-        subscribe_template: &[r#"{{"id":1, "method":"SUBSCRIBE", "params": ["{}@depth{}@100ms"]}}"#],
-        parse: (binance_parser as ParseFunc),
-        render_url: false,
-        heartbeat: None,
-        clear: || {},
-    },
-    "bitstamp" => Api {
-        endpoint: "wss://ws.bitstamp.net",
-        subscribe_template: &[r#"{{"event":"bts:subscribe","data":{{"channel":"order_book_{}"}}}}"#],
-        parse: (bitstamp_parser as ParseFunc),
-        render_url: false,
-        heartbeat: None,
-        clear: || {},
-    },
-    "kraken" => Api {
-        endpoint: "wss://ws.kraken.com",
-        subscribe_template: &[
-            r#"{{"event":"subscribe","pair":["{}"], "subscription": {{"name":"book","depth":25}}}}"#,
-            r#"{{"event":"subscribe","pair":["{}"], "subscription": {{"name":"ticker"}}}}"#],
-        parse: (kraken_parser as ParseFunc),
-        render_url: false,
-        heartbeat: None,
-        clear: kraken_clear,
-    }
-};
-
-#[cfg(test)]
-mod tests {
-    use bigdecimal::BigDecimal;
-    use std::str::FromStr;
-    #[test]
-    fn test_subscribe_text() {
-        let rendered = super::WS_APIMAP
-            .get("binance")
-            .unwrap()
-            .subscribe_text("BTCUSDT", 20)
-            .unwrap();
-        assert_eq!(
-            rendered,
-            r#"{"id": 1, "method": "SUBSCRIBE", "params": ["BTCUSDT@depth20@100ms"]}"#
-        );
-    }
-    #[test]
-    fn test_binance_parse() {
-        // subscription response, return empty Orderbook
-        let out = (super::WS_APIMAP.get("binance").unwrap().parse)(
-            r#"{"id": 1, "result": null}"#.to_string(),
-        )
-        .unwrap();
-        assert_eq!(out, super::Orderbook::new("binance"));
-
-        // normal event
-        let out = (super::WS_APIMAP.get("binance").unwrap().parse)(
-            r#"{"lastUpdateId": 160, "bids":[["0.01", "0.2"]], "asks": []}"#.to_string(),
-        )
-        .unwrap();
-        let mut ob = super::Orderbook::new("binance");
-        ob.insert(
-            super::Side::Bid,
-            BigDecimal::from_str("0.01").unwrap(),
-            BigDecimal::from_str("0.2").unwrap(),
-        );
-        if let Some(o) = out.as_ref() {
-            ob.timestamp = o.timestamp;
-        }
-        assert_eq!(out, Some(ob));
-    }
-    #[test]
-    fn test_bitstamp_parse() {
-        // subscription response
-        let out = (super::WS_APIMAP.get("bitstamp").unwrap().parse)(
-            r#"{"event": "bts:subscription_succeeded", "channel": "order_book_btcusd", "data": {}}"#
-                .to_string(),
-        )
-        .unwrap();
-        assert_eq!(out, super::Orderbook::new("bitstamp"));
+type NewFunc = fn() -> Box<dyn ExchangeAdapter>;
 
-        // normal event
-        let out = (super::WS_APIMAP.get("bitstamp").unwrap().parse)(
-            r#"{"data":{
-                "timestamp":"1691595437",
-                "microtimestamp":"1691595437334962",
-                "bids":[],
-                "asks":[["29737","0.67548438"],["29738","0.67255217"]]
-            },"channel":"order_book_btcusd","event":"data"}"#
-                .to_string(),
-        )
-        .unwrap();
-        let mut ob = super::Orderbook::new("bitstamp");
-        ob.insert(
-            super::Side::Ask,
-            BigDecimal::from_str("29737").unwrap(),
-            BigDecimal::from_str("0.67548438").unwrap(),
-        );
-        ob.insert(
-            super::Side::Ask,
-            BigDecimal::from_str("29738").unwrap(),
-            BigDecimal::from_str("0.67255217").unwrap(),
-        );
-        if let Some(o) = out.as_ref {
-            ob.timestamp = o.timestamp;
-        }
-        assert_eq!(out, Some(ob));
-    }
-}
+// The API Map compile-time static map that creates the adapter handling depth orderbook
+// subscription and parsing, of the exchanges enabled by their cargo feature. See build.rs
+include!(concat!(env!("OUT_DIR"), "/ws_apimap.rs"));
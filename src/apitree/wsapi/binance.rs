@@ -0,0 +1,247 @@
+use super::{render, supported_depth, ExchangeAdapter, ParsedEvent};
+use crate::apitree::sdk;
+use crate::error::{Error, Rejection, Result};
+use crate::fixed::Fixed;
+use crate::orderbook::{Orderbook, Side};
+use crate::ratelimit::RateLimit;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub struct Binance {
+    endpoint: &'static str,
+    subscribe_template: &'static [&'static str],
+    unsubscribe_template: &'static [&'static str],
+    rate_limit: RateLimit,
+    // pair => book. The PartialBookDepth doesn't contain the symbol,
+    // so the combined stream is used to get the pair from the stream name.
+    books: HashMap<String, Orderbook>,
+}
+
+// the depths of the partial book streams
+const DEPTHS: [u32; 3] = [5, 10, 20];
+
+pub fn spot() -> Box<dyn ExchangeAdapter> {
+    Box::new(Binance {
+        endpoint: "wss://stream.binance.com:9443/stream",
+        subscribe_template: &[
+            r#"{{"id": 1, "method": "SUBSCRIBE", "params": ["{}@depth{}@100ms"]}}"#,
+            r#"{{"id": 2, "method": "SUBSCRIBE", "params": ["{}@ticker"]}}"#,
+        ],
+        unsubscribe_template: &[
+            r#"{{"id": 3, "method": "UNSUBSCRIBE", "params": ["{}@depth{}@100ms"]}}"#,
+            r#"{{"id": 4, "method": "UNSUBSCRIBE", "params": ["{}@ticker"]}}"#,
+        ],
+        // 5 incoming messages per second
+        rate_limit: RateLimit {
+            burst: 5,
+            per_sec: 5.0,
+        },
+        books: HashMap::new(),
+    })
+}
+
+impl ExchangeAdapter for Binance {
+    fn endpoint(&self) -> &'static str {
+        self.endpoint
+    }
+
+    // the spot testnet streams the same combined streams
+    fn testnet(&mut self) -> Result<()> {
+        self.endpoint = "wss://testnet.binance.vision/stream";
+        Ok(())
+    }
+
+    fn supported_depths(&self) -> &'static [u32] {
+        &DEPTHS
+    }
+
+    // the streams of a combined stream connection
+    fn max_streams(&self) -> usize {
+        1024
+    }
+
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            self.subscribe_template,
+            pair,
+            supported_depth(&DEPTHS, level),
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            self.unsubscribe_template,
+            pair,
+            supported_depth(&DEPTHS, level),
+        )
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        self.rate_limit
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        // the depth and the ticker in one pass, the fields of the other stream stay empty
+        #[derive(Deserialize, Debug)]
+        struct Data<'a> {
+            #[serde(rename = "e", default)]
+            event: &'a str,
+            // ticker
+            #[serde(rename = "c", default)]
+            close: &'a str,
+            #[serde(rename = "v", default)]
+            volume: &'a str,
+            // depth
+            #[serde(borrow, default)]
+            bids: Vec<[&'a str; 2]>,
+            #[serde(borrow, default)]
+            asks: Vec<[&'a str; 2]>,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Failure {
+            code: i64,
+            msg: String,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Combined<'a> {
+            stream: Option<&'a str>,
+            #[serde(borrow)]
+            data: Option<Data<'a>>,
+            #[serde(default)]
+            result: Value,
+            error: Option<Failure>,
+        }
+        let result: Combined = serde_json::from_str(raw)?;
+        if let Some(failure) = result.error {
+            // the requests share one id, the pair is unknown
+            return Err(Error::SubscriptionFailed(Rejection {
+                pair: String::new(),
+                reason: format!("{} {}", failure.code, failure.msg),
+                retry: None,
+            }));
+        }
+        let (Some(stream), Some(data)) = (result.stream, result.data) else {
+            // this is a subscription response
+            if result.result != Value::Null {
+                return Err(Error::ParseError("result not empty".to_string()));
+            }
+            return Ok(ParsedEvent::Ignore);
+        };
+        // $pair@depth20@100ms, $pair@ticker
+        let pair = stream.split('@').next().unwrap_or_default();
+        let ob = self
+            .books
+            .entry(pair.to_string())
+            .or_insert_with(|| Orderbook::with_pair("binance", pair));
+
+        if data.event == "24hrTicker" {
+            ob.last_price = Fixed::from_str(data.close)?;
+            ob.volume = Fixed::from_str(data.volume)?;
+        } else {
+            // the levels the snapshot didn't change keep their time
+            sdk::replace(ob, Side::Bid, data.bids)?;
+            sdk::replace(ob, Side::Ask, data.asks)?;
+        }
+        Ok(ParsedEvent::Book(ob.clone()))
+    }
+
+    fn reset(&mut self) {
+        self.books.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{self, SharedClock, SimulatedClock};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_subscribe_text() {
+        let rendered = spot().subscribe_messages("BTCUSDT", 20).unwrap();
+        assert_eq!(
+            rendered[0],
+            r#"{"id": 1, "method": "SUBSCRIBE", "params": ["BTCUSDT@depth20@100ms"]}"#
+        );
+        // rounded up to a partial stream, the books are trimmed locally
+        let rendered = spot().subscribe_messages("btcusdt", 7).unwrap();
+        assert!(
+            rendered[0].contains("btcusdt@depth10@100ms"),
+            "{}",
+            rendered[0]
+        );
+        let rendered = spot().unsubscribe_messages("btcusdt", 100).unwrap();
+        assert!(
+            rendered[0].contains("btcusdt@depth20@100ms"),
+            "{}",
+            rendered[0]
+        );
+    }
+    #[test]
+    fn test_binance_parse() {
+        let mut api = spot();
+        // subscription response, return empty Orderbook
+        let out = api.parse(r#"{"id": 1, "result": null}"#).unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        let out =
+            api.parse(r#"{"error":{"code":2,"msg":"Invalid request: unknown stream"},"id":1}"#);
+        assert!(matches!(out, Err(Error::SubscriptionFailed(r)) if r.pair.is_empty()));
+
+        // normal event
+        let out = api
+            .parse(
+                r#"{"stream":"btcusdt@depth20@100ms",
+                "data":{"lastUpdateId": 160, "bids":[["0.01", "0.2"]], "asks": []}}"#,
+            )
+            .unwrap();
+        let mut ob = Orderbook::with_pair("binance", "btcusdt");
+        ob.insert(
+            Side::Bid,
+            Fixed::from_str("0.01").unwrap(),
+            Fixed::from_str("0.2").unwrap(),
+        );
+        if let ParsedEvent::Book(o) = &out {
+            ob.timestamp = o.timestamp;
+            ob.received_ts = o.received_ts;
+            ob.bid_time = o.bid_time.clone();
+            ob.ask_time = o.ask_time.clone();
+        }
+        assert_eq!(out, ParsedEvent::Book(ob));
+
+        // books of different pairs are kept apart
+        let out = api
+            .parse(r#"{"stream":"ethusdt@depth20@100ms","data":{"bids":[],"asks":[["2","1"]]}}"#)
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.pair, "ethusdt");
+        assert_eq!(out.bid.len(), 0);
+        assert_eq!(out.ask.len(), 1);
+    }
+    #[test]
+    fn test_snapshot_keeps_unchanged_times() {
+        let simulated = SimulatedClock::new(1_000_000);
+        let clock: SharedClock = Arc::new(simulated.clone());
+        let mut api = spot();
+        let out = clock::scope(&clock, || {
+            api.parse(
+                r#"{"stream":"btcusdt@depth20@100ms",
+                "data":{"bids":[["1", "1"], ["2", "1"]], "asks": [["3", "1"]]}}"#,
+            )
+            .unwrap();
+            simulated.advance(1000);
+            api.parse(
+                r#"{"stream":"btcusdt@depth20@100ms",
+                "data":{"bids":[["1", "1"], ["2", "5"]], "asks": [["3", "1"]]}}"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap()
+        });
+        assert_eq!(out.bid_time[&Fixed::from(1)], 1_000_000);
+        assert_eq!(out.bid_time[&Fixed::from(2)], 1_001_000);
+        assert_eq!(out.ask_time[&Fixed::from(3)], 1_000_000);
+    }
+}
@@ -0,0 +1,307 @@
+use super::{render, ExchangeAdapter, ParsedEvent};
+use crate::apitree::sdk;
+use crate::config::NetworkSetting;
+use crate::error::{Error, Result};
+use crate::fixed::Fixed;
+use crate::net;
+use crate::orderbook::{Orderbook, Side};
+use crate::ratelimit::RateLimit;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+struct Book {
+    ob: Orderbook,
+    // lastUpdateId of the snapshot, or u of the last applied update
+    last_id: u64,
+    // set once an update bridging the snapshot is applied. Then pu must chain
+    synced: bool,
+}
+
+#[derive(Default)]
+pub struct BinanceFutures {
+    // pair => book
+    books: HashMap<String, Book>,
+    testnet: bool,
+}
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::<BinanceFutures>::default()
+}
+
+#[async_trait]
+impl ExchangeAdapter for BinanceFutures {
+    fn endpoint(&self) -> &'static str {
+        match self.testnet {
+            true => "wss://stream.binancefuture.com/stream",
+            false => "wss://fstream.binance.com/stream",
+        }
+    }
+
+    fn testnet(&mut self) -> Result<()> {
+        self.testnet = true;
+        Ok(())
+    }
+
+    // the streams of a combined stream connection
+    fn max_streams(&self) -> usize {
+        200
+    }
+
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"id":1, "method":"SUBSCRIBE", "params": ["{}@depth@100ms"]}}"#,
+                r#"{{"id":2, "method":"SUBSCRIBE", "params": ["{}@markPrice@1s"]}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"id":3, "method":"UNSUBSCRIBE", "params": ["{}@depth@100ms"]}}"#,
+                r#"{{"id":4, "method":"UNSUBSCRIBE", "params": ["{}@markPrice@1s"]}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    // 10 incoming messages per second
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            burst: 10,
+            per_sec: 10.0,
+        }
+    }
+
+    // the diff stream needs the REST snapshot as the baseline
+    async fn snapshot(&mut self, pair: &str, network: &NetworkSetting) -> Result<()> {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Snapshot<'a> {
+            last_update_id: u64,
+            #[serde(borrow)]
+            bids: Vec<[&'a str; 2]>,
+            #[serde(borrow)]
+            asks: Vec<[&'a str; 2]>,
+        }
+        let host = match self.testnet {
+            true => "testnet.binancefuture.com",
+            false => "fapi.binance.com",
+        };
+        let url = format!(
+            "https://{}/fapi/v1/depth?symbol={}&limit=1000",
+            host,
+            pair.to_uppercase()
+        );
+        let raw = net::http_get(&url, network).await?;
+        let result: Snapshot = serde_json::from_str(&raw)?;
+        let mut ob = Orderbook::with_pair("binance_futures", pair);
+        sdk::apply(&mut ob, Side::Bid, result.bids)?;
+        sdk::apply(&mut ob, Side::Ask, result.asks)?;
+        self.books.insert(
+            pair.to_lowercase(),
+            Book {
+                ob,
+                last_id: result.last_update_id,
+                synced: false,
+            },
+        );
+        Ok(())
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        #[derive(Deserialize, Debug)]
+        struct DepthUpdate<'a> {
+            #[serde(rename = "U")]
+            first_id: u64,
+            #[serde(rename = "u")]
+            last_id: u64,
+            #[serde(rename = "pu")]
+            prev_id: u64,
+            #[serde(borrow)]
+            b: Vec<[&'a str; 2]>,
+            #[serde(borrow)]
+            a: Vec<[&'a str; 2]>,
+        }
+        #[derive(Deserialize, Debug)]
+        struct MarkPrice<'a> {
+            #[serde(rename = "p")]
+            mark_price: &'a str,
+            #[serde(rename = "r")]
+            funding_rate: &'a str,
+        }
+        #[derive(Deserialize, Debug)]
+        #[serde(tag = "e")]
+        enum Data<'a> {
+            #[serde(rename = "depthUpdate", borrow)]
+            Depth(DepthUpdate<'a>),
+            #[serde(rename = "markPriceUpdate", borrow)]
+            MarkPrice(MarkPrice<'a>),
+        }
+        #[derive(Deserialize, Debug)]
+        struct Combined<'a> {
+            stream: Option<&'a str>,
+            #[serde(borrow)]
+            data: Option<Data<'a>>,
+            #[serde(default)]
+            result: Value,
+        }
+        let result: Combined = serde_json::from_str(raw)?;
+        // $pair@depth@100ms, $pair@markPrice@1s
+        let (Some(stream), Some(data)) = (result.stream, result.data) else {
+            // this is a subscription response
+            if result.result != Value::Null {
+                return Err(Error::ParseError("result not empty".to_string()));
+            }
+            return Ok(ParsedEvent::Ignore);
+        };
+        let pair = stream.split('@').next().unwrap_or_default();
+        let book = self
+            .books
+            .get_mut(pair)
+            .ok_or_else(|| Error::Desync(format!("binance_futures has no snapshot of {}", pair)))?;
+        match data {
+            Data::Depth(update) => {
+                if book.synced {
+                    if update.prev_id != book.last_id {
+                        return Err(Error::Desync(format!(
+                            "binance_futures {} expects pu {}, got {}",
+                            pair, book.last_id, update.prev_id
+                        )));
+                    }
+                } else if update.last_id < book.last_id {
+                    // already included in the snapshot
+                    return Ok(ParsedEvent::Ignore);
+                } else if update.first_id > book.last_id {
+                    return Err(Error::Desync(format!(
+                        "binance_futures {} missed updates {}..{}",
+                        pair, book.last_id, update.first_id
+                    )));
+                }
+                sdk::apply(&mut book.ob, Side::Bid, update.b)?;
+                sdk::apply(&mut book.ob, Side::Ask, update.a)?;
+                book.last_id = update.last_id;
+                book.synced = true;
+                Ok(ParsedEvent::Book(book.ob.clone()))
+            }
+            Data::MarkPrice(update) => {
+                book.ob.mark_price = Some(Fixed::from_str(update.mark_price)?);
+                book.ob.funding_rate = Some(Fixed::from_str(update.funding_rate)?);
+                // the book is unchanged until it has been synced
+                if !book.synced {
+                    return Ok(ParsedEvent::Ignore);
+                }
+                Ok(ParsedEvent::Book(book.ob.clone()))
+            }
+        }
+    }
+
+    // the update ids follow on across the connections, pu chains on the kept book
+    fn resume(&mut self) -> Vec<String> {
+        self.books.retain(|_, book| book.synced);
+        self.books.keys().cloned().collect()
+    }
+
+    fn reset(&mut self) {
+        self.books.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binance_futures_parse() {
+        let mut ob = Orderbook::with_pair("binance_futures", "btcusdt");
+        ob.insert(
+            Side::Bid,
+            Fixed::from_str("100").unwrap(),
+            Fixed::from_str("1").unwrap(),
+        );
+        let mut api = BinanceFutures::default();
+        api.books.insert(
+            "btcusdt".to_string(),
+            Book {
+                ob,
+                last_id: 10,
+                synced: false,
+            },
+        );
+        let update = |first: u64, last: u64, prev: u64, bid: &str| {
+            format!(
+                r#"{{"stream":"btcusdt@depth@100ms","data":{{"e":"depthUpdate","E":1,"T":1,
+                "s":"BTCUSDT","U":{},"u":{},"pu":{},"b":[["{}","2"]],"a":[]}}}}"#,
+                first, last, prev, bid
+            )
+        };
+        assert_eq!(
+            api.parse(r#"{"result":null,"id":1}"#).unwrap(),
+            ParsedEvent::Ignore
+        );
+        // older than the snapshot
+        assert_eq!(
+            api.parse(&update(5, 9, 4, "99")).unwrap(),
+            ParsedEvent::Ignore
+        );
+        // mark price before the book is synced
+        let mark = r#"{"stream":"btcusdt@markPrice@1s","data":{"e":"markPriceUpdate","E":1,
+            "s":"BTCUSDT","p":"100.5","i":"100.4","P":"100.6","r":"0.0001","T":1}}"#;
+        assert_eq!(api.parse(mark).unwrap(), ParsedEvent::Ignore);
+        // bridges the snapshot
+        let out = api.parse(&update(9, 12, 8, "100")).unwrap().book().unwrap();
+        assert_eq!(
+            out.bid.get(&Fixed::from_str("100").unwrap()),
+            Some(&Fixed::from_str("2").unwrap())
+        );
+        assert_eq!(out.funding_rate, Some(Fixed::from_str("0.0001").unwrap()));
+        // chained by pu
+        let out = api
+            .parse(&update(13, 14, 12, "99"))
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.len(), 2);
+        let out = api.parse(mark).unwrap().book().unwrap();
+        assert_eq!(out.mark_price, Some(Fixed::from_str("100.5").unwrap()));
+        // gap
+        assert!(matches!(
+            api.parse(&update(16, 17, 15, "98")),
+            Err(Error::Desync(_))
+        ));
+    }
+
+    #[test]
+    fn test_binance_futures_resume() {
+        let book = |synced: bool| Book {
+            ob: Orderbook::with_pair("binance_futures", "btcusdt"),
+            last_id: 10,
+            synced,
+        };
+        let mut api = BinanceFutures::default();
+        api.books.insert("btcusdt".to_string(), book(true));
+        // not synced yet, the snapshot is fetched again
+        api.books.insert("ethusdt".to_string(), book(false));
+        assert_eq!(api.resume(), vec!["btcusdt"]);
+        let update = |last: u64, prev: u64| {
+            format!(
+                r#"{{"stream":"btcusdt@depth@100ms","data":{{"e":"depthUpdate","E":1,"T":1,
+                "s":"BTCUSDT","U":{},"u":{},"pu":{},"b":[["100","2"]],"a":[]}}}}"#,
+                prev + 1,
+                last,
+                prev
+            )
+        };
+        // the first update of the new connection chains on the kept book
+        assert!(api.parse(&update(12, 10)).unwrap().book().is_some());
+        // it would be a desync otherwise
+        assert!(matches!(api.parse(&update(15, 13)), Err(Error::Desync(_))));
+    }
+}
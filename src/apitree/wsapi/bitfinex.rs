@@ -0,0 +1,205 @@
+use super::{render, ExchangeAdapter, ParsedEvent};
+use crate::error::{Error, Rejection, Result};
+use crate::fixed::Fixed;
+use crate::orderbook::{Orderbook, Side};
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// the error code of a subscription already made
+const DUPLICATE_SUBSCRIPTION: u64 = 10301;
+
+#[derive(Default)]
+pub struct Bitfinex {
+    // channel id => (symbol, book). The data frames only carry the channel id
+    channels: HashMap<u64, (String, Orderbook)>,
+}
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::<Bitfinex>::default()
+}
+
+// [price, count, amount]. count = 0 removes the price, amount < 0 is on the ask side
+type Entry = (f64, u64, f64);
+
+fn apply(ob: &mut Orderbook, (price, count, amount): Entry) -> Result<()> {
+    let parse = |value: f64| {
+        Fixed::from_f64(value)
+            .ok_or_else(|| Error::ParseError(format!("bitfinex malformed number {}", value)))
+    };
+    let (price, amount) = (parse(price)?, parse(amount)?);
+    let side = if amount < Fixed::ZERO {
+        Side::Ask
+    } else {
+        Side::Bid
+    };
+    if count == 0 {
+        ob.insert(side, price, Fixed::ZERO);
+    } else {
+        ob.insert(side, price, amount.abs());
+    }
+    Ok(())
+}
+
+impl ExchangeAdapter for Bitfinex {
+    fn endpoint(&self) -> &'static str {
+        "wss://api-pub.bitfinex.com/ws/2"
+    }
+
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[r#"{{"event":"subscribe","channel":"book","symbol":"{}","prec":"P0","len":"25"}}"#],
+            pair,
+            level,
+        )
+    }
+
+    // unsubscribing is done by the channel id given in the subscription ack
+    fn unsubscribe_messages(&self, pair: &str, _level: u32) -> Result<Vec<String>> {
+        let chan_id = self
+            .channels
+            .iter()
+            .find(|(_, (symbol, _))| symbol == pair)
+            .map(|(chan_id, _)| *chan_id)
+            .ok_or_else(|| Error::Other(anyhow!("bitfinex has no channel of {}", pair)))?;
+        Ok(vec![format!(
+            r#"{{"event":"unsubscribe","chanId":{}}}"#,
+            chan_id
+        )])
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct WsEvent {
+            event: String,
+            #[serde(default)]
+            chan_id: u64,
+            #[serde(default)]
+            symbol: String,
+            #[serde(default)]
+            code: u64,
+            #[serde(default)]
+            msg: String,
+        }
+        if raw.starts_with('{') {
+            let result: WsEvent = serde_json::from_str(raw)?;
+            match result.event.as_str() {
+                "subscribed" => {
+                    let ob = Orderbook::with_pair("bitfinex", &result.symbol);
+                    self.channels.insert(result.chan_id, (result.symbol, ob));
+                }
+                "unsubscribed" => {
+                    self.channels.remove(&result.chan_id);
+                }
+                // already subscribed
+                "error" if result.code == DUPLICATE_SUBSCRIPTION => {}
+                "error" => {
+                    return Err(Error::SubscriptionFailed(Rejection {
+                        pair: result.symbol,
+                        reason: format!("{} {}", result.code, result.msg),
+                        retry: None,
+                    }))
+                }
+                // info, conf
+                _ => {}
+            }
+            return Ok(ParsedEvent::Ignore);
+        }
+        #[derive(Deserialize, Debug)]
+        #[serde(untagged)]
+        enum Data<'a> {
+            Heartbeat(&'a str),
+            Snapshot(Vec<Entry>),
+            Update(Entry),
+        }
+        let (chan_id, data): (u64, Data) = serde_json::from_str(raw)
+            .map_err(|_| Error::ParseError(format!("bitfinex unknown frame: {}", raw)))?;
+        if let Data::Heartbeat(hb) = data {
+            return match hb {
+                "hb" => Ok(ParsedEvent::Ignore),
+                _ => Err(Error::ParseError(format!(
+                    "bitfinex unknown frame: {}",
+                    raw
+                ))),
+            };
+        }
+        let (_symbol, ob) = self
+            .channels
+            .get_mut(&chan_id)
+            .ok_or_else(|| Error::Desync(format!("bitfinex unknown channel {}", chan_id)))?;
+        match data {
+            Data::Snapshot(entries) => {
+                ob.clear();
+                for entry in entries {
+                    apply(ob, entry)?;
+                }
+            }
+            Data::Update(entry) => apply(ob, entry)?,
+            Data::Heartbeat(_) => {}
+        }
+        Ok(ParsedEvent::Book(ob.clone()))
+    }
+
+    fn reset(&mut self) {
+        self.channels.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_bitfinex_parse() {
+        let mut api = new();
+        assert!(api.unsubscribe_messages("tBTCUSD", 25).is_err());
+        let out = api
+            .parse(
+                r#"{"event":"subscribed","channel":"book","chanId":17082,"symbol":"tBTCUSD",
+                "prec":"P0","freq":"F0","len":"25","pair":"BTCUSD"}"#,
+            )
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        assert_eq!(
+            api.unsubscribe_messages("tBTCUSD", 25).unwrap(),
+            vec![r#"{"event":"unsubscribe","chanId":17082}"#.to_string()]
+        );
+
+        // snapshot
+        let out = api
+            .parse(r#"[17082,[[7254.7,3,3.3],[7254.6,2,1.5],[7255,1,-0.2]]]"#)
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.len(), 2);
+        assert_eq!(
+            out.ask.get(&Fixed::from_str("7255").unwrap()),
+            Some(&Fixed::from_str("0.2").unwrap())
+        );
+
+        // heartbeat
+        assert_eq!(api.parse(r#"[17082,"hb"]"#).unwrap(), ParsedEvent::Ignore);
+
+        let out = api.parse(
+            r#"{"event":"error","msg":"subscribe: dup","code":10301,"channel":"book","symbol":"tBTCUSD"}"#,
+        );
+        assert_eq!(out.unwrap(), ParsedEvent::Ignore);
+        let out = api.parse(
+            r#"{"event":"error","msg":"symbol: invalid","code":10300,"channel":"book","symbol":"tBTCUSDX"}"#,
+        );
+        assert!(matches!(out, Err(Error::SubscriptionFailed(r)) if r.pair == "tBTCUSDX"));
+
+        // remove a bid level
+        let out = api
+            .parse(r#"[17082,[7254.6,0,1]]"#)
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.len(), 1);
+
+        // unknown channel
+        assert!(api.parse(r#"[1,[7254.6,1,1]]"#).is_err());
+    }
+}
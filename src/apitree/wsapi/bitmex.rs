@@ -0,0 +1,284 @@
+use super::{render, ExchangeAdapter, ParsedEvent};
+use crate::error::{Error, Rejection, Result};
+use crate::fixed::Fixed;
+use crate::orderbook::{Orderbook, Side};
+use serde::Deserialize;
+use serde_json::Number;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const TABLE: &str = "orderBookL2_25";
+
+struct Book {
+    ob: Orderbook,
+    // the updates and deletes only carry the id of the level: id => its side and price
+    levels: HashMap<u64, (Side, Fixed)>,
+}
+
+#[derive(Default)]
+pub struct Bitmex {
+    // symbol => book, from its partial on
+    books: HashMap<String, Book>,
+    testnet: bool,
+}
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::<Bitmex>::default()
+}
+
+#[derive(Deserialize, Debug)]
+struct Level<'a> {
+    symbol: &'a str,
+    id: u64,
+    side: &'a str,
+    // absent on the deletes
+    #[serde(default)]
+    size: Option<Number>,
+    // absent on the updates and the deletes
+    #[serde(default)]
+    price: Option<Number>,
+}
+
+fn side(side: &str) -> Result<Side> {
+    match side {
+        "Buy" => Ok(Side::Bid),
+        "Sell" => Ok(Side::Ask),
+        _ => Err(Error::ParseError(format!("bitmex unknown side {}", side))),
+    }
+}
+
+fn number(value: Option<&Number>, what: &str, id: u64) -> Result<Fixed> {
+    let value = value.ok_or_else(|| Error::ParseError(format!("bitmex {} has no {}", id, what)))?;
+    Ok(Fixed::from_str(&value.to_string())?)
+}
+
+impl Book {
+    fn apply(&mut self, action: &str, level: &Level) -> Result<()> {
+        match action {
+            "partial" | "insert" => {
+                let (side, price) = (
+                    side(level.side)?,
+                    number(level.price.as_ref(), "price", level.id)?,
+                );
+                self.ob
+                    .insert(side, price, number(level.size.as_ref(), "size", level.id)?);
+                self.levels.insert(level.id, (side, price));
+            }
+            "update" => {
+                let (side, price) = self.price(level.id)?;
+                self.ob
+                    .insert(side, price, number(level.size.as_ref(), "size", level.id)?);
+            }
+            "delete" => {
+                let (side, price) = self.price(level.id)?;
+                self.ob.insert(side, price, Fixed::ZERO);
+                self.levels.remove(&level.id);
+            }
+            _ => {
+                return Err(Error::ParseError(format!(
+                    "bitmex unknown action {}",
+                    action
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    // an id never inserted means a missed message
+    fn price(&self, id: u64) -> Result<(Side, Fixed)> {
+        self.levels
+            .get(&id)
+            .copied()
+            .ok_or_else(|| Error::Desync(format!("bitmex {} has no level {}", self.ob.pair, id)))
+    }
+}
+
+impl ExchangeAdapter for Bitmex {
+    fn endpoint(&self) -> &'static str {
+        match self.testnet {
+            true => "wss://ws.testnet.bitmex.com/realtime",
+            false => "wss://ws.bitmex.com/realtime",
+        }
+    }
+
+    fn testnet(&mut self) -> Result<()> {
+        self.testnet = true;
+        Ok(())
+    }
+
+    // pair is the symbol, ex: XBTUSD, ETHUSD, XBTUSDT
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[r#"{{"op":"subscribe","args":["orderBookL2_25:{}"]}}"#],
+            pair,
+            level,
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[r#"{{"op":"unsubscribe","args":["orderBookL2_25:{}"]}}"#],
+            pair,
+            level,
+        )
+    }
+
+    // bitmex drops the connections idle for a minute, and answers pong
+    fn heartbeat(&self) -> Option<(u64, String)> {
+        Some((30, "ping".to_string()))
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        #[derive(Deserialize, Debug)]
+        struct Filter<'a> {
+            #[serde(default)]
+            symbol: &'a str,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Request<'a> {
+            #[serde(borrow, default)]
+            args: Vec<&'a str>,
+        }
+        #[derive(Deserialize, Debug)]
+        struct WsEvent<'a> {
+            #[serde(default)]
+            table: &'a str,
+            #[serde(default)]
+            action: &'a str,
+            #[serde(borrow, default)]
+            data: Vec<Level<'a>>,
+            #[serde(borrow, default)]
+            filter: Option<Filter<'a>>,
+            #[serde(default)]
+            error: Option<String>,
+            #[serde(borrow, default)]
+            request: Option<Request<'a>>,
+        }
+        if raw == "pong" {
+            return Ok(ParsedEvent::Ignore);
+        }
+        let result: WsEvent = serde_json::from_str(raw)?;
+        if let Some(reason) = result.error {
+            // the pair of a refused subscription is in the request, ex: orderBookL2_25:XBTUSD
+            let pair = result
+                .request
+                .iter()
+                .flat_map(|request| request.args.iter())
+                .find_map(|arg| arg.strip_prefix("orderBookL2_25:"))
+                .unwrap_or_default();
+            return Err(Error::SubscriptionFailed(Rejection {
+                pair: pair.to_string(),
+                reason,
+                retry: None,
+            }));
+        }
+        if result.table.is_empty() {
+            // welcome and subscription responses
+            return Ok(ParsedEvent::Ignore);
+        }
+        if result.table != TABLE {
+            return Err(Error::ParseError(
+                "non-orderbook signal passed it".to_string(),
+            ));
+        }
+        // one symbol per message, each is subscribed on its own
+        let symbol = match result.data.first() {
+            Some(level) => level.symbol,
+            None => result.filter.map(|f| f.symbol).unwrap_or_default(),
+        };
+        if result.action == "partial" {
+            self.books.insert(
+                symbol.to_string(),
+                Book {
+                    ob: Orderbook::with_pair("bitmex", symbol),
+                    levels: HashMap::new(),
+                },
+            );
+        }
+        // the updates sent before the partial are already in it
+        let Some(book) = self.books.get_mut(symbol) else {
+            return Ok(ParsedEvent::Ignore);
+        };
+        // a half applied message would leave a hole in the book
+        for level in result.data.iter() {
+            book.apply(result.action, level).map_err(|e| match e {
+                Error::Desync(_) => e,
+                e => Error::Desync(format!("bitmex {}: {}", symbol, e)),
+            })?;
+        }
+        Ok(ParsedEvent::Book(book.ob.clone()))
+    }
+
+    fn reset(&mut self) {
+        self.books.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitmex_parse() {
+        let mut api = new();
+        let welcome = r#"{"info":"Welcome to the BitMEX Realtime API.","version":"2.0.0",
+            "timestamp":"2024-01-01T00:00:00.000Z","limit":{"remaining":39}}"#;
+        assert_eq!(api.parse(welcome).unwrap(), ParsedEvent::Ignore);
+        assert_eq!(api.parse("pong").unwrap(), ParsedEvent::Ignore);
+        let message = |action: &str, data: &str| {
+            format!(
+                r#"{{"table":"orderBookL2_25","action":"{}","filter":{{"symbol":"XBTUSD"}},"data":{}}}"#,
+                action, data
+            )
+        };
+        // before the partial
+        let update = message(
+            "update",
+            r#"[{"symbol":"XBTUSD","id":1,"side":"Buy","size":5}]"#,
+        );
+        assert_eq!(api.parse(&update).unwrap(), ParsedEvent::Ignore);
+
+        let partial = message(
+            "partial",
+            r#"[{"symbol":"XBTUSD","id":1,"side":"Buy","size":100,"price":30000.5},
+                {"symbol":"XBTUSD","id":2,"side":"Buy","size":200,"price":30000},
+                {"symbol":"XBTUSD","id":3,"side":"Sell","size":300,"price":30001}]"#,
+        );
+        let out = api.parse(&partial).unwrap().book().unwrap();
+        assert_eq!(out.pair, "XBTUSD");
+        assert_eq!((out.bid.len(), out.ask.len()), (2, 1));
+
+        // keyed by id only
+        let out = api.parse(&update).unwrap().book().unwrap();
+        let price = Fixed::from_str("30000.5").unwrap();
+        assert_eq!(out.bid.get(&price), Some(&Fixed::from(5)));
+        let delete = message("delete", r#"[{"symbol":"XBTUSD","id":2,"side":"Buy"}]"#);
+        let out = api.parse(&delete).unwrap().book().unwrap();
+        assert_eq!(out.bid.len(), 1);
+        let insert = message(
+            "insert",
+            r#"[{"symbol":"XBTUSD","id":4,"side":"Sell","size":50,"price":30002}]"#,
+        );
+        let out = api.parse(&insert).unwrap().book().unwrap();
+        assert_eq!(out.ask.get(&Fixed::from(30002)), Some(&Fixed::from(50)));
+
+        // the deleted level is gone
+        assert!(matches!(api.parse(&delete), Err(Error::Desync(_))));
+        api.reset();
+        assert_eq!(api.parse(&insert).unwrap(), ParsedEvent::Ignore);
+    }
+
+    #[test]
+    fn test_bitmex_rejection() {
+        let mut api = new();
+        let raw = r#"{"status":400,"error":"Unknown or expired symbol: XBTUSDX","meta":{},
+            "request":{"op":"subscribe","args":["orderBookL2_25:XBTUSDX"]}}"#;
+        match api.parse(raw) {
+            Err(Error::SubscriptionFailed(rejection)) => {
+                assert_eq!(rejection.pair, "XBTUSDX");
+                assert_eq!(rejection.reason, "Unknown or expired symbol: XBTUSDX");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use super::{render, ExchangeAdapter, ParsedEvent};
+use crate::apitree::sdk;
+use crate::config::NetworkSetting;
+use crate::error::{Error, Rejection, Result};
+use crate::net;
+use crate::orderbook::{Orderbook, Side};
+use async_trait::async_trait;
+use log::error;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// a pair on the diff_order_book channel. Most of the pairs hold a book.
+#[allow(clippy::large_enum_variant)]
+enum Diff {
+    // subscribed, waiting for the rest snapshot
+    Pending,
+    // the book the diffs apply on, the microtimestamps of the snapshot and of the last diff
+    // applied
+    Book(Orderbook, u128, u128),
+    // a gap was found, the pair moved back to the order_book channel. false until the
+    // diff channel is unsubscribed.
+    Fallback(bool),
+}
+
+pub struct Bitstamp {
+    // pair => state, for the incremental pairs only
+    diffs: HashMap<String, Diff>,
+}
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::new(Bitstamp {
+        diffs: HashMap::new(),
+    })
+}
+
+fn subscription(event: &str, channel: &str, pair: &str) -> String {
+    format!(
+        r#"{{"event":"bts:{}","data":{{"channel":"{}{}"}}}}"#,
+        event, channel, pair
+    )
+}
+
+fn micros_of(micros: &str) -> Result<u128> {
+    micros
+        .parse::<u128>()
+        .map_err(|e| Error::ParseError(format!("bitstamp microtimestamp {}: {}", micros, e)))
+}
+
+// the control events carry an empty data object
+#[derive(Deserialize, Debug)]
+struct LiveDetailOrderbook<'a> {
+    // unix micros
+    #[serde(borrow)]
+    microtimestamp: Option<&'a str>,
+    #[serde(borrow, default)]
+    bids: Vec<[&'a str; 2]>,
+    #[serde(borrow, default)]
+    asks: Vec<[&'a str; 2]>,
+}
+
+impl Bitstamp {
+    // apply a diff on the rest snapshot. Bitstamp numbers neither the diffs nor the
+    // snapshots: a diff older than the last one applied means they came out of order, the
+    // book is taken again (Desync). A crossed book means some were missed, the pair falls
+    // back to the full books of the order_book channel.
+    fn diff(&mut self, pair: &str, data: LiveDetailOrderbook) -> Result<ParsedEvent> {
+        let diff = self
+            .diffs
+            .get_mut(pair)
+            .ok_or_else(|| Error::ParseError(format!("bitstamp unsubscribed diff {}", pair)))?;
+        let micros = micros_of(data.microtimestamp.unwrap_or_default())?;
+        let (ob, snapshot, last) = match diff {
+            Diff::Pending => {
+                return Err(Error::Desync(format!(
+                    "bitstamp has no snapshot of {}",
+                    pair
+                )))
+            }
+            Diff::Fallback(true) => return Ok(ParsedEvent::Ignore),
+            Diff::Fallback(false) => {
+                *diff = Diff::Fallback(true);
+                let unsubscribe = subscription("unsubscribe", "diff_order_book_", pair);
+                return Ok(ParsedEvent::Reply(unsubscribe));
+            }
+            Diff::Book(ob, snapshot, last) => (ob, *snapshot, last),
+        };
+        if micros <= snapshot {
+            // sent before the snapshot
+            return Ok(ParsedEvent::Ignore);
+        }
+        if micros <= *last {
+            return Err(Error::Desync(format!(
+                "bitstamp {} diff {} after {}",
+                pair, micros, last
+            )));
+        }
+        *last = micros;
+        sdk::apply(ob, Side::Bid, data.bids)?;
+        sdk::apply(ob, Side::Ask, data.asks)?;
+        let crossed = match (ob.bid.keys().next_back(), ob.ask.keys().next()) {
+            (Some(bid), Some(ask)) => bid >= ask,
+            _ => false,
+        };
+        if crossed {
+            error!("bitstamp {} crossed after a diff, back to order_book", pair);
+            *diff = Diff::Fallback(false);
+            return Ok(ParsedEvent::Reply(subscription(
+                "subscribe",
+                "order_book_",
+                pair,
+            )));
+        }
+        ob.exchange_ts = Some(micros / 1000);
+        Ok(ParsedEvent::Book(ob.clone()))
+    }
+}
+
+#[async_trait]
+impl ExchangeAdapter for Bitstamp {
+    fn endpoint(&self) -> &'static str {
+        "wss://ws.bitstamp.net"
+    }
+
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        if self.diffs.contains_key(pair) {
+            return Ok(vec![subscription("subscribe", "diff_order_book_", pair)]);
+        }
+        render(
+            &[r#"{{"event":"bts:subscribe","data":{{"channel":"order_book_{}"}}}}"#],
+            pair,
+            level,
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        match self.diffs.get(pair) {
+            Some(Diff::Fallback(_)) => Ok(vec![
+                subscription("unsubscribe", "diff_order_book_", pair),
+                subscription("unsubscribe", "order_book_", pair),
+            ]),
+            Some(_) => Ok(vec![subscription("unsubscribe", "diff_order_book_", pair)]),
+            None => render(
+                &[r#"{{"event":"bts:unsubscribe","data":{{"channel":"order_book_{}"}}}}"#],
+                pair,
+                level,
+            ),
+        }
+    }
+
+    fn incremental(&mut self, pair: &str) -> Result<()> {
+        self.diffs.insert(pair.to_string(), Diff::Pending);
+        Ok(())
+    }
+
+    // the whole book of an incremental pair. The diffs already received are applied
+    // afterwards by the parser, skipping the ones older than the snapshot.
+    async fn snapshot(&mut self, pair: &str, network: &NetworkSetting) -> Result<()> {
+        #[derive(Deserialize, Debug)]
+        struct Snapshot<'a> {
+            microtimestamp: &'a str,
+            #[serde(borrow)]
+            bids: Vec<[&'a str; 2]>,
+            #[serde(borrow)]
+            asks: Vec<[&'a str; 2]>,
+        }
+        if !self.diffs.contains_key(pair) {
+            return Ok(());
+        }
+        let url = format!("https://www.bitstamp.net/api/v2/order_book/{}/", pair);
+        let raw = net::http_get(&url, network).await?;
+        let result: Snapshot = serde_json::from_str(&raw)?;
+        let micros = micros_of(result.microtimestamp)?;
+        let mut ob = Orderbook::with_pair("bitstamp", pair);
+        sdk::apply(&mut ob, Side::Bid, result.bids)?;
+        sdk::apply(&mut ob, Side::Ask, result.asks)?;
+        ob.exchange_ts = Some(micros / 1000);
+        self.diffs
+            .insert(pair.to_string(), Diff::Book(ob, micros, micros));
+        Ok(())
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        #[derive(Deserialize, Debug)]
+        struct WsEvent<'a> {
+            #[serde(borrow)]
+            data: LiveDetailOrderbook<'a>,
+            event: &'a str,
+            channel: &'a str,
+        }
+        let result: WsEvent = serde_json::from_str(raw)?;
+        if result.event == "bts:error" {
+            return Err(rejection(raw));
+        }
+        if result.event != "data" {
+            // reconnect
+            return Ok(ParsedEvent::Ignore);
+        }
+        if let Some(pair) = result.channel.strip_prefix("diff_order_book_") {
+            return self.diff(pair, result.data);
+        }
+        let Some(pair) = result.channel.strip_prefix("order_book_") else {
+            return Err(Error::ParseError(
+                "non-orderbook signal passed it".to_string(),
+            ));
+        };
+        let mut ob = Orderbook::with_pair("bitstamp", pair);
+        if let Some(micros) = result.data.microtimestamp {
+            ob.exchange_ts = Some(micros_of(micros)? / 1000);
+        }
+        sdk::apply(&mut ob, Side::Bid, result.data.bids)?;
+        sdk::apply(&mut ob, Side::Ask, result.data.asks)?;
+        Ok(ParsedEvent::Book(ob))
+    }
+
+    fn reset(&mut self) {
+        for diff in self.diffs.values_mut() {
+            *diff = Diff::Pending;
+        }
+    }
+}
+
+// a refused subscription. The channel is often empty, the pair unknown then.
+fn rejection(raw: &str) -> Error {
+    #[derive(Deserialize, Debug, Default)]
+    struct Message {
+        #[serde(default)]
+        message: String,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Failure<'a> {
+        #[serde(default)]
+        channel: &'a str,
+        #[serde(default)]
+        data: Message,
+    }
+    let failure: Failure = match serde_json::from_str(raw) {
+        Ok(failure) => failure,
+        Err(e) => return e.into(),
+    };
+    let pair = ["diff_order_book_", "order_book_"]
+        .iter()
+        .find_map(|prefix| failure.channel.strip_prefix(prefix))
+        .unwrap_or_default();
+    Error::SubscriptionFailed(Rejection {
+        pair: pair.to_string(),
+        reason: failure.data.message,
+        retry: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Fixed;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_bitstamp_parse() {
+        let mut api = new();
+        // subscription response
+        let out = api
+            .parse(
+                r#"{"event": "bts:subscription_succeeded", "channel": "order_book_btcusd", "data": {}}"#,
+            )
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        let out = api.parse(
+            r#"{"event":"bts:error","channel":"order_book_btcusdx","data":{"code":null,"message":"Bad subscription string."}}"#,
+        );
+        let Err(Error::SubscriptionFailed(rejection)) = out else {
+            panic!("{:?}", out);
+        };
+        assert_eq!(rejection.pair, "btcusdx");
+        assert_eq!(rejection.reason, "Bad subscription string.");
+
+        // normal event
+        let out = api
+            .parse(
+                r#"{"data":{
+                "timestamp":"1691595437",
+                "microtimestamp":"1691595437334962",
+                "bids":[],
+                "asks":[["29737","0.67548438"],["29738","0.67255217"]]
+            },"channel":"order_book_btcusd","event":"data"}"#,
+            )
+            .unwrap();
+        let mut ob = Orderbook::with_pair("bitstamp", "btcusd");
+        ob.insert(
+            Side::Ask,
+            Fixed::from_str("29737").unwrap(),
+            Fixed::from_str("0.67548438").unwrap(),
+        );
+        ob.insert(
+            Side::Ask,
+            Fixed::from_str("29738").unwrap(),
+            Fixed::from_str("0.67255217").unwrap(),
+        );
+        if let ParsedEvent::Book(o) = &out {
+            assert_eq!(o.exchange_ts, Some(1691595437334));
+            ob.exchange_ts = o.exchange_ts;
+            ob.timestamp = o.timestamp;
+            ob.received_ts = o.received_ts;
+            ob.bid_time = o.bid_time.clone();
+            ob.ask_time = o.ask_time.clone();
+        }
+        assert_eq!(out, ParsedEvent::Book(ob));
+    }
+
+    fn diff(micros: u64, bids: &str, asks: &str) -> String {
+        format!(
+            r#"{{"data":{{"timestamp":"0","microtimestamp":"{}","bids":{},"asks":{}}},"channel":"diff_order_book_btcusd","event":"data"}}"#,
+            micros, bids, asks
+        )
+    }
+
+    #[test]
+    fn test_bitstamp_diff() {
+        let mut api = Bitstamp {
+            diffs: HashMap::new(),
+        };
+        api.incremental("btcusd").unwrap();
+        assert!(api.subscribe_messages("btcusd", 20).unwrap()[0]
+            .contains(r#""channel":"diff_order_book_btcusd""#));
+        assert!(api.subscribe_messages("ethusd", 20).unwrap()[0]
+            .contains(r#""channel":"order_book_ethusd""#));
+        assert!(matches!(
+            api.parse(&diff(1, "[]", "[]")),
+            Err(Error::Desync(_))
+        ));
+
+        // the rest snapshot
+        let mut ob = Orderbook::with_pair("bitstamp", "btcusd");
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        ob.insert(Side::Bid, fixed("100"), fixed("1"));
+        ob.insert(Side::Bid, fixed("99"), fixed("1"));
+        ob.insert(Side::Ask, fixed("101"), fixed("1"));
+        api.diffs
+            .insert("btcusd".to_string(), Diff::Book(ob, 1000, 1000));
+
+        // older than the snapshot
+        let out = api.parse(&diff(900, r#"[["100","0"]]"#, "[]")).unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        let out = api
+            .parse(&diff(1100, r#"[["100","0"]]"#, r#"[["102","2"]]"#))
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.keys().collect::<Vec<_>>(), vec![&fixed("99")]);
+        assert_eq!(out.ask.len(), 2);
+        assert_eq!(out.exchange_ts, Some(1));
+        // older than the last diff, after the snapshot
+        assert!(matches!(
+            api.parse(&diff(1050, "[]", "[]")),
+            Err(Error::Desync(_))
+        ));
+
+        // crossed, back to the full books
+        let out = api.parse(&diff(1200, r#"[["101.5","1"]]"#, "[]")).unwrap();
+        assert_eq!(
+            out,
+            ParsedEvent::Reply(
+                r#"{"event":"bts:subscribe","data":{"channel":"order_book_btcusd"}}"#.to_string()
+            )
+        );
+        let out = api.parse(&diff(1300, "[]", "[]")).unwrap();
+        assert_eq!(
+            out,
+            ParsedEvent::Reply(
+                r#"{"event":"bts:unsubscribe","data":{"channel":"diff_order_book_btcusd"}}"#
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            api.parse(&diff(1400, "[]", "[]")).unwrap(),
+            ParsedEvent::Ignore
+        );
+        assert_eq!(api.unsubscribe_messages("btcusd", 20).unwrap().len(), 2);
+    }
+}
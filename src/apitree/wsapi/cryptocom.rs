@@ -0,0 +1,128 @@
+use super::ParsedEvent;
+use crate::apitree::sdk::{self, Books};
+use crate::error::{Error, Result};
+use serde::Deserialize;
+
+// pair is the instrument name, ex: BTC_USDT.
+// Every push is a full snapshot of the subscribed depth.
+exchange_adapter! {
+    adapter: Cryptocom,
+    exchange: "cryptocom",
+    endpoint: "wss://stream.crypto.com/exchange/v1/market",
+    depths: [10, 50],
+    subscribe: [
+        r#"{{"id":1,"method":"subscribe","params":{{"channels":["book.{}.{}"],"book_subscription_type":"SNAPSHOT"}}}}"#,
+    ],
+    unsubscribe: [r#"{{"id":2,"method":"unsubscribe","params":{{"channels":["book.{}.{}"]}}}}"#],
+    parse: parse,
+}
+
+fn parse(_: &mut Books, raw: &str) -> Result<ParsedEvent> {
+    // levels are [price, amount, number of orders]
+    #[derive(Deserialize, Debug)]
+    struct Book<'a> {
+        #[serde(borrow, default)]
+        bids: Vec<Vec<&'a str>>,
+        #[serde(borrow, default)]
+        asks: Vec<Vec<&'a str>>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct Subscription<'a> {
+        channel: &'a str,
+        instrument_name: &'a str,
+        #[serde(borrow)]
+        data: Vec<Book<'a>>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct WsEvent<'a> {
+        #[serde(default)]
+        id: i64,
+        #[serde(default)]
+        method: &'a str,
+        #[serde(default)]
+        code: i64,
+        #[serde(borrow)]
+        result: Option<Subscription<'a>>,
+    }
+    let result: WsEvent = serde_json::from_str(raw)?;
+    // crypto.com sends {"id": n, "method": "public/heartbeat"} every 30 seconds, and
+    // disconnects unless the same id is sent back with public/respond-heartbeat
+    if result.method == "public/heartbeat" {
+        return Ok(ParsedEvent::Reply(format!(
+            r#"{{"id":{},"method":"public/respond-heartbeat"}}"#,
+            result.id
+        )));
+    }
+    if result.code != 0 {
+        return Err(Error::Exchange(raw.to_string()));
+    }
+    let Some(subscription) = result.result else {
+        // responses of subscribe and unsubscribe
+        return Ok(ParsedEvent::Ignore);
+    };
+    if subscription.channel != "book" {
+        return Err(Error::ParseError(
+            "non-orderbook signal passed it".to_string(),
+        ));
+    }
+    // the last push of the message is the latest book
+    let (bids, asks) = subscription
+        .data
+        .into_iter()
+        .last()
+        .map_or((vec![], vec![]), |book| (book.bids, book.asks));
+    let ob = sdk::book("cryptocom", subscription.instrument_name, bids, asks)?;
+    Ok(ParsedEvent::Book(ob))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Fixed;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_cryptocom_reply() {
+        let mut api = new();
+        assert_eq!(
+            api.parse(r#"{"id":1587523073344,"method":"public/heartbeat","code":0}"#)
+                .unwrap(),
+            ParsedEvent::Reply(
+                r#"{"id":1587523073344,"method":"public/respond-heartbeat"}"#.to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_cryptocom_parse() {
+        let mut api = new();
+        let rendered = api.subscribe_messages("BTC_USDT", 20).unwrap();
+        assert!(rendered[0].contains(r#""book.BTC_USDT.50""#));
+
+        let out = api
+            .parse(r#"{"id":1,"method":"subscribe","code":0}"#)
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        assert!(matches!(
+            api.parse(r#"{"id":1,"method":"subscribe","code":10004,"message":"BAD_REQUEST"}"#),
+            Err(Error::Exchange(_))
+        ));
+
+        let out = api
+            .parse(
+                r#"{"id":-1,"method":"subscribe","code":0,"result":{"instrument_name":"BTC_USDT",
+                "subscription":"book.BTC_USDT.10","channel":"book","depth":10,"data":[{
+                "asks":[["30082.5","0.1689","1"],["30083.0","0.1288","1"]],
+                "bids":[["30077.3","0.0280","1"]],"t":1654780033786,"tt":1654780033755,"u":542048017824}]}}"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.pair, "BTC_USDT");
+        assert_eq!(out.ask.len(), 2);
+        assert_eq!(
+            out.bid.get(&Fixed::from_str("30077.3").unwrap()),
+            Some(&Fixed::from_str("0.0280").unwrap())
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use super::{render, ExchangeAdapter, ParsedEvent};
+use crate::apitree::seq::SeqTracker;
+use crate::error::{Error, Result};
+use crate::fixed::Fixed;
+use crate::orderbook::{Orderbook, Side};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub struct Deribit {
+    books: HashMap<String, Orderbook>,
+    // the change_id of the last applied message
+    seq: SeqTracker,
+    testnet: bool,
+}
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::new(Deribit {
+        books: HashMap::new(),
+        seq: SeqTracker::new("deribit"),
+        testnet: false,
+    })
+}
+
+// ["new" | "change" | "delete", price, amount]
+fn apply(
+    ob: &mut Orderbook,
+    side: Side,
+    entries: Vec<(&str, serde_json::Number, serde_json::Number)>,
+) -> Result<()> {
+    for (action, price, amount) in entries {
+        let price = Fixed::from_str(&price.to_string())?;
+        let amount = match action {
+            "delete" => Fixed::from(0),
+            _ => Fixed::from_str(&amount.to_string())?,
+        };
+        ob.insert(side, price, amount);
+    }
+    Ok(())
+}
+
+impl ExchangeAdapter for Deribit {
+    fn endpoint(&self) -> &'static str {
+        match self.testnet {
+            true => "wss://test.deribit.com/ws/api/v2",
+            false => "wss://www.deribit.com/ws/api/v2",
+        }
+    }
+
+    fn testnet(&mut self) -> Result<()> {
+        self.testnet = true;
+        Ok(())
+    }
+
+    // pair is the instrument name, ex: BTC-PERPETUAL, BTC-29DEC23-40000-C
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"jsonrpc":"2.0","id":1,"method":"public/subscribe","params":{{"channels":["book.{}.100ms"]}}}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"jsonrpc":"2.0","id":2,"method":"public/unsubscribe","params":{{"channels":["book.{}.100ms"]}}}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    // deribit drops the idle connections
+    fn heartbeat(&self) -> Option<(u64, String)> {
+        Some((
+            30,
+            r#"{"jsonrpc":"2.0","id":3,"method":"public/test","params":{}}"#.to_string(),
+        ))
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        #[derive(Deserialize, Debug)]
+        struct Book<'a> {
+            r#type: &'a str,
+            instrument_name: &'a str,
+            change_id: u64,
+            #[serde(default)]
+            prev_change_id: Option<u64>,
+            #[serde(borrow)]
+            bids: Vec<(&'a str, serde_json::Number, serde_json::Number)>,
+            #[serde(borrow)]
+            asks: Vec<(&'a str, serde_json::Number, serde_json::Number)>,
+        }
+        // the data is only decoded once the channel is known to be a book
+        #[derive(Deserialize, Debug)]
+        struct Params<'a> {
+            channel: &'a str,
+            #[serde(borrow)]
+            data: &'a RawValue,
+        }
+        #[derive(Deserialize, Debug)]
+        struct WsEvent<'a> {
+            #[serde(default)]
+            method: &'a str,
+            #[serde(borrow, default)]
+            params: Option<Params<'a>>,
+            #[serde(borrow, default)]
+            error: Option<&'a RawValue>,
+        }
+        let result: WsEvent = serde_json::from_str(raw)?;
+        if result.error.is_some() {
+            return Err(Error::Exchange(raw.to_string()));
+        }
+        let Some(params) = result.params.filter(|_| result.method == "subscription") else {
+            // responses of subscribe and test
+            return Ok(ParsedEvent::Ignore);
+        };
+        if !params.channel.starts_with("book.") {
+            return Err(Error::ParseError(
+                "non-orderbook signal passed it".to_string(),
+            ));
+        }
+        let book: Book = serde_json::from_str(params.data.get())?;
+        let pair = book.instrument_name;
+        if book.r#type == "snapshot" {
+            let ob = Orderbook::with_pair("deribit", pair);
+            self.books.insert(pair.to_string(), ob);
+            self.seq.baseline(pair, book.change_id);
+        } else {
+            self.seq.chain(pair, book.prev_change_id, book.change_id)?;
+        }
+        let ob = self
+            .books
+            .get_mut(pair)
+            .ok_or_else(|| Error::Desync(format!("deribit has no snapshot of {}", pair)))?;
+        // the change_id is taken, a half applied message would leave a hole in the book
+        apply(ob, Side::Bid, book.bids)
+            .and_then(|_| apply(ob, Side::Ask, book.asks))
+            .map_err(|e| Error::Desync(format!("deribit {}: {}", pair, e)))?;
+        Ok(ParsedEvent::Book(ob.clone()))
+    }
+
+    fn reset(&mut self) {
+        self.books.clear();
+        self.seq.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deribit_testnet() {
+        let mut api = new();
+        assert_eq!(api.endpoint(), "wss://www.deribit.com/ws/api/v2");
+        api.testnet().unwrap();
+        assert_eq!(api.endpoint(), "wss://test.deribit.com/ws/api/v2");
+    }
+
+    #[test]
+    fn test_deribit_parse() {
+        let mut api = new();
+        let out = api
+            .parse(r#"{"jsonrpc":"2.0","id":1,"result":["book.BTC-PERPETUAL.100ms"]}"#)
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+
+        let book = |kind: &str, prev: u64, id: u64, bids: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"subscription","params":{{"channel":"book.BTC-PERPETUAL.100ms",
+                "data":{{"type":"{}","timestamp":1,"instrument_name":"BTC-PERPETUAL","prev_change_id":{},
+                "change_id":{},"bids":{},"asks":[["new",30001.5,100.0]]}}}}}}"#,
+                kind, prev, id, bids
+            )
+        };
+        // a change before the snapshot
+        assert!(api.parse(&book("change", 9, 10, "[]")).is_err());
+
+        let out = api
+            .parse(&book(
+                "snapshot",
+                0,
+                10,
+                r#"[["new",30000.0,50.0],["new",29999.5,20.0]]"#,
+            ))
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.pair, "BTC-PERPETUAL");
+        assert_eq!(out.bid.len(), 2);
+        assert_eq!(out.ask.len(), 1);
+
+        let out = api
+            .parse(&book(
+                "change",
+                10,
+                11,
+                r#"[["delete",29999.5,0.0],["change",30000.0,70.0]]"#,
+            ))
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.len(), 1);
+        assert_eq!(out.bid.get(&Fixed::from(30000)), Some(&Fixed::from(70)));
+
+        // gap
+        assert!(matches!(
+            api.parse(&book("change", 12, 13, "[]")),
+            Err(Error::Desync(_))
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use super::{render, ExchangeAdapter, ParsedEvent};
+use crate::apitree::sdk::Books;
+use crate::apitree::seq::SeqTracker;
+use crate::config::NetworkSetting;
+use crate::error::{Error, Result};
+use crate::net;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+pub struct Gateio {
+    books: Books,
+    seq: SeqTracker,
+}
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::new(Gateio::new())
+}
+
+impl Gateio {
+    fn new() -> Gateio {
+        Gateio {
+            books: Books::new("gateio"),
+            seq: SeqTracker::new("gateio"),
+        }
+    }
+}
+
+#[async_trait]
+impl ExchangeAdapter for Gateio {
+    fn endpoint(&self) -> &'static str {
+        "wss://api.gateio.ws/ws/v4/"
+    }
+
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"time":0,"channel":"spot.order_book_update","event":"subscribe","payload":["{}","100ms"]}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"time":0,"channel":"spot.order_book_update","event":"unsubscribe","payload":["{}","100ms"]}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    // load the REST snapshot as the baseline. The updates already received are applied
+    // afterwards by the parser, skipping the ones older than the snapshot.
+    async fn snapshot(&mut self, pair: &str, network: &NetworkSetting) -> Result<()> {
+        #[derive(Deserialize, Debug)]
+        struct Snapshot<'a> {
+            id: u64,
+            #[serde(borrow)]
+            bids: Vec<[&'a str; 2]>,
+            #[serde(borrow)]
+            asks: Vec<[&'a str; 2]>,
+        }
+        let url = format!(
+            "https://api.gateio.ws/api/v4/spot/order_book?currency_pair={}&limit=100&with_id=true",
+            pair
+        );
+        let raw = net::http_get(&url, network).await?;
+        let result: Snapshot = serde_json::from_str(&raw)?;
+        self.books.snapshot(pair, result.bids, result.asks)?;
+        self.seq.baseline(pair, result.id);
+        Ok(())
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        #[derive(Deserialize, Debug)]
+        struct Update<'a> {
+            s: &'a str,
+            #[serde(rename = "U")]
+            first_id: u64,
+            #[serde(rename = "u")]
+            last_id: u64,
+            #[serde(borrow, default)]
+            b: Vec<[&'a str; 2]>,
+            #[serde(borrow, default)]
+            a: Vec<[&'a str; 2]>,
+        }
+        // the result is a status object on the subscription responses
+        #[derive(Deserialize, Debug)]
+        struct WsEvent<'a> {
+            channel: &'a str,
+            event: &'a str,
+            #[serde(borrow)]
+            result: Option<&'a RawValue>,
+        }
+        let result: WsEvent = serde_json::from_str(raw)?;
+        if result.event != "update" {
+            // subscription response
+            return Ok(ParsedEvent::Ignore);
+        }
+        if result.channel != "spot.order_book_update" {
+            return Err(Error::ParseError(
+                "non-orderbook signal passed it".to_string(),
+            ));
+        }
+        let data = result
+            .result
+            .ok_or_else(|| Error::ParseError(format!("gateio update without result: {}", raw)))?;
+        let update: Update = serde_json::from_str(data.get())?;
+        if !self.seq.range(update.s, update.first_id, update.last_id)? {
+            // already included in the snapshot
+            return Ok(ParsedEvent::Ignore);
+        }
+        // the id is taken, a half applied update would leave a hole in the book
+        self.books.delta(update.s, update.b, update.a)
+    }
+
+    // the update ids follow on across the connections
+    fn resume(&mut self) -> Vec<String> {
+        self.books.pairs()
+    }
+
+    fn reset(&mut self) {
+        self.books.clear();
+        self.seq.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Fixed;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_gateio_parse() {
+        let mut api = Gateio::new();
+        api.books
+            .snapshot("BTC_USDT", vec![["100", "1"]], vec![])
+            .unwrap();
+        api.seq.baseline("BTC_USDT", 10);
+        let update = |first: u64, last: u64, bid: &str| {
+            format!(
+                r#"{{"time":1,"channel":"spot.order_book_update","event":"update","result":{{
+                    "t":1,"e":"depthUpdate","E":1,"s":"BTC_USDT","U":{},"u":{},
+                    "b":[["{}","2"]],"a":[]}}}}"#,
+                first, last, bid
+            )
+        };
+        // older than the snapshot
+        assert_eq!(
+            api.parse(&update(5, 10, "99")).unwrap(),
+            ParsedEvent::Ignore
+        );
+        // overlaps with the snapshot
+        let out = api.parse(&update(9, 12, "100")).unwrap().book().unwrap();
+        assert_eq!(out.bid.len(), 1);
+        assert_eq!(
+            out.bid.get(&Fixed::from_str("100").unwrap()),
+            Some(&Fixed::from_str("2").unwrap())
+        );
+        // gap
+        assert!(matches!(
+            api.parse(&update(14, 15, "98")),
+            Err(Error::Desync(_))
+        ));
+        // resumed on a new connection, the ids follow on
+        assert_eq!(api.resume(), vec!["BTC_USDT"]);
+        assert!(api.parse(&update(13, 13, "98")).unwrap().book().is_some());
+        // no snapshot after reset
+        api.reset();
+        assert!(matches!(
+            api.parse(&update(13, 13, "98")),
+            Err(Error::Desync(_))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+use super::ParsedEvent;
+use crate::apitree::sdk::{self, Books};
+use crate::error::{Error, Rejection, Result};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::io::Read;
+
+exchange_adapter! {
+    adapter: Huobi,
+    exchange: "huobi",
+    endpoint: "wss://api.huobi.pro/ws",
+    subscribe: [r#"{{"sub":"market.{}.depth.step0","id":"depth"}}"#],
+    unsubscribe: [r#"{{"unsub":"market.{}.depth.step0","id":"depth"}}"#],
+    decode: decode,
+    parse: parse,
+}
+
+// every frame is gzip compressed
+fn decode(raw: &[u8]) -> Result<String> {
+    let mut result = String::new();
+    GzDecoder::new(raw).read_to_string(&mut result)?;
+    Ok(result)
+}
+
+fn parse(_: &mut Books, raw: &str) -> Result<ParsedEvent> {
+    #[derive(Deserialize, Debug)]
+    struct Tick {
+        bids: Vec<[serde_json::Number; 2]>,
+        asks: Vec<[serde_json::Number; 2]>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct WsEvent<'a> {
+        ping: Option<u64>,
+        status: Option<&'a str>,
+        // the reason of a refused subscription, ex: invalid topic market.x.depth.step0
+        #[serde(rename = "err-msg", default)]
+        err_msg: String,
+        #[serde(default)]
+        ch: &'a str,
+        tick: Option<Tick>,
+    }
+    let result: WsEvent = serde_json::from_str(raw)?;
+    // huobi pings with {"ping": ts}, and expects {"pong": ts} back
+    if let Some(ts) = result.ping {
+        return Ok(ParsedEvent::Reply(format!(r#"{{"pong":{}}}"#, ts)));
+    }
+    if let Some(status) = result.status {
+        // subscription response
+        if status != "ok" {
+            let pair = result
+                .err_msg
+                .split_once("market.")
+                .and_then(|(_, topic)| topic.split_once('.'))
+                .map_or("", |(pair, _)| pair);
+            return Err(Error::SubscriptionFailed(Rejection {
+                pair: pair.to_string(),
+                reason: result.err_msg.clone(),
+                retry: None,
+            }));
+        }
+        return Ok(ParsedEvent::Ignore);
+    }
+    // market.$symbol.depth.step0
+    let parts: Vec<&str> = result.ch.split('.').collect();
+    let (["market", pair, "depth", ..], Some(tick)) = (&parts[..], result.tick) else {
+        return Err(Error::ParseError(
+            "non-orderbook signal passed it".to_string(),
+        ));
+    };
+    // step0 depth is always a full snapshot
+    let ob = sdk::book("huobi", pair, tick.bids, tick.asks)?;
+    Ok(ParsedEvent::Book(ob))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Fixed;
+    use crate::orderbook::{Orderbook, Side};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_huobi_parse() {
+        let mut api = new();
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(br#"{"ping": 1492420473027}"#).unwrap();
+        let raw = api.decode(&encoder.finish().unwrap()).unwrap();
+        assert_eq!(
+            api.parse(&raw).unwrap(),
+            ParsedEvent::Reply(r#"{"pong":1492420473027}"#.to_string())
+        );
+
+        // subscription response
+        let out = api
+            .parse(r#"{"id":"depth","status":"ok","subbed":"market.btcusdt.depth.step0","ts":1}"#)
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        let out = api.parse(
+            r#"{"id":"depth","status":"error","err-code":"bad-request","err-msg":"invalid topic market.btcusdx.depth.step0","ts":1}"#,
+        );
+        assert!(matches!(out, Err(Error::SubscriptionFailed(r)) if r.pair == "btcusdx"));
+
+        // normal event
+        let out = api
+            .parse(
+                r#"{"ch":"market.btcusdt.depth.step0","ts":1,"tick":{
+                "bids":[[29737.1,0.5]],
+                "asks":[[29738,1.25]],
+                "version":100,"ts":1
+            }}"#,
+            )
+            .unwrap();
+        let mut ob = Orderbook::with_pair("huobi", "btcusdt");
+        ob.insert(
+            Side::Bid,
+            Fixed::from_str("29737.1").unwrap(),
+            Fixed::from_str("0.5").unwrap(),
+        );
+        ob.insert(
+            Side::Ask,
+            Fixed::from_str("29738").unwrap(),
+            Fixed::from_str("1.25").unwrap(),
+        );
+        if let ParsedEvent::Book(o) = &out {
+            ob.timestamp = o.timestamp;
+            ob.received_ts = o.received_ts;
+            ob.bid_time = o.bid_time.clone();
+            ob.ask_time = o.ask_time.clone();
+        }
+        assert_eq!(out, ParsedEvent::Book(ob));
+    }
+}
@@ -0,0 +1,337 @@
+use super::{render, supported_depth, ExchangeAdapter, ParsedEvent};
+use crate::apitree::sdk;
+use crate::error::{Error, Rejection, Result};
+use crate::fixed::Fixed;
+use crate::orderbook::{Orderbook, Side};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Default)]
+pub struct Kraken {
+    // pair => book
+    books: HashMap<String, Orderbook>,
+}
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::<Kraken>::default()
+}
+
+// the book depths kraken accepts
+const DEPTHS: [u32; 5] = [10, 25, 100, 500, 1000];
+
+// kraken wants the pairs written XBT/USD. XBTUSD => XBT/USD
+fn slashed(pair: &str) -> Option<String> {
+    (!pair.contains('/') && pair.len() == 6 && pair.is_ascii())
+        .then(|| format!("{}/{}", &pair[..3], &pair[3..]).to_uppercase())
+}
+
+// the answer to a subscription, the error of a refused one
+fn subscription_status(raw: &str) -> Result<ParsedEvent> {
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Status {
+        #[serde(default)]
+        event: String,
+        #[serde(default)]
+        status: String,
+        #[serde(default)]
+        pair: String,
+        #[serde(default)]
+        error_message: String,
+    }
+    let status: Status = serde_json::from_str(raw)?;
+    if status.event != "subscriptionStatus" || status.status != "error" {
+        // heartbeat, systemStatus, subscribed
+        return Ok(ParsedEvent::Ignore);
+    }
+    let retry = match status.error_message.contains("ISO 4217-A3") {
+        true => slashed(&status.pair),
+        false => None,
+    };
+    Err(Error::SubscriptionFailed(Rejection {
+        pair: status.pair,
+        reason: status.error_message,
+        retry,
+    }))
+}
+
+// [price, volume, timestamp] or [price, volume, timestamp, "r"] for the republished levels.
+// The exchange time of the book is the latest of the level timestamps, in seconds.
+fn apply(ob: &mut Orderbook, side: Side, entries: Vec<Vec<&str>>) -> Result<()> {
+    for entry in entries.iter() {
+        let Some(timestamp_str) = entry.get(2) else {
+            return Err(Error::ParseError(format!(
+                "kraken malformed level {:?}",
+                entry
+            )));
+        };
+        let timestamp = timestamp_str
+            .parse::<f64>()
+            .map_err(|e| Error::ParseError(format!("kraken timestamp {}: {}", timestamp_str, e)))?;
+        let timestamp = (timestamp * 1000.0) as u128;
+        ob.exchange_ts = ob.exchange_ts.max(Some(timestamp));
+    }
+    sdk::apply(ob, side, entries)
+}
+
+impl ExchangeAdapter for Kraken {
+    fn endpoint(&self) -> &'static str {
+        "wss://ws.kraken.com"
+    }
+
+    fn supported_depths(&self) -> &'static [u32] {
+        &DEPTHS
+    }
+
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"event":"subscribe","pair":["{}"], "subscription": {{"name":"book","depth":{}}}}}"#,
+                r#"{{"event":"subscribe","pair":["{}"], "subscription": {{"name":"ticker"}}}}"#,
+            ],
+            pair,
+            supported_depth(&DEPTHS, level),
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"event":"unsubscribe","pair":["{}"], "subscription": {{"name":"book","depth":{}}}}}"#,
+                r#"{{"event":"unsubscribe","pair":["{}"], "subscription": {{"name":"ticker"}}}}"#,
+            ],
+            pair,
+            supported_depth(&DEPTHS, level),
+        )
+    }
+
+    // the updates received before the websocket snapshot are applied on the seeded book
+    fn seed(&mut self, pair: &str, mut ob: Orderbook) -> Result<()> {
+        ob.name = "kraken".to_string();
+        self.books.insert(pair.to_string(), ob);
+        Ok(())
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        if raw.as_bytes()[0] as char == '{' {
+            return subscription_status(raw);
+        }
+        // [channel_id, data.., channel_name, pair]
+        // A book update changing both sides carries two data objects.
+        // the data objects stay raw until the channel name tells their type
+        let result: Vec<&RawValue> = serde_json::from_str(raw)?;
+        let [_channel_id, payloads @ .., channel_name, pair] = &result[..] else {
+            return Err(Error::ParseError(format!("kraken unknown frame: {}", raw)));
+        };
+        if payloads.is_empty() {
+            return Err(Error::ParseError(format!(
+                "kraken frame without data: {}",
+                raw
+            )));
+        }
+        let channel_name: &str = serde_json::from_str(channel_name.get())?;
+        let pair: &str = serde_json::from_str(pair.get())?;
+        let ob = self
+            .books
+            .entry(pair.to_string())
+            .or_insert_with(|| Orderbook::with_pair("kraken", pair));
+        if let Some(book_depth) = channel_name.strip_prefix("book-") {
+            #[derive(Deserialize, Debug)]
+            struct Data<'a> {
+                // snapshot
+                #[serde(borrow, default)]
+                r#as: Vec<Vec<&'a str>>,
+                #[serde(borrow, default)]
+                bs: Vec<Vec<&'a str>>,
+                // update
+                #[serde(borrow, default)]
+                a: Vec<Vec<&'a str>>,
+                #[serde(borrow, default)]
+                b: Vec<Vec<&'a str>>,
+            }
+            for (index, payload) in payloads.iter().enumerate() {
+                let data: Data = serde_json::from_str(payload.get())?;
+                if index == 0 && (!data.bs.is_empty() || !data.r#as.is_empty()) {
+                    ob.clear();
+                }
+                apply(ob, Side::Bid, data.bs)?;
+                apply(ob, Side::Bid, data.b)?;
+                apply(ob, Side::Ask, data.r#as)?;
+                apply(ob, Side::Ask, data.a)?;
+            }
+            // kraken doesn't send the deletes of the levels pushed out of the subscribed depth,
+            // so the book is trimmed here.
+            let book_depth = book_depth
+                .parse::<u32>()
+                .map_err(|e| Error::ParseError(format!("kraken {}: {}", channel_name, e)))?;
+            ob.trim(book_depth);
+            return Ok(ParsedEvent::Book(ob.clone()));
+        } else if channel_name == "ticker" {
+            // data:
+            // - a: best ask [3]
+            // - b: best bid [3]
+            // - c: close [2]
+            // - v: volume [2] (today, last24hr)
+            #[derive(Deserialize, Debug)]
+            struct Data<'a> {
+                #[serde(borrow)]
+                c: [&'a str; 2],
+                #[serde(borrow)]
+                v: [&'a str; 2],
+            }
+            let data: Data = serde_json::from_str(payloads[0].get())?;
+            ob.volume = Fixed::from_str(data.v[1])?;
+            ob.last_price = Fixed::from_str(data.c[0])?;
+            return Ok(ParsedEvent::Book(ob.clone()));
+        }
+        Ok(ParsedEvent::Ignore)
+    }
+
+    fn reset(&mut self) {
+        self.books.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kraken_parse() {
+        let mut api = new();
+        let out = api
+            .parse(r#"{"event":"systemStatus","status":"online"}"#)
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+
+        // snapshot
+        let out = api
+            .parse(
+                r#"[0,{"as":[["5541.30000","2.50700000","1534614248.123678"]],
+                    "bs":[["5541.20000","1.52900000","1534614248.765567"]]},"book-25","XBT/USD"]"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.len(), 1);
+        assert_eq!(out.ask.len(), 1);
+        // the latest level
+        assert_eq!(out.exchange_ts, Some(1534614248765));
+
+        // update
+        let out = api
+            .parse(
+                r#"[0,{"b":[["5541.10000","1.00000000","1534614335.345903"]]},"book-25","XBT/USD"]"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.len(), 2);
+        assert_eq!(out.exchange_ts, Some(1534614335345));
+
+        api.reset();
+        let out = api
+            .parse(
+                r#"[0,{"b":[["5541.10000","1.00000000","1534614335.345903"]]},"book-25","XBT/USD"]"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.bid.len(), 1);
+    }
+
+    #[test]
+    fn test_kraken_rejection() {
+        let mut api = new();
+        let out = api
+            .parse(r#"{"channelID":10001,"channelName":"book-10","event":"subscriptionStatus","pair":"XBT/USD","status":"subscribed","subscription":{"depth":10,"name":"book"}}"#)
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        assert_eq!(
+            api.parse(r#"{"event":"heartbeat"}"#).unwrap(),
+            ParsedEvent::Ignore
+        );
+
+        let mut rejected = |raw: &str| match api.parse(raw) {
+            Err(Error::SubscriptionFailed(rejection)) => rejection,
+            out => panic!("{:?}", out),
+        };
+        let rejection = rejected(
+            r#"{"errorMessage":"Currency pair not in ISO 4217-A3 format XBTUSD","event":"subscriptionStatus","pair":"XBTUSD","status":"error","subscription":{"depth":10,"name":"book"}}"#,
+        );
+        assert_eq!(rejection.pair, "XBTUSD");
+        assert_eq!(rejection.retry.as_deref(), Some("XBT/USD"));
+        let rejection = rejected(
+            r#"{"errorMessage":"Currency pair not supported XBT/USDX","event":"subscriptionStatus","pair":"XBT/USDX","status":"error","subscription":{"depth":10,"name":"book"}}"#,
+        );
+        assert_eq!(rejection.reason, "Currency pair not supported XBT/USDX");
+        assert_eq!(rejection.retry, None);
+    }
+
+    #[test]
+    fn test_kraken_seed() {
+        let mut api = new();
+        let mut seeded = Orderbook::with_pair("rest", "XBT/USD");
+        seeded.insert(Side::Ask, Fixed::from(5542), Fixed::from(1));
+        api.seed("XBT/USD", seeded).unwrap();
+        let out = api
+            .parse(
+                r#"[0,{"b":[["5541.10000","1.00000000","1534614335.345903"]]},"book-25","XBT/USD"]"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.name, "kraken");
+        assert_eq!((out.bid.len(), out.ask.len()), (1, 1));
+
+        let mut api = super::super::WS_APIMAP.get("bitstamp").unwrap()();
+        assert!(matches!(
+            api.seed("btcusd", Orderbook::new("rest")),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_kraken_depth() {
+        let api = new();
+        assert_eq!(
+            api.subscribe_messages("XBT/USD", 100).unwrap()[0],
+            r#"{"event":"subscribe","pair":["XBT/USD"], "subscription": {"name":"book","depth":100}}"#
+        );
+        assert_eq!(supported_depth(api.supported_depths(), 5), 10);
+        assert_eq!(supported_depth(api.supported_depths(), 26), 100);
+        assert_eq!(supported_depth(api.supported_depths(), 5000), 1000);
+    }
+
+    #[test]
+    fn test_kraken_parse_both_sides() {
+        let mut api = new();
+        api.parse(
+            r#"[0,{"as":[["5541.30000","2.50700000","1534614248.123678"],["5541.40000","1","1534614248.123678"]],
+                "bs":[["5541.20000","1.52900000","1534614248.765567"]]},"book-10","XBT/USD"]"#,
+        )
+        .unwrap();
+        // both sides in one message, with a republished level
+        let out = api
+            .parse(
+                r#"[1234,{"a":[["5541.30000","0.00000000","1534614335.345903"],
+                    ["5541.40000","3.00000000","1534614335.345903","r"]]},
+                    {"b":[["5541.10000","1.00000000","1534614335.345903"]],"c":"974942666"},
+                    "book-10","XBT/USD"]"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.pair, "XBT/USD");
+        assert_eq!(out.ask.len(), 1);
+        assert_eq!(
+            out.ask.get(&Fixed::from_str("5541.4").unwrap()),
+            Some(&Fixed::from(3))
+        );
+        assert_eq!(out.bid.len(), 2);
+
+        assert!(api.parse(r#"[0,"book-10","XBT/USD"]"#).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use super::{render, ExchangeAdapter, ParsedEvent};
+use crate::apitree::sdk;
+use crate::config::NetworkSetting;
+use crate::error::{Error, Result};
+use crate::net;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+pub struct Kucoin;
+
+pub fn new() -> Box<dyn ExchangeAdapter> {
+    Box::new(Kucoin)
+}
+
+#[async_trait]
+impl ExchangeAdapter for Kucoin {
+    // the REST url handing out the token and the websocket server
+    fn endpoint(&self) -> &'static str {
+        "https://api.kucoin.com/api/v1/bullet-public"
+    }
+
+    async fn prepare(&mut self, network: &NetworkSetting) -> Result<String> {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Server {
+            endpoint: String,
+        }
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Data {
+            token: String,
+            instance_servers: Vec<Server>,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Bullet {
+            code: String,
+            data: Data,
+        }
+        let raw = net::http_post(self.endpoint(), network).await?;
+        let result: Bullet = serde_json::from_str(&raw)?;
+        if result.code != "200000" {
+            return Err(Error::Exchange(raw));
+        }
+        let server = result
+            .data
+            .instance_servers
+            .first()
+            .ok_or_else(|| Error::Exchange("kucoin returns no instance server".to_string()))?;
+        Ok(format!("{}?token={}", server.endpoint, result.data.token))
+    }
+
+    fn subscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"id":"depth","type":"subscribe","topic":"/spotMarket/level2Depth50:{}","response":true}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    fn unsubscribe_messages(&self, pair: &str, level: u32) -> Result<Vec<String>> {
+        render(
+            &[
+                r#"{{"id":"depth","type":"unsubscribe","topic":"/spotMarket/level2Depth50:{}","response":true}}"#,
+            ],
+            pair,
+            level,
+        )
+    }
+
+    fn heartbeat(&self) -> Option<(u64, String)> {
+        Some((18, r#"{"id":"ping","type":"ping"}"#.to_string()))
+    }
+
+    fn parse(&mut self, raw: &str) -> Result<ParsedEvent> {
+        #[derive(Deserialize, Debug)]
+        struct Depth<'a> {
+            #[serde(borrow)]
+            bids: Vec<[&'a str; 2]>,
+            #[serde(borrow)]
+            asks: Vec<[&'a str; 2]>,
+        }
+        // the data is a string on the errors, so it is only decoded on the messages
+        #[derive(Deserialize, Debug)]
+        struct WsEvent<'a> {
+            r#type: &'a str,
+            #[serde(default)]
+            topic: &'a str,
+            #[serde(borrow)]
+            data: Option<&'a RawValue>,
+        }
+        let result: WsEvent = serde_json::from_str(raw)?;
+        match result.r#type {
+            "message" => {}
+            "error" => return Err(Error::Exchange(raw.to_string())),
+            // welcome, ack and pong
+            _ => return Ok(ParsedEvent::Ignore),
+        }
+        // /spotMarket/level2Depth50:$symbol
+        let Some(pair) = result
+            .topic
+            .strip_prefix("/spotMarket/level2Depth")
+            .and_then(|t| t.split_once(':'))
+            .map(|(_, pair)| pair)
+        else {
+            return Err(Error::ParseError(
+                "non-orderbook signal passed it".to_string(),
+            ));
+        };
+        // level2Depth50 pushes the full 50 levels every time
+        let data = result
+            .data
+            .ok_or_else(|| Error::ParseError(format!("kucoin depth without data: {}", raw)))?;
+        let result: Depth = serde_json::from_str(data.get())?;
+        let ob = sdk::book("kucoin", pair, result.bids, result.asks)?;
+        Ok(ParsedEvent::Book(ob))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kucoin_parse() {
+        let mut api = new();
+        let out = api.parse(r#"{"id":"abc","type":"welcome"}"#).unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+
+        let out = api
+            .parse(
+                r#"{"type":"message","topic":"/spotMarket/level2Depth50:BTC-USDT","subject":"level2",
+                "data":{"asks":[["9989","8"]],"bids":[["9988","2"],["9987","1"]],"timestamp":1}}"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.name, "kucoin");
+        assert_eq!(out.bid.len(), 2);
+        assert_eq!(out.ask.len(), 1);
+
+        let out = api.parse(r#"{"id":"1","type":"error","code":404}"#);
+        assert!(out.is_err());
+    }
+}
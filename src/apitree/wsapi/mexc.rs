@@ -0,0 +1,152 @@
+use super::ParsedEvent;
+use crate::apitree::sdk::{self, Books, Level};
+use crate::error::{Error, Rejection, Result};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+// the symbols are upper case on mexc, ex: BTCUSDT
+exchange_adapter! {
+    adapter: Mexc,
+    exchange: "mexc",
+    endpoint: "wss://wbs.mexc.com/ws",
+    depths: [5, 10, 20],
+    pair: str::to_uppercase,
+    subscribe: [r#"{{"method":"SUBSCRIPTION","params":["spot@public.limit.depth.v3.api@{}@{}"]}}"#],
+    unsubscribe: [
+        r#"{{"method":"UNSUBSCRIPTION","params":["spot@public.limit.depth.v3.api@{}@{}"]}}"#,
+    ],
+    // mexc drops the connections without a ping for a minute
+    heartbeat: (20, r#"{"method":"PING"}"#),
+    decode: decode,
+    parse: parse,
+}
+
+#[derive(Deserialize, Debug)]
+struct Entry<'a> {
+    #[serde(rename = "p")]
+    price: &'a str,
+    #[serde(rename = "v")]
+    volume: &'a str,
+}
+
+impl Level for Entry<'_> {
+    fn parts(&self) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+        Some((self.price.into(), self.volume.into()))
+    }
+}
+
+// only the json channels are subscribed. The protobuf ones are served by another endpoint.
+fn decode(raw: &[u8]) -> Result<String> {
+    std::str::from_utf8(raw)
+        .map(|s| s.to_string())
+        .map_err(|_| Error::ParseError("mexc protobuf frames are not supported".to_string()))
+}
+
+fn parse(_: &mut Books, raw: &str) -> Result<ParsedEvent> {
+    #[derive(Deserialize, Debug)]
+    struct Depth<'a> {
+        #[serde(borrow, default)]
+        bids: Vec<Entry<'a>>,
+        #[serde(borrow, default)]
+        asks: Vec<Entry<'a>>,
+    }
+    #[derive(Deserialize, Debug)]
+    struct WsEvent<'a> {
+        // channel
+        #[serde(default)]
+        c: &'a str,
+        #[serde(borrow)]
+        d: Option<Depth<'a>>,
+        // symbol
+        #[serde(default)]
+        s: &'a str,
+        // responses of subscribe and ping. The message may hold escapes
+        code: Option<i64>,
+        #[serde(borrow, default)]
+        msg: Cow<'a, str>,
+    }
+    let result: WsEvent = serde_json::from_str(raw)?;
+    if let Some(code) = result.code {
+        if code != 0 {
+            return Err(Error::Exchange(raw.to_string()));
+        }
+        // a rejected subscription is answered with code 0 as well, ex:
+        // Not Subscribed successfully! [spot@public.limit.depth.v3.api@XXX@20]. Reason: Blocked!
+        if result.msg.contains("Not Subscribed") {
+            let pair = result
+                .msg
+                .split_once("api@")
+                .and_then(|(_, channel)| channel.split_once('@'))
+                .map_or("", |(pair, _)| pair);
+            return Err(Error::SubscriptionFailed(Rejection {
+                pair: pair.to_string(),
+                reason: result.msg.to_string(),
+                retry: None,
+            }));
+        }
+        return Ok(ParsedEvent::Ignore);
+    }
+    if !result.c.starts_with("spot@public.limit.depth") {
+        return Err(Error::ParseError(
+            "non-orderbook signal passed it".to_string(),
+        ));
+    }
+    let depth = result
+        .d
+        .ok_or_else(|| Error::ParseError(format!("mexc depth without data: {}", raw)))?;
+    // every push is the whole top of the book
+    let ob = sdk::book("mexc", result.s, depth.bids, depth.asks)?;
+    Ok(ParsedEvent::Book(ob))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Fixed;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_mexc_subscribe() {
+        let rendered = new().subscribe_messages("btcusdt", 15).unwrap();
+        assert_eq!(
+            rendered,
+            vec![
+                r#"{"method":"SUBSCRIPTION","params":["spot@public.limit.depth.v3.api@BTCUSDT@20"]}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mexc_parse() {
+        let mut api = new();
+        let out = api
+            .parse(r#"{"id":0,"code":0,"msg":"spot@public.limit.depth.v3.api@BTCUSDT@20"}"#)
+            .unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        let out = api.parse(r#"{"id":0,"code":0,"msg":"PONG"}"#).unwrap();
+        assert_eq!(out, ParsedEvent::Ignore);
+        assert!(matches!(
+            api.parse(
+                r#"{"id":0,"code":0,"msg":"Not Subscribed successfully! [spot@public.limit.depth.v3.api@XXX@20].  Reason： Blocked! "}"#
+            ),
+            Err(Error::SubscriptionFailed(r)) if r.pair == "XXX"
+        ));
+
+        let out = api
+            .parse(
+                r#"{"c":"spot@public.limit.depth.v3.api@BTCUSDT@20","d":{"asks":[{"p":"29001.5","v":"0.5"}],
+                "bids":[{"p":"29000.5","v":"1.25"},{"p":"29000","v":"2"}],"e":"spot@public.limit.depth.v3.api",
+                "r":"3407459756"},"s":"BTCUSDT","t":1661932660144}"#,
+            )
+            .unwrap()
+            .book()
+            .unwrap();
+        assert_eq!(out.pair, "BTCUSDT");
+        assert_eq!(out.bid.len(), 2);
+        assert_eq!(
+            out.ask.get(&Fixed::from_str("29001.5").unwrap()),
+            Some(&Fixed::from_str("0.5").unwrap())
+        );
+        assert!(api.decode(&[0x0a, 0xff, 0xfe]).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::config::ArbitrageSetting;
+use crate::orderbook::Orderbook;
+use crate::proto::ArbitrageSignal;
+
+// compare the best bid of every exchange against the best ask of every other exchange
+// of the same pair.
+pub fn signals(
+    pair: &str,
+    books: &[&Orderbook],
+    setting: &ArbitrageSetting,
+) -> Vec<ArbitrageSignal> {
+    let mut result = vec![];
+    for sell in books.iter() {
+        let Some((bid, bid_amount)) = sell.bid.last_key_value() else {
+            continue;
+        };
+        for buy in books.iter().filter(|b| b.name != sell.name) {
+            let Some((ask, ask_amount)) = buy.ask.first_key_value() else {
+                continue;
+            };
+            if bid <= ask {
+                continue;
+            }
+            let (Some(sell_price), Some(buy_price)) = (bid.to_f64(), ask.to_f64()) else {
+                continue;
+            };
+            let gross_bps = (sell_price - buy_price) / buy_price * 10000.0;
+            let net_bps = gross_bps - setting.fee(&sell.name) - setting.fee(&buy.name);
+            if net_bps <= setting.threshold_bps {
+                continue;
+            }
+            result.push(ArbitrageSignal {
+                pair: pair.to_string(),
+                sell_exchange: sell.name.clone(),
+                sell_price,
+                sell_amount: bid_amount.to_f64().unwrap_or_default(),
+                buy_exchange: buy.name.clone(),
+                buy_price,
+                buy_amount: ask_amount.to_f64().unwrap_or_default(),
+                net_bps,
+            });
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Fixed;
+    use crate::orderbook::Side;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    fn book(name: &str, bid: &str, ask: &str) -> Orderbook {
+        let mut ob = Orderbook::new(name);
+        ob.insert(Side::Bid, Fixed::from_str(bid).unwrap(), Fixed::from(2));
+        ob.insert(Side::Ask, Fixed::from_str(ask).unwrap(), Fixed::from(3));
+        ob
+    }
+
+    #[test]
+    fn test_signals() {
+        // A bids 10010, B asks 10000 => 10 bps
+        let a = book("A", "10010", "10020");
+        let b = book("B", "9990", "10000");
+        let mut setting = ArbitrageSetting {
+            threshold_bps: 5.0,
+            fee_bps: HashMap::new(),
+        };
+        let result = signals("btcusd", &[&a, &b], &setting);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].sell_exchange, "A");
+        assert_eq!(result[0].buy_exchange, "B");
+        assert_eq!(result[0].sell_amount, 2.0);
+        assert_eq!(result[0].buy_amount, 3.0);
+        assert!((result[0].net_bps - 10.0).abs() < 1e-9);
+
+        // eaten by the fees
+        setting.fee_bps.insert("A".to_string(), 3.0);
+        setting.fee_bps.insert("B".to_string(), 3.0);
+        assert!(signals("btcusd", &[&a, &b], &setting).is_empty());
+    }
+}
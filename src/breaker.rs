@@ -0,0 +1,77 @@
+use crate::config::CircuitBreakerSetting;
+use std::collections::VecDeque;
+use tokio::time::{Duration, Instant};
+
+// counts the failures of one exchange connection.
+// closed => reconnect right away. open => wait the cooldown. After the cooldown the
+// circuit is half open: the first failure opens it again, the first book closes it.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    setting: CircuitBreakerSetting,
+    failures: VecDeque<Instant>,
+    half_open: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(setting: &CircuitBreakerSetting) -> CircuitBreaker {
+        CircuitBreaker {
+            setting: setting.clone(),
+            failures: VecDeque::new(),
+            half_open: false,
+        }
+    }
+
+    // count one failure. Returns the cooldown if the circuit opens.
+    pub fn failure(&mut self, now: Instant) -> Option<Duration> {
+        let window = Duration::from_secs(self.setting.window_secs);
+        while self
+            .failures
+            .front()
+            .is_some_and(|t| now.saturating_duration_since(*t) > window)
+        {
+            self.failures.pop_front();
+        }
+        self.failures.push_back(now);
+        if !self.half_open && self.failures.len() < self.setting.failures.max(1) as usize {
+            return None;
+        }
+        self.failures.clear();
+        self.half_open = true;
+        Some(Duration::from_secs(self.setting.cooldown_secs))
+    }
+
+    // the exchange sent a book. Returns true if it recovers from an open circuit.
+    pub fn success(&mut self) -> bool {
+        self.failures.clear();
+        std::mem::take(&mut self.half_open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker() {
+        let mut breaker = CircuitBreaker::new(&CircuitBreakerSetting {
+            failures: 3,
+            window_secs: 60,
+            cooldown_secs: 300,
+        });
+        let start = Instant::now();
+        assert_eq!(breaker.failure(start), None);
+        assert_eq!(breaker.failure(start + Duration::from_secs(50)), None);
+        // the first failure left the window
+        assert_eq!(breaker.failure(start + Duration::from_secs(70)), None);
+        assert_eq!(
+            breaker.failure(start + Duration::from_secs(80)),
+            Some(Duration::from_secs(300))
+        );
+        // half open after the cooldown
+        let after = start + Duration::from_secs(400);
+        assert_eq!(breaker.failure(after), Some(Duration::from_secs(300)));
+        assert!(breaker.success());
+        assert!(!breaker.success());
+        assert_eq!(breaker.failure(after), None);
+    }
+}
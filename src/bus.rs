@@ -0,0 +1,155 @@
+use crate::orderbook::{AggregatedOrderbook, Orderbook};
+use crate::proto::{ConnectionState, TickerSummary, VenueBook};
+use log::debug;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// the events buffered for the slow consumers of the bus
+const CAPACITY: usize = 1024;
+
+// what happens in the market data pipeline. The executors and the replay send them to the
+// aggregation task, which passes every one of them on to the bus.
+#[derive(Debug, Clone)]
+pub enum Event {
+    // a book parsed from an exchange, aggregated under symbol
+    BookUpdate {
+        exchange: String,
+        symbol: String,
+        book: Arc<Orderbook>,
+    },
+    // published with each summary of a symbol
+    Ticker(TickerSummary),
+    // the connection state of an exchange changed. error is the cause of a disconnection.
+    Status {
+        exchange: String,
+        state: ConnectionState,
+        error: String,
+    },
+    // the book of an exchange missed updates, its executor resyncs it
+    Desync {
+        exchange: String,
+        reason: String,
+    },
+    // the exchange refused the subscription of the pair, which is no longer subscribed
+    SubscriptionFailed {
+        exchange: String,
+        pair: String,
+        reason: String,
+    },
+    // the books of an exchange, or of one of its pairs, leave the aggregation
+    Removed {
+        exchange: String,
+        pair: Option<String>,
+    },
+}
+
+// Broadcast of the events to the consumers outside of the aggregation, ex: the ticker
+// streams. A consumer falling behind skips the events it missed.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        let (tx, _) = broadcast::channel(CAPACITY);
+        EventBus { tx }
+    }
+
+    pub fn send(&self, event: Event) {
+        // no consumer
+        let _ = self.tx.send(event);
+    }
+
+    // the events sent from now on, until every EventBus clone is dropped
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+// feed the TickerSummaries streams from the bus, until it closes.
+pub async fn forward_tickers(
+    mut rx: broadcast::Receiver<Event>,
+    tickers: broadcast::Sender<TickerSummary>,
+) {
+    loop {
+        match rx.recv().await {
+            Ok(Event::Ticker(ticker)) => {
+                // no subscribers
+                let _ = tickers.send(ticker);
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(n)) => debug!("ticker forwarder skipped {} events", n),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+// feed the ExchangeBook streams from the bus, until it closes. Each book is published
+// alone, as a summary of depth levels of its symbol.
+pub async fn forward_books(
+    mut rx: broadcast::Receiver<Event>,
+    books: broadcast::Sender<VenueBook>,
+    depth: u32,
+) {
+    loop {
+        match rx.recv().await {
+            Ok(Event::BookUpdate {
+                exchange,
+                symbol,
+                book,
+            }) => {
+                // nothing to build without subscribers
+                if books.receiver_count() == 0 {
+                    continue;
+                }
+                let mut agg = AggregatedOrderbook::new();
+                agg.merge(&book);
+                match agg.finalize(depth) {
+                    Ok(mut summary) => {
+                        summary.pair = symbol;
+                        let _ = books.send(VenueBook {
+                            exchange,
+                            pair: book.pair.clone(),
+                            summary,
+                        });
+                    }
+                    Err(e) => debug!("{} {}: {}", exchange, book.pair, e),
+                }
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(n)) => debug!("book forwarder skipped {} events", n),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_forward_tickers() {
+        let bus = EventBus::new();
+        let (tickers, mut tickers_rx) = broadcast::channel(4);
+        let handle = tokio::spawn(forward_tickers(bus.subscribe(), tickers));
+        bus.send(Event::Desync {
+            exchange: "gateio".to_string(),
+            reason: "missed 3..5".to_string(),
+        });
+        bus.send(Event::Ticker(TickerSummary {
+            pair: "btcusdt".to_string(),
+            ..Default::default()
+        }));
+        assert_eq!(tickers_rx.recv().await.unwrap().pair, "btcusdt");
+        // closed once the last sender is gone
+        drop(bus);
+        handle.await.unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use crate::config::{CaptureSetting, RecorderSetting};
+use crate::recorder::{self, Record};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::JoinHandle;
+
+// receives the unmodified exchange payloads, before they are parsed.
+pub trait CaptureSink: Send + Sync {
+    // received_ms is the local unix time the payload was read from the socket
+    fn capture(&self, exchange: &str, received_ms: u64, raw: &str);
+}
+
+// forwards the payloads as raw records, for in-process consumers
+pub struct ChannelSink(pub UnboundedSender<Record>);
+
+impl CaptureSink for ChannelSink {
+    fn capture(&self, exchange: &str, received_ms: u64, raw: &str) {
+        let _ = self.0.send(Record::Raw {
+            ts: received_ms,
+            exchange: exchange.to_string(),
+            raw: raw.to_string(),
+        });
+    }
+}
+
+// the exchanges captured and the sink they go to
+#[derive(Clone)]
+pub struct Capture {
+    // empty => all
+    exchanges: Vec<String>,
+    sink: Arc<dyn CaptureSink>,
+}
+
+impl Capture {
+    pub fn new(exchanges: Vec<String>, sink: Arc<dyn CaptureSink>) -> Capture {
+        Capture { exchanges, sink }
+    }
+    pub fn sink_of(&self, exchange: &str) -> Option<Arc<dyn CaptureSink>> {
+        if self.exchanges.is_empty() || self.exchanges.iter().any(|e| e == exchange) {
+            Some(self.sink.clone())
+        } else {
+            None
+        }
+    }
+}
+
+// the file sink: raw records in rotated files, readable by --replay. exchanges, the pairs
+// of every exchange configured, goes to the header of the protobuf files.
+// The writer stops once every Capture clone is dropped.
+pub fn file(
+    setting: &CaptureSetting,
+    mut exchanges: BTreeMap<String, Vec<String>>,
+) -> (Capture, JoinHandle<()>) {
+    let (tx, rx) = unbounded_channel();
+    let recorder_setting = RecorderSetting {
+        path: setting.path.clone(),
+        books: false,
+        raw: true,
+        rotate_bytes: setting.rotate_bytes,
+        rotate_secs: setting.rotate_secs,
+        format: setting.format,
+    };
+    if !setting.exchanges.is_empty() {
+        exchanges.retain(|exchange, _| setting.exchanges.contains(exchange));
+    }
+    let handle = tokio::spawn(recorder::run(recorder_setting, exchanges, rx));
+    let capture = Capture::new(setting.exchanges.clone(), Arc::new(ChannelSink(tx)));
+    (capture, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sink_of() {
+        let (tx, mut rx) = unbounded_channel();
+        let capture = Capture::new(vec!["kraken".to_string()], Arc::new(ChannelSink(tx)));
+        assert!(capture.sink_of("binance").is_none());
+        let sink = capture.sink_of("kraken").unwrap();
+        sink.capture("kraken", 1, r#"{"event":"heartbeat"}"#);
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            Record::Raw {
+                ts: 1,
+                exchange: "kraken".to_string(),
+                raw: r#"{"event":"heartbeat"}"#.to_string(),
+            }
+        );
+
+        let (tx, _rx) = unbounded_channel();
+        let capture = Capture::new(vec![], Arc::new(ChannelSink(tx)));
+        assert!(capture.sink_of("binance").is_some());
+    }
+}
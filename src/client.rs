@@ -1,42 +1,300 @@
 #![allow(dead_code)]
 mod config;
+mod health;
 mod proto;
+mod tls;
 use anyhow::{anyhow, Result};
 use clap::Parser;
-use config::Config;
+use config::{Command, Config, Render};
 use futures_util::StreamExt;
-use proto::Empty;
-use proto::OrderbookAggregatorClient;
+use proto::orderbook::orderbook_aggregator_client::OrderbookAggregatorClient;
+use proto::{
+    ConnectionState, Empty, ExchangeRequest, Level, StatusReport, Summary, SummaryRequest,
+};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::SystemTime;
+use tokio::net::UnixStream;
+use tokio::time::{sleep, Duration};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tower::service_fn;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let mut config = Config::parse();
-    println!("loading from {}", config.config_path);
-    config.load()?;
+// reconnection backoff, doubled on every failure in a row
+const MIN_BACKOFF_SECS: u64 = 1;
+const MAX_BACKOFF_SECS: u64 = 30;
+
+const CLEAR: &str = "\x1b[2J\x1b[H";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+
+async fn connect(config: &Config) -> Result<Channel> {
     let server_addr = config
         .inner
         .server_addr
+        .clone()
         .unwrap_or_else(|| "127.0.0.1".to_string());
-    let connect_addr = format!("http://{}:{}", server_addr, config.inner.server_port);
-    let mut client = OrderbookAggregatorClient::connect(connect_addr)
+    let server_port = config.inner.server_port;
+    // the scheme stays http over tls, the connector does the handshake
+    let endpoint = Endpoint::from_shared(format!("http://{}:{}", server_addr, server_port))?;
+    match (
+        config.inner.grpc_uds_path.clone(),
+        config.inner.tls.as_ref(),
+    ) {
+        // the uri of the endpoint is ignored by the connector
+        (Some(path), _) => {
+            endpoint
+                .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect(path.clone())))
+                .await
+        }
+        (None, Some(setting)) => {
+            let connector = tls::connector(setting)?;
+            endpoint
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    tls::connect(connector.clone(), server_addr.clone(), server_port)
+                }))
+                .await
+        }
+        (None, None) => endpoint.connect().await,
+    }
+    .map_err(|e| anyhow!("{:?}", e))
+}
+
+fn ladder_row(out: &mut String, color: &str, level: &Level) {
+    let _ = writeln!(
+        out,
+        "{}{:>16.8} {:>16.8}  {}{}",
+        color, level.price, level.amount, level.exchange, RESET
+    );
+}
+
+// asks on top, best prices next to the spread
+fn ladder(summary: &Summary) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", summary.pair);
+    for level in summary.asks.iter().rev() {
+        ladder_row(&mut out, RED, level);
+    }
+    let crossed = if summary.crossed { ", CROSSED" } else { "" };
+    let partial = match summary.partial {
+        true => format!(", PARTIAL without {}", summary.stale_exchanges.join(",")),
+        false => String::new(),
+    };
+    let _ = writeln!(
+        out,
+        "{}{:>16.8} spread ({:.2} bps), mid {:.8}{}{}{}",
+        HIGHLIGHT, summary.spread, summary.spread_bps, summary.mid_price, crossed, partial, RESET
+    );
+    for level in summary.bids.iter() {
+        ladder_row(&mut out, GREEN, level);
+    }
+    out
+}
+
+// "3.2s", "-" if never
+fn age(now_ms: u64, ts_ms: u64) -> String {
+    match ts_ms {
+        0 => "-".to_string(),
+        ts_ms => format!("{:.1}s", now_ms.saturating_sub(ts_ms) as f64 / 1000.0),
+    }
+}
+
+// one row per exchange: its state, pairs, the age of its last message, reconnects and error
+fn status_table(report: &StatusReport, now_ms: u64) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<20} {:<16} {:<24} {:>10} {:>10}  LAST ERROR",
+        "EXCHANGE", "STATE", "PAIRS", "LAST MSG", "RECONNECTS"
+    );
+    for status in report.exchanges.iter() {
+        let mut state = status.state().as_str_name().to_string();
+        if status.state() == ConnectionState::Down && status.down_until_ms > now_ms {
+            let _ = write!(state, " {}s", (status.down_until_ms - now_ms) / 1000);
+        }
+        let _ = writeln!(
+            out,
+            "{:<20} {:<16} {:<24} {:>10} {:>10}  {}",
+            status.exchange,
+            state,
+            status.pairs.join(","),
+            age(now_ms, status.last_message_ms),
+            status.reconnects,
+            status.last_error
+        );
+    }
+    out
+}
+
+// print the state of the exchanges of the server once.
+async fn status(config: &Config) -> Result<()> {
+    let channel = connect(config).await?;
+    let token = config.inner.auth_tokens.first().cloned();
+    let mut client = OrderbookAggregatorClient::with_interceptor(channel, proto::with_token(token));
+    let report = client
+        .get_status(tonic::Request::new(Empty {}))
         .await
-        .map_err(|e| anyhow!("{:?}", e))?;
-    let req = tonic::Request::new(Empty {});
-    let mut stream = client
-        .book_summary(req)
+        .map_err(|e| anyhow!("{:?}", e))?
+        .into_inner();
+    let now_ms = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_millis() as u64;
+    print!("{}", status_table(&report, now_ms));
+    Ok(())
+}
+
+// print the pairs the exchange offers once.
+async fn pairs(config: &Config, exchange: &str) -> Result<()> {
+    let channel = connect(config).await?;
+    let token = config.inner.auth_tokens.first().cloned();
+    let mut client = OrderbookAggregatorClient::with_interceptor(channel, proto::with_token(token));
+    let request = ExchangeRequest {
+        exchange: exchange.to_string(),
+    };
+    let list = client
+        .list_supported_pairs(tonic::Request::new(request))
         .await
         .map_err(|e| anyhow!("{:?}", e))?
         .into_inner();
+    for pair in list.pairs {
+        println!("{}", pair);
+    }
+    Ok(())
+}
+
+// stream the summaries until the stream breaks. Returns the number received and the cause.
+async fn run(config: &Config, latest: &mut BTreeMap<String, Summary>) -> (u64, Result<()>) {
+    let channel = match connect(config).await {
+        Ok(channel) => channel,
+        Err(e) => return (0, Err(e)),
+    };
+    let token = config.inner.auth_tokens.first().cloned();
+    let mut client = OrderbookAggregatorClient::with_interceptor(channel, proto::with_token(token));
+    let request = SummaryRequest {
+        pairs: config.pairs.clone(),
+        depth: config.depth,
+    };
+    let mut stream = match client.book_summary(tonic::Request::new(request)).await {
+        Ok(response) => response.into_inner(),
+        Err(e) => return (0, Err(anyhow!("{:?}", e))),
+    };
+    let mut received = 0;
     while let Some(result) = stream.next().await {
-        match result {
-            Ok(summary) => {
-                println!("{:?}", summary);
-            }
-            Err(err) => {
-                println!("{:?}", err);
-                return Err(anyhow!("{:?}", err));
+        let summary = match result {
+            Ok(summary) => summary,
+            Err(e) => return (received, Err(anyhow!("{:?}", e))),
+        };
+        received += 1;
+        match config.render {
+            Render::Debug => println!("{:?}", summary),
+            Render::Ladder => {
+                latest.insert(summary.pair.clone(), summary);
+                let screen: Vec<String> = latest.values().map(ladder).collect();
+                print!("{}{}", CLEAR, screen.join("\n"));
             }
         }
     }
-    Ok(())
+    (received, Err(anyhow!("stream closed by the server")))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut config = Config::parse();
+    println!(
+        "loading from {}",
+        config.path().unwrap_or("the command line")
+    );
+    config.load()?;
+    match config.command.as_ref() {
+        Some(Command::Status) => return status(&config).await,
+        Some(Command::Pairs { exchange }) => return pairs(&config, exchange).await,
+        None => {}
+    }
+    let mut latest = BTreeMap::new();
+    let mut backoff = MIN_BACKOFF_SECS;
+    loop {
+        let (received, result) = run(&config, &mut latest).await;
+        if received > 0 {
+            backoff = MIN_BACKOFF_SECS;
+        }
+        if let Err(e) = result {
+            eprintln!("{}, reconnect in {} secs", e, backoff);
+        }
+        sleep(Duration::from_secs(backoff)).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF_SECS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::ExchangeStatus;
+
+    #[test]
+    fn test_ladder() {
+        let level = |price: f64| Level {
+            exchange: "binance".to_string(),
+            price,
+            amount: 1.0,
+            ..Default::default()
+        };
+        let summary = Summary {
+            pair: "btcusdt".to_string(),
+            spread: 1.0,
+            bids: vec![level(100.0), level(99.0)],
+            asks: vec![level(101.0), level(102.0)],
+            ..Default::default()
+        };
+        let out = ladder(&summary);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "btcusdt");
+        // the best ask right above the spread
+        assert!(lines[1].contains("102.0"));
+        assert!(lines[2].contains("101.0"));
+        assert!(lines[3].starts_with(HIGHLIGHT));
+        assert!(lines[4].contains("100.0"));
+
+        let partial = Summary {
+            partial: true,
+            stale_exchanges: vec!["kraken".to_string()],
+            ..summary
+        };
+        assert!(ladder(&partial).contains("PARTIAL without kraken"));
+    }
+
+    #[test]
+    fn test_status_table() {
+        let report = StatusReport {
+            exchanges: vec![
+                ExchangeStatus {
+                    exchange: "binance".to_string(),
+                    state: ConnectionState::Connected as i32,
+                    last_message_ms: 9_500,
+                    pairs: vec!["btcusdt".to_string(), "ethusdt".to_string()],
+                    ..Default::default()
+                },
+                ExchangeStatus {
+                    exchange: "kraken".to_string(),
+                    state: ConnectionState::Down as i32,
+                    reconnects: 5,
+                    last_error: "timeout".to_string(),
+                    down_until_ms: 40_000,
+                    ..Default::default()
+                },
+            ],
+        };
+        let out = status_table(&report, 10_000);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("EXCHANGE"));
+        let binance: Vec<&str> = lines[1].split_whitespace().collect();
+        assert_eq!(
+            binance,
+            vec!["binance", "CONNECTED", "btcusdt,ethusdt", "0.5s", "0"]
+        );
+        let kraken: Vec<&str> = lines[2].split_whitespace().collect();
+        assert_eq!(kraken, vec!["kraken", "DOWN", "30s", "-", "5", "timeout"]);
+    }
 }
@@ -0,0 +1,124 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// the unix time in milliseconds the books are stamped with, and their levels aged against.
+pub trait Clock: Send + Sync + Debug {
+    fn now_ms(&self) -> u128;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u128 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    }
+}
+
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+// a clock moved by hand. The replay sets it to the recorded time of each message, so that
+// the replayed books keep their original times. The clones share the time.
+#[derive(Debug, Default, Clone)]
+pub struct SimulatedClock(Arc<AtomicU64>);
+
+impl SimulatedClock {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn new(now_ms: u64) -> SimulatedClock {
+        SimulatedClock(Arc::new(AtomicU64::new(now_ms)))
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.0.store(now_ms, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn advance(&self, ms: u64) {
+        self.0.fetch_add(ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now_ms(&self) -> u128 {
+        self.0.load(Ordering::Relaxed) as u128
+    }
+}
+
+thread_local! {
+    // the clock of the books built on this thread. None => the system clock.
+    static CURRENT: RefCell<Option<SharedClock>> = const { RefCell::new(None) };
+}
+
+// puts the previous clock back, even if f panics
+struct Restore(Option<SharedClock>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+// run f with the books it builds stamped by clock. The parsers are synchronous, so the
+// clock reaches the books of any adapter without being passed through each of them.
+pub fn scope<R>(clock: &SharedClock, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.borrow_mut().replace(clock.clone()));
+    let _restore = Restore(previous);
+    f()
+}
+
+// the time of the clock in scope on this thread
+pub fn now_ms() -> u128 {
+    CURRENT.with(|current| match current.borrow().as_ref() {
+        Some(clock) => clock.now_ms(),
+        None => SystemClock.now_ms(),
+    })
+}
+
+// the first multiple of interval_ms since the unix epoch after both now_ms and last, ex:
+// every second on the second. After last, so that a timer waking a bit early doesn't fire
+// twice for the same boundary.
+pub fn next_boundary(now_ms: u128, last: u128, interval_ms: u64) -> u128 {
+    let interval = interval_ms.max(1) as u128;
+    (now_ms.max(last) / interval + 1) * interval
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope() {
+        let simulated = SimulatedClock::new(1000);
+        let clock: SharedClock = Arc::new(simulated.clone());
+        assert!(now_ms() > 1000);
+        scope(&clock, || {
+            assert_eq!(now_ms(), 1000);
+            simulated.advance(500);
+            assert_eq!(now_ms(), 1500);
+            // nested
+            scope(&system(), || assert!(now_ms() > 1500));
+            assert_eq!(now_ms(), 1500);
+        });
+        assert!(now_ms() > 1500);
+    }
+
+    #[test]
+    fn test_next_boundary() {
+        assert_eq!(next_boundary(61_234, 0, 1000), 62_000);
+        assert_eq!(next_boundary(61_234, 0, 60_000), 120_000);
+        // on a boundary, the next one
+        assert_eq!(next_boundary(62_000, 0, 1000), 63_000);
+        // woke early, the boundary already fired isn't repeated
+        assert_eq!(next_boundary(61_999, 62_000, 1000), 63_000);
+    }
+}
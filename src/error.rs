@@ -0,0 +1,131 @@
+use crate::fixed::ParseFixedError;
+use crate::orderbook::Crossed;
+use std::fmt;
+use thiserror::Error;
+use tokio::sync::mpsc::error::SendError;
+use tokio_tungstenite::tungstenite;
+
+// Errors of the exchange pipeline: apitree adapters, Exchange and executor.
+// The executor decides how to recover from the kind of the error.
+#[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    // the websocket is gone. Reconnect.
+    #[error("connection closed: {0}")]
+    ConnectionClosed(String),
+    // one message cannot be understood. Skip it.
+    #[error("parse error: {0}")]
+    ParseError(String),
+    // the local book no longer matches the exchange. Resync from a fresh snapshot.
+    #[error("desync: {0}")]
+    Desync(String),
+    // the exchange or the operation is not supported. Retrying won't help.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    // the exchange asks us to slow down. Back off before retrying.
+    #[error("rate limited: {0}")]
+    RateLimited(String),
+    // the exchange answers with an error event.
+    #[error("exchange error: {0}")]
+    Exchange(String),
+    // the exchange refuses a subscription. The connection is still fine.
+    #[error("subscription failed: {0}")]
+    SubscriptionFailed(Rejection),
+    // boxed to keep the Result small
+    #[error("websocket: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// a subscription refused by the exchange
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    // the pair as subscribed. Empty when the exchange doesn't say which.
+    pub pair: String,
+    pub reason: String,
+    // the pair written the way the exchange expects, to subscribe instead
+    pub retry: Option<String>,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pair.as_str() {
+            "" => write!(f, "{}", self.reason),
+            pair => write!(f, "{} {}", pair, self.reason),
+        }
+    }
+}
+
+// keep the typed error if there's one wrapped in the anyhow error.
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<Error>() {
+            Ok(e) => e,
+            Err(e) => Error::Other(e),
+        }
+    }
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(e: tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+impl From<bigdecimal::ParseBigDecimalError> for Error {
+    fn from(e: bigdecimal::ParseBigDecimalError) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+impl From<ParseFixedError> for Error {
+    fn from(e: ParseFixedError) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(e: std::str::Utf8Error) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::ParseError(e.to_string())
+    }
+}
+
+impl From<Crossed> for Error {
+    fn from(e: Crossed) -> Self {
+        Error::Desync(e.to_string())
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(_: SendError<T>) -> Self {
+        Error::ConnectionClosed("the writer has stopped".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let e: anyhow::Error = Error::RateLimited("binance".to_string()).into();
+        assert!(matches!(Error::from(e), Error::RateLimited(_)));
+        let e = anyhow::anyhow!("some error");
+        assert!(matches!(Error::from(e), Error::Other(_)));
+    }
+}
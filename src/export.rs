@@ -0,0 +1,431 @@
+use crate::config::{ExportFormat, ExportSetting};
+use crate::proto::Summary;
+use crate::recorder::{get_unixtime, Record};
+use anyhow::{Context, Result};
+use log::{error, info};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time;
+
+// a summary flattened to the columns of the export
+#[derive(Debug, PartialEq)]
+pub struct Row {
+    // when the summary was exported, unix millis
+    pub ts_ms: u64,
+    pub pair: String,
+    // the columns after the pair, None if the summary has no such value, ex: a side empty
+    pub values: Vec<Option<f64>>,
+}
+
+// the columns of the values of the rows, with levels per side
+pub fn columns(levels: u32) -> Vec<String> {
+    let mut columns: Vec<String> = ["best_bid", "best_ask", "mid_price", "spread", "spread_bps"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    for side in ["bid", "ask"] {
+        for level in 1..=levels {
+            columns.push(format!("{}_price_{}", side, level));
+            columns.push(format!("{}_amount_{}", side, level));
+        }
+    }
+    columns
+}
+
+impl Row {
+    pub fn new(ts_ms: u64, summary: &Summary, levels: u32) -> Row {
+        let best = |levels: &[crate::proto::Level]| levels.first().map(|l| l.price);
+        // the mid and the spread need both sides
+        let both = !summary.bids.is_empty() && !summary.asks.is_empty();
+        let mut values = vec![
+            best(&summary.bids),
+            best(&summary.asks),
+            both.then_some(summary.mid_price),
+            both.then_some(summary.spread),
+            both.then_some(summary.spread_bps),
+        ];
+        for side in [&summary.bids, &summary.asks] {
+            for index in 0..levels as usize {
+                let level = side.get(index);
+                values.push(level.map(|l| l.price));
+                values.push(level.map(|l| l.amount));
+            }
+        }
+        Row {
+            ts_ms,
+            pair: summary.pair.clone(),
+            values,
+        }
+    }
+}
+
+// the file of the rows, in one of the formats
+trait Writer: Send {
+    // the bytes the row takes
+    fn write(&mut self, row: Row) -> Result<u64>;
+    fn flush(&mut self) -> Result<()>;
+    // complete the file, no row is written afterwards
+    fn finish(&mut self) -> Result<()>;
+}
+
+struct CsvWriter {
+    file: BufWriter<File>,
+}
+
+impl CsvWriter {
+    fn create(path: &str, levels: u32) -> Result<CsvWriter> {
+        let file = File::create(path).with_context(|| format!("unable to create {}", path))?;
+        let mut file = BufWriter::new(file);
+        writeln!(file, "ts_ms,pair,{}", columns(levels).join(","))?;
+        Ok(CsvWriter { file })
+    }
+}
+
+// the pair quoted if it holds a separator or a quote
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+impl Writer for CsvWriter {
+    fn write(&mut self, row: Row) -> Result<u64> {
+        let mut line = format!("{},{}", row.ts_ms, csv_field(&row.pair));
+        for value in row.values.iter() {
+            line.push(',');
+            if let Some(value) = value {
+                line.push_str(&value.to_string());
+            }
+        }
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        Ok(line.len() as u64)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.file.flush()?)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet {
+    use super::{columns, Row, Writer};
+    use anyhow::{Context, Result};
+    use arrow2::array::{Array, Float64Array, UInt64Array, Utf8Array};
+    use arrow2::chunk::Chunk;
+    use arrow2::datatypes::{DataType, Field, Schema};
+    use arrow2::io::parquet::write::{
+        transverse, CompressionOptions, Encoding, FileWriter, RowGroupIterator, Version,
+        WriteOptions,
+    };
+    use std::fs::File;
+
+    // the rows of a row group. The rows buffered are lost if the process is killed.
+    const ROW_GROUP_ROWS: usize = 10_000;
+
+    pub struct ParquetWriter {
+        writer: FileWriter<File>,
+        schema: Schema,
+        options: WriteOptions,
+        rows: Vec<Row>,
+    }
+
+    impl ParquetWriter {
+        pub fn create(path: &str, levels: u32) -> Result<ParquetWriter> {
+            let mut fields = vec![
+                Field::new("ts_ms", DataType::UInt64, false),
+                Field::new("pair", DataType::Utf8, false),
+            ];
+            for column in columns(levels) {
+                fields.push(Field::new(column, DataType::Float64, true));
+            }
+            let schema = Schema::from(fields);
+            let options = WriteOptions {
+                write_statistics: true,
+                compression: CompressionOptions::Snappy,
+                version: Version::V2,
+                data_pagesize_limit: None,
+            };
+            let file = File::create(path).with_context(|| format!("unable to create {}", path))?;
+            let writer = FileWriter::try_new(file, schema.clone(), options)?;
+            Ok(ParquetWriter {
+                writer,
+                schema,
+                options,
+                rows: vec![],
+            })
+        }
+
+        // write the buffered rows as a row group
+        fn write_group(&mut self) -> Result<()> {
+            if self.rows.is_empty() {
+                return Ok(());
+            }
+            let rows = std::mem::take(&mut self.rows);
+            let mut arrays: Vec<Box<dyn Array>> = vec![
+                UInt64Array::from_vec(rows.iter().map(|r| r.ts_ms).collect()).boxed(),
+                Utf8Array::<i32>::from_iter_values(rows.iter().map(|r| r.pair.as_str())).boxed(),
+            ];
+            for index in 0..self.schema.fields.len() - 2 {
+                let values = rows.iter().map(|r| r.values.get(index).copied().flatten());
+                arrays.push(Float64Array::from_iter(values).boxed());
+            }
+            let encodings = self
+                .schema
+                .fields
+                .iter()
+                .map(|f| transverse(&f.data_type, |_| Encoding::Plain))
+                .collect();
+            let groups = RowGroupIterator::try_new(
+                vec![Chunk::try_new(arrays)].into_iter(),
+                &self.schema,
+                self.options,
+                encodings,
+            )?;
+            for group in groups {
+                self.writer.write(group?)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Writer for ParquetWriter {
+        fn write(&mut self, row: Row) -> Result<u64> {
+            let bytes = 8 + row.pair.len() + 8 * row.values.len();
+            self.rows.push(row);
+            if self.rows.len() >= ROW_GROUP_ROWS {
+                self.write_group()?;
+            }
+            Ok(bytes as u64)
+        }
+
+        // the rows wait for a full row group
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            self.write_group()?;
+            self.writer.end(None)?;
+            Ok(())
+        }
+    }
+}
+
+// Append the rows of the summaries, rotating the files by size and time like the recorder.
+pub struct Exporter {
+    setting: ExportSetting,
+    writer: Option<Box<dyn Writer>>,
+    written: u64,
+    opened_at: Instant,
+}
+
+impl Exporter {
+    pub fn new(setting: ExportSetting) -> Exporter {
+        Exporter {
+            setting,
+            writer: None,
+            written: 0,
+            opened_at: Instant::now(),
+        }
+    }
+
+    fn should_rotate(&self) -> bool {
+        if self.writer.is_none() {
+            return true;
+        }
+        (self.setting.rotate_bytes > 0 && self.written >= self.setting.rotate_bytes)
+            || (self.setting.rotate_secs > 0
+                && self.opened_at.elapsed() >= Duration::from_secs(self.setting.rotate_secs))
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.finish()?;
+        let extension = match self.setting.format {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        };
+        let path = format!("{}.{}.{}", self.setting.path, get_unixtime(), extension);
+        info!("exporting to {}", path);
+        let levels = self.setting.levels;
+        self.writer = Some(match self.setting.format {
+            ExportFormat::Csv => Box::new(CsvWriter::create(&path, levels)?),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => Box::new(parquet::ParquetWriter::create(&path, levels)?),
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => return Err(unsupported()),
+        });
+        self.written = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    pub fn write(&mut self, ts_ms: u64, summary: &Summary) -> Result<()> {
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        let row = Row::new(ts_ms, summary, self.setting.levels);
+        // writer is always set after rotate
+        if let Some(writer) = self.writer.as_mut() {
+            self.written += writer.write(row)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // complete the current file
+    pub fn finish(&mut self) -> Result<()> {
+        match self.writer.take() {
+            Some(mut writer) => writer.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+fn unsupported() -> anyhow::Error {
+    anyhow::anyhow!("export format is Parquet, but the server is built without the parquet feature")
+}
+
+// start the exporter. The summaries sent are written until every sender is dropped, then
+// the file is completed.
+pub fn start(setting: ExportSetting) -> Result<(UnboundedSender<Record>, JoinHandle<()>)> {
+    #[cfg(not(feature = "parquet"))]
+    if setting.format == ExportFormat::Parquet {
+        return Err(unsupported());
+    }
+    let (tx, mut rx) = unbounded_channel::<Record>();
+    let handle = tokio::spawn(async move {
+        let mut exporter = Exporter::new(setting);
+        let mut interval = time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                record = rx.recv() => match record {
+                    Some(Record::Summary { ts, summary }) => {
+                        if let Err(e) = exporter.write(ts, &summary) {
+                            error!("export: {}", e);
+                        }
+                    }
+                    Some(_) => {}
+                    None => break,
+                },
+                _ = interval.tick() => {
+                    if let Err(e) = exporter.flush() {
+                        error!("export flush: {}", e);
+                    }
+                }
+            }
+        }
+        if let Err(e) = exporter.finish() {
+            error!("export finish: {}", e);
+        }
+    });
+    Ok((tx, handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::Level;
+    use std::fs;
+
+    fn summary() -> Summary {
+        let level = |price, amount| Level {
+            exchange: "binance".to_string(),
+            price,
+            amount,
+            ..Default::default()
+        };
+        Summary {
+            pair: "BTC,USDT".to_string(),
+            bids: vec![level(99.0, 1.0), level(98.0, 2.0)],
+            asks: vec![level(101.0, 0.5)],
+            mid_price: 100.0,
+            spread: 2.0,
+            spread_bps: 200.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_row() {
+        let row = Row::new(1, &summary(), 2);
+        assert_eq!(row.values.len(), columns(2).len());
+        let value = |column: &str| row.values[columns(2).iter().position(|c| c == column).unwrap()];
+        assert_eq!(value("best_bid"), Some(99.0));
+        assert_eq!(value("spread_bps"), Some(200.0));
+        assert_eq!(value("bid_amount_2"), Some(2.0));
+        assert_eq!(value("ask_price_2"), None);
+
+        let one_sided = Summary {
+            asks: vec![],
+            ..summary()
+        };
+        let row = Row::new(1, &one_sided, 2);
+        assert_eq!(row.values[..5], [Some(99.0), None, None, None, None]);
+    }
+
+    #[test]
+    fn test_csv() {
+        let dir = std::env::temp_dir().join(format!("export_test_{}", get_unixtime()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut exporter = Exporter::new(ExportSetting {
+            path: dir.join("summaries").to_string_lossy().to_string(),
+            format: ExportFormat::Csv,
+            levels: 1,
+            rotate_bytes: 0,
+            rotate_secs: 0,
+        });
+        exporter.write(1700000000000, &summary()).unwrap();
+        exporter.finish().unwrap();
+        let file = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        assert_eq!(file.extension().unwrap(), "csv");
+        let content = fs::read_to_string(&file).unwrap();
+        assert_eq!(
+            content,
+            "ts_ms,pair,best_bid,best_ask,mid_price,spread,spread_bps,bid_price_1,bid_amount_1,ask_price_1,ask_amount_1\n\
+             1700000000000,\"BTC,USDT\",99,101,100,2,200,99,1,101,0.5\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet() {
+        use arrow2::io::parquet::read;
+        let dir = std::env::temp_dir().join(format!("export_parquet_test_{}", get_unixtime()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut exporter = Exporter::new(ExportSetting {
+            path: dir.join("summaries").to_string_lossy().to_string(),
+            format: ExportFormat::Parquet,
+            levels: 2,
+            rotate_bytes: 0,
+            rotate_secs: 0,
+        });
+        for ts in 0..3 {
+            exporter.write(ts, &summary()).unwrap();
+        }
+        exporter.finish().unwrap();
+        let path = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        let mut file = fs::File::open(&path).unwrap();
+        let metadata = read::read_metadata(&mut file).unwrap();
+        assert_eq!(metadata.num_rows, 3);
+        let schema = read::infer_schema(&metadata).unwrap();
+        assert_eq!(schema.fields.len(), 2 + columns(2).len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,355 @@
+use crate::config::RoundingMode;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+use std::str::FromStr;
+
+// the most decimals kept. Enough for the satoshis and the 18 decimals tokens.
+const MAX_SCALE: u32 = 18;
+// the mantissas parsed stay below, so that one more digit can't overflow
+const PARSE_LIMIT: i128 = 10i128.pow(37);
+
+fn pow10(n: u32) -> i128 {
+    10i128.pow(n)
+}
+
+// a decimal number stored as mantissa * 10^-scale, without allocation.
+// The scale is the one quoted by the exchange for the pair, trailing zeros stripped, so that
+// the equal numbers have the same representation whatever the exchange.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Fixed {
+    mantissa: i64,
+    scale: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseFixedError(String);
+
+impl fmt::Display for ParseFixedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid decimal: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseFixedError {}
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed {
+        mantissa: 0,
+        scale: 0,
+    };
+    pub const MAX: Fixed = Fixed {
+        mantissa: i64::MAX,
+        scale: 0,
+    };
+    pub const BASIS_POINT: Fixed = Fixed {
+        mantissa: 1,
+        scale: 4,
+    };
+
+    // the least significant decimals are rounded off (half away from zero) until it fits.
+    // None if the integer part alone doesn't fit.
+    fn new(mut mantissa: i128, mut scale: u32) -> Option<Fixed> {
+        while scale > 0 && (scale > MAX_SCALE || i64::try_from(mantissa).is_err()) {
+            let rounding = if mantissa < 0 { -5 } else { 5 };
+            mantissa = (mantissa + rounding) / 10;
+            scale -= 1;
+        }
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        if mantissa == 0 {
+            return Some(Fixed::ZERO);
+        }
+        Some(Fixed {
+            mantissa: i64::try_from(mantissa).ok()?,
+            scale,
+        })
+    }
+
+    // the mantissas of both, on the same scale
+    fn align(&self, other: &Fixed) -> (i128, i128, u32) {
+        let scale = self.scale.max(other.scale);
+        (
+            self.mantissa as i128 * pow10(scale - self.scale),
+            other.mantissa as i128 * pow10(scale - other.scale),
+            scale,
+        )
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn abs(self) -> Fixed {
+        Fixed {
+            mantissa: self.mantissa.saturating_abs(),
+            scale: self.scale,
+        }
+    }
+
+    // Option to stay a drop-in of ToPrimitive::to_f64
+    pub fn to_f64(self) -> Option<f64> {
+        Some(self.mantissa as f64 / 10f64.powi(self.scale as i32))
+    }
+
+    pub fn from_f64(value: f64) -> Option<Fixed> {
+        value.to_string().parse().ok()
+    }
+
+    // the nearest multiple of the tick, downwards or upwards. The value itself for a tick <= 0.
+    pub fn round_to(self, tick: Fixed, up: bool) -> Fixed {
+        if tick.mantissa <= 0 {
+            return self;
+        }
+        let (value, tick_mantissa, scale) = self.align(&tick);
+        let ticks = if up {
+            -(-value).div_euclid(tick_mantissa)
+        } else {
+            value.div_euclid(tick_mantissa)
+        };
+        Fixed::new(ticks * tick_mantissa, scale).unwrap_or(Fixed::MAX)
+    }
+}
+
+impl Fixed {
+    // the decimals past the given ones dropped with mode
+    pub fn round_dp(self, decimals: u32, mode: RoundingMode) -> Fixed {
+        if self.scale <= decimals {
+            return self;
+        }
+        let divisor = pow10(self.scale - decimals);
+        let mantissa = (self.mantissa as i128).abs();
+        let (quotient, remainder) = (mantissa / divisor, mantissa % divisor);
+        let half = divisor / 2;
+        let carry = match mode {
+            RoundingMode::HalfUp => remainder >= half,
+            RoundingMode::HalfEven => remainder > half || (remainder == half && quotient % 2 == 1),
+            RoundingMode::Down => false,
+            RoundingMode::Up => remainder > 0,
+        };
+        let rounded = quotient + carry as i128;
+        let signed = if self.mantissa < 0 { -rounded } else { rounded };
+        Fixed::new(signed, decimals).unwrap_or(Fixed::MAX)
+    }
+}
+
+impl Ord for Fixed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, _) = self.align(other);
+        a.cmp(&b)
+    }
+}
+
+impl PartialOrd for Fixed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Fixed {
+    type Err = ParseFixedError;
+
+    // [-+]digits[.digits][e[-+]digits]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseFixedError(s.to_string());
+        let (number, exponent) = match s.find(['e', 'E']) {
+            Some(index) => (
+                &s[..index],
+                s[index + 1..].parse::<i32>().map_err(|_| err())?,
+            ),
+            None => (s, 0),
+        };
+        let (negative, number) = match number.as_bytes().first() {
+            Some(b'-') => (true, &number[1..]),
+            Some(b'+') => (false, &number[1..]),
+            _ => (false, number),
+        };
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(err());
+        }
+        let mut mantissa: i128 = 0;
+        let mut scale: i32 = -exponent;
+        for (index, c) in integer.chars().chain(fraction.chars()).enumerate() {
+            let digit = c.to_digit(10).ok_or_else(err)? as i128;
+            let is_fraction = index >= integer.len();
+            match mantissa.checked_mul(10).and_then(|m| m.checked_add(digit)) {
+                Some(m) if m < PARSE_LIMIT => {
+                    mantissa = m;
+                    if is_fraction {
+                        scale += 1;
+                    }
+                }
+                // the decimals beyond the precision are dropped
+                _ if is_fraction => continue,
+                _ => scale -= 1,
+            }
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        if scale < 0 {
+            mantissa = u32::try_from(-scale)
+                .ok()
+                .filter(|n| *n < 38)
+                .and_then(|n| mantissa.checked_mul(pow10(n)))
+                .ok_or_else(err)?;
+            scale = 0;
+        }
+        Fixed::new(mantissa, scale as u32).ok_or_else(err)
+    }
+}
+
+// {:.N} pads the decimals with zeros up to N, it doesn't round
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = self.scale as usize;
+        let padding = f.precision().map_or(0, |p| p.saturating_sub(scale));
+        let zeros = "0".repeat(padding);
+        if self.scale == 0 {
+            let point = if padding > 0 { "." } else { "" };
+            return write!(f, "{}{}{}", self.mantissa, point, zeros);
+        }
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        if digits.len() > scale {
+            let (integer, fraction) = digits.split_at(digits.len() - scale);
+            write!(f, "{}{}.{}{}", sign, integer, fraction, zeros)
+        } else {
+            write!(f, "{}0.{:0>width$}{}", sign, digits, zeros, width = scale)
+        }
+    }
+}
+
+impl fmt::Debug for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl From<i64> for Fixed {
+    fn from(value: i64) -> Self {
+        Fixed {
+            mantissa: value,
+            scale: 0,
+        }
+    }
+}
+
+impl From<i32> for Fixed {
+    fn from(value: i32) -> Self {
+        Fixed::from(value as i64)
+    }
+}
+
+// the sums saturate, a book never holds that much
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        let (a, b, scale) = self.align(&other);
+        Fixed::new(a + b, scale).unwrap_or(Fixed::MAX)
+    }
+}
+
+impl AddAssign<&Fixed> for Fixed {
+    fn add_assign(&mut self, other: &Fixed) {
+        *self = *self + *other;
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        self + -other
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, other: Fixed) -> Fixed {
+        let mantissa = self.mantissa as i128 * other.mantissa as i128;
+        Fixed::new(mantissa, self.scale + other.scale).unwrap_or(Fixed::MAX)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed {
+            mantissa: -self.mantissa,
+            scale: self.scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(s: &str) -> Fixed {
+        Fixed::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(fixed("29000.10"), fixed("29000.1"));
+        assert_eq!(fixed("29000.10").to_string(), "29000.1");
+        assert_eq!(fixed("0.00001234").to_string(), "0.00001234");
+        assert_eq!(fixed("-1.5").to_string(), "-1.5");
+        assert_eq!(fixed("100"), Fixed::from(100));
+        assert_eq!(fixed("1e-8").to_string(), "0.00000001");
+        assert_eq!(fixed("1.5E3"), Fixed::from(1500));
+        assert_eq!(fixed("0.000"), Fixed::ZERO);
+        // beyond the precision
+        assert_eq!(
+            fixed("123456789.1234567890123456789").to_string(),
+            "123456789.123456789"
+        );
+        assert!(Fixed::from_str("").is_err());
+        assert!(Fixed::from_str("1.2.3").is_err());
+        assert!(Fixed::from_str("abc").is_err());
+        assert!(Fixed::from_str("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert!(fixed("100.01") > fixed("100.001"));
+        assert!(fixed("-0.5") < Fixed::ZERO);
+        assert_eq!(fixed("0.1") + fixed("0.25"), fixed("0.35"));
+        assert_eq!(fixed("100") * fixed("0.999"), fixed("99.9"));
+        assert_eq!(fixed("100.3").to_f64(), Some(100.3));
+        assert_eq!(Fixed::from_f64(10.0), Some(Fixed::from(10)));
+        assert_eq!(-fixed("1.5"), fixed("-1.5"));
+
+        let tick = fixed("0.1");
+        assert_eq!(fixed("100.04").round_to(tick, false), fixed("100"));
+        assert_eq!(fixed("100.21").round_to(tick, true), fixed("100.3"));
+        assert_eq!(fixed("100.2").round_to(tick, true), fixed("100.2"));
+        assert_eq!(fixed("-0.05").round_to(tick, false), fixed("-0.1"));
+    }
+
+    #[test]
+    fn test_round_dp() {
+        let round = |s: &str, mode| fixed(s).round_dp(2, mode);
+        assert_eq!(round("1.005", RoundingMode::HalfUp), fixed("1.01"));
+        assert_eq!(round("1.005", RoundingMode::HalfEven), fixed("1"));
+        assert_eq!(round("1.015", RoundingMode::HalfEven), fixed("1.02"));
+        assert_eq!(round("1.0051", RoundingMode::HalfEven), fixed("1.01"));
+        assert_eq!(round("-1.005", RoundingMode::HalfUp), fixed("-1.01"));
+        assert_eq!(round("1.009", RoundingMode::Down), fixed("1"));
+        assert_eq!(round("1.001", RoundingMode::Up), fixed("1.01"));
+        assert_eq!(round("-1.001", RoundingMode::Up), fixed("-1.01"));
+        assert_eq!(round("1.5", RoundingMode::Up), fixed("1.5"));
+        assert_eq!(
+            fixed("0.999").round_dp(0, RoundingMode::HalfUp),
+            Fixed::from(1)
+        );
+
+        assert_eq!(format!("{:.2}", fixed("1.5")), "1.50");
+        assert_eq!(format!("{:.2}", fixed("7")), "7.00");
+        assert_eq!(format!("{:.2}", fixed("-0.005")), "-0.005");
+        assert_eq!(format!("{:.4}", fixed("0.05")), "0.0500");
+    }
+}
@@ -0,0 +1,197 @@
+// test only. The golden fixtures of the websocket adapters: src/test_resource/golden/
+// {exchange}/{case}.jsonl holds the frames of a captured session, one per line, and
+// {case}.yaml the books expected after the last of them. The frames must carry the baseline
+// of the books, the rest snapshots aren't fetched.
+// GOLDEN_UPDATE=1 rewrites the yaml files from what the adapters parse, to review in the diff.
+use crate::apitree;
+use crate::fixed::Fixed;
+use crate::orderbook::Orderbook;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const ROOT: &str = "src/test_resource/golden";
+
+// [price, amount] of each level, best price first
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct GoldenBook {
+    pub bids: Vec<[String; 2]>,
+    pub asks: Vec<[String; 2]>,
+}
+
+// pair => its book after the last frame
+pub type Golden = BTreeMap<String, GoldenBook>;
+
+// (price, amount) of the levels of a side
+type Levels = Vec<(Fixed, Fixed)>;
+
+impl From<&Orderbook> for GoldenBook {
+    fn from(ob: &Orderbook) -> GoldenBook {
+        let level = |(price, amount): (&Fixed, &Fixed)| [price.to_string(), amount.to_string()];
+        GoldenBook {
+            bids: ob.bid.iter().rev().map(level).collect(),
+            asks: ob.ask.iter().map(level).collect(),
+        }
+    }
+}
+
+impl GoldenBook {
+    // the levels as numbers, so that 1.50 matches 1.5
+    fn levels(&self) -> Result<(Levels, Levels)> {
+        let parse = |levels: &Vec<[String; 2]>| {
+            levels
+                .iter()
+                .map(|[price, amount]| Ok((Fixed::from_str(price)?, Fixed::from_str(amount)?)))
+                .collect::<Result<Levels>>()
+        };
+        Ok((parse(&self.bids)?, parse(&self.asks)?))
+    }
+}
+
+// a case of the fixtures: the frames and the expected books next to them
+pub struct Case {
+    pub exchange: String,
+    pub frames: PathBuf,
+    pub expected: PathBuf,
+}
+
+impl Case {
+    pub fn name(&self) -> String {
+        let case = self
+            .frames
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        format!("{}/{}", self.exchange, case)
+    }
+}
+
+// every case under root, by exchange then name
+pub fn cases(root: &str) -> Result<Vec<Case>> {
+    let mut cases = vec![];
+    for dir in fs::read_dir(root).with_context(|| format!("read {}", root))? {
+        let dir = dir?.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let exchange = dir.file_name().unwrap_or_default().to_string_lossy();
+        for file in fs::read_dir(&dir)? {
+            let frames = file?.path();
+            if frames.extension().is_some_and(|e| e == "jsonl") {
+                cases.push(Case {
+                    exchange: exchange.to_string(),
+                    expected: frames.with_extension("yaml"),
+                    frames,
+                });
+            }
+        }
+    }
+    cases.sort_by(|a, b| a.frames.cmp(&b.frames));
+    Ok(cases)
+}
+
+// the books the adapter of the exchange makes of the frames. A failed frame fails with
+// its line.
+pub fn parse(exchange: &str, frames: &Path) -> Result<Golden> {
+    let mut api = apitree::ws(exchange)?;
+    let content = fs::read_to_string(frames).with_context(|| format!("read {:?}", frames))?;
+    let mut books = HashMap::new();
+    for (index, frame) in content.lines().enumerate() {
+        if frame.is_empty() {
+            continue;
+        }
+        let parsed = api
+            .parse(frame)
+            .map_err(|e| anyhow!("{:?}:{}: {}", frames, index + 1, e))?;
+        if let Some(ob) = parsed.book() {
+            books.insert(ob.pair.clone(), ob);
+        }
+    }
+    Ok(books
+        .iter()
+        .map(|(pair, ob)| (pair.clone(), GoldenBook::from(ob)))
+        .collect())
+}
+
+// the difference of the parsed books from the expected ones, None if they match
+pub fn compare(expected: &Golden, parsed: &Golden) -> Result<Option<String>> {
+    let mut problems = vec![];
+    let pairs: BTreeSet<&String> = expected.keys().chain(parsed.keys()).collect();
+    for pair in pairs {
+        match (expected.get(pair), parsed.get(pair)) {
+            (Some(expected), Some(parsed)) => {
+                let (expected, parsed) = (expected.levels()?, parsed.levels()?);
+                if expected.0 != parsed.0 {
+                    problems.push(format!("{} bids {:?} != {:?}", pair, parsed.0, expected.0));
+                }
+                if expected.1 != parsed.1 {
+                    problems.push(format!("{} asks {:?} != {:?}", pair, parsed.1, expected.1));
+                }
+            }
+            (Some(_), None) => problems.push(format!("{}: no book parsed", pair)),
+            (None, Some(_)) => problems.push(format!("{}: not expected", pair)),
+            (None, None) => {}
+        }
+    }
+    Ok((!problems.is_empty()).then(|| problems.join(", ")))
+}
+
+// run the case against its expected books, or write them with update
+pub fn check(case: &Case, update: bool) -> Result<Option<String>> {
+    let parsed = parse(&case.exchange, &case.frames)?;
+    if update {
+        fs::write(&case.expected, serde_yaml::to_string(&parsed)?)?;
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&case.expected)
+        .with_context(|| format!("read {:?}, GOLDEN_UPDATE=1 writes it", case.expected))?;
+    let expected: Golden = serde_yaml::from_str(&content)?;
+    compare(&expected, &parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden() {
+        let update = std::env::var_os("GOLDEN_UPDATE").is_some();
+        let mut failures = vec![];
+        let mut ran = 0;
+        for case in cases(ROOT).unwrap() {
+            // the exchanges out of the build
+            if apitree::ws(&case.exchange).is_err() {
+                continue;
+            }
+            ran += 1;
+            match check(&case, update) {
+                Ok(None) => {}
+                Ok(Some(problem)) => failures.push(format!("{}: {}", case.name(), problem)),
+                Err(e) => failures.push(format!("{}: {}", case.name(), e)),
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+        assert!(ran > 0 || !cfg!(feature = "all"));
+    }
+
+    #[test]
+    fn test_compare() {
+        let book = |bids: &[[&str; 2]]| GoldenBook {
+            bids: bids.iter().map(|l| l.map(String::from)).collect(),
+            asks: vec![],
+        };
+        let expected = Golden::from([("btcusd".to_string(), book(&[["100.10", "1"]]))]);
+        let same = Golden::from([("btcusd".to_string(), book(&[["100.1", "1.0"]]))]);
+        assert_eq!(compare(&expected, &same).unwrap(), None);
+        let other = Golden::from([
+            ("btcusd".to_string(), book(&[["100.1", "2"]])),
+            ("ethusd".to_string(), book(&[])),
+        ]);
+        let problem = compare(&expected, &other).unwrap().unwrap();
+        assert!(problem.contains("btcusd bids"), "{}", problem);
+        assert!(problem.contains("ethusd: not expected"), "{}", problem);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::proto::{ConnectionState, ExchangeStatus, StatusReport};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+struct ExchangeHealth {
+    state: ConnectionState,
+    last_message: u64,
+    pairs: Vec<String>,
+    reconnects: u64,
+    last_error: String,
+    down_until: u64,
+}
+
+// Shared registry of the exchange connection health.
+// Updated by each executor, and read by the grpc GetStatus call.
+#[derive(Debug, Clone, Default)]
+pub struct HealthRegistry {
+    inner: Arc<Mutex<BTreeMap<String, ExchangeHealth>>>,
+}
+
+impl HealthRegistry {
+    pub fn new() -> HealthRegistry {
+        HealthRegistry::default()
+    }
+
+    fn update<F: FnOnce(&mut ExchangeHealth)>(&self, exchange: &str, f: F) {
+        let mut tmp = self.inner.lock().unwrap();
+        let health = tmp
+            .entry(exchange.to_string())
+            .or_insert_with(|| ExchangeHealth {
+                state: ConnectionState::Disconnected,
+                last_message: 0,
+                pairs: vec![],
+                reconnects: 0,
+                last_error: String::new(),
+                down_until: 0,
+            });
+        f(health);
+    }
+
+    pub fn connecting(&self, exchange: &str, pairs: &[String]) {
+        self.update(exchange, |h| {
+            h.state = ConnectionState::Connecting;
+            h.pairs = pairs.to_vec();
+            h.down_until = 0;
+        });
+    }
+
+    pub fn connected(&self, exchange: &str) {
+        self.update(exchange, |h| h.state = ConnectionState::Connected);
+    }
+
+    // mark the exchange as disconnected. The executor will reconnect right after.
+    pub fn disconnected(&self, exchange: &str, error: &str) {
+        self.update(exchange, |h| {
+            h.state = ConnectionState::Disconnected;
+            h.reconnects += 1;
+            h.last_error = error.to_string();
+        });
+    }
+
+    // the circuit breaker opened, the executor waits the cooldown before reconnecting.
+    // until_ms: the unix time the cooldown ends.
+    pub fn down(&self, exchange: &str, until_ms: u64) {
+        self.update(exchange, |h| {
+            h.state = ConnectionState::Down;
+            h.down_until = until_ms;
+        });
+    }
+
+    // disabled through the admin service, the executor waits to be enabled.
+    pub fn disabled(&self, exchange: &str) {
+        self.update(exchange, |h| h.state = ConnectionState::Disabled);
+    }
+
+    // forget an exchange which is no longer configured.
+    pub fn remove(&self, exchange: &str) {
+        self.inner.lock().unwrap().remove(exchange);
+    }
+
+    // now_ms: the unix time the message was received
+    pub fn message(&self, exchange: &str, now_ms: u64) {
+        self.update(exchange, |h| h.last_message = now_ms);
+    }
+
+    // the exchanges which sent a message since the unix time `since`.
+    pub fn live(&self, since: u64) -> Vec<String> {
+        let tmp = self.inner.lock().unwrap();
+        tmp.iter()
+            .filter(|(_, h)| h.last_message > 0 && h.last_message >= since)
+            .map(|(exchange, _)| exchange.clone())
+            .collect()
+    }
+
+    pub fn report(&self) -> StatusReport {
+        let tmp = self.inner.lock().unwrap();
+        StatusReport {
+            exchanges: tmp
+                .iter()
+                .map(|(exchange, h)| ExchangeStatus {
+                    exchange: exchange.clone(),
+                    state: h.state as i32,
+                    last_message_ms: h.last_message,
+                    pairs: h.pairs.clone(),
+                    reconnects: h.reconnects,
+                    last_error: h.last_error.clone(),
+                    down_until_ms: h.down_until,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let registry = HealthRegistry::new();
+        registry.connecting("kraken", &["XBT/USD".to_string()]);
+        registry.connected("binance");
+        registry.disconnected("kraken", "close kraken");
+        let report = registry.report();
+        assert_eq!(report.exchanges.len(), 2);
+        assert_eq!(report.exchanges[0].exchange, "binance");
+        assert_eq!(report.exchanges[0].state, ConnectionState::Connected as i32);
+        assert_eq!(report.exchanges[1].pairs, vec!["XBT/USD".to_string()]);
+        assert_eq!(report.exchanges[1].reconnects, 1);
+        assert_eq!(report.exchanges[1].last_error, "close kraken");
+
+        registry.down("kraken", 60_000);
+        let report = registry.report();
+        assert_eq!(report.exchanges[1].state, ConnectionState::Down as i32);
+        assert_eq!(report.exchanges[1].down_until_ms, 60_000);
+        registry.connecting("kraken", &["XBT/USD".to_string()]);
+        let report = registry.report();
+        assert_eq!(
+            report.exchanges[1].state,
+            ConnectionState::Connecting as i32
+        );
+        assert_eq!(report.exchanges[1].down_until_ms, 0);
+    }
+
+    #[test]
+    fn test_live() {
+        let registry = HealthRegistry::new();
+        registry.connected("binance");
+        assert!(registry.live(1000).is_empty());
+        registry.message("binance", 10_000);
+        registry.message("kraken", 5_000);
+        assert_eq!(registry.live(9_000), vec!["binance".to_string()]);
+    }
+}
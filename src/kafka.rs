@@ -0,0 +1,102 @@
+use crate::config::KafkaSetting;
+use crate::recorder::Record;
+use anyhow::Result;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+
+// the topic and the key of a record. None => not published.
+#[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+fn route<'a>(setting: &'a KafkaSetting, record: &'a Record) -> Option<(&'a str, &'a str)> {
+    match record {
+        Record::Summary { summary, .. } => Some((&setting.summary_topic, &summary.pair)),
+        Record::Book { pair, .. } => Some((setting.book_topic.as_deref()?, pair)),
+        Record::Raw { .. } => None,
+    }
+}
+
+// start the producer. The records sent are published as json until every sender is dropped.
+#[cfg(feature = "kafka")]
+pub fn start(setting: KafkaSetting) -> Result<(UnboundedSender<Record>, JoinHandle<()>)> {
+    use log::{error, info};
+    use rdkafka::config::ClientConfig;
+    use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+    use std::time::Duration;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", &setting.brokers);
+    for (key, value) in setting.properties.iter() {
+        config.set(key, value);
+    }
+    let producer: FutureProducer = config.create()?;
+    info!("kafka producer to {}", setting.brokers);
+    let (tx, mut rx) = unbounded_channel::<Record>();
+    let handle = tokio::spawn(async move {
+        while let Some(record) = rx.recv().await {
+            let Some((topic, key)) = route(&setting, &record) else {
+                continue;
+            };
+            let payload = match serde_json::to_string(&record) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    error!("kafka: {}", e);
+                    continue;
+                }
+            };
+            // queued by librdkafka, the delivery isn't waited for
+            let record = FutureRecord::to(topic).key(key).payload(&payload);
+            if let Err((e, _)) = producer.send_result(record) {
+                error!("kafka {}: {}", topic, e);
+            }
+        }
+        if let Err(e) = producer.flush(Duration::from_secs(5)) {
+            error!("kafka flush: {}", e);
+        }
+    });
+    Ok((tx, handle))
+}
+
+#[cfg(not(feature = "kafka"))]
+pub fn start(_setting: KafkaSetting) -> Result<(UnboundedSender<Record>, JoinHandle<()>)> {
+    Err(anyhow::anyhow!(
+        "kafka is configured, but the server is built without the kafka feature"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::Summary;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_route() {
+        let mut setting = KafkaSetting {
+            brokers: "localhost:9092".to_string(),
+            summary_topic: "summaries".to_string(),
+            book_topic: None,
+            properties: HashMap::new(),
+        };
+        let summary = Record::Summary {
+            ts: 1,
+            summary: Summary {
+                pair: "BTC-USDT".to_string(),
+                ..Default::default()
+            },
+        };
+        assert_eq!(route(&setting, &summary), Some(("summaries", "BTC-USDT")));
+        let book = Record::Book {
+            ts: 1,
+            exchange: "binance".to_string(),
+            pair: "btcusdt".to_string(),
+            mark_price: None,
+            funding_rate: None,
+            bids: vec![],
+            asks: vec![],
+        };
+        assert_eq!(route(&setting, &book), None);
+        setting.book_topic = Some("books".to_string());
+        assert_eq!(route(&setting, &book), Some(("books", "btcusdt")));
+        assert_eq!(route(&setting, &Record::raw("binance", "{}")), None);
+    }
+}
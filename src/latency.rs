@@ -0,0 +1,151 @@
+use crate::orderbook::Orderbook;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Delta {
+    sum: u128,
+    count: u64,
+    last: u128,
+}
+
+impl Delta {
+    fn add(&mut self, ms: u128) {
+        self.sum += ms;
+        self.count += 1;
+        self.last = ms;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ExchangeLatency {
+    // exchange time => local receive time. Only the feeds stamping their messages.
+    feed: Delta,
+    // local receive time => grpc publish time
+    publish: Delta,
+    // pair => receive time of the last book counted, a book published twice counts once
+    counted: BTreeMap<String, u128>,
+}
+
+// picks one of the deltas of an exchange
+type Select = fn(&ExchangeLatency) -> Delta;
+
+// Shared registry of the latencies of each exchange, from the exchange time to the receive
+// time to the publish time. Updated by the publisher, read by the /metrics probe.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyRegistry {
+    inner: Arc<Mutex<BTreeMap<String, ExchangeLatency>>>,
+}
+
+impl LatencyRegistry {
+    pub fn new() -> LatencyRegistry {
+        LatencyRegistry::default()
+    }
+
+    // the book was published at published_ms. The clocks of the exchanges drift a little
+    // ahead sometimes, the negative deltas count as 0.
+    pub fn published(&self, orderbook: &Orderbook, published_ms: u128) {
+        let mut tmp = self.inner.lock().unwrap();
+        let latency = tmp.entry(orderbook.name.clone()).or_default();
+        let counted = latency.counted.entry(orderbook.pair.clone()).or_default();
+        if *counted >= orderbook.received_ts {
+            return;
+        }
+        *counted = orderbook.received_ts;
+        if let Some(exchange_ts) = orderbook.exchange_ts {
+            latency
+                .feed
+                .add(orderbook.received_ts.saturating_sub(exchange_ts));
+        }
+        latency
+            .publish
+            .add(published_ms.saturating_sub(orderbook.received_ts));
+    }
+
+    // forget an exchange which is no longer configured.
+    pub fn remove(&self, exchange: &str) {
+        self.inner.lock().unwrap().remove(exchange);
+    }
+
+    // the prometheus text exposition of the latencies
+    pub fn metrics(&self) -> String {
+        let tmp = self.inner.lock().unwrap();
+        let mut out = String::new();
+        let metrics: [(&str, &str, Select); 2] = [
+            (
+                "feed",
+                "milliseconds from the exchange time of a book to its receive time",
+                |l| l.feed,
+            ),
+            (
+                "publish",
+                "milliseconds from the receive time of a book to its grpc publish",
+                |l| l.publish,
+            ),
+        ];
+        for (name, help, delta) in metrics {
+            let metric = format!("market_aggregator_{}_latency_ms", name);
+            let _ = writeln!(out, "# HELP {} {}", metric, help);
+            let _ = writeln!(out, "# TYPE {} summary", metric);
+            for (exchange, latency) in tmp.iter() {
+                let delta = delta(latency);
+                if delta.count == 0 {
+                    continue;
+                }
+                let label = format!("{{exchange=\"{}\"}}", exchange);
+                let _ = writeln!(out, "{}_sum{} {}", metric, label, delta.sum);
+                let _ = writeln!(out, "{}_count{} {}", metric, label, delta.count);
+            }
+            let _ = writeln!(out, "# HELP {}_last the latest of {}", metric, metric);
+            let _ = writeln!(out, "# TYPE {}_last gauge", metric);
+            for (exchange, latency) in tmp.iter() {
+                let delta = delta(latency);
+                if delta.count > 0 {
+                    let _ = writeln!(
+                        out,
+                        "{}_last{{exchange=\"{}\"}} {}",
+                        metric, exchange, delta.last
+                    );
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency() {
+        let registry = LatencyRegistry::new();
+        let mut ob = Orderbook::with_pair("bitstamp", "btcusd");
+        ob.exchange_ts = Some(1000);
+        ob.received_ts = 1040;
+        registry.published(&ob, 1045);
+        // published again with another book of the symbol
+        registry.published(&ob, 1050);
+        ob.exchange_ts = Some(1100);
+        ob.received_ts = 1120;
+        registry.published(&ob, 1121);
+        // no exchange time
+        let mut binance = Orderbook::with_pair("binance", "btcusdt");
+        binance.received_ts = 1000;
+        registry.published(&binance, 1002);
+
+        let metrics = registry.metrics();
+        for line in [
+            r#"market_aggregator_feed_latency_ms_sum{exchange="bitstamp"} 60"#,
+            r#"market_aggregator_feed_latency_ms_count{exchange="bitstamp"} 2"#,
+            r#"market_aggregator_feed_latency_ms_last{exchange="bitstamp"} 20"#,
+            r#"market_aggregator_publish_latency_ms_sum{exchange="bitstamp"} 6"#,
+            r#"market_aggregator_publish_latency_ms_count{exchange="binance"} 1"#,
+            r#"market_aggregator_publish_latency_ms_last{exchange="binance"} 2"#,
+        ] {
+            assert!(metrics.lines().any(|l| l == line), "{}", line);
+        }
+        assert!(!metrics.contains(r#"feed_latency_ms_sum{exchange="binance"}"#));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::config::RedisSetting;
+use crate::proto::{Level, Summary};
+use crate::recorder::Record;
+use log::{error, info};
+use redis::aio::ConnectionManager;
+use redis::streams::StreamMaxlen;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+// wait between the attempts to reach a redis that is down
+const RETRY_SECS: u64 = 5;
+
+// the fields of the {prefix}:{pair}:top hash. The prices of an empty side are 0.
+fn top_of_book(summary: &Summary, ts: u64) -> Vec<(&'static str, String)> {
+    fn best(levels: &[Level]) -> (f64, f64, &str) {
+        levels
+            .first()
+            .map_or((0.0, 0.0, ""), |l| (l.price, l.amount, l.exchange.as_str()))
+    }
+    let (bid, bid_amount, bid_exchange) = best(&summary.bids);
+    let (ask, ask_amount, ask_exchange) = best(&summary.asks);
+    vec![
+        ("best_bid", bid.to_string()),
+        ("best_bid_amount", bid_amount.to_string()),
+        ("best_bid_exchange", bid_exchange.to_string()),
+        ("best_ask", ask.to_string()),
+        ("best_ask_amount", ask_amount.to_string()),
+        ("best_ask_exchange", ask_exchange.to_string()),
+        ("spread", summary.spread.to_string()),
+        ("spread_bps", summary.spread_bps.to_string()),
+        ("crossed", summary.crossed.to_string()),
+        ("mid_price", summary.mid_price.to_string()),
+        ("ts", ts.to_string()),
+    ]
+}
+
+// the commands writing one summary, applied at once
+fn pipeline(setting: &RedisSetting, summary: &Summary, ts: u64) -> redis::Pipeline {
+    let key = format!("{}:{}", setting.prefix, summary.pair);
+    let (top_key, depth_key) = (format!("{}:top", key), format!("{}:depth", key));
+    let top = top_of_book(summary, ts);
+    let depth = serde_json::to_string(summary).unwrap_or_default();
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.del(&top_key).ignore();
+    pipe.hset_multiple(&top_key, &top).ignore();
+    pipe.set(&depth_key, depth).ignore();
+    if setting.ttl_secs > 0 {
+        let ttl = setting.ttl_secs as usize;
+        pipe.expire(&top_key, ttl).ignore();
+        pipe.expire(&depth_key, ttl).ignore();
+    }
+    if setting.stream_maxlen > 0 {
+        let maxlen = StreamMaxlen::Approx(setting.stream_maxlen);
+        pipe.xadd_maxlen(format!("{}:stream", key), maxlen, "*", &top)
+            .ignore();
+    }
+    pipe
+}
+
+async fn run(setting: RedisSetting, mut rx: UnboundedReceiver<Record>) {
+    let client = match redis::Client::open(setting.url.as_str()) {
+        Ok(client) => client,
+        Err(e) => {
+            error!("redis {}: {}", setting.url, e);
+            return;
+        }
+    };
+    let mut connection: Option<ConnectionManager> = None;
+    let mut retry_at = Instant::now();
+    while let Some(record) = rx.recv().await {
+        let Record::Summary { ts, summary } = record else {
+            continue;
+        };
+        // the summaries are dropped while redis is down, only the latest matters
+        if connection.is_none() && Instant::now() >= retry_at {
+            match ConnectionManager::new(client.clone()).await {
+                Ok(c) => {
+                    info!("redis connected to {}", setting.url);
+                    connection = Some(c);
+                }
+                Err(e) => {
+                    error!("redis {}: {}", setting.url, e);
+                    retry_at = Instant::now() + Duration::from_secs(RETRY_SECS);
+                }
+            }
+        }
+        let Some(connection) = connection.as_mut() else {
+            continue;
+        };
+        // the connection manager reconnects by itself
+        let result: redis::RedisResult<()> = pipeline(&setting, &summary, ts)
+            .query_async(connection)
+            .await;
+        if let Err(e) = result {
+            error!("redis {}: {}", summary.pair, e);
+        }
+    }
+}
+
+// start the writer. It stops once every sender is dropped.
+pub fn start(setting: RedisSetting) -> (UnboundedSender<Record>, JoinHandle<()>) {
+    let (tx, rx) = unbounded_channel();
+    (tx, tokio::spawn(run(setting, rx)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_of_book() {
+        let summary = Summary {
+            spread: 1.5,
+            mid_price: 100.75,
+            bids: vec![Level {
+                exchange: "binance".to_string(),
+                price: 100.0,
+                amount: 2.0,
+                ..Default::default()
+            }],
+            pair: "BTC-USDT".to_string(),
+            ..Default::default()
+        };
+        let top = top_of_book(&summary, 1);
+        assert_eq!(top[0], ("best_bid", "100".to_string()));
+        assert_eq!(top[1], ("best_bid_amount", "2".to_string()));
+        assert_eq!(top[2], ("best_bid_exchange", "binance".to_string()));
+        // empty side
+        assert_eq!(top[3], ("best_ask", "0".to_string()));
+        assert_eq!(top[6], ("spread", "1.5".to_string()));
+
+        let setting = RedisSetting {
+            url: "redis://127.0.0.1".to_string(),
+            prefix: "orderbook".to_string(),
+            ttl_secs: 10,
+            stream_maxlen: 100,
+        };
+        let pipe = pipeline(&setting, &summary, 1);
+        let names: Vec<String> = pipe
+            .cmd_iter()
+            .map(|cmd| match cmd.args_iter().next() {
+                Some(redis::Arg::Simple(name)) => String::from_utf8_lossy(name).to_string(),
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec!["DEL", "HMSET", "SET", "EXPIRE", "EXPIRE", "XADD"]
+        );
+    }
+}
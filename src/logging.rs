@@ -0,0 +1,134 @@
+use crate::config::{LogFormat, LogLevel};
+use log::info;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::SystemTime;
+
+// the fields attached to every line logged from an executor task
+#[derive(Default, Clone)]
+struct LogContext {
+    exchange: String,
+    pair: String,
+}
+
+tokio::task_local! {
+    static CONTEXT: RefCell<LogContext>;
+}
+
+// run the future with the exchange attached to its logs.
+pub fn scope<F: Future>(exchange: &str, fut: F) -> impl Future<Output = F::Output> {
+    let context = LogContext {
+        exchange: exchange.to_string(),
+        ..Default::default()
+    };
+    CONTEXT.scope(RefCell::new(context), fut)
+}
+
+// attach the pair to the following logs of the task. Empty => no pair.
+// No-op outside of scope.
+pub fn set_pair(pair: &str) {
+    let _ = CONTEXT.try_with(|context| {
+        let mut context = context.borrow_mut();
+        if context.pair != pair {
+            context.pair = pair.to_string();
+        }
+    });
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    // unix millis
+    ts: u128,
+    level: &'a str,
+    // the log target. Module path unless the call names the event
+    event: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    exchange: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pair: &'a str,
+    message: String,
+}
+
+fn json_line(ts: u128, record: &log::Record) -> String {
+    let context = CONTEXT
+        .try_with(|context| context.borrow().clone())
+        .unwrap_or_default();
+    let line = Line {
+        ts,
+        level: record.level().as_str(),
+        event: record.target(),
+        exchange: &context.exchange,
+        pair: &context.pair,
+        message: record.args().to_string(),
+    };
+    serde_json::to_string(&line).unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e))
+}
+
+pub fn setup(
+    log_file: Option<String>,
+    log_level: LogLevel,
+    log_format: LogFormat,
+) -> Result<(), fern::InitError> {
+    let tmp = fern::Dispatch::new()
+        .format(move |out, message, record| match log_format {
+            LogFormat::Text => out.finish(format_args!("{}", message)),
+            LogFormat::Json => {
+                let ts = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis());
+                out.finish(format_args!("{}", json_line(ts, record)))
+            }
+        })
+        // the dispatch lets every level through, log::max_level filters, see set_level
+        .level(log::LevelFilter::Debug)
+        .chain(std::io::stdout());
+    if let Some(path) = log_file {
+        tmp.chain(fern::log_file(path)?).apply()?;
+    } else {
+        tmp.apply()?;
+    }
+    log::set_max_level(log_level.to_level_filter());
+    Ok(())
+}
+
+// change the level at runtime, without a restart dropping the subscriptions
+pub fn set_level(log_level: LogLevel) {
+    if log::max_level() != log_level.to_level_filter() {
+        log::set_max_level(log_level.to_level_filter());
+        info!("log level set to {:?}", log_level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line() -> String {
+        json_line(
+            1,
+            &log::Record::builder()
+                .level(log::Level::Error)
+                .target("reconnect")
+                .args(format_args!("stream gets closed"))
+                .build(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_json_line() {
+        assert_eq!(
+            line(),
+            r#"{"ts":1,"level":"ERROR","event":"reconnect","message":"stream gets closed"}"#
+        );
+        let result = scope("binance", async {
+            set_pair("btcusdt");
+            line()
+        })
+        .await;
+        assert_eq!(
+            result,
+            r#"{"ts":1,"level":"ERROR","event":"reconnect","exchange":"binance","pair":"btcusdt","message":"stream gets closed"}"#
+        );
+    }
+}
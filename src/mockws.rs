@@ -0,0 +1,767 @@
+// test only. A local websocket server playing canned exchange payloads, so the exchange
+// connections can be tested without the live venues.
+use crate::config::{Environment, ExchangeSetting, NetworkSetting};
+use futures_util::{SinkExt, StreamExt};
+use std::fs;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+// the payloads of src/test_resource/mock/{name}.jsonl, one per line.
+pub fn fixture(name: &str) -> Vec<String> {
+    let path = format!("src/test_resource/mock/{}.jsonl", name);
+    fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("read {}: {}", path, e))
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_string())
+        .collect()
+}
+
+pub fn setting(pair: &str) -> ExchangeSetting {
+    ExchangeSetting {
+        pair: pair.to_string(),
+        ws_api: true,
+        wait_secs: 1,
+        symbol: None,
+        fee_bps: 0.0,
+        hybrid: false,
+        incremental: false,
+        min_wait_ms: 0,
+        max_wait_ms: 0,
+        contract: None,
+        internal_depth: None,
+        publish_depth: None,
+        environment: Environment::Production,
+        quote: None,
+    }
+}
+
+pub struct MockExchange {
+    pub url: String,
+    // the text messages sent by the clients, ex: the subscriptions
+    pub received: UnboundedReceiver<String>,
+    handle: JoinHandle<()>,
+}
+
+impl MockExchange {
+    // one session of payloads per connection, in order. The payloads are sent after the
+    // first message of the client. The connection is closed at the end of every session
+    // but the last one, which stays open until the client leaves.
+    pub async fn start(sessions: Vec<Vec<String>>) -> MockExchange {
+        MockExchange::serve(sessions, true).await
+    }
+
+    // one session per connection, served at the same time, ex: the shards of an exchange.
+    // Every connection stays open until the client leaves.
+    pub async fn concurrent(sessions: Vec<Vec<String>>) -> MockExchange {
+        MockExchange::serve(sessions, false).await
+    }
+
+    async fn serve(sessions: Vec<Vec<String>>, close: bool) -> MockExchange {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (tx, received) = unbounded_channel();
+        let handle = tokio::spawn(async move {
+            let last = sessions.len().saturating_sub(1);
+            let mut connections = JoinSet::new();
+            for (index, session) in sessions.into_iter().enumerate() {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let tx = tx.clone();
+                let close = close && index < last;
+                let connection = async move {
+                    let Ok(ws_stream) = tokio_tungstenite::accept_async(stream).await else {
+                        return;
+                    };
+                    let (mut sink, mut stream) = ws_stream.split();
+                    let mut session = Some(session);
+                    while let Some(Ok(message)) = stream.next().await {
+                        let Message::Text(text) = message else {
+                            continue;
+                        };
+                        let _ = tx.send(text);
+                        if let Some(payloads) = session.take() {
+                            for payload in payloads {
+                                let _ = sink.send(Message::Text(payload)).await;
+                            }
+                            if close {
+                                let _ = sink.send(Message::Close(None)).await;
+                                break;
+                            }
+                        }
+                    }
+                };
+                // the next session starts once this one is closed
+                match close {
+                    true => connection.await,
+                    false => {
+                        connections.spawn(connection);
+                    }
+                }
+            }
+            while connections.join_next().await.is_some() {}
+        });
+        MockExchange {
+            url,
+            received,
+            handle,
+        }
+    }
+
+    // the network setting pointing the exchange to the mock
+    pub fn network(&self, exchange: &str) -> NetworkSetting {
+        let mut network = NetworkSetting::default();
+        network
+            .endpoints
+            .insert(exchange.to_string(), self.url.clone());
+        network
+    }
+}
+
+impl Drop for MockExchange {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apitree::wsapi::{ExchangeAdapter, ParsedEvent};
+    use crate::bus::Event;
+    use crate::config::{ExchangeChange, LagPolicy};
+    use crate::error::{self, Error};
+    use crate::fixed::Fixed;
+    use crate::health::HealthRegistry;
+    use crate::orderbook::Orderbook;
+    use crate::proto::{
+        AggServer, ConnectionState, Control, OrderbookAggregator, PairRequest, Summary,
+        SummaryRequest,
+    };
+    use crate::{
+        apply_control, executor, pair_names, reconnect, watch_config, Exchange, ExecutorContext,
+        Executors,
+    };
+    use async_trait::async_trait;
+    use std::collections::{BTreeSet, HashMap};
+    use std::str::FromStr;
+    use tokio::time::{timeout, Duration};
+    use tokio_util::sync::CancellationToken;
+    use tonic::Request;
+
+    // the first book the exchange parses from the fixture
+    async fn first_book(exchange: &str, pair: &str) -> (Orderbook, MockExchange) {
+        first_book_offloaded(exchange, pair, 0).await
+    }
+
+    async fn first_book_offloaded(
+        exchange: &str,
+        pair: &str,
+        offload_bytes: usize,
+    ) -> (Orderbook, MockExchange) {
+        let mock = MockExchange::start(vec![fixture(exchange)]).await;
+        let mut client = Exchange::new(exchange);
+        client.offload_bytes = offload_bytes;
+        client
+            .connect(vec![setting(pair)], &mock.network(exchange))
+            .await
+            .unwrap();
+        let book = timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(book) = client.next().await.unwrap() {
+                    if !book.bid.is_empty() {
+                        return book;
+                    }
+                }
+            }
+        })
+        .await
+        .unwrap();
+        client.close().await;
+        (book, mock)
+    }
+
+    async fn received(mock: &mut MockExchange) -> String {
+        timeout(Duration::from_secs(5), mock.received.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn next(rx: &mut UnboundedReceiver<Event>) -> Event {
+        timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    fn best_bid(book: &Orderbook) -> Fixed {
+        *book.bid.keys().next_back().unwrap()
+    }
+
+    #[cfg(all(
+        feature = "exchange-binance",
+        feature = "exchange-bitstamp",
+        feature = "exchange-kraken"
+    ))]
+    #[tokio::test]
+    async fn test_exchange_next() {
+        let (book, mut mock) = first_book("binance", "btcusdt").await;
+        assert_eq!(book.pair, "btcusdt");
+        assert_eq!(best_bid(&book), Fixed::from_str("29000.10").unwrap());
+        assert!(received(&mut mock).await.contains("btcusdt@depth"));
+
+        let (book, mut mock) = first_book("bitstamp", "btcusd").await;
+        assert_eq!(book.pair, "btcusd");
+        assert_eq!(best_bid(&book), Fixed::from_str("29001").unwrap());
+        assert!(received(&mut mock).await.contains("order_book_btcusd"));
+
+        let (book, _) = first_book("kraken", "XBT/USD").await;
+        assert_eq!(book.pair, "XBT/USD");
+        assert_eq!(best_bid(&book), Fixed::from_str("29003").unwrap());
+    }
+
+    #[cfg(feature = "exchange-binance")]
+    #[tokio::test]
+    async fn test_exchange_shards() {
+        // 2 streams per connection, the depth and the ticker of one pair
+        let eth: Vec<String> = fixture("binance")
+            .iter()
+            .map(|p| p.replace("btcusdt", "ethusdt"))
+            .collect();
+        let mut mock = MockExchange::concurrent(vec![fixture("binance"), eth]).await;
+        let mut client = Exchange::new("binance");
+        client.max_streams = Some(2);
+        client
+            .connect(
+                vec![setting("btcusdt"), setting("ethusdt")],
+                &mock.network("binance"),
+            )
+            .await
+            .unwrap();
+        let mut pairs = BTreeSet::new();
+        timeout(Duration::from_secs(5), async {
+            while pairs.len() < 2 {
+                if let Some(book) = client.next().await.unwrap() {
+                    pairs.insert(book.pair);
+                }
+            }
+        })
+        .await
+        .unwrap();
+        // each connection subscribes its own pair
+        let mut subscriptions = vec![];
+        for _ in 0..4 {
+            subscriptions.push(received(&mut mock).await);
+        }
+        let subscribed = |pair: &str| subscriptions.iter().filter(|s| s.contains(pair)).count();
+        assert_eq!((subscribed("btcusdt"), subscribed("ethusdt")), (2, 2));
+        // the new pair has no room left
+        assert!(client.subscribe("solusdt").await.is_err());
+        client.unsubscribe("ethusdt").await.unwrap();
+        for _ in 0..2 {
+            assert!(received(&mut mock).await.contains("UNSUBSCRIBE"));
+        }
+        client.subscribe("solusdt").await.unwrap();
+        assert!(received(&mut mock).await.contains("solusdt@depth"));
+        client.close().await;
+    }
+
+    #[cfg(feature = "exchange-binance")]
+    #[tokio::test]
+    async fn test_exchange_offload() {
+        // every frame goes to the blocking pool, the adapter comes back for the next one
+        let (book, _) = first_book_offloaded("binance", "btcusdt", 1).await;
+        let (inline, _) = first_book("binance", "btcusdt").await;
+        assert_eq!((book.bid, book.ask), (inline.bid, inline.ask));
+    }
+
+    #[cfg(feature = "exchange-cryptocom")]
+    #[tokio::test]
+    async fn test_exchange_reply() {
+        // the heartbeat is answered before the book comes
+        let (book, mut mock) = first_book("cryptocom", "BTC_USDT").await;
+        assert_eq!(best_bid(&book), Fixed::from_str("29005").unwrap());
+        assert!(received(&mut mock).await.contains("book.BTC_USDT."));
+        assert_eq!(
+            received(&mut mock).await,
+            r#"{"id":1692000000000,"method":"public/respond-heartbeat"}"#
+        );
+    }
+
+    #[cfg(feature = "exchange-bitstamp")]
+    #[tokio::test]
+    async fn test_executor_reconnect() {
+        let payloads = fixture("bitstamp");
+        let mock = MockExchange::start(vec![payloads.clone(), payloads]).await;
+        let (tx, mut rx) = unbounded_channel();
+        let health = HealthRegistry::new();
+        let shutdown = CancellationToken::new();
+        let ctx = ExecutorContext {
+            network: mock.network("bitstamp"),
+            tx,
+            health: health.clone(),
+            recorder: None,
+            capture: None,
+            trace: None,
+            depth: 10,
+            breaker: None,
+            backfill: false,
+            parse_offload_bytes: 0,
+            shutdown: shutdown.clone(),
+        };
+        let (_control_tx, control_rx) = unbounded_channel();
+        let handle = tokio::spawn(executor(
+            "bitstamp".to_string(),
+            vec![setting("btcusd")],
+            ctx,
+            control_rx,
+        ));
+        // one book per connection, between the status changes
+        let mut books = 0;
+        while books < 2 {
+            let event = timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if let Event::BookUpdate {
+                exchange, symbol, ..
+            } = event
+            {
+                assert_eq!((exchange.as_str(), symbol.as_str()), ("bitstamp", "btcusd"));
+                books += 1;
+            }
+        }
+        let status = health.report().exchanges.remove(0);
+        assert_eq!(status.reconnects, 1);
+        assert_eq!(status.state, ConnectionState::Connected as i32);
+
+        shutdown.cancel();
+        timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[cfg(feature = "exchange-bitstamp")]
+    #[tokio::test]
+    async fn test_executor_rejection() {
+        let mut payloads = vec![
+            r#"{"event":"bts:error","channel":"order_book_ethusd","data":{"code":null,"message":"Bad subscription string."}}"#.to_string(),
+        ];
+        payloads.extend(fixture("bitstamp"));
+        let mock = MockExchange::start(vec![payloads]).await;
+        let (tx, mut rx) = unbounded_channel();
+        let health = HealthRegistry::new();
+        let shutdown = CancellationToken::new();
+        let ctx = ExecutorContext {
+            network: mock.network("bitstamp"),
+            tx,
+            health: health.clone(),
+            recorder: None,
+            capture: None,
+            trace: None,
+            depth: 10,
+            breaker: None,
+            backfill: false,
+            parse_offload_bytes: 0,
+            shutdown: shutdown.clone(),
+        };
+        let (_control_tx, control_rx) = unbounded_channel();
+        let handle = tokio::spawn(executor(
+            "bitstamp".to_string(),
+            vec![setting("btcusd"), setting("ethusd")],
+            ctx,
+            control_rx,
+        ));
+        // the rejected pair is given up, the other one still streams
+        let (mut failed, mut book) = (None, false);
+        while failed.is_none() || !book {
+            let event = timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            match event {
+                Event::SubscriptionFailed { pair, reason, .. } => failed = Some((pair, reason)),
+                Event::BookUpdate { symbol, .. } => book |= symbol == "btcusd",
+                _ => {}
+            }
+        }
+        assert_eq!(
+            failed,
+            Some(("ethusd".to_string(), "Bad subscription string.".to_string()))
+        );
+        let status = health.report().exchanges.remove(0);
+        assert_eq!(status.pairs, vec!["btcusd"]);
+        assert_eq!(status.reconnects, 0);
+
+        shutdown.cancel();
+        timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[cfg(feature = "exchange-binance")]
+    #[tokio::test]
+    async fn test_executor_subscriptions() {
+        let mut mock = MockExchange::concurrent(vec![fixture("binance"), fixture("binance")]).await;
+        let (tx, _rx) = unbounded_channel();
+        let ctx = ExecutorContext {
+            network: mock.network("binance"),
+            tx,
+            health: HealthRegistry::new(),
+            recorder: None,
+            capture: None,
+            trace: None,
+            depth: 10,
+            breaker: None,
+            backfill: false,
+            parse_offload_bytes: 0,
+            shutdown: CancellationToken::new(),
+        };
+        let request = |pair: &str| PairRequest {
+            exchange: "binance".to_string(),
+            pair: pair.to_string(),
+        };
+        let mut pairs = vec![setting("btcusdt")];
+        let mut client = Exchange::new("binance");
+        client.connect(pairs.clone(), &ctx.network).await.unwrap();
+        // the depth and the ticker of each pair
+        for _ in 0..2 {
+            assert!(received(&mut mock).await.contains("btcusdt"));
+        }
+
+        // the pair joins the running connection
+        apply_control(
+            &mut client,
+            &mut pairs,
+            Control::Subscribe(request("ethusdt")),
+            &ctx,
+        )
+        .await
+        .unwrap();
+        for _ in 0..2 {
+            let subscription = received(&mut mock).await;
+            assert!(subscription.contains("ethusdt") && subscription.contains("\"SUBSCRIBE\""));
+        }
+        assert_eq!(client.shards.len(), 1);
+        assert_eq!(client.shards[0].pairs, vec!["btcusdt", "ethusdt"]);
+        assert_eq!(pair_names(&pairs), vec!["btcusdt", "ethusdt"]);
+
+        let duplicate = apply_control(
+            &mut client,
+            &mut pairs,
+            Control::Subscribe(request("ethusdt")),
+            &ctx,
+        )
+        .await;
+        assert!(duplicate
+            .unwrap_err()
+            .to_string()
+            .contains("already subscribed"));
+        assert_eq!(client.shards[0].pairs.len(), 2);
+
+        apply_control(
+            &mut client,
+            &mut pairs,
+            Control::Unsubscribe(request("ethusdt")),
+            &ctx,
+        )
+        .await
+        .unwrap();
+        for _ in 0..2 {
+            let unsubscription = received(&mut mock).await;
+            assert!(unsubscription.contains("ethusdt") && unsubscription.contains("UNSUBSCRIBE"));
+        }
+        assert_eq!(client.pairs, vec!["btcusdt"]);
+        assert_eq!(pair_names(&pairs), vec!["btcusdt"]);
+
+        // the reconnection only subscribes the pairs left
+        assert!(
+            reconnect(&mut client, "binance", &pairs, &mut None, &ctx, false)
+                .await
+                .unwrap()
+        );
+        for _ in 0..2 {
+            assert!(!received(&mut mock).await.contains("ethusdt"));
+        }
+        client.close().await;
+    }
+
+    // a websocket api taking its pairs in the url
+    struct Rendered(Box<dyn ExchangeAdapter>);
+
+    #[async_trait]
+    impl ExchangeAdapter for Rendered {
+        fn endpoint(&self) -> &'static str {
+            self.0.endpoint()
+        }
+
+        fn render_url(&self) -> bool {
+            true
+        }
+
+        fn subscribe_messages(&self, pair: &str, level: u32) -> error::Result<Vec<String>> {
+            self.0.subscribe_messages(pair, level)
+        }
+
+        fn unsubscribe_messages(&self, pair: &str, level: u32) -> error::Result<Vec<String>> {
+            self.0.unsubscribe_messages(pair, level)
+        }
+
+        fn parse(&mut self, raw: &str) -> error::Result<ParsedEvent> {
+            self.0.parse(raw)
+        }
+    }
+
+    #[cfg(feature = "exchange-binance")]
+    #[tokio::test]
+    async fn test_exchange_render_url() {
+        let mock = MockExchange::start(vec![fixture("binance")]).await;
+        let mut client = Exchange::new("binance");
+        client
+            .connect(vec![setting("btcusdt")], &mock.network("binance"))
+            .await
+            .unwrap();
+        let adapter = client.shards[0].adapter.take().unwrap();
+        client.shards[0].adapter = Some(Box::new(Rendered(adapter)));
+        // the pairs of the url can't change on the running connection
+        assert!(matches!(
+            client.subscribe("ethusdt").await,
+            Err(Error::Unsupported(_))
+        ));
+        assert!(matches!(
+            client.unsubscribe("btcusdt").await,
+            Err(Error::Unsupported(_))
+        ));
+        assert_eq!(client.pairs, vec!["btcusdt"]);
+        client.close().await;
+    }
+
+    #[cfg(feature = "exchange-bitstamp")]
+    #[tokio::test]
+    async fn test_graceful_shutdown() {
+        let mock = MockExchange::start(vec![fixture("bitstamp")]).await;
+        let (tx, mut rx) = unbounded_channel();
+        let shutdown = CancellationToken::new();
+        let ctx = ExecutorContext {
+            network: mock.network("bitstamp"),
+            tx,
+            health: HealthRegistry::new(),
+            recorder: None,
+            capture: None,
+            trace: None,
+            depth: 10,
+            breaker: None,
+            backfill: false,
+            parse_offload_bytes: 0,
+            shutdown: shutdown.clone(),
+        };
+        let (_control_tx, control_rx) = unbounded_channel();
+        let handle = tokio::spawn(executor(
+            "bitstamp".to_string(),
+            vec![setting("btcusd")],
+            ctx,
+            control_rx,
+        ));
+        // the executor is streaming
+        timeout(Duration::from_secs(5), async {
+            while !matches!(rx.recv().await, Some(Event::BookUpdate { .. })) {}
+        })
+        .await
+        .unwrap();
+
+        let server = AggServer::new(
+            shutdown.clone(),
+            HealthRegistry::new(),
+            20,
+            LagPolicy::default(),
+            100,
+        );
+        let mut subscribers = vec![];
+        for _ in 0..2 {
+            let request = Request::new(SummaryRequest::default());
+            subscribers.push(server.book_summary(request).await.unwrap().into_inner());
+        }
+        // still buffered when the shutdown comes
+        for spread in 0..3 {
+            let summary = Summary {
+                spread: spread as f64,
+                pair: "btcusd".to_string(),
+                ..Default::default()
+            };
+            server.tx.send(Ok(summary)).unwrap();
+        }
+        shutdown.cancel();
+
+        for subscriber in subscribers {
+            let spreads: Vec<f64> = timeout(Duration::from_secs(5), subscriber.collect::<Vec<_>>())
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|summary| summary.unwrap().spread)
+                .collect();
+            // the drained summaries, then the end of the stream
+            assert_eq!(spreads, vec![0.0, 1.0, 2.0]);
+        }
+        assert!(server.closed.is_cancelled());
+        timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[cfg(feature = "exchange-bitstamp")]
+    #[tokio::test]
+    async fn test_executor_connect_error() {
+        // nothing listens on the port anymore
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let mut network = NetworkSetting::default();
+        network.endpoints.insert("bitstamp".to_string(), url);
+        let (tx, _rx) = unbounded_channel();
+        let health = HealthRegistry::new();
+        let ctx = ExecutorContext {
+            network,
+            tx,
+            health: health.clone(),
+            recorder: None,
+            capture: None,
+            trace: None,
+            depth: 10,
+            breaker: None,
+            backfill: false,
+            parse_offload_bytes: 0,
+            shutdown: CancellationToken::new(),
+        };
+        let (_control_tx, control_rx) = unbounded_channel();
+        let result = executor(
+            "bitstamp".to_string(),
+            vec![setting("btcusd")],
+            ctx,
+            control_rx,
+        )
+        .await;
+        assert!(result.is_err());
+        let status = health.report().exchanges.remove(0);
+        assert_eq!(status.state, ConnectionState::Disconnected as i32);
+        assert!(!status.last_error.is_empty());
+    }
+
+    #[cfg(feature = "exchange-bitstamp")]
+    #[tokio::test]
+    async fn test_executor_restart() {
+        let payloads = fixture("bitstamp");
+        let mock = MockExchange::concurrent(vec![payloads.clone(), payloads]).await;
+        let (tx, mut rx) = unbounded_channel();
+        let shutdown = CancellationToken::new();
+        let ctx = ExecutorContext {
+            network: mock.network("bitstamp"),
+            tx,
+            health: HealthRegistry::new(),
+            recorder: None,
+            capture: None,
+            trace: None,
+            depth: 10,
+            breaker: None,
+            backfill: false,
+            parse_offload_bytes: 0,
+            shutdown: shutdown.clone(),
+        };
+        let mut executors = Executors::new(ctx);
+        executors
+            .start("bitstamp".to_string(), vec![setting("btcusd")])
+            .await;
+        while !matches!(next(&mut rx).await, Event::BookUpdate { .. }) {}
+
+        // the books of the previous executor are removed before the new one streams
+        executors
+            .start("bitstamp".to_string(), vec![setting("btcusd")])
+            .await;
+        loop {
+            match next(&mut rx).await {
+                Event::Removed { pair: None, .. } => break,
+                Event::BookUpdate { .. } => {}
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+        while !matches!(next(&mut rx).await, Event::BookUpdate { .. }) {}
+        // and nothing removes the books of the new one
+        let removed = timeout(Duration::from_millis(500), async {
+            loop {
+                if let Event::Removed { .. } = next(&mut rx).await {
+                    return;
+                }
+            }
+        })
+        .await;
+        assert!(removed.is_err());
+        assert!(executors.control("bitstamp").is_some());
+
+        shutdown.cancel();
+        timeout(Duration::from_secs(5), executors.join())
+            .await
+            .unwrap();
+    }
+
+    #[cfg(feature = "exchange-bitstamp")]
+    #[tokio::test]
+    async fn test_watch_config() {
+        let dir = std::env::temp_dir().join("market_aggregator_watch_config");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        let config = |pair: &str| {
+            format!(
+                "exchange_pair_map:\n  bitstamp:\n    - pair: {}\nserver_port: 50051\nlog_level: Info\n",
+                pair
+            )
+        };
+        fs::write(&path, config("btcusd")).unwrap();
+        let (tx, mut rx) = unbounded_channel();
+        let shutdown = CancellationToken::new();
+        let current = HashMap::from([("bitstamp".to_string(), vec![setting("btcusd")])]);
+        let watcher = tokio::spawn(watch_config(
+            path.to_string_lossy().to_string(),
+            vec![],
+            current,
+            tx,
+            shutdown.clone(),
+        ));
+        // let the watcher start before the file changes
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        fs::write(&path, config("ethusd")).unwrap();
+        let mut changes = vec![];
+        for _ in 0..2 {
+            changes.push(
+                timeout(Duration::from_secs(5), rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            );
+        }
+        // the modified exchange is restarted
+        assert_eq!(changes[0], ExchangeChange::Stop("bitstamp".to_string()));
+        match &changes[1] {
+            ExchangeChange::Start(exchange, settings) => {
+                assert_eq!(exchange, "bitstamp");
+                assert_eq!(settings[0].pair, "ethusd");
+            }
+            change => panic!("unexpected {:?}", change),
+        }
+
+        shutdown.cancel();
+        timeout(Duration::from_secs(5), watcher)
+            .await
+            .unwrap()
+            .unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,13 @@
+use crate::clock::{self, SharedClock};
+use crate::config::{ContractSetting, PrecisionSetting, RoundingMode};
+use crate::fixed::Fixed;
+use crate::proto::orderbook::{Band, DepthAt};
 use crate::proto::{Level, Summary};
+use crate::strategy::{Merge, Output, SharedStrategy};
 use anyhow::{anyhow, Result};
-use bigdecimal::{BigDecimal, ToPrimitive, Zero};
-use std::collections::BTreeMap;
-use std::ops::Bound;
-use std::time::SystemTime;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
 
 #[derive(Clone, Copy)]
 pub enum Side {
@@ -11,53 +15,165 @@ pub enum Side {
     Ask,
 }
 
-fn get_unixtime() -> u128 {
-    SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_millis()
+// best bid >= best ask within one venue. The feed is out of sync and needs a resync.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Crossed {
+    pub exchange: String,
+    pub bid: Fixed,
+    pub ask: Fixed,
 }
 
+impl fmt::Display for Crossed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} book crossed: bid {} >= ask {}",
+            self.exchange, self.bid, self.ask
+        )
+    }
+}
+
+impl std::error::Error for Crossed {}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Orderbook {
     pub(crate) name: String,
-    pub(crate) bid: BTreeMap<BigDecimal, BigDecimal>,
-    pub(crate) ask: BTreeMap<BigDecimal, BigDecimal>,
-    pub(crate) volume: BigDecimal,
-    pub(crate) last_price: BigDecimal,
+    // the pair in the notation of the exchange. Empty if the feed doesn't tell.
+    pub(crate) pair: String,
+    pub(crate) bid: BTreeMap<Fixed, Fixed>,
+    pub(crate) ask: BTreeMap<Fixed, Fixed>,
+    pub(crate) volume: Fixed,
+    pub(crate) last_price: Fixed,
     pub(crate) timestamp: u128,
+    // unix millis the exchange stamped the last update with, for the feeds telling it
+    pub(crate) exchange_ts: Option<u128>,
+    // unix millis the last update was received
+    pub(crate) received_ts: u128,
+    // price => unix millis of the last change of the level
+    pub(crate) bid_time: BTreeMap<Fixed, u128>,
+    pub(crate) ask_time: BTreeMap<Fixed, u128>,
+    // fee in basis points merged into the prices, see ExchangeSetting.fee_bps
+    pub(crate) fee_bps: f64,
+    // the quote currency of the prices, when converted into the one of the symbol, see
+    // ExchangeSetting.quote
+    pub(crate) quote: Option<String>,
+    // derivatives only
+    pub(crate) mark_price: Option<Fixed>,
+    pub(crate) funding_rate: Option<Fixed>,
 }
 
 impl Orderbook {
     pub fn clear(&mut self) {
         self.bid.clear();
         self.ask.clear();
+        self.bid_time.clear();
+        self.ask_time.clear();
     }
-    pub fn insert(&mut self, side: Side, price: BigDecimal, volume: BigDecimal) {
-        match side {
-            Side::Bid => {
-                self.bid.remove(&price);
-                if !volume.is_zero() {
-                    self.bid.insert(price, volume);
-                }
-            }
-            Side::Ask => {
-                self.ask.remove(&price);
-                if !volume.is_zero() {
-                    self.ask.insert(price, volume);
-                }
-            }
+    pub fn insert(&mut self, side: Side, price: Fixed, volume: Fixed) {
+        let (levels, times) = match side {
+            Side::Bid => (&mut self.bid, &mut self.bid_time),
+            Side::Ask => (&mut self.ask, &mut self.ask_time),
         };
+        levels.remove(&price);
+        times.remove(&price);
+        if !volume.is_zero() {
+            times.insert(price, clock::now_ms());
+            levels.insert(price, volume);
+        }
+    }
+    // replace the levels of the side with the ones of a snapshot. The unchanged levels, same
+    // price and amount, keep the time of their last change.
+    pub fn replace(&mut self, side: Side, snapshot: BTreeMap<Fixed, Fixed>) {
+        let (levels, times) = match side {
+            Side::Bid => (&mut self.bid, &mut self.bid_time),
+            Side::Ask => (&mut self.ask, &mut self.ask_time),
+        };
+        let now = clock::now_ms();
+        let mut kept = BTreeMap::new();
+        for (price, volume) in snapshot.iter() {
+            let time = match levels.get(price) {
+                Some(old) if old == volume => times.get(price).copied().unwrap_or(now),
+                _ => now,
+            };
+            kept.insert(*price, time);
+        }
+        *levels = snapshot;
+        *times = kept;
     }
     pub fn new(name: &str) -> Orderbook {
         Orderbook {
             name: name.to_string(),
+            pair: String::new(),
             bid: BTreeMap::new(),
             ask: BTreeMap::new(),
-            timestamp: get_unixtime(),
-            last_price: BigDecimal::zero(),
-            volume: BigDecimal::zero(),
+            timestamp: clock::now_ms(),
+            exchange_ts: None,
+            received_ts: clock::now_ms(),
+            last_price: Fixed::ZERO,
+            volume: Fixed::ZERO,
+            bid_time: BTreeMap::new(),
+            ask_time: BTreeMap::new(),
+            fee_bps: 0.0,
+            quote: None,
+            mark_price: None,
+            funding_rate: None,
+        }
+    }
+    pub fn with_pair(name: &str, pair: &str) -> Orderbook {
+        let mut ob = Orderbook::new(name);
+        ob.pair = pair.to_string();
+        ob
+    }
+    // the amounts in contracts converted to the base currency, ex: 100 contracts of 100 USD
+    // are 0.2 BTC at 50000. A level without a base amount at its price is dropped.
+    pub fn convert_contracts(&mut self, contract: &ContractSetting) {
+        let size = contract.size;
+        let convert = |price: &Fixed, amount: &Fixed| match contract.inverse {
+            true => amount
+                .to_f64()
+                .zip(price.to_f64())
+                .and_then(|(amount, price)| {
+                    (price > 0.0)
+                        .then(|| Fixed::from_f64(amount * size / price))
+                        .flatten()
+                }),
+            false => Fixed::from_f64(size).map(|size| *amount * size),
+        };
+        for levels in [&mut self.bid, &mut self.ask] {
+            let converted = std::mem::take(levels)
+                .into_iter()
+                .filter_map(|(price, amount)| convert(&price, &amount).map(|a| (price, a)))
+                .filter(|(_, amount)| !amount.is_zero())
+                .collect();
+            *levels = converted;
         }
+        let (bid, ask) = (&self.bid, &self.ask);
+        self.bid_time.retain(|price, _| bid.contains_key(price));
+        self.ask_time.retain(|price, _| ask.contains_key(price));
+    }
+    // check the book is neither crossed nor locked
+    pub fn check_crossed(&self) -> Result<(), Crossed> {
+        if let (Some((bid, _)), Some((ask, _))) =
+            (self.bid.last_key_value(), self.ask.first_key_value())
+        {
+            if bid >= ask {
+                return Err(Crossed {
+                    exchange: self.name.clone(),
+                    bid: *bid,
+                    ask: *ask,
+                });
+            }
+        }
+        Ok(())
+    }
+    // the cumulative (bid, ask) amounts within offset_bps of the mid price.
+    // None if a side is empty.
+    pub fn depth_at(&self, offset_bps: f64) -> Option<(Fixed, Fixed)> {
+        depth_within(
+            self.bid.iter().rev().map(|(p, v)| (*p, *v)),
+            self.ask.iter().map(|(p, v)| (*p, *v)),
+            offset_bps,
+        )
     }
     // used to trim bid/ask to level numbers of price bars
     pub fn trim(&mut self, level: u32) {
@@ -69,196 +185,1055 @@ impl Orderbook {
         for _ in (level as usize)..l {
             self.ask.pop_last();
         }
+        let (bid, ask) = (&self.bid, &self.ask);
+        self.bid_time.retain(|price, _| bid.contains_key(price));
+        self.ask_time.retain(|price, _| ask.contains_key(price));
     }
 }
 
+// the sums of the (price, amount) of each side, best price first, within offset_bps of
+// the mid price. None if a side is empty.
+fn depth_within(
+    mut bids: impl Iterator<Item = (Fixed, Fixed)>,
+    mut asks: impl Iterator<Item = (Fixed, Fixed)>,
+    offset_bps: f64,
+) -> Option<(Fixed, Fixed)> {
+    let (best_bid, best_ask) = (bids.next()?, asks.next()?);
+    let offset = Fixed::from_f64(offset_bps)?;
+    let mid = (best_bid.0 + best_ask.0) * Fixed::from(5000) * Fixed::BASIS_POINT;
+    let lowest = mid * (Fixed::from(10000) - offset) * Fixed::BASIS_POINT;
+    let highest = mid * (Fixed::from(10000) + offset) * Fixed::BASIS_POINT;
+    let sum = |levels: &mut dyn Iterator<Item = (Fixed, Fixed)>| {
+        let mut amount = Fixed::ZERO;
+        for (_, volume) in levels {
+            amount += &volume;
+        }
+        amount
+    };
+    let bid = sum(&mut std::iter::once(best_bid)
+        .chain(bids)
+        .take_while(|(price, _)| *price >= lowest));
+    let ask = sum(&mut std::iter::once(best_ask)
+        .chain(asks)
+        .take_while(|(price, _)| *price <= highest));
+    Some((bid, ask))
+}
+
+pub(crate) fn to_f64(value: &Fixed, what: &str) -> Result<f64> {
+    value
+        .to_f64()
+        .ok_or_else(|| anyhow!("{} conversion error: {:?}", what, value))
+}
+
+pub(crate) fn round(value: Fixed, decimals: Option<u32>, mode: RoundingMode) -> Fixed {
+    decimals.map_or(value, |decimals| value.round_dp(decimals, mode))
+}
+
+// volume weighted average price and total amount of the levels.
+fn vwap(levels: &[Level]) -> (f64, f64) {
+    let total: f64 = levels.iter().map(|l| l.amount).sum();
+    if total == 0.0 {
+        return (0.0, 0.0);
+    }
+    let notional: f64 = levels.iter().map(|l| l.price * l.amount).sum();
+    (notional / total, total)
+}
+
+// the prices of an exchange quoted in another currency than the symbol, multiplied by rate
+#[derive(Debug, PartialEq)]
+pub struct Conversion {
+    // the currency quoted by the exchange, ex: USDT
+    pub quote: String,
+    pub rate: Fixed,
+}
+
+// one exchange on one price of the aggregated book
+#[derive(Debug)]
+pub struct Entry {
+    pub exchange: String,
+    pub volume: Fixed,
+    // unix millis of the last change
+    pub time: u128,
+    // the price quoted by the exchange, before the fee adjustment and the conversion
+    pub raw_price: Fixed,
+    pub conversion: Option<Arc<Conversion>>,
+}
+
+// moves the price by the fee, against the taker: bids down and asks up.
+pub(crate) fn adjust(price: Fixed, fee_bps: f64, side: Side) -> Fixed {
+    if fee_bps == 0.0 {
+        return price;
+    }
+    let Some(fee) = Fixed::from_f64(fee_bps) else {
+        return price;
+    };
+    let factor = match side {
+        Side::Bid => Fixed::from(10000) - fee,
+        Side::Ask => Fixed::from(10000) + fee,
+    };
+    price * factor * Fixed::BASIS_POINT
+}
+
+// rounds the price to a multiple of the tick, against the taker: bids down and asks up,
+// a bucket never looks better than the prices in it.
+fn bucket(price: Fixed, tick: Fixed, side: Side) -> Fixed {
+    price.round_to(tick, matches!(side, Side::Ask))
+}
+
+// the levels smaller than min are dust. Left out of the aggregation, or with fold, their
+// amounts are added to the next level of the same exchange.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DustFilter {
+    pub min: Fixed,
+    // min is in the quote currency, price * amount
+    pub quote: bool,
+    pub fold: bool,
+}
+
+impl DustFilter {
+    fn is_dust(&self, price: Fixed, volume: Fixed) -> bool {
+        let quantity = if self.quote { price * volume } else { volume };
+        quantity < self.min
+    }
+}
+
+// (price, volume, time) of one side of the book from the best price, without the dust
+fn levels_of(
+    levels: &BTreeMap<Fixed, Fixed>,
+    times: &BTreeMap<Fixed, u128>,
+    default_time: u128,
+    side: Side,
+    dust: Option<&DustFilter>,
+) -> Vec<(Fixed, Fixed, u128)> {
+    let best_first: Box<dyn Iterator<Item = (&Fixed, &Fixed)>> = match side {
+        Side::Bid => Box::new(levels.iter().rev()),
+        Side::Ask => Box::new(levels.iter()),
+    };
+    let mut out = vec![];
+    // the dust waiting to be folded, and its oldest time
+    let mut carry: Option<(Fixed, u128)> = None;
+    for (price, volume) in best_first {
+        let time = times.get(price).copied().unwrap_or(default_time);
+        let (volume, time) = match carry.take() {
+            Some((carried, carried_time)) => (*volume + carried, time.min(carried_time)),
+            None => (*volume, time),
+        };
+        match dust {
+            Some(dust) if dust.is_dust(*price, volume) => {
+                if dust.fold {
+                    carry = Some((volume, time));
+                }
+            }
+            _ => out.push((*price, volume, time)),
+        }
+    }
+    out
+}
+
 // AggregatedOrderbook works like this:
 // new() -> merge(ob1) -> merge(ob2) -> ... -> merge(obN) -> finalize(max_level)
 // max_level here is used to limit the depth of orderbook to reach in this call
 #[derive(Debug)]
 pub struct AggregatedOrderbook {
     pub spread: f64,
-    pub bid: BTreeMap<BigDecimal, Vec<(String, BigDecimal)>>,
-    pub ask: BTreeMap<BigDecimal, Vec<(String, BigDecimal)>>,
+    pub bid: BTreeMap<Fixed, Vec<Entry>>,
+    pub ask: BTreeMap<Fixed, Vec<Entry>>,
+    // how the merged entries become the published levels
+    pub strategy: SharedStrategy,
+    // None => the prices are ranked as quoted
+    pub tick_size: Option<Fixed>,
+    // None => every level is kept
+    pub dust: Option<DustFilter>,
+    // the offsets from the mid price, in basis points, finalize reports the depth within
+    pub depth_offsets: Vec<f64>,
+    // the upper edges of the bands around the mid price, in basis points, finalize reports
+    // the amounts of each exchange within
+    pub band_edges: Vec<f64>,
+    // the levels are aged against it
+    pub clock: SharedClock,
+    // the rounding of the published prices and amounts
+    pub precision: PrecisionSetting,
+    // fill the decimal strings of the levels
+    pub decimal_strings: bool,
+    // exchange => the conversion of its prices into the quote of the symbol
+    pub conversions: HashMap<String, Arc<Conversion>>,
 }
 
 impl AggregatedOrderbook {
-    // merge the content from one orderbook
+    // merge the content from one orderbook, ranked by the prices converted and net of its fee
     pub fn merge(&mut self, orderbook: &Orderbook) {
-        let name = &orderbook.name;
-        for (price, volume) in orderbook.bid.iter() {
-            self.bid
-                .entry(price.clone())
-                .and_modify(|e| e.push((name.clone(), volume.clone())))
-                .or_insert_with(|| vec![(name.clone(), volume.clone())]);
-        }
-        for (price, volume) in orderbook.ask.iter() {
-            self.ask
-                .entry(price.clone())
-                .and_modify(|e| e.push((name.clone(), volume.clone())))
-                .or_insert_with(|| vec![(name.clone(), volume.clone())]);
+        let (dust, timestamp) = (self.dust.as_ref(), orderbook.timestamp);
+        let conversion = self.conversions.get(&orderbook.name).cloned();
+        let sides = [
+            (
+                Side::Bid,
+                levels_of(
+                    &orderbook.bid,
+                    &orderbook.bid_time,
+                    timestamp,
+                    Side::Bid,
+                    dust,
+                ),
+                &mut self.bid,
+            ),
+            (
+                Side::Ask,
+                levels_of(
+                    &orderbook.ask,
+                    &orderbook.ask_time,
+                    timestamp,
+                    Side::Ask,
+                    dust,
+                ),
+                &mut self.ask,
+            ),
+        ];
+        for (side, levels, merged) in sides {
+            for (price, volume, time) in levels {
+                let converted = conversion.as_ref().map_or(price, |c| price * c.rate);
+                let mut key = adjust(converted, orderbook.fee_bps, side);
+                if let Some(tick) = self.tick_size {
+                    key = bucket(key, tick, side);
+                }
+                let entries = merged.entry(key).or_default();
+                // several prices of the exchange in one bucket make one entry, at the best
+                // of them and the oldest time
+                if let Some(entry) = entries.iter_mut().find(|e| e.exchange == orderbook.name) {
+                    entry.volume += &volume;
+                    entry.time = entry.time.min(time);
+                    let better = match side {
+                        Side::Bid => price > entry.raw_price,
+                        Side::Ask => price < entry.raw_price,
+                    };
+                    if better {
+                        entry.raw_price = price;
+                    }
+                    continue;
+                }
+                entries.push(Entry {
+                    exchange: orderbook.name.clone(),
+                    volume,
+                    time,
+                    raw_price: price,
+                    conversion: conversion.clone(),
+                });
+            }
         }
         self.spread = 0.0;
     }
     pub fn new() -> AggregatedOrderbook {
         AggregatedOrderbook {
-            spread: std::f64::NAN,
+            spread: f64::NAN,
             bid: BTreeMap::new(),
             ask: BTreeMap::new(),
+            strategy: Arc::new(Merge),
+            tick_size: None,
+            dust: None,
+            depth_offsets: vec![],
+            band_edges: vec![],
+            clock: clock::system(),
+            precision: PrecisionSetting::default(),
+            decimal_strings: false,
+            conversions: HashMap::new(),
         }
     }
-    // calculate the spread, output the stored price and volume data to grpc's Summary
-    pub fn finalize(&mut self, level: u32) -> Result<Summary> {
-        let mut cursor = self.bid.upper_bound(Bound::Unbounded);
-        let mut counter = 0;
-        let mut bids = vec![];
-        'bid_outer: for _ in 0..level {
-            if let Some((price, v)) = cursor.key_value() {
-                for (exchange, volume) in v.iter() {
-                    counter += 1;
-                    bids.push(Level {
-                        exchange: exchange.clone(),
-                        price: price
-                            .to_f64()
-                            .ok_or_else(|| anyhow!("price conversion error: {:?}", price))?,
-                        amount: volume
-                            .to_f64()
-                            .ok_or_else(|| anyhow!("volume conversion error: {:?}", volume))?,
-                    });
-                    if counter == 10 {
-                        break 'bid_outer;
-                    }
-                }
-                // notice move_prev is to move to the previous element in tree,
-                // not the order of upper bound or lower bound.
-                if cursor.peek_prev().is_some() {
-                    cursor.move_prev();
-                } else {
-                    break;
+    // 0 or less => the prices are ranked as quoted
+    pub fn set_tick_size(&mut self, tick_size: f64) {
+        self.tick_size = Fixed::from_f64(tick_size).filter(|tick| *tick > Fixed::ZERO);
+    }
+    // check no venue is crossed with itself after merging.
+    // Crossing between different venues is a valid market state and is kept.
+    // The fee keeps the order of one venue, its quoted prices are compared.
+    pub fn check_crossed(&self) -> Result<(), Crossed> {
+        let mut best_bid = BTreeMap::<&str, &Fixed>::new();
+        for v in self.bid.values().rev() {
+            for entry in v.iter() {
+                best_bid
+                    .entry(entry.exchange.as_str())
+                    .or_insert(&entry.raw_price);
+            }
+        }
+        let mut best_ask = BTreeMap::<&str, &Fixed>::new();
+        for v in self.ask.values() {
+            for entry in v.iter() {
+                best_ask
+                    .entry(entry.exchange.as_str())
+                    .or_insert(&entry.raw_price);
+            }
+        }
+        for (exchange, bid) in best_bid {
+            match best_ask.get(exchange) {
+                Some(ask) if bid >= *ask => {
+                    return Err(Crossed {
+                        exchange: exchange.to_string(),
+                        bid: *bid,
+                        ask: **ask,
+                    })
                 }
-            } else {
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    // the depth_at of the merged books, ranked by the prices net of the fees
+    pub fn depth_at(&self, offset_bps: f64) -> Option<(Fixed, Fixed)> {
+        let amount = |entries: &Vec<Entry>| {
+            let mut amount = Fixed::ZERO;
+            for entry in entries {
+                amount += &entry.volume;
+            }
+            amount
+        };
+        depth_within(
+            self.bid.iter().rev().map(|(p, v)| (*p, amount(v))),
+            self.ask.iter().map(|(p, v)| (*p, amount(v))),
+            offset_bps,
+        )
+    }
+    // the amounts (bid, ask) of each exchange within each of the band_edges of the mid price
+    // of the merged books, ranked by the prices net of the fees: a band takes the prices from
+    // the previous edge (excluded, 0 included) to its own. By band then exchange, each
+    // exchange of the books in every band. None if a side is empty.
+    pub fn bands(&self) -> Option<Vec<(usize, String, Fixed, Fixed)>> {
+        let (best_bid, best_ask) = (self.bid.keys().next_back()?, self.ask.keys().next()?);
+        let mid = (*best_bid + *best_ask) * Fixed::from(5000) * Fixed::BASIS_POINT;
+        let edges = self
+            .band_edges
+            .iter()
+            .map(|edge| Fixed::from_f64(*edge))
+            .collect::<Option<Vec<Fixed>>>()?;
+        let lowest: Vec<Fixed> = edges
+            .iter()
+            .map(|edge| mid * (Fixed::from(10000) - *edge) * Fixed::BASIS_POINT)
+            .collect();
+        let highest: Vec<Fixed> = edges
+            .iter()
+            .map(|edge| mid * (Fixed::from(10000) + *edge) * Fixed::BASIS_POINT)
+            .collect();
+        let mut amounts: BTreeMap<(usize, &str), (Fixed, Fixed)> = BTreeMap::new();
+        for entry in self.bid.values().chain(self.ask.values()).flatten() {
+            for band in 0..edges.len() {
+                amounts.entry((band, entry.exchange.as_str())).or_default();
+            }
+        }
+        for (price, entries) in self.bid.iter().rev() {
+            let Some(band) = lowest.iter().position(|lowest| price >= lowest) else {
                 break;
+            };
+            for entry in entries {
+                amounts.get_mut(&(band, entry.exchange.as_str()))?.0 += &entry.volume;
             }
         }
-        let mut cursor = self.ask.lower_bound(Bound::Unbounded);
-        let mut counter = 0;
-        let mut asks = vec![];
-        'ask_outer: for _ in 0..level {
-            if let Some((price, v)) = cursor.key_value() {
-                for (exchange, volume) in v.iter() {
-                    counter += 1;
-                    asks.push(Level {
-                        exchange: exchange.clone(),
-                        price: price
-                            .to_f64()
-                            .ok_or_else(|| anyhow!("price conversion error: {:?}", price))?,
-                        amount: volume
-                            .to_f64()
-                            .ok_or_else(|| anyhow!("volume conversion error: {:?}", volume))?,
-                    });
-                    if counter == 10 {
-                        break 'ask_outer;
-                    }
-                }
-                if cursor.peek_next().is_some() {
-                    cursor.move_next();
-                } else {
-                    break;
-                }
-            } else {
+        for (price, entries) in self.ask.iter() {
+            let Some(band) = highest.iter().position(|highest| price <= highest) else {
                 break;
+            };
+            for entry in entries {
+                amounts.get_mut(&(band, entry.exchange.as_str()))?.1 += &entry.volume;
             }
         }
+        Some(
+            amounts
+                .into_iter()
+                .map(|((band, exchange), (bid, ask))| (band, exchange.to_string(), bid, ask))
+                .collect(),
+        )
+    }
+    // min <= 0 => every level is kept
+    pub fn set_dust_filter(&mut self, min: f64, quote: bool, fold: bool) {
+        self.dust = Fixed::from_f64(min)
+            .filter(|min| *min > Fixed::ZERO)
+            .map(|min| DustFilter { min, quote, fold });
+    }
+    // calculate the spread, output the stored price and volume data to grpc's Summary
+    pub fn finalize(&mut self, level: u32) -> Result<Summary> {
+        let output = Output {
+            precision: &self.precision,
+            decimal_strings: self.decimal_strings,
+            now: self.clock.now_ms(),
+        };
+        let bids = self
+            .strategy
+            .levels(&mut self.bid.iter().rev(), level, &output)?;
+        let asks = self.strategy.levels(&mut self.ask.iter(), level, &output)?;
         let best_bid = bids.first();
         let best_ask = asks.first();
-        let spread = match (best_bid, best_ask) {
-            (Some(v), Some(w)) => w.price - v.price,
-            _ => 0.0,
+        let (spread, mid_price) = match (best_bid, best_ask) {
+            (Some(v), Some(w)) => (w.price - v.price, (w.price + v.price) / 2.0),
+            _ => (0.0, 0.0),
+        };
+        let spread_bps = match mid_price > 0.0 {
+            true => spread / mid_price * 10000.0,
+            false => 0.0,
         };
-        Ok(Summary { spread, bids, asks })
+        let (bid_vwap, bid_liquidity) = vwap(&bids);
+        let (ask_vwap, ask_liquidity) = vwap(&asks);
+        let mut depth_at = vec![];
+        let (amount_decimals, rounding) = (self.precision.amount_decimals, self.precision.rounding);
+        for offset_bps in self.depth_offsets.iter() {
+            if let Some((bid, ask)) = self.depth_at(*offset_bps) {
+                depth_at.push(DepthAt {
+                    offset_bps: *offset_bps,
+                    bid_amount: to_f64(&round(bid, amount_decimals, rounding), "volume")?,
+                    ask_amount: to_f64(&round(ask, amount_decimals, rounding), "volume")?,
+                });
+            }
+        }
+        let mut bands = vec![];
+        for (band, exchange, bid, ask) in self.bands().unwrap_or_default() {
+            bands.push(Band {
+                from_bps: band.checked_sub(1).map_or(0.0, |i| self.band_edges[i]),
+                to_bps: self.band_edges[band],
+                exchange,
+                bid_amount: to_f64(&round(bid, amount_decimals, rounding), "volume")?,
+                ask_amount: to_f64(&round(ask, amount_decimals, rounding), "volume")?,
+            });
+        }
+        Ok(Summary {
+            spread,
+            bids,
+            asks,
+            mid_price,
+            bid_vwap,
+            ask_vwap,
+            bid_liquidity,
+            ask_liquidity,
+            depth_at,
+            bands,
+            spread_bps,
+            crossed: spread < 0.0,
+            ..Default::default()
+        })
+    }
+}
+
+// the books received within window_ms of the latest one, and the exchanges of the others,
+// sorted. 0 => every book.
+pub fn synchronized(books: Vec<&Orderbook>, window_ms: u64) -> (Vec<&Orderbook>, Vec<String>) {
+    let Some(latest) = books.iter().map(|ob| ob.received_ts).max() else {
+        return (books, vec![]);
+    };
+    if window_ms == 0 {
+        return (books, vec![]);
     }
+    let (synced, stale): (Vec<&Orderbook>, Vec<&Orderbook>) = books
+        .into_iter()
+        .partition(|ob| latest - ob.received_ts <= window_ms as u128);
+    let mut stale: Vec<String> = stale.iter().map(|ob| ob.name.clone()).collect();
+    stale.sort();
+    stale.dedup();
+    (synced, stale)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::SimulatedClock;
+    use crate::proto::orderbook::Contribution;
+    use crate::strategy::Consolidate;
     use std::str::FromStr;
+    use std::sync::Arc;
+
+    // the ages depend on the clock
+    fn without_age(levels: Vec<Level>) -> Vec<Level> {
+        levels
+            .into_iter()
+            .map(|mut level| {
+                level.age_ms = 0;
+                for contribution in level.contributions.iter_mut() {
+                    contribution.age_ms = 0;
+                }
+                level
+            })
+            .collect()
+    }
 
     #[test]
     fn test_orderbook_trim() {
-        let default_quantity: BigDecimal = BigDecimal::from_str("10").unwrap();
+        let default_quantity: Fixed = Fixed::from_str("10").unwrap();
         let mut ob = Orderbook::new("");
-        ob.insert(
-            Side::Ask,
-            BigDecimal::from_str("1").unwrap(),
-            default_quantity.clone(),
-        );
-        ob.insert(
-            Side::Ask,
-            BigDecimal::from_str("2").unwrap(),
-            default_quantity.clone(),
-        );
+        ob.insert(Side::Ask, Fixed::from_str("1").unwrap(), default_quantity);
+        ob.insert(Side::Ask, Fixed::from_str("2").unwrap(), default_quantity);
         ob.trim(1);
         assert_eq!(ob.bid.len(), 0);
         assert_eq!(ob.ask.len(), 1);
-        let one = BigDecimal::from_str("1").unwrap();
+        let one = Fixed::from_str("1").unwrap();
         assert_eq!(ob.ask.first_key_value(), Some((&one, &default_quantity)));
     }
     #[test]
+    fn test_convert_contracts() {
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        let mut ob = Orderbook::new("binance-coinm");
+        ob.insert(Side::Bid, fixed("50000"), fixed("100"));
+        ob.insert(Side::Ask, fixed("40000"), fixed("3"));
+        let mut inverse = ob.clone();
+        inverse.convert_contracts(&ContractSetting {
+            size: 100.0,
+            inverse: true,
+        });
+        assert_eq!(inverse.bid.get(&fixed("50000")), Some(&fixed("0.2")));
+        assert_eq!(inverse.ask.get(&fixed("40000")), Some(&fixed("0.0075")));
+        ob.convert_contracts(&ContractSetting {
+            size: 0.001,
+            inverse: false,
+        });
+        assert_eq!(ob.bid.get(&fixed("50000")), Some(&fixed("0.1")));
+        assert_eq!(ob.ask.get(&fixed("40000")), Some(&fixed("0.003")));
+    }
+    #[test]
     fn test_agg_merge() {
-        let default_quantity: BigDecimal = BigDecimal::from_str("10").unwrap();
+        let default_quantity: Fixed = Fixed::from_str("10").unwrap();
         let mut ob1 = Orderbook::new("A");
-        ob1.insert(
-            Side::Ask,
-            BigDecimal::from_str("1").unwrap(),
-            default_quantity.clone(),
-        );
-        ob1.insert(
-            Side::Ask,
-            BigDecimal::from_str("2").unwrap(),
-            default_quantity.clone(),
-        );
+        ob1.insert(Side::Ask, Fixed::from_str("1").unwrap(), default_quantity);
+        ob1.insert(Side::Ask, Fixed::from_str("2").unwrap(), default_quantity);
         let mut ob2 = Orderbook::new("B");
-        ob2.insert(
-            Side::Ask,
-            BigDecimal::from_str("1").unwrap(),
-            default_quantity.clone(),
-        );
-        ob2.insert(
-            Side::Ask,
-            BigDecimal::from_str("3").unwrap(),
-            default_quantity.clone(),
-        );
+        ob2.insert(Side::Ask, Fixed::from_str("1").unwrap(), default_quantity);
+        ob2.insert(Side::Ask, Fixed::from_str("3").unwrap(), default_quantity);
         let mut agg = AggregatedOrderbook::new();
         agg.merge(&ob1);
         agg.merge(&ob2);
         let summary = agg.finalize(4).unwrap();
         assert_eq!(summary.spread, 0.0);
         assert_eq!(
-            summary.asks,
+            without_age(summary.asks.clone()),
             vec![
                 Level {
                     exchange: "A".to_string(),
                     price: 1.,
-                    amount: 10.
+                    amount: 10.,
+                    contributions: vec![],
+                    age_ms: 0,
+                    raw_price: 1.,
+                    ..Default::default()
                 },
                 Level {
                     exchange: "B".to_string(),
                     price: 1.,
-                    amount: 10.
+                    amount: 10.,
+                    contributions: vec![],
+                    age_ms: 0,
+                    raw_price: 1.,
+                    ..Default::default()
                 },
                 Level {
                     exchange: "A".to_string(),
                     price: 2.,
-                    amount: 10.
+                    amount: 10.,
+                    contributions: vec![],
+                    age_ms: 0,
+                    raw_price: 2.,
+                    ..Default::default()
                 },
                 Level {
                     exchange: "B".to_string(),
                     price: 3.,
-                    amount: 10.
+                    amount: 10.,
+                    contributions: vec![],
+                    age_ms: 0,
+                    raw_price: 3.,
+                    ..Default::default()
                 },
             ]
         );
         assert_eq!(summary.bids.len(), 0);
+        assert_eq!(summary.mid_price, 0.0);
+        assert_eq!(summary.ask_vwap, 1.75);
+        assert_eq!(summary.ask_liquidity, 40.0);
+        assert_eq!(summary.bid_liquidity, 0.0);
+    }
+    #[test]
+    fn test_agg_mid_vwap() {
+        let mut ob = Orderbook::new("A");
+        ob.insert(
+            Side::Bid,
+            Fixed::from_str("99").unwrap(),
+            Fixed::from_str("1").unwrap(),
+        );
+        ob.insert(
+            Side::Bid,
+            Fixed::from_str("98").unwrap(),
+            Fixed::from_str("3").unwrap(),
+        );
+        ob.insert(
+            Side::Ask,
+            Fixed::from_str("101").unwrap(),
+            Fixed::from_str("2").unwrap(),
+        );
+        let mut agg = AggregatedOrderbook::new();
+        agg.merge(&ob);
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(summary.spread, 2.0);
+        assert_eq!(summary.mid_price, 100.0);
+        assert_eq!(summary.spread_bps, 200.0);
+        assert!(!summary.crossed);
+        assert_eq!(summary.bid_vwap, 98.25);
+        assert_eq!(summary.bid_liquidity, 4.0);
+        assert_eq!(summary.ask_vwap, 101.0);
+        assert_eq!(summary.ask_liquidity, 2.0);
+    }
+    #[test]
+    fn test_agg_consolidate() {
+        let mut ob1 = Orderbook::new("A");
+        ob1.insert(
+            Side::Ask,
+            Fixed::from_str("1").unwrap(),
+            Fixed::from_str("10").unwrap(),
+        );
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(
+            Side::Ask,
+            Fixed::from_str("1").unwrap(),
+            Fixed::from_str("5").unwrap(),
+        );
+        ob2.insert(
+            Side::Ask,
+            Fixed::from_str("2").unwrap(),
+            Fixed::from_str("5").unwrap(),
+        );
+        let mut agg = AggregatedOrderbook::new();
+        agg.strategy = Arc::new(Consolidate);
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(
+            without_age(summary.asks.clone()),
+            vec![
+                Level {
+                    exchange: "A,B".to_string(),
+                    price: 1.,
+                    amount: 15.,
+                    contributions: vec![
+                        Contribution {
+                            exchange: "A".to_string(),
+                            amount: 10.,
+                            age_ms: 0,
+                            raw_price: 1.,
+                            ..Default::default()
+                        },
+                        Contribution {
+                            exchange: "B".to_string(),
+                            amount: 5.,
+                            age_ms: 0,
+                            raw_price: 1.,
+                            ..Default::default()
+                        },
+                    ],
+                    age_ms: 0,
+                    raw_price: 1.,
+                    ..Default::default()
+                },
+                Level {
+                    exchange: "B".to_string(),
+                    price: 2.,
+                    amount: 5.,
+                    contributions: vec![Contribution {
+                        exchange: "B".to_string(),
+                        amount: 5.,
+                        age_ms: 0,
+                        raw_price: 2.,
+                        ..Default::default()
+                    }],
+                    age_ms: 0,
+                    raw_price: 2.,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+    #[test]
+    fn test_agg_depth() {
+        // 30 bid and 30 ask levels on each exchange, sharing the same prices
+        let mut ob1 = Orderbook::new("A");
+        let mut ob2 = Orderbook::new("B");
+        for i in 1..=30 {
+            for ob in [&mut ob1, &mut ob2] {
+                ob.insert(Side::Bid, Fixed::from(100 - i), Fixed::from(1));
+                ob.insert(Side::Ask, Fixed::from(100 + i), Fixed::from(1));
+            }
+        }
+        let mut agg = AggregatedOrderbook::new();
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        let summary = agg.finalize(25).unwrap();
+        assert_eq!(summary.bids.len(), 25);
+        assert_eq!(summary.asks.len(), 25);
+        // two exchanges per price
+        assert_eq!(summary.bids.last().unwrap().price, 87.);
+        assert_eq!(summary.asks.last().unwrap().price, 113.);
+        assert_eq!(agg.finalize(5).unwrap().asks.len(), 5);
+
+        agg.strategy = Arc::new(Consolidate);
+        let summary = agg.finalize(25).unwrap();
+        assert_eq!(summary.bids.len(), 25);
+        assert_eq!(summary.asks.len(), 25);
+        assert_eq!(summary.bids.last().unwrap().price, 75.);
+        assert_eq!(summary.asks.last().unwrap().price, 125.);
+        // fewer levels than asked
+        assert_eq!(agg.finalize(100).unwrap().asks.len(), 30);
+    }
+    #[test]
+    fn test_crossed() {
+        let quantity = Fixed::from_str("1").unwrap();
+        let mut ob1 = Orderbook::new("A");
+        ob1.insert(Side::Bid, Fixed::from_str("2").unwrap(), quantity);
+        ob1.insert(Side::Ask, Fixed::from_str("3").unwrap(), quantity);
+        assert!(ob1.check_crossed().is_ok());
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(Side::Bid, Fixed::from_str("4").unwrap(), quantity);
+        ob2.insert(Side::Ask, Fixed::from_str("5").unwrap(), quantity);
+
+        // crossing between venues is fine
+        let mut agg = AggregatedOrderbook::new();
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        assert!(agg.check_crossed().is_ok());
+
+        // locked book
+        ob2.insert(Side::Ask, Fixed::from_str("4").unwrap(), quantity);
+        let crossed = Crossed {
+            exchange: "B".to_string(),
+            bid: Fixed::from_str("4").unwrap(),
+            ask: Fixed::from_str("4").unwrap(),
+        };
+        assert_eq!(ob2.check_crossed(), Err(crossed.clone()));
+        let mut agg = AggregatedOrderbook::new();
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        assert_eq!(agg.check_crossed(), Err(crossed));
+    }
+    #[test]
+    fn test_replace_keeps_unchanged_times() {
+        let simulated = SimulatedClock::new(1_000_000);
+        let clock: SharedClock = Arc::new(simulated.clone());
+        let ob = clock::scope(&clock, || {
+            let mut ob = Orderbook::new("A");
+            ob.insert(Side::Bid, Fixed::from(1), Fixed::from(1));
+            ob.insert(Side::Bid, Fixed::from(2), Fixed::from(1));
+            ob.insert(Side::Bid, Fixed::from(3), Fixed::from(1));
+            simulated.advance(1000);
+            ob.replace(
+                Side::Bid,
+                BTreeMap::from([
+                    (Fixed::from(1), Fixed::from(1)),
+                    (Fixed::from(2), Fixed::from(5)),
+                    (Fixed::from(4), Fixed::from(1)),
+                ]),
+            );
+            ob
+        });
+        assert_eq!(
+            ob.bid_time,
+            BTreeMap::from([
+                (Fixed::from(1), 1_000_000),
+                (Fixed::from(2), 1_001_000),
+                (Fixed::from(4), 1_001_000),
+            ])
+        );
+        assert_eq!(ob.bid[&Fixed::from(2)], Fixed::from(5));
+        assert!(!ob.bid.contains_key(&Fixed::from(3)));
+    }
+    #[test]
+    fn test_level_age() {
+        let simulated = SimulatedClock::new(1_000_000);
+        let clock: SharedClock = Arc::new(simulated.clone());
+        let (ob1, mut ob2) = clock::scope(&clock, || {
+            let mut ob1 = Orderbook::new("A");
+            ob1.insert(Side::Bid, Fixed::from(1), Fixed::from(1));
+            // A has not updated the price for a minute
+            simulated.advance(60000);
+            let mut ob2 = Orderbook::new("B");
+            ob2.insert(Side::Bid, Fixed::from(1), Fixed::from(1));
+            ob2.insert(Side::Bid, Fixed::from(2), Fixed::from(1));
+            (ob1, ob2)
+        });
+        simulated.advance(500);
+        let mut agg = AggregatedOrderbook::new();
+        agg.strategy = Arc::new(Consolidate);
+        agg.clock = clock.clone();
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(summary.bids[0].age_ms, 500);
+        // the oldest contribution
+        assert_eq!(summary.bids[1].age_ms, 60500);
+        assert_eq!(summary.bids[1].contributions[0].age_ms, 60500);
+        assert_eq!(summary.bids[1].contributions[1].age_ms, 500);
+
+        // the times follow the levels
+        ob2.insert(Side::Bid, Fixed::from(2), Fixed::ZERO);
+        assert_eq!(ob2.bid_time.len(), 1);
+        ob2.trim(0);
+        assert!(ob2.bid_time.is_empty());
+    }
+    #[test]
+    fn test_agg_dust() {
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        let mut ob1 = Orderbook::new("A");
+        // dust on top of the book
+        ob1.insert(Side::Bid, fixed("101"), fixed("0.001"));
+        ob1.insert(Side::Bid, fixed("100.5"), fixed("0.002"));
+        ob1.insert(Side::Bid, fixed("100"), fixed("1"));
+        ob1.insert(Side::Ask, fixed("102"), fixed("0.001"));
+        ob1.insert(Side::Ask, fixed("103"), fixed("2"));
+        // the last level is dust, nothing to fold it into
+        ob1.insert(Side::Ask, fixed("104"), fixed("0.001"));
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(Side::Bid, fixed("99"), fixed("0.5"));
+
+        let mut agg = AggregatedOrderbook::new();
+        agg.set_dust_filter(0.01, false, false);
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        let summary = agg.finalize(10).unwrap();
+        let prices = |levels: &[Level]| -> Vec<(f64, f64)> {
+            levels.iter().map(|l| (l.price, l.amount)).collect()
+        };
+        assert_eq!(prices(&summary.bids), vec![(100.0, 1.0), (99.0, 0.5)]);
+        assert_eq!(prices(&summary.asks), vec![(103.0, 2.0)]);
+
+        // folded into the next level of the exchange
+        let mut agg = AggregatedOrderbook::new();
+        agg.set_dust_filter(0.01, false, true);
+        agg.merge(&ob1);
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(prices(&summary.bids), vec![(100.0, 1.003)]);
+        assert_eq!(prices(&summary.asks), vec![(103.0, 2.001)]);
+
+        // in quote terms, 0.5 * 99 is below 60
+        let mut agg = AggregatedOrderbook::new();
+        agg.set_dust_filter(60.0, true, false);
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(prices(&summary.bids), vec![(100.0, 1.0)]);
+
+        agg.set_dust_filter(0.0, false, false);
+        assert_eq!(agg.dust, None);
+    }
+    #[test]
+    fn test_precision() {
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        let mut ob = Orderbook::new("A");
+        ob.insert(Side::Bid, fixed("29000.125"), fixed("0.123456"));
+        ob.insert(Side::Ask, fixed("29000.5"), fixed("2"));
+        let mut agg = AggregatedOrderbook::new();
+        agg.precision = PrecisionSetting {
+            price_decimals: Some(2),
+            amount_decimals: Some(4),
+            rounding: RoundingMode::HalfEven,
+        };
+        agg.decimal_strings = true;
+        agg.merge(&ob);
+        let summary = agg.finalize(10).unwrap();
+        let bid = &summary.bids[0];
+        assert_eq!((bid.price, bid.amount), (29000.12, 0.1235));
+        assert_eq!(
+            (bid.decimal_price.as_str(), bid.decimal_amount.as_str()),
+            ("29000.12", "0.1235")
+        );
+        let ask = &summary.asks[0];
+        assert_eq!(
+            (ask.decimal_price.as_str(), ask.decimal_amount.as_str()),
+            ("29000.50", "2.0000")
+        );
+
+        // as quoted
+        let mut agg = AggregatedOrderbook::new();
+        agg.merge(&ob);
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(summary.bids[0].price, 29000.125);
+        assert!(summary.bids[0].decimal_price.is_empty());
+    }
+    #[test]
+    fn test_depth_at() {
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        let mut ob1 = Orderbook::new("A");
+        ob1.insert(Side::Bid, fixed("99.99"), fixed("1"));
+        ob1.insert(Side::Bid, fixed("99.9"), fixed("2"));
+        ob1.insert(Side::Bid, fixed("99"), fixed("4"));
+        ob1.insert(Side::Ask, fixed("100.01"), fixed("1"));
+        ob1.insert(Side::Ask, fixed("100.2"), fixed("3"));
+        // mid 100: 99.99 is 1 bp away, 99.9 10 bps, 100.2 20 bps
+        assert_eq!(ob1.depth_at(0.0), Some((Fixed::ZERO, Fixed::ZERO)));
+        assert_eq!(ob1.depth_at(10.0), Some((fixed("3"), fixed("1"))));
+        assert_eq!(ob1.depth_at(25.0), Some((fixed("3"), fixed("4"))));
+        assert_eq!(ob1.depth_at(100.0), Some((fixed("7"), fixed("4"))));
+        assert_eq!(Orderbook::new("B").depth_at(10.0), None);
+
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(Side::Bid, fixed("99.95"), fixed("5"));
+        let mut agg = AggregatedOrderbook::new();
+        agg.depth_offsets = vec![10.0, 25.0];
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(summary.depth_at.len(), 2);
+        assert_eq!(summary.depth_at[0].offset_bps, 10.0);
+        assert_eq!(summary.depth_at[0].bid_amount, 8.0);
+        assert_eq!(summary.depth_at[0].ask_amount, 1.0);
+        assert_eq!(summary.depth_at[1].ask_amount, 4.0);
+    }
+    #[test]
+    fn test_bands() {
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        let mut ob1 = Orderbook::new("A");
+        ob1.insert(Side::Bid, fixed("99.99"), fixed("1"));
+        ob1.insert(Side::Bid, fixed("99.9"), fixed("2"));
+        ob1.insert(Side::Bid, fixed("99"), fixed("4"));
+        ob1.insert(Side::Ask, fixed("100.01"), fixed("1"));
+        ob1.insert(Side::Ask, fixed("100.2"), fixed("3"));
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(Side::Bid, fixed("99.95"), fixed("5"));
+        let mut agg = AggregatedOrderbook::new();
+        agg.band_edges = vec![5.0, 10.0, 25.0];
+        assert_eq!(agg.bands(), None);
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        // mid 100: 99.99 is 1 bp away, 99.95 5 bps, 99.9 10 bps, 100.2 20 bps, 99 out
+        let bands = agg.bands().unwrap();
+        let expect = |band: usize, exchange: &str, bid: &str, ask: &str| {
+            (band, exchange.to_string(), fixed(bid), fixed(ask))
+        };
+        assert_eq!(
+            bands,
+            vec![
+                expect(0, "A", "1", "1"),
+                expect(0, "B", "5", "0"),
+                expect(1, "A", "2", "0"),
+                expect(1, "B", "0", "0"),
+                expect(2, "A", "0", "3"),
+                expect(2, "B", "0", "0"),
+            ]
+        );
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(summary.bands.len(), 6);
+        let band = &summary.bands[2];
+        assert_eq!((band.from_bps, band.to_bps), (5.0, 10.0));
+        assert_eq!((band.exchange.as_str(), band.bid_amount), ("A", 2.0));
+    }
+    #[test]
+    fn test_crossed_spread() {
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        let mut ob1 = Orderbook::new("A");
+        ob1.insert(Side::Bid, fixed("101"), fixed("1"));
+        ob1.insert(Side::Ask, fixed("102"), fixed("1"));
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(Side::Bid, fixed("98"), fixed("1"));
+        ob2.insert(Side::Ask, fixed("99"), fixed("1"));
+        let mut agg = AggregatedOrderbook::new();
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        // A bids above the ask of B
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(summary.spread, -2.0);
+        assert_eq!(summary.spread_bps, -200.0);
+        assert!(summary.crossed);
+
+        let summary = AggregatedOrderbook::new().finalize(10).unwrap();
+        assert_eq!((summary.spread_bps, summary.crossed), (0.0, false));
+    }
+    #[test]
+    fn test_synchronized() {
+        let book = |name: &str, received_ts: u128| {
+            let mut ob = Orderbook::new(name);
+            ob.received_ts = received_ts;
+            ob
+        };
+        let (a, b, c) = (book("A", 1000), book("B", 1180), book("C", 1250));
+        let (synced, stale) = synchronized(vec![&a, &b, &c], 100);
+        let names: Vec<&str> = synced.iter().map(|ob| ob.name.as_str()).collect();
+        assert_eq!((names, stale), (vec!["B", "C"], vec!["A".to_string()]));
+        // off
+        let (synced, stale) = synchronized(vec![&a, &b, &c], 0);
+        assert_eq!((synced.len(), stale.len()), (3, 0));
+        assert_eq!(synchronized(vec![], 100), (vec![], vec![]));
+    }
+    #[test]
+    fn test_agg_fee() {
+        let mut ob1 = Orderbook::new("A");
+        ob1.fee_bps = 10.0;
+        ob1.insert(Side::Bid, Fixed::from(100), Fixed::from(1));
+        ob1.insert(Side::Ask, Fixed::from(101), Fixed::from(1));
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(Side::Bid, Fixed::from_str("99.95").unwrap(), Fixed::from(1));
+        ob2.insert(Side::Ask, Fixed::from(101), Fixed::from(1));
+        let mut agg = AggregatedOrderbook::new();
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        assert_eq!(agg.check_crossed(), Ok(()));
+        let summary = agg.finalize(10).unwrap();
+        // A pays 10 bps: bid 100 => 99.9, ask 101 => 101.101
+        assert_eq!(summary.bids[0].exchange, "B");
+        assert_eq!(summary.bids[1].exchange, "A");
+        assert_eq!(summary.bids[1].price, 99.9);
+        assert_eq!(summary.bids[1].raw_price, 100.0);
+        assert_eq!(summary.asks[0].exchange, "B");
+        assert_eq!(summary.asks[1].price, 101.101);
+        assert_eq!(summary.asks[1].raw_price, 101.0);
+    }
+
+    #[test]
+    fn test_agg_conversion() {
+        let mut ob1 = Orderbook::new("A");
+        ob1.insert(Side::Bid, Fixed::from(100), Fixed::from(1));
+        ob1.insert(Side::Ask, Fixed::from(102), Fixed::from(1));
+        // quoted in USDT, at 0.99 USD
+        let mut ob2 = Orderbook::new("B");
+        ob2.quote = Some("USDT".to_string());
+        ob2.insert(Side::Bid, Fixed::from(101), Fixed::from(1));
+        ob2.insert(Side::Ask, Fixed::from(103), Fixed::from(1));
+        let mut agg = AggregatedOrderbook::new();
+        agg.conversions.insert(
+            "B".to_string(),
+            Arc::new(Conversion {
+                quote: "USDT".to_string(),
+                rate: Fixed::from_str("0.99").unwrap(),
+            }),
+        );
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        let summary = agg.finalize(10).unwrap();
+        // bid 101 => 99.99, ask 103 => 101.97
+        assert_eq!(summary.bids[0].exchange, "A");
+        assert_eq!(summary.bids[1].price, 99.99);
+        assert_eq!(summary.bids[1].raw_price, 101.0);
+        assert_eq!(summary.bids[1].quote, "USDT");
+        assert_eq!(summary.bids[1].quote_rate, 0.99);
+        assert_eq!(summary.asks[0].exchange, "B");
+        assert_eq!(summary.asks[0].price, 101.97);
+        assert_eq!(summary.asks[1].quote, "");
+    }
+
+    #[test]
+    fn test_agg_tick_size() {
+        let price = |p: &str| Fixed::from_str(p).unwrap();
+        let mut ob1 = Orderbook::new("A");
+        ob1.insert(Side::Bid, price("100.04"), Fixed::from(1));
+        ob1.insert(Side::Bid, price("100.01"), Fixed::from(2));
+        ob1.insert(Side::Ask, price("100.21"), Fixed::from(1));
+        let mut ob2 = Orderbook::new("B");
+        ob2.insert(Side::Bid, price("100.0"), Fixed::from(3));
+        ob2.insert(Side::Ask, price("100.3"), Fixed::from(1));
+        let mut agg = AggregatedOrderbook::new();
+        agg.strategy = Arc::new(Consolidate);
+        agg.set_tick_size(0.1);
+        agg.merge(&ob1);
+        agg.merge(&ob2);
+        assert_eq!(agg.check_crossed(), Ok(()));
+        let summary = agg.finalize(10).unwrap();
+        assert_eq!(summary.bids.len(), 1);
+        assert_eq!(summary.bids[0].price, 100.0);
+        assert_eq!(summary.bids[0].amount, 6.0);
+        // one contribution per exchange, at its best price
+        assert_eq!(summary.bids[0].contributions.len(), 2);
+        assert_eq!(summary.bids[0].contributions[0].amount, 3.0);
+        assert_eq!(summary.bids[0].contributions[0].raw_price, 100.04);
+        // asks round up
+        assert_eq!(summary.asks.len(), 1);
+        assert_eq!(summary.asks[0].price, 100.3);
+        assert_eq!(summary.asks[0].amount, 2.0);
+
+        agg.set_tick_size(0.0);
+        assert_eq!(agg.tick_size, None);
     }
 }
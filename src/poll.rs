@@ -0,0 +1,136 @@
+use crate::config::ExchangeSetting;
+use crate::fixed::Fixed;
+use crate::orderbook::Orderbook;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+use tokio::time::Duration;
+
+type Levels = (BTreeMap<Fixed, Fixed>, BTreeMap<Fixed, Fixed>);
+
+// Pace of the rest polls of a pair, or of the rounds over several. Each round halves the
+// interval if a book changed since its previous poll, and doubles it if all of them were
+// static, within [min, max]. min == max => a fixed interval.
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    current: Duration,
+    min: Duration,
+    max: Duration,
+    // pair => the levels of its last poll
+    last: HashMap<String, Levels>,
+    // a book changed in the current round
+    changed: bool,
+}
+
+impl AdaptiveInterval {
+    // starts at initial, clamped to [min, max]
+    pub fn new(initial: Duration, min: Duration, max: Duration) -> AdaptiveInterval {
+        let max = max.max(min);
+        AdaptiveInterval {
+            current: initial.clamp(min, max),
+            min,
+            max,
+            last: HashMap::new(),
+            changed: false,
+        }
+    }
+
+    // a fixed interval
+    pub fn fixed(interval: Duration) -> AdaptiveInterval {
+        AdaptiveInterval::new(interval, interval, interval)
+    }
+
+    // the pace of the polls of the pair: wait_secs, adaptive within min_wait_ms and
+    // max_wait_ms if set
+    pub fn of(setting: &ExchangeSetting) -> AdaptiveInterval {
+        let wait = Duration::from_secs(setting.wait_secs.max(1));
+        match setting.max_wait_ms > 0 {
+            true => AdaptiveInterval::new(
+                wait,
+                Duration::from_millis(setting.min_wait_ms),
+                Duration::from_millis(setting.max_wait_ms),
+            ),
+            false => AdaptiveInterval::fixed(wait),
+        }
+    }
+
+    // compare the book polled to the previous one of its pair
+    pub fn observe(&mut self, orderbook: &Orderbook) {
+        let levels = (orderbook.bid.clone(), orderbook.ask.clone());
+        // the first poll of a pair tells nothing
+        if let Some(previous) = self.last.insert(orderbook.pair.clone(), levels) {
+            self.changed |= previous.0 != orderbook.bid || previous.1 != orderbook.ask;
+        }
+    }
+
+    // a round over the pairs ended, returns the interval before the next one
+    pub fn round(&mut self) -> Duration {
+        let next = if self.changed {
+            self.current / 2
+        } else if self.last.is_empty() {
+            self.current
+        } else {
+            self.current * 2
+        };
+        self.current = next.clamp(self.min, self.max);
+        self.changed = false;
+        self.current
+    }
+}
+
+// a random part of the interval, up to ratio of it. The pairs polled on the same pace
+// drift apart instead of hitting the exchange together.
+pub fn jitter(interval: Duration, ratio: f64) -> Duration {
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    interval.mul_f64(ratio * random)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::Side;
+    use std::str::FromStr;
+
+    fn book(bid: &str) -> Orderbook {
+        let mut ob = Orderbook::with_pair("bitstamp", "btcusd");
+        ob.insert(
+            Side::Bid,
+            Fixed::from_str(bid).unwrap(),
+            Fixed::from_str("1").unwrap(),
+        );
+        ob
+    }
+
+    #[test]
+    fn test_adaptive_interval() {
+        let (min, max) = (Duration::from_millis(250), Duration::from_secs(8));
+        let mut interval = AdaptiveInterval::new(Duration::from_secs(3), min, max);
+        // the first round
+        assert_eq!(interval.round(), Duration::from_secs(3));
+        // nothing to compare yet
+        interval.observe(&book("100"));
+        assert_eq!(interval.round(), Duration::from_secs(6));
+        // static
+        interval.observe(&book("100"));
+        assert_eq!(interval.round(), max);
+        // changing, down to min
+        for (i, expected) in [4000, 2000, 1000, 500, 250, 250].into_iter().enumerate() {
+            interval.observe(&book(&(101 + i).to_string()));
+            assert_eq!(interval.round(), Duration::from_millis(expected));
+        }
+
+        let mut fixed = AdaptiveInterval::fixed(Duration::from_secs(3));
+        fixed.observe(&book("100"));
+        fixed.observe(&book("101"));
+        assert_eq!(fixed.round(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let interval = Duration::from_secs(2);
+        let jitters: Vec<Duration> = (0..20).map(|_| jitter(interval, 0.1)).collect();
+        assert!(jitters.iter().all(|j| *j <= Duration::from_millis(200)));
+        assert!(jitters.iter().any(|j| *j != jitters[0]));
+        assert_eq!(jitter(interval, 0.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,379 @@
+use crate::health::HealthRegistry;
+use crate::latency::LatencyRegistry;
+use crate::proto::{Snapshots, Summary, SummaryFilter, SummaryRequest};
+use crate::recorder::get_unixtime;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use anyhow::Result;
+use futures_util::stream;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::select;
+use tokio::time::{self, Duration};
+use tokio_util::sync::CancellationToken;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+// what the kubernetes probes look at
+#[derive(Clone)]
+pub struct Probe {
+    // set once the grpc server is bound
+    pub grpc_listening: Arc<AtomicBool>,
+    health: HealthRegistry,
+    latency: LatencyRegistry,
+    // the latest summaries, for /book
+    snapshots: Snapshots,
+    // a feed is live if it sent a message within the last live_secs
+    live_secs: u64,
+    // serve the live book page on /ui
+    pub ui: bool,
+}
+
+// the live book page, fed by /ui/events
+const UI_PAGE: &str = include_str!("ui.html");
+
+// the pace of the ui events
+const UI_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, PartialEq, Debug)]
+struct ProbeReport {
+    grpc_listening: bool,
+    live_exchanges: Vec<String>,
+}
+
+impl ProbeReport {
+    // listening, and at least one exchange feed is live
+    fn ready(&self) -> bool {
+        self.grpc_listening && !self.live_exchanges.is_empty()
+    }
+}
+
+impl Probe {
+    pub fn new(
+        health: HealthRegistry,
+        latency: LatencyRegistry,
+        snapshots: Snapshots,
+        live_secs: u64,
+    ) -> Probe {
+        Probe {
+            grpc_listening: Arc::new(AtomicBool::new(false)),
+            health,
+            latency,
+            snapshots,
+            live_secs,
+            ui: false,
+        }
+    }
+
+    fn report(&self) -> ProbeReport {
+        ProbeReport {
+            grpc_listening: self.grpc_listening.load(Ordering::Relaxed),
+            live_exchanges: self
+                .health
+                .live(get_unixtime().saturating_sub(self.live_secs * 1000)),
+        }
+    }
+}
+
+fn respond(ok: bool, report: ProbeReport) -> HttpResponse {
+    if ok {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+// liveness: the grpc server is listening
+async fn healthz(probe: web::Data<Probe>) -> HttpResponse {
+    let report = probe.report();
+    respond(report.grpc_listening, report)
+}
+
+// readiness
+async fn readyz(probe: web::Data<Probe>) -> HttpResponse {
+    let report = probe.report();
+    respond(report.ready(), report)
+}
+
+// the latencies, in the prometheus text format
+async fn metrics(probe: web::Data<Probe>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(probe.latency.metrics())
+}
+
+fn default_depth() -> u32 {
+    10
+}
+
+#[derive(Deserialize)]
+struct BookQuery {
+    // the levels per side, 0 => the published depth
+    #[serde(default = "default_depth")]
+    depth: u32,
+}
+
+// the latest summary of a symbol as json, for a human with curl
+async fn book(
+    probe: web::Data<Probe>,
+    pair: web::Path<String>,
+    query: web::Query<BookQuery>,
+) -> HttpResponse {
+    let pair = pair.into_inner();
+    let summary = probe.snapshots.lock().unwrap().get(&pair).cloned();
+    let filter = SummaryFilter::new(SummaryRequest {
+        pairs: vec![],
+        depth: query.depth,
+    });
+    match summary.and_then(|summary| filter.apply(summary)) {
+        Some(summary) => HttpResponse::Ok().json(summary),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("no summary of {}", pair)
+        })),
+    }
+}
+
+async fn ui_page() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(UI_PAGE)
+}
+
+impl Probe {
+    // the server-sent events of a tick: a summary event per symbol changed since sent, then
+    // a status event of the exchanges
+    fn ui_events(&self, sent: &mut HashMap<String, Summary>) -> Result<String> {
+        let mut events = String::new();
+        let snapshots = self.snapshots.lock().unwrap().clone();
+        for (symbol, summary) in snapshots {
+            if sent.get(&symbol) == Some(&summary) {
+                continue;
+            }
+            let data = serde_json::to_string(&summary)?;
+            let _ = write!(events, "event: summary\ndata: {}\n\n", data);
+            sent.insert(symbol, summary);
+        }
+        let status = serde_json::to_string(&self.health.report())?;
+        let _ = write!(events, "event: status\ndata: {}\n\n", status);
+        Ok(events)
+    }
+}
+
+// the summaries and the exchange statuses of the ui, until the client leaves or the server
+// closes
+async fn ui_events(probe: web::Data<Probe>, closed: web::Data<CancellationToken>) -> HttpResponse {
+    let state = (
+        probe.get_ref().clone(),
+        closed.get_ref().clone(),
+        HashMap::new(),
+        time::interval(UI_INTERVAL),
+    );
+    let events = stream::unfold(
+        state,
+        |(probe, closed, mut sent, mut interval)| async move {
+            select! {
+                _ = interval.tick() => {}
+                _ = closed.cancelled() => return None,
+            }
+            let events = probe.ui_events(&mut sent).map(web::Bytes::from);
+            let events = events.map_err(actix_web::error::ErrorInternalServerError);
+            Some((events, (probe, closed, sent, interval)))
+        },
+    );
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+// keep the grpc.health.v1 statuses of the services in step with /readyz until closed.
+// "" is the whole server.
+pub async fn report_grpc(
+    probe: Probe,
+    mut reporter: HealthReporter,
+    services: Vec<&'static str>,
+    closed: CancellationToken,
+) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    let mut serving = None;
+    loop {
+        select! {
+            _ = interval.tick() => {}
+            _ = closed.cancelled() => return,
+        }
+        let ready = probe.report().ready();
+        if serving == Some(ready) {
+            continue;
+        }
+        serving = Some(ready);
+        let status = if ready {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+        info!("grpc health: {:?}", status);
+        for service in services.iter() {
+            reporter.set_service_status(service, status).await;
+        }
+    }
+}
+
+// serves /healthz, /readyz, /metrics, /book/{pair} and the ui until closed.
+pub async fn run(listener: TcpListener, probe: Probe, closed: CancellationToken) -> Result<()> {
+    info!("probe server listening on {}", listener.local_addr()?);
+    let ui_closed = closed.clone();
+    let server = HttpServer::new(move || {
+        let ui = probe.ui;
+        App::new()
+            .app_data(web::Data::new(probe.clone()))
+            .app_data(web::Data::new(ui_closed.clone()))
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz))
+            .route("/metrics", web::get().to(metrics))
+            // the symbols may have a slash, ex: XBT/USD
+            .route("/book/{pair:.*}", web::get().to(book))
+            .configure(|cfg| {
+                if ui {
+                    cfg.route("/ui", web::get().to(ui_page))
+                        .route("/ui/events", web::get().to(ui_events));
+                }
+            })
+    })
+    .workers(1)
+    .disable_signals()
+    .listen(listener)?
+    .run();
+    let handle = server.handle();
+    select! {
+        result = server => result?,
+        _ = closed.cancelled() => handle.stop(true).await,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{Level, Summary};
+    use actix_web::body::MessageBody;
+    use actix_web::http::StatusCode;
+
+    async fn probe_status(probe: &Probe, ready: bool) -> (StatusCode, String) {
+        let data = web::Data::new(probe.clone());
+        let response = if ready {
+            readyz(data).await
+        } else {
+            healthz(data).await
+        };
+        let status = response.status();
+        let body = response.into_body().try_into_bytes().unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn test_probe() {
+        let health = HealthRegistry::new();
+        let probe = Probe::new(
+            health.clone(),
+            LatencyRegistry::new(),
+            Snapshots::default(),
+            30,
+        );
+        let (status, _) = probe_status(&probe, false).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        probe.grpc_listening.store(true, Ordering::Relaxed);
+        let (status, _) = probe_status(&probe, false).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = probe_status(&probe, true).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, r#"{"grpc_listening":true,"live_exchanges":[]}"#);
+
+        health.message("binance", get_unixtime());
+        let (status, body) = probe_status(&probe, true).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"{"grpc_listening":true,"live_exchanges":["binance"]}"#
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_book() {
+        let snapshots = Snapshots::default();
+        let level = |price: f64| Level {
+            exchange: "binance".to_string(),
+            price,
+            amount: 1.0,
+            ..Default::default()
+        };
+        snapshots.lock().unwrap().insert(
+            "XBT/USD".to_string(),
+            Summary {
+                pair: "XBT/USD".to_string(),
+                bids: vec![level(100.0), level(99.0)],
+                asks: vec![level(101.0), level(102.0)],
+                ..Default::default()
+            },
+        );
+        let probe = Probe::new(HealthRegistry::new(), LatencyRegistry::new(), snapshots, 30);
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(probe))
+                .route("/book/{pair:.*}", web::get().to(book)),
+        )
+        .await;
+        let request = actix_web::test::TestRequest::get()
+            .uri("/book/XBT/USD?depth=1")
+            .to_request();
+        let summary: Summary = actix_web::test::call_and_read_body_json(&app, request).await;
+        assert_eq!(summary.bids.len(), 1);
+        assert_eq!(summary.asks[0].price, 101.0);
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/book/ETH/USD")
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_ui_events() {
+        let (health, snapshots) = (HealthRegistry::new(), Snapshots::default());
+        let probe = Probe::new(
+            health.clone(),
+            LatencyRegistry::new(),
+            snapshots.clone(),
+            30,
+        );
+        health.connected("binance");
+        let summary = |spread: f64| Summary {
+            pair: "BTC-USDT".to_string(),
+            spread,
+            ..Default::default()
+        };
+        snapshots
+            .lock()
+            .unwrap()
+            .insert("BTC-USDT".to_string(), summary(1.0));
+        let mut sent = HashMap::new();
+        let events = probe.ui_events(&mut sent).unwrap();
+        assert!(events.starts_with("event: summary\ndata: {"), "{}", events);
+        assert!(events.contains(r#""pair":"BTC-USDT""#), "{}", events);
+        assert!(events.contains("event: status\ndata: "), "{}", events);
+        assert!(events.contains(r#""exchange":"binance""#), "{}", events);
+        // only the changed summaries are sent again
+        let events = probe.ui_events(&mut sent).unwrap();
+        assert!(events.starts_with("event: status"), "{}", events);
+        snapshots
+            .lock()
+            .unwrap()
+            .insert("BTC-USDT".to_string(), summary(2.0));
+        let events = probe.ui_events(&mut sent).unwrap();
+        assert_eq!(events.matches("event: summary").count(), 1);
+    }
+}
@@ -0,0 +1,238 @@
+use super::{BookDelta, DeltaAction, Level, LevelDelta, Summary};
+use std::collections::HashMap;
+
+// (exchange, price bits) => level
+type Side = HashMap<(String, u64), Level>;
+
+fn key(level: &Level) -> (String, u64) {
+    (level.exchange.clone(), level.price.to_bits())
+}
+
+// the ages change on every summary, they don't make a level changed.
+fn without_age(level: &Level) -> Level {
+    let mut level = level.clone();
+    level.age_ms = 0;
+    for contribution in level.contributions.iter_mut() {
+        contribution.age_ms = 0;
+    }
+    level
+}
+
+// the summary carries the same book: only the ages and the times differ.
+pub fn unchanged(previous: &Summary, summary: &Summary) -> bool {
+    let without_times = |summary: &Summary| Summary {
+        bids: summary.bids.iter().map(without_age).collect(),
+        asks: summary.asks.iter().map(without_age).collect(),
+        exchange_ts_ms: 0,
+        received_ts_ms: 0,
+        published_ts_ms: 0,
+        ..summary.clone()
+    };
+    without_times(previous) == without_times(summary)
+}
+
+fn side(levels: &[Level]) -> Side {
+    levels.iter().map(|l| (key(l), l.clone())).collect()
+}
+
+// the deletions first, in the order of the previous book, then the additions and the
+// updates in the order of the new one.
+fn diff(old: &Side, old_levels: &[Level], new: &[Level]) -> Vec<LevelDelta> {
+    let new_side = side(new);
+    let mut deltas: Vec<LevelDelta> = old_levels
+        .iter()
+        .filter(|l| !new_side.contains_key(&key(l)))
+        .map(|l| LevelDelta {
+            action: DeltaAction::Delete as i32,
+            level: Some(Level {
+                amount: 0.0,
+                ..l.clone()
+            }),
+        })
+        .collect();
+    for level in new {
+        let action = match old.get(&key(level)) {
+            None => DeltaAction::Add,
+            Some(previous) if without_age(previous) != without_age(level) => DeltaAction::Update,
+            Some(_) => continue,
+        };
+        deltas.push(LevelDelta {
+            action: action as i32,
+            level: Some(level.clone()),
+        });
+    }
+    deltas
+}
+
+fn snapshot(levels: &[Level]) -> Vec<LevelDelta> {
+    levels
+        .iter()
+        .map(|l| LevelDelta {
+            action: DeltaAction::Add as i32,
+            level: Some(l.clone()),
+        })
+        .collect()
+}
+
+struct Last {
+    summary: Summary,
+    bids: Side,
+    asks: Side,
+    sequence: u64,
+    // deltas sent since the last snapshot
+    since_snapshot: u32,
+}
+
+// the state of one BookDeltas stream: the last book sent of every pair.
+pub struct DeltaState {
+    last: HashMap<String, Last>,
+    // 0 => only the first message of a pair is a snapshot
+    snapshot_every: u32,
+}
+
+impl DeltaState {
+    pub fn new(snapshot_every: u32) -> DeltaState {
+        DeltaState {
+            last: HashMap::new(),
+            snapshot_every,
+        }
+    }
+
+    pub fn next(&mut self, summary: Summary) -> BookDelta {
+        let last = self.last.get(&summary.pair);
+        let resync = last.is_none_or(|last| {
+            self.snapshot_every > 0 && last.since_snapshot >= self.snapshot_every
+        });
+        let (bids, asks, since_snapshot) = match last {
+            Some(last) if !resync => (
+                diff(&last.bids, &last.summary.bids, &summary.bids),
+                diff(&last.asks, &last.summary.asks, &summary.asks),
+                last.since_snapshot + 1,
+            ),
+            _ => (snapshot(&summary.bids), snapshot(&summary.asks), 0),
+        };
+        let sequence = last.map_or(0, |last| last.sequence + 1);
+        let delta = BookDelta {
+            pair: summary.pair.clone(),
+            snapshot: resync,
+            bids,
+            asks,
+            sequence,
+        };
+        self.last.insert(
+            summary.pair.clone(),
+            Last {
+                bids: side(&summary.bids),
+                asks: side(&summary.asks),
+                summary,
+                sequence,
+                since_snapshot,
+            },
+        );
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(exchange: &str, price: f64, amount: f64) -> Level {
+        Level {
+            exchange: exchange.to_string(),
+            price,
+            amount,
+            ..Default::default()
+        }
+    }
+
+    fn summary(bids: Vec<Level>, asks: Vec<Level>) -> Summary {
+        Summary {
+            pair: "btcusdt".to_string(),
+            bids,
+            asks,
+            ..Default::default()
+        }
+    }
+
+    // rebuild the side from the deltas the way a subscriber would
+    fn apply(book: &mut Vec<Level>, deltas: &[LevelDelta], snapshot: bool, bids: bool) {
+        if snapshot {
+            book.clear();
+        }
+        for delta in deltas {
+            let level = delta.level.clone().unwrap();
+            book.retain(|l| key(l) != key(&level));
+            if delta.action != DeltaAction::Delete as i32 {
+                book.push(level);
+            }
+        }
+        book.sort_by(|a, b| {
+            let order = a.price.partial_cmp(&b.price).unwrap();
+            if bids {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let first = summary(vec![level("a", 100.0, 1.0)], vec![level("a", 101.0, 1.0)]);
+        let mut resent = first.clone();
+        resent.bids[0].age_ms = 100;
+        resent.published_ts_ms = 1;
+        assert!(unchanged(&first, &resent));
+        resent.asks[0].amount = 2.0;
+        assert!(!unchanged(&first, &resent));
+    }
+
+    #[test]
+    fn test_delta() {
+        let mut state = DeltaState::new(2);
+        let first = summary(
+            vec![level("a", 100.0, 1.0), level("b", 100.0, 2.0)],
+            vec![level("a", 101.0, 1.0)],
+        );
+        let delta = state.next(first.clone());
+        assert!(delta.snapshot);
+        assert_eq!(delta.sequence, 0);
+        assert_eq!(delta.bids.len(), 2);
+
+        let mut aged = first.clone();
+        aged.bids[0].age_ms = 100;
+        let delta = state.next(aged);
+        assert!(!delta.snapshot);
+        assert_eq!(delta.sequence, 1);
+        assert!(delta.bids.is_empty() && delta.asks.is_empty());
+
+        let second = summary(
+            vec![level("a", 100.0, 3.0), level("a", 99.0, 1.0)],
+            vec![level("a", 101.0, 1.0)],
+        );
+        let delta = state.next(second.clone());
+        let actions: Vec<i32> = delta.bids.iter().map(|d| d.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                DeltaAction::Delete as i32,
+                DeltaAction::Update as i32,
+                DeltaAction::Add as i32
+            ]
+        );
+        assert_eq!(delta.bids[0].level.as_ref().unwrap().exchange, "b");
+        let mut bids = first.bids.clone();
+        apply(&mut bids, &delta.bids, false, true);
+        assert_eq!(bids, second.bids);
+        assert!(delta.asks.is_empty());
+
+        // resync after 2 deltas
+        let delta = state.next(second.clone());
+        assert!(delta.snapshot);
+        assert_eq!(delta.sequence, 3);
+        let mut asks = vec![level("c", 1.0, 1.0)];
+        apply(&mut asks, &delta.asks, true, false);
+        assert_eq!(asks, second.asks);
+    }
+}
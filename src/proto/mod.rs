@@ -1,16 +1,90 @@
-mod orderbook;
-use futures_util::{ready, task::Context, task::Poll, Stream};
-pub use orderbook::orderbook_aggregator_client::*;
+pub mod delta;
+// the messages only one of the bins uses are imported from it, not re-exported
+pub mod orderbook;
+use crate::config::{LagPolicy, LogLevel};
+use crate::health::HealthRegistry;
+use delta::DeltaState;
+use futures_util::future::BoxFuture;
+use futures_util::{ready, task::Context, task::Poll, Stream, StreamExt};
+use log::info;
+pub use orderbook::admin_server::*;
 pub use orderbook::orderbook_aggregator_server::*;
-pub use orderbook::{Empty, Level, Summary};
-use tokio::sync::broadcast::{self, error::RecvError};
+pub use orderbook::{
+    ArbitrageSignal, BookDelta, BookTicker, ConnectionState, DeltaAction, Empty, ExchangeRequest,
+    ExchangeStatus, Level, LevelDelta, LogLevelRequest, PairList, PairRequest, StatusReport,
+    Summary, SummaryRequest, TickerSummary,
+};
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, TryRecvError},
+};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
-use tokio_util::sync::ReusableBoxFuture;
+use tokio_util::sync::{CancellationToken, ReusableBoxFuture};
 
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use tonic::{Code, Request, Response, Status};
 
+// served by the grpc reflection, so that tools like grpcurl find the service without the proto.
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("aggregator_descriptor.bin");
+
+// the commands of the Admin service
+#[derive(Debug, Clone, PartialEq)]
+pub enum Control {
+    Subscribe(PairRequest),
+    Unsubscribe(PairRequest),
+    Disable(ExchangeRequest),
+    Enable(ExchangeRequest),
+}
+
+impl Control {
+    pub fn exchange(&self) -> &str {
+        match self {
+            Control::Subscribe(request) | Control::Unsubscribe(request) => &request.exchange,
+            Control::Disable(request) | Control::Enable(request) => &request.exchange,
+        }
+    }
+
+    // "" for the commands on a whole exchange
+    pub fn pair(&self) -> &str {
+        match self {
+            Control::Subscribe(request) | Control::Unsubscribe(request) => &request.pair,
+            Control::Disable(_) | Control::Enable(_) => "",
+        }
+    }
+}
+
+// the control command, and the channel to report the result back to the grpc caller
+pub type ControlRequest = (Control, oneshot::Sender<Result<(), String>>);
+
+// exchange => the pairs it offers. Set by the server, the exchange apis aren't in the client.
+type PairsFuture = BoxFuture<'static, Result<Vec<String>, Status>>;
+
+#[derive(Clone)]
+pub struct PairLister(pub Arc<dyn Fn(String) -> PairsFuture + Send + Sync>);
+
+impl fmt::Debug for PairLister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PairLister")
+    }
+}
+
+// the book of one pair of an exchange, alone in a summary of its symbol
+#[derive(Debug, Clone)]
+pub struct VenueBook {
+    pub exchange: String,
+    // in the notation of the exchange
+    pub pair: String,
+    pub summary: Summary,
+}
+
+// symbol => the latest summary published
+pub type Snapshots = Arc<Mutex<HashMap<String, Summary>>>;
+
 // A wrapper on the grpc server api
 #[derive(Debug)]
 pub struct AggServer {
@@ -20,79 +94,801 @@ pub struct AggServer {
     #[allow(dead_code)]
     broadcast_rx: broadcast::Receiver<Result<Summary, Status>>, // To have same lifetimea s AggServer
     broadcast_tx: broadcast::Sender<Result<Summary, Status>>,
+    // cancelled once the broadcast channel is drained after shutdown.
+    pub closed: CancellationToken,
+    health: HealthRegistry,
+    // used by the subscribers not asking for their own
+    lag_policy: LagPolicy,
+    signals_tx: broadcast::Sender<ArbitrageSignal>,
+    tickers_tx: broadcast::Sender<TickerSummary>,
+    book_tickers_tx: broadcast::Sender<BookTicker>,
+    venue_books_tx: broadcast::Sender<VenueBook>,
+    snapshots: Snapshots,
+    // deltas of a pair between two full snapshots on BookDeltas
+    delta_snapshot_every: u32,
+    // None => ListSupportedPairs is unimplemented
+    pair_lister: Option<PairLister>,
 }
 
 impl AggServer {
-    pub fn new() -> AggServer {
+    pub fn new(
+        shutdown: CancellationToken,
+        health: HealthRegistry,
+        capacity: usize,
+        lag_policy: LagPolicy,
+        delta_snapshot_every: u32,
+    ) -> AggServer {
         let (tx, mut rx) = unbounded_channel();
-        let (btx, brx) = broadcast::channel(20);
+        let (btx, brx) = broadcast::channel(capacity);
+        let (signals_tx, _) = broadcast::channel(capacity);
+        let (tickers_tx, _) = broadcast::channel(capacity);
+        let (book_tickers_tx, _) = broadcast::channel(capacity);
+        let (venue_books_tx, _) = broadcast::channel(capacity);
         let cbtx = btx.clone();
+        let snapshots = Snapshots::default();
+        let csnapshots = snapshots.clone();
+        let send = move |item: SummaryResult| {
+            if let Ok(summary) = item.as_ref() {
+                let mut snapshots = csnapshots.lock().unwrap();
+                snapshots.insert(summary.pair.clone(), summary.clone());
+            }
+            cbtx.send(item).unwrap();
+        };
+        let closed = CancellationToken::new();
+        let ccloned = closed.clone();
         let handle = tokio::spawn(async move {
-            while let Some(item) = rx.recv().await {
-                cbtx.send(item).unwrap();
+            loop {
+                tokio::select! {
+                    item = rx.recv() => match item {
+                        Some(item) => send(item),
+                        None => break,
+                    },
+                    _ = shutdown.cancelled() => {
+                        // deliver what's still buffered before closing the streams
+                        rx.close();
+                        while let Some(item) = rx.recv().await {
+                            send(item);
+                        }
+                        break;
+                    }
+                }
             }
+            ccloned.cancel();
         });
         AggServer {
             main_loop: handle,
             tx,
             broadcast_rx: brx,
             broadcast_tx: btx,
+            closed,
+            health,
+            lag_policy,
+            signals_tx,
+            tickers_tx,
+            book_tickers_tx,
+            venue_books_tx,
+            snapshots,
+            delta_snapshot_every,
+            pair_lister: None,
         }
     }
+
+    pub fn set_pair_lister(&mut self, lister: PairLister) {
+        self.pair_lister = Some(lister);
+    }
+
+    // the latest summary of each symbol, shared with the http /book endpoint
+    pub fn snapshots(&self) -> Snapshots {
+        self.snapshots.clone()
+    }
+
+    // the channel the arbitrage signals are published on. Sending fails without subscribers.
+    pub fn signals(&self) -> broadcast::Sender<ArbitrageSignal> {
+        self.signals_tx.clone()
+    }
+
+    // the channel the tickers are published on. Sending fails without subscribers.
+    pub fn tickers(&self) -> broadcast::Sender<TickerSummary> {
+        self.tickers_tx.clone()
+    }
+
+    // the channel the top of book tickers are published on. Sending fails without subscribers.
+    pub fn book_tickers(&self) -> broadcast::Sender<BookTicker> {
+        self.book_tickers_tx.clone()
+    }
+
+    // the channel the books of the exchanges are published on. Sending fails without
+    // subscribers.
+    pub fn venue_books(&self) -> broadcast::Sender<VenueBook> {
+        self.venue_books_tx.clone()
+    }
+
+    // the channel every summary is published on, shared with the other transports.
+    pub fn broadcaster(&self) -> broadcast::Sender<Result<Summary, Status>> {
+        self.broadcast_tx.clone()
+    }
+}
+
+// send the command to the market data, and wait for its result
+async fn send_control(
+    tx: &UnboundedSender<ControlRequest>,
+    control: Control,
+) -> Result<Response<Empty>, Status> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    tx.send((control, reply_tx))
+        .map_err(|_| Status::new(Code::Unavailable, "market data is not running"))?;
+    match reply_rx.await {
+        Ok(Ok(())) => Ok(Response::new(Empty {})),
+        Ok(Err(e)) => Err(Status::new(Code::InvalidArgument, e)),
+        Err(_) => Err(Status::new(Code::Unavailable, "market data is not running")),
+    }
+}
+
+// the Admin service, sending the commands to the market data
+#[derive(Debug)]
+pub struct AdminService {
+    control: UnboundedSender<ControlRequest>,
+}
+
+impl AdminService {
+    pub fn new(control: UnboundedSender<ControlRequest>) -> AdminService {
+        AdminService { control }
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn disable_exchange(
+        &self,
+        request: Request<ExchangeRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        send_control(&self.control, Control::Disable(request.into_inner())).await
+    }
+
+    async fn enable_exchange(
+        &self,
+        request: Request<ExchangeRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        send_control(&self.control, Control::Enable(request.into_inner())).await
+    }
+
+    async fn subscribe(&self, request: Request<PairRequest>) -> Result<Response<Empty>, Status> {
+        send_control(&self.control, Control::Subscribe(request.into_inner())).await
+    }
+
+    async fn unsubscribe(&self, request: Request<PairRequest>) -> Result<Response<Empty>, Status> {
+        send_control(&self.control, Control::Unsubscribe(request.into_inner())).await
+    }
+
+    // the logger filters on log::max_level, see logging::setup
+    async fn set_log_level(
+        &self,
+        request: Request<LogLevelRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let level: LogLevel = request
+            .into_inner()
+            .level
+            .parse()
+            .map_err(|e: String| Status::new(Code::InvalidArgument, e))?;
+        log::set_max_level(level.to_level_filter());
+        info!("log level set to {:?}", level);
+        Ok(Response::new(Empty {}))
+    }
 }
 
 type SummaryResult = Result<Summary, Status>;
 
+// the token in the "authorization: Bearer <token>" metadata
+fn bearer_token<T>(request: &Request<T>) -> Option<&str> {
+    request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// the subscriber named by the x-client-id metadata, or its address.
+fn client_id<T>(request: &Request<T>) -> String {
+    match request
+        .metadata()
+        .get("x-client-id")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(id) => id.to_string(),
+        None => request
+            .remote_addr()
+            .map_or_else(|| "unknown".to_string(), |addr| addr.to_string()),
+    }
+}
+
+// server side interceptor accepting the requests carrying one of the tokens. Empty => no auth.
+// tonic's interceptors return Result<Request<()>, Status>, Status is large.
+#[allow(clippy::result_large_err)]
+pub fn authenticate(
+    tokens: Vec<String>,
+) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |request| {
+        if tokens.is_empty() {
+            return Ok(request);
+        }
+        match bearer_token(&request) {
+            Some(token) if tokens.iter().any(|t| t == token) => Ok(request),
+            _ => Err(Status::new(Code::Unauthenticated, "invalid token")),
+        }
+    }
+}
+
+// client side interceptor attaching the token. None => nothing attached.
+// tonic's interceptors return Result<Request<()>, Status>, Status is large.
+#[allow(dead_code, clippy::result_large_err)]
+pub fn with_token(
+    token: Option<String>,
+) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |mut request| {
+        if let Some(token) = token.as_ref() {
+            let value = format!("Bearer {}", token)
+                .parse()
+                .map_err(|_| Status::new(Code::InvalidArgument, "malformed token"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+// the summaries a subscriber asked for
+#[derive(Debug, Clone, Default)]
+pub struct SummaryFilter {
+    // empty => every pair
+    pairs: HashSet<String>,
+    // 0 => untrimmed
+    depth: usize,
+}
+
+impl SummaryFilter {
+    pub fn new(request: SummaryRequest) -> SummaryFilter {
+        SummaryFilter {
+            pairs: request.pairs.into_iter().collect(),
+            depth: request.depth as usize,
+        }
+    }
+    // None if the pair wasn't asked for. The statistics of the summary are kept, computed
+    // over the published depth.
+    pub fn apply(&self, mut summary: Summary) -> Option<Summary> {
+        if !self.pairs.is_empty() && !self.pairs.contains(&summary.pair) {
+            return None;
+        }
+        if self.depth > 0 {
+            summary.bids.truncate(self.depth);
+            summary.asks.truncate(self.depth);
+        }
+        Some(summary)
+    }
+}
+
 pub struct BroadcastStream {
-    inner: ReusableBoxFuture<'static, (SummaryResult, broadcast::Receiver<SummaryResult>)>,
+    // sent before the broadcast ones
+    initial: VecDeque<Summary>,
+    filter: SummaryFilter,
+    policy: LagPolicy,
+    // names the subscriber in the logs
+    client: Arc<str>,
+    inner: ReusableBoxFuture<
+        'static,
+        (
+            SummaryResult,
+            broadcast::Receiver<SummaryResult>,
+            CancellationToken,
+        ),
+    >,
+}
+
+// drop everything buffered but the newest summary.
+fn skip_to_latest(rx: &mut broadcast::Receiver<SummaryResult>) -> Option<SummaryResult> {
+    let mut latest = None;
+    loop {
+        match rx.try_recv() {
+            Ok(item) => latest = Some(item),
+            Err(TryRecvError::Lagged(_)) => continue,
+            Err(TryRecvError::Empty | TryRecvError::Closed) => return latest,
+        }
+    }
 }
 
+// the stream items of tonic are Result<Summary, Status>, Status is large.
+#[allow(clippy::result_large_err)]
 async fn make_future(
     mut rx: broadcast::Receiver<SummaryResult>,
+    closed: CancellationToken,
+    policy: LagPolicy,
+    client: Arc<str>,
 ) -> (
     Result<Summary, Status>,
     broadcast::Receiver<Result<Summary, Status>>,
+    CancellationToken,
 ) {
-    let result = rx.recv().await.unwrap_or_else(|e| match e {
-        RecvError::Closed => Err(Status::new(Code::Aborted, "closed")),
-        RecvError::Lagged(_) => Err(Status::new(Code::DeadlineExceeded, "timeout")),
-    });
-    (result, rx)
+    let result = tokio::select! {
+        // drain the buffered summaries first
+        biased;
+        result = rx.recv() => result.unwrap_or_else(|e| match e {
+            RecvError::Closed => Err(Status::new(Code::Aborted, "closed")),
+            RecvError::Lagged(n) => {
+                info!(
+                    "subscriber {} lagged behind by {} summaries, {:?}",
+                    client, n, policy
+                );
+                let lagged = format!("lagged behind by {} summaries", n);
+                match policy {
+                    LagPolicy::SkipToLatest => skip_to_latest(&mut rx)
+                        .unwrap_or_else(|| Err(Status::new(Code::ResourceExhausted, lagged))),
+                    LagPolicy::Error => Err(Status::new(Code::ResourceExhausted, lagged)),
+                    LagPolicy::Disconnect => Err(Status::new(Code::Aborted, lagged)),
+                }
+            }
+        }),
+        _ = closed.cancelled() => Err(Status::new(Code::Aborted, "shutdown")),
+    };
+    (result, rx, closed)
 }
 
 impl BroadcastStream {
-    pub fn new(rx: broadcast::Receiver<SummaryResult>) -> Self {
+    pub fn new(
+        rx: broadcast::Receiver<SummaryResult>,
+        closed: CancellationToken,
+        policy: LagPolicy,
+        client: &str,
+    ) -> Self {
+        let client: Arc<str> = Arc::from(client);
+        info!("subscriber {} connected", client);
         Self {
-            inner: ReusableBoxFuture::new(make_future(rx)),
+            initial: VecDeque::new(),
+            filter: SummaryFilter::default(),
+            policy,
+            client: client.clone(),
+            inner: ReusableBoxFuture::new(make_future(rx, closed, policy, client)),
         }
     }
+
+    pub fn with_initial(mut self, initial: impl IntoIterator<Item = Summary>) -> Self {
+        self.initial.extend(initial);
+        self
+    }
+
+    // only the summaries passing the filter are sent, the initial ones included
+    pub fn with_filter(mut self, filter: SummaryFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+// the grpc stream is dropped when the subscriber leaves or lags out.
+impl Drop for BroadcastStream {
+    fn drop(&mut self) {
+        info!("subscriber {} disconnected", self.client);
+    }
 }
 
 impl Stream for BroadcastStream {
     type Item = Result<Summary, Status>;
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let (result, rx) = ready!(self.inner.poll(cx));
-        self.inner.set(make_future(rx));
-        match result {
-            Ok(item) => Poll::Ready(Some(Ok(item))),
-            Err(status) => match status.code() {
-                Code::Aborted => Poll::Ready(None),
-                _ => Poll::Ready(Some(Err(status))),
-            },
+        while let Some(summary) = self.initial.pop_front() {
+            if let Some(summary) = self.filter.apply(summary) {
+                return Poll::Ready(Some(Ok(summary)));
+            }
+        }
+        loop {
+            let (result, rx, closed) = ready!(self.inner.poll(cx));
+            let (policy, client) = (self.policy, self.client.clone());
+            self.inner.set(make_future(rx, closed, policy, client));
+            return match result {
+                Ok(item) => match self.filter.apply(item) {
+                    Some(item) => Poll::Ready(Some(Ok(item))),
+                    None => continue,
+                },
+                Err(status) => match status.code() {
+                    Code::Aborted => Poll::Ready(None),
+                    _ => Poll::Ready(Some(Err(status))),
+                },
+            };
         }
     }
 }
 
+type SignalStream = Pin<Box<dyn Stream<Item = Result<ArbitrageSignal, Status>> + Send>>;
+type TickerStream = Pin<Box<dyn Stream<Item = Result<TickerSummary, Status>> + Send>>;
+type BookTickerStream = Pin<Box<dyn Stream<Item = Result<BookTicker, Status>> + Send>>;
+type DeltaStream = Pin<Box<dyn Stream<Item = Result<BookDelta, Status>> + Send>>;
+type SummaryStream = Pin<Box<dyn Stream<Item = Result<Summary, Status>> + Send>>;
+
 #[tonic::async_trait]
 impl OrderbookAggregator for AggServer {
     type BookSummaryStream = BroadcastStream;
+    type ArbitrageSignalsStream = SignalStream;
+    type BookDeltasStream = DeltaStream;
+    type TickerSummariesStream = TickerStream;
+    type BestBookTickerStream = BookTickerStream;
+    type ExchangeBookStream = SummaryStream;
     async fn book_summary(
         &self,
-        _request: Request<Empty>,
+        request: Request<SummaryRequest>,
     ) -> Result<Response<Self::BookSummaryStream>, Status> {
+        let policy = match request.metadata().get("x-lag-policy") {
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(LagPolicy::parse)
+                .ok_or_else(|| Status::new(Code::InvalidArgument, "unknown x-lag-policy"))?,
+            None => self.lag_policy,
+        };
+        let client = client_id(&request);
+        let filter = SummaryFilter::new(request.into_inner());
         let btx = self.broadcast_tx.clone();
+        // subscribe first, nothing published in between is missed
         let brx = btx.subscribe();
+        let initial: Vec<Summary> = self.snapshots.lock().unwrap().values().cloned().collect();
+
+        Ok(Response::new(
+            BroadcastStream::new(brx, self.closed.clone(), policy, &client)
+                .with_initial(initial)
+                .with_filter(filter),
+        ))
+    }
+
+    // the signals are only meaningful while fresh, the lagged ones are skipped.
+    async fn arbitrage_signals(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ArbitrageSignalsStream>, Status> {
+        let stream = tokio_stream::wrappers::BroadcastStream::new(self.signals_tx.subscribe())
+            .filter_map(|item| async move { item.ok().map(Ok) })
+            .take_until(self.closed.clone().cancelled_owned());
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    // a newer ticker of the pair supersedes the lagged ones.
+    async fn ticker_summaries(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::TickerSummariesStream>, Status> {
+        let stream = tokio_stream::wrappers::BroadcastStream::new(self.tickers_tx.subscribe())
+            .filter_map(|item| async move { item.ok().map(Ok) })
+            .take_until(self.closed.clone().cancelled_owned());
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn best_book_ticker(
+        &self,
+        request: Request<SummaryRequest>,
+    ) -> Result<Response<Self::BestBookTickerStream>, Status> {
+        let pairs: HashSet<String> = request.into_inner().pairs.into_iter().collect();
+        let stream = tokio_stream::wrappers::BroadcastStream::new(self.book_tickers_tx.subscribe())
+            .filter_map(move |item| {
+                let ticker = item
+                    .ok()
+                    .filter(|t| pairs.is_empty() || pairs.contains(&t.pair));
+                async move { ticker.map(Ok) }
+            })
+            .take_until(self.closed.clone().cancelled_owned());
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    // a newer book of the pair supersedes the lagged ones.
+    async fn exchange_book(
+        &self,
+        request: Request<PairRequest>,
+    ) -> Result<Response<Self::ExchangeBookStream>, Status> {
+        let PairRequest { exchange, pair } = request.into_inner();
+        if exchange.is_empty() {
+            return Err(Status::new(Code::InvalidArgument, "exchange is required"));
+        }
+        let stream = tokio_stream::wrappers::BroadcastStream::new(self.venue_books_tx.subscribe())
+            .filter_map(move |item| {
+                let summary = item
+                    .ok()
+                    .filter(|book| book.exchange == exchange)
+                    .filter(|book| {
+                        pair.is_empty()
+                            || book.pair.eq_ignore_ascii_case(&pair)
+                            || book.summary.pair == pair
+                    })
+                    .map(|book| book.summary);
+                async move { summary.map(Ok) }
+            })
+            .take_until(self.closed.clone().cancelled_owned());
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    // diffed per stream against what it last sent, a lagging subscriber skipping
+    // summaries still gets a consistent book.
+    async fn book_deltas(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<Self::BookDeltasStream>, Status> {
+        let brx = self.broadcast_tx.subscribe();
+        let initial: Vec<Summary> = self.snapshots.lock().unwrap().values().cloned().collect();
+        let mut state = DeltaState::new(self.delta_snapshot_every);
+        let client = client_id(&request);
+        // the stream items of tonic are Result<BookDelta, Status>, Status is large.
+        #[allow(clippy::result_large_err)]
+        let stream =
+            BroadcastStream::new(brx, self.closed.clone(), LagPolicy::SkipToLatest, &client)
+                .with_initial(initial)
+                .map(move |item| item.map(|summary| state.next(summary)));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_snapshot(
+        &self,
+        request: Request<PairRequest>,
+    ) -> Result<Response<Summary>, Status> {
+        let pair = request.into_inner().pair;
+        let snapshots = self.snapshots.lock().unwrap();
+        snapshots
+            .get(&pair)
+            .cloned()
+            .map(Response::new)
+            .ok_or_else(|| Status::new(Code::NotFound, format!("no summary of {}", pair)))
+    }
+
+    async fn list_supported_pairs(
+        &self,
+        request: Request<ExchangeRequest>,
+    ) -> Result<Response<PairList>, Status> {
+        let exchange = request.into_inner().exchange;
+        let lister = self
+            .pair_lister
+            .as_ref()
+            .ok_or_else(|| Status::new(Code::Unimplemented, "no pair listing"))?;
+        let pairs = (lister.0)(exchange.clone()).await?;
+        Ok(Response::new(PairList { exchange, pairs }))
+    }
+
+    async fn get_status(&self, _request: Request<Empty>) -> Result<Response<StatusReport>, Status> {
+        Ok(Response::new(self.health.report()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn lagged_stream(policy: LagPolicy) -> BroadcastStream {
+        let (btx, brx) = broadcast::channel(2);
+        for i in 0..5 {
+            btx.send(Ok(Summary {
+                spread: i as f64,
+                ..Default::default()
+            }))
+            .unwrap();
+        }
+        BroadcastStream::new(brx, CancellationToken::new(), policy, "test")
+    }
+
+    // a server with no exchange yet
+    fn server() -> AggServer {
+        AggServer::new(
+            CancellationToken::new(),
+            HealthRegistry::new(),
+            20,
+            LagPolicy::default(),
+            100,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_lag_policy() {
+        let mut stream = lagged_stream(LagPolicy::SkipToLatest);
+        assert_eq!(stream.next().await.unwrap().unwrap().spread, 4.0);
+
+        let mut stream = lagged_stream(LagPolicy::Error);
+        let status = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+
+        let mut stream = lagged_stream(LagPolicy::Disconnect);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn test_client_id() {
+        let mut request = Request::new(());
+        assert_eq!(client_id(&request), "unknown");
+        request
+            .metadata_mut()
+            .insert("x-client-id", "dashboard".parse().unwrap());
+        assert_eq!(client_id(&request), "dashboard");
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let mut open = authenticate(vec![]);
+        assert!(open(Request::new(())).is_ok());
+
+        let mut auth = authenticate(vec!["a".to_string(), "b".to_string()]);
+        let status = auth(Request::new(())).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        let request = with_token(Some("b".to_string()))(Request::new(())).unwrap();
+        assert!(auth(request).is_ok());
+        let request = with_token(Some("c".to_string()))(Request::new(())).unwrap();
+        assert!(auth(request).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let server = server();
+        let request = || {
+            Request::new(PairRequest {
+                pair: "btcusdt".to_string(),
+                ..Default::default()
+            })
+        };
+        let status = server.get_snapshot(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let summary = Summary {
+            spread: 1.0,
+            pair: "btcusdt".to_string(),
+            ..Default::default()
+        };
+        let mut live = server.broadcaster().subscribe();
+        server.tx.send(Ok(summary.clone())).unwrap();
+        live.recv().await.unwrap().unwrap();
+        let snapshot = server.get_snapshot(request()).await.unwrap().into_inner();
+        assert_eq!(snapshot, summary);
+
+        // a new subscriber starts from the snapshot
+        let request = Request::new(SummaryRequest::default());
+        let response = server.book_summary(request).await.unwrap();
+        let mut stream = response.into_inner();
+        assert_eq!(stream.next().await.unwrap().unwrap(), summary);
+    }
+
+    #[tokio::test]
+    async fn test_list_supported_pairs() {
+        let mut server = server();
+        let request = |exchange: &str| {
+            Request::new(ExchangeRequest {
+                exchange: exchange.to_string(),
+            })
+        };
+        let status = server
+            .list_supported_pairs(request("kraken"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unimplemented);
+
+        server.set_pair_lister(PairLister(Arc::new(|exchange: String| {
+            Box::pin(async move {
+                match exchange.as_str() {
+                    "kraken" => Ok(vec!["XBT/USD".to_string()]),
+                    _ => Err(Status::new(Code::NotFound, exchange)),
+                }
+            })
+        })));
+        let list = server
+            .list_supported_pairs(request("kraken"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(list.exchange, "kraken");
+        assert_eq!(list.pairs, vec!["XBT/USD"]);
+        let status = server
+            .list_supported_pairs(request("okx"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_book() {
+        let server = server();
+        let request = |exchange: &str, pair: &str| {
+            Request::new(PairRequest {
+                exchange: exchange.to_string(),
+                pair: pair.to_string(),
+            })
+        };
+        let status = server.exchange_book(request("", "")).await.err().unwrap();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let mut by_pair = server
+            .exchange_book(request("kraken", "xbt/usd"))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut by_symbol = server
+            .exchange_book(request("kraken", "BTC-USD"))
+            .await
+            .unwrap()
+            .into_inner();
+        let book = |exchange: &str, pair: &str, spread: f64| VenueBook {
+            exchange: exchange.to_string(),
+            pair: pair.to_string(),
+            summary: Summary {
+                spread,
+                pair: "BTC-USD".to_string(),
+                ..Default::default()
+            },
+        };
+        let books = server.venue_books();
+        books.send(book("bitstamp", "btcusd", 1.0)).unwrap();
+        books.send(book("kraken", "XBT/USD", 2.0)).unwrap();
+        assert_eq!(by_pair.next().await.unwrap().unwrap().spread, 2.0);
+        assert_eq!(by_symbol.next().await.unwrap().unwrap().spread, 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_summary_filter() {
+        let server = server();
+        let summary = |pair: &str| Summary {
+            pair: pair.to_string(),
+            bids: vec![Level::default(); 3],
+            asks: vec![Level::default(); 2],
+            ..Default::default()
+        };
+        let mut live = server.broadcaster().subscribe();
+        server.tx.send(Ok(summary("btcusdt"))).unwrap();
+        live.recv().await.unwrap().unwrap();
+        let request = Request::new(SummaryRequest {
+            pairs: vec!["ethusdt".to_string()],
+            depth: 2,
+        });
+        let mut stream = server.book_summary(request).await.unwrap().into_inner();
+        // the btcusdt snapshot and summary are skipped
+        server.tx.send(Ok(summary("btcusdt"))).unwrap();
+        server.tx.send(Ok(summary("ethusdt"))).unwrap();
+        let received = stream.next().await.unwrap().unwrap();
+        assert_eq!(received.pair, "ethusdt");
+        assert_eq!((received.bids.len(), received.asks.len()), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_admin() {
+        let (control, mut control_rx) = unbounded_channel();
+        let admin = AdminService::new(control);
+        let request = || {
+            Request::new(ExchangeRequest {
+                exchange: "kraken".to_string(),
+            })
+        };
+        let market = tokio::spawn(async move {
+            let (command, reply): ControlRequest = control_rx.recv().await.unwrap();
+            assert_eq!(command.exchange(), "kraken");
+            assert_eq!(command.pair(), "");
+            assert!(matches!(command, Control::Disable(_)));
+            reply.send(Ok(())).unwrap();
+            let (command, reply) = control_rx.recv().await.unwrap();
+            assert!(matches!(command, Control::Enable(_)));
+            reply
+                .send(Err("kraken is not running".to_string()))
+                .unwrap();
+        });
+        assert!(admin.disable_exchange(request()).await.is_ok());
+        let status = admin.enable_exchange(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        market.await.unwrap();
+
+        // the market data has stopped
+        let status = admin.disable_exchange(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+    }
 
-        Ok(Response::new(BroadcastStream::new(brx)))
+    #[tokio::test]
+    async fn test_set_log_level() {
+        // the log level doesn't go through the market data, which has stopped
+        let (control, _) = unbounded_channel();
+        let admin = AdminService::new(control);
+        let level = |level: &str| {
+            Request::new(LogLevelRequest {
+                level: level.to_string(),
+            })
+        };
+        assert!(admin.set_log_level(level("warning")).await.is_ok());
+        assert_eq!(log::max_level(), log::LevelFilter::Warn);
+        assert!(admin.set_log_level(level("DEBUG")).await.is_ok());
+        assert_eq!(log::max_level(), log::LevelFilter::Debug);
+        // a bad level leaves the filter as it is
+        let status = admin.set_log_level(level("verbose")).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(log::max_level(), log::LevelFilter::Debug);
     }
 }
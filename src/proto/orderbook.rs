@@ -1,16 +1,108 @@
+#[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Empty {}
+#[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Summary {
+    /// best ask - best bid, 0 if either side is empty. Negative when crossed.
     #[prost(double, tag = "1")]
     pub spread: f64,
     #[prost(message, repeated, tag = "2")]
     pub bids: ::prost::alloc::vec::Vec<Level>,
     #[prost(message, repeated, tag = "3")]
     pub asks: ::prost::alloc::vec::Vec<Level>,
+    /// (best bid + best ask) / 2, 0 if either side is empty.
+    #[prost(double, tag = "4")]
+    pub mid_price: f64,
+    /// volume weighted average price over the published levels of each side.
+    #[prost(double, tag = "5")]
+    pub bid_vwap: f64,
+    #[prost(double, tag = "6")]
+    pub ask_vwap: f64,
+    /// total amount over the published levels of each side.
+    #[prost(double, tag = "7")]
+    pub bid_liquidity: f64,
+    #[prost(double, tag = "8")]
+    pub ask_liquidity: f64,
+    /// the symbol the books are aggregated under, see ExchangeSetting.symbol in the config.
+    #[prost(string, tag = "9")]
+    pub pair: ::prost::alloc::string::String,
+    /// (bid amount - ask amount) / (bid amount + ask amount) over the top analytics_levels
+    /// price levels of each side, in [-1, 1]. 0 if both sides are empty.
+    #[prost(double, tag = "10")]
+    pub imbalance: f64,
+    /// the vwaps of both sides over the same levels, each weighted by the amount of the
+    /// opposite side. 0 if either side is empty.
+    #[prost(double, tag = "11")]
+    pub microprice: f64,
+    /// unix millis, filled with summary_timestamps in the config. 0 if unknown.
+    /// the latest exchange time of the books, for the feeds stamping their messages.
+    #[prost(uint64, tag = "12")]
+    pub exchange_ts_ms: u64,
+    /// the latest receive time of the books.
+    #[prost(uint64, tag = "13")]
+    pub received_ts_ms: u64,
+    /// when the summary was published.
+    #[prost(uint64, tag = "14")]
+    pub published_ts_ms: u64,
+    /// the cumulative amounts within each of the depth_offsets_bps of the config from the mid
+    /// price, over the whole merged books. Empty if a side is.
+    #[prost(message, repeated, tag = "15")]
+    pub depth_at: ::prost::alloc::vec::Vec<DepthAt>,
+    /// published on a boundary of the snapshot_interval_ms of the config, whether the books
+    /// changed or not, ex: the close of a bar.
+    #[prost(bool, tag = "16")]
+    pub scheduled: bool,
+    /// the amounts of each exchange within the bands of the band_edges_bps of the config
+    /// around the mid price, by band then exchange. Empty if a side is.
+    #[prost(message, repeated, tag = "17")]
+    pub bands: ::prost::alloc::vec::Vec<Band>,
+    /// the spread relative to the mid price, in basis points, comparable across the pairs.
+    /// 0 if either side is empty, negative when crossed.
+    #[prost(double, tag = "18")]
+    pub spread_bps: f64,
+    /// the best bid is above the best ask: an exchange bids above the ask of another, ex: an
+    /// arbitrage. The book of each exchange is never crossed, see the desync.
+    #[prost(bool, tag = "19")]
+    pub crossed: bool,
+    /// with sync_window_ms in the config: an exchange of the symbol has no book received within
+    /// the window of the latest one. Its book is left out of the levels.
+    #[prost(bool, tag = "20")]
+    pub partial: bool,
+    /// the exchanges left out, sorted.
+    #[prost(string, repeated, tag = "21")]
+    pub stale_exchanges: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DepthAt {
+    #[prost(double, tag = "1")]
+    pub offset_bps: f64,
+    #[prost(double, tag = "2")]
+    pub bid_amount: f64,
+    #[prost(double, tag = "3")]
+    pub ask_amount: f64,
+}
+/// the prices from_bps (excluded, 0 included) to to_bps (included) away from the mid price.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Band {
+    #[prost(double, tag = "1")]
+    pub from_bps: f64,
+    #[prost(double, tag = "2")]
+    pub to_bps: f64,
+    #[prost(string, tag = "3")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "4")]
+    pub bid_amount: f64,
+    #[prost(double, tag = "5")]
+    pub ask_amount: f64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Level {
@@ -20,6 +112,376 @@ pub struct Level {
     pub price: f64,
     #[prost(double, tag = "3")]
     pub amount: f64,
+    /// per exchange breakdown, only filled in consolidate mode.
+    #[prost(message, repeated, tag = "4")]
+    pub contributions: ::prost::alloc::vec::Vec<Contribution>,
+    /// milliseconds since the oldest contributing exchange last updated this price.
+    #[prost(uint64, tag = "5")]
+    pub age_ms: u64,
+    /// the price quoted by the exchange. price is net of its fee_bps, and the levels are
+    /// ranked by it. In consolidate mode, the one of the first contribution.
+    #[prost(double, tag = "6")]
+    pub raw_price: f64,
+    /// price and amount as exact decimals, rounded to the output precision. Filled with
+    /// decimal_strings in the config.
+    #[prost(string, tag = "7")]
+    pub decimal_price: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub decimal_amount: ::prost::alloc::string::String,
+    /// the currency raw_price is quoted in, when converted into the quote of the symbol at
+    /// quote_rate, see ExchangeSetting.quote in the config. Empty if not converted. In
+    /// consolidate mode, those of the first contribution.
+    #[prost(string, tag = "9")]
+    pub quote: ::prost::alloc::string::String,
+    #[prost(double, tag = "10")]
+    pub quote_rate: f64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Contribution {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "2")]
+    pub amount: f64,
+    /// milliseconds since the exchange last updated this price.
+    #[prost(uint64, tag = "3")]
+    pub age_ms: u64,
+    /// the price quoted by the exchange, before the fee adjustment.
+    #[prost(double, tag = "4")]
+    pub raw_price: f64,
+    /// see Level.
+    #[prost(string, tag = "5")]
+    pub quote: ::prost::alloc::string::String,
+    #[prost(double, tag = "6")]
+    pub quote_rate: f64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeStatus {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(enumeration = "ConnectionState", tag = "2")]
+    pub state: i32,
+    /// unix time in milliseconds of the last message received, 0 if none yet.
+    #[prost(uint64, tag = "3")]
+    pub last_message_ms: u64,
+    #[prost(string, repeated, tag = "4")]
+    pub pairs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(uint64, tag = "5")]
+    pub reconnects: u64,
+    #[prost(string, tag = "6")]
+    pub last_error: ::prost::alloc::string::String,
+    /// unix time in milliseconds the cooldown of a down exchange ends, 0 otherwise.
+    #[prost(uint64, tag = "7")]
+    pub down_until_ms: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatusReport {
+    #[prost(message, repeated, tag = "1")]
+    pub exchanges: ::prost::alloc::vec::Vec<ExchangeStatus>,
+}
+/// selling at the best bid of sell_exchange and buying at the best ask of buy_exchange
+/// earns more than the configured threshold.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ArbitrageSignal {
+    #[prost(string, tag = "1")]
+    pub pair: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub sell_exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "3")]
+    pub sell_price: f64,
+    /// amount available at the best bid
+    #[prost(double, tag = "4")]
+    pub sell_amount: f64,
+    #[prost(string, tag = "5")]
+    pub buy_exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "6")]
+    pub buy_price: f64,
+    /// amount available at the best ask
+    #[prost(double, tag = "7")]
+    pub buy_amount: f64,
+    /// (sell_price - buy_price) / buy_price in basis points, net of the fees of both exchanges.
+    #[prost(double, tag = "8")]
+    pub net_bps: f64,
+}
+/// a level is identified by its exchange and price.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LevelDelta {
+    #[prost(enumeration = "DeltaAction", tag = "1")]
+    pub action: i32,
+    /// the amount of a deleted level is 0.
+    #[prost(message, optional, tag = "2")]
+    pub level: ::core::option::Option<Level>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BookDelta {
+    #[prost(string, tag = "1")]
+    pub pair: ::prost::alloc::string::String,
+    /// true => the levels are the whole book, replacing the previous one.
+    #[prost(bool, tag = "2")]
+    pub snapshot: bool,
+    #[prost(message, repeated, tag = "3")]
+    pub bids: ::prost::alloc::vec::Vec<LevelDelta>,
+    #[prost(message, repeated, tag = "4")]
+    pub asks: ::prost::alloc::vec::Vec<LevelDelta>,
+    /// per pair, incremented by 1 on every delta of the stream.
+    #[prost(uint64, tag = "5")]
+    pub sequence: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeTicker {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "2")]
+    pub last_price: f64,
+    /// traded in the last 24 hours, in the base currency
+    #[prost(double, tag = "3")]
+    pub volume: f64,
+}
+/// only the exchanges streaming a ticker are listed.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TickerSummary {
+    #[prost(string, tag = "1")]
+    pub pair: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub tickers: ::prost::alloc::vec::Vec<ExchangeTicker>,
+    /// the last prices weighted by the volumes, their average if no volume is known.
+    #[prost(double, tag = "3")]
+    pub composite_price: f64,
+    #[prost(double, tag = "4")]
+    pub total_volume: f64,
+}
+/// the best bid and ask across the exchanges of a pair, net of their fees. The ties go to
+/// the larger amount.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BookTicker {
+    #[prost(string, tag = "1")]
+    pub pair: ::prost::alloc::string::String,
+    #[prost(double, tag = "2")]
+    pub bid_price: f64,
+    #[prost(double, tag = "3")]
+    pub bid_amount: f64,
+    #[prost(string, tag = "4")]
+    pub bid_exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "5")]
+    pub ask_price: f64,
+    #[prost(double, tag = "6")]
+    pub ask_amount: f64,
+    #[prost(string, tag = "7")]
+    pub ask_exchange: ::prost::alloc::string::String,
+    /// ask - bid, 0 if either side is empty.
+    #[prost(double, tag = "8")]
+    pub spread: f64,
+    /// unix millis the book moving it was received, or of the removal of a book.
+    #[prost(uint64, tag = "9")]
+    pub received_ts_ms: u64,
+}
+/// wire compatible with Empty, which asks for every pair at the published depth.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SummaryRequest {
+    /// the symbols of the summaries. Empty => every pair.
+    #[prost(string, repeated, tag = "1")]
+    pub pairs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// the levels per side, at most the published depth. 0 => the published depth.
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PairRequest {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub pair: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeRequest {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LogLevelRequest {
+    /// error, warning, info or debug
+    #[prost(string, tag = "1")]
+    pub level: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PairList {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub pairs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// the binary recordings: the 4 bytes "MAGR", then the RecordingHeader and the RecordEntry
+/// records, each one prefixed by its length as a varint.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordingHeader {
+    /// the version of the format, incremented on incompatible changes. Currently 1.
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    /// unix time in milliseconds the file was started.
+    #[prost(uint64, tag = "2")]
+    pub start_ts_ms: u64,
+    /// the exchanges and their pairs configured when the file was started.
+    #[prost(message, repeated, tag = "3")]
+    pub exchanges: ::prost::alloc::vec::Vec<RecordedExchange>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordedExchange {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub pairs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// one of summary, book and raw is set.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordEntry {
+    /// local unix time in milliseconds.
+    #[prost(uint64, tag = "1")]
+    pub ts_ms: u64,
+    #[prost(message, optional, tag = "2")]
+    pub summary: ::core::option::Option<Summary>,
+    #[prost(message, optional, tag = "3")]
+    pub book: ::core::option::Option<RecordedBook>,
+    #[prost(message, optional, tag = "4")]
+    pub raw: ::core::option::Option<RecordedRaw>,
+}
+/// the book of an exchange before the aggregation, best prices first.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordedBook {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub pair: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "3")]
+    pub mark_price: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub funding_rate: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "5")]
+    pub bids: ::prost::alloc::vec::Vec<RecordedLevel>,
+    #[prost(message, repeated, tag = "6")]
+    pub asks: ::prost::alloc::vec::Vec<RecordedLevel>,
+}
+/// decimal strings, to keep the precision of the exchange.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordedLevel {
+    #[prost(string, tag = "1")]
+    pub price: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub quantity: ::prost::alloc::string::String,
+}
+/// the message as received from the exchange.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordedRaw {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub raw: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ConnectionState {
+    Disconnected = 0,
+    Connecting = 1,
+    Connected = 2,
+    /// the circuit breaker opened after too many failures, reconnecting after the cooldown.
+    Down = 3,
+    /// disabled through the Admin service, until enabled again.
+    Disabled = 4,
+}
+impl ConnectionState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition changes) but not the variant names.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ConnectionState::Disconnected => "DISCONNECTED",
+            ConnectionState::Connecting => "CONNECTING",
+            ConnectionState::Connected => "CONNECTED",
+            ConnectionState::Down => "DOWN",
+            ConnectionState::Disabled => "DISABLED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "DISCONNECTED" => Some(Self::Disconnected),
+            "CONNECTING" => Some(Self::Connecting),
+            "CONNECTED" => Some(Self::Connected),
+            "DOWN" => Some(Self::Down),
+            "DISABLED" => Some(Self::Disabled),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum DeltaAction {
+    Add = 0,
+    Update = 1,
+    Delete = 2,
+}
+impl DeltaAction {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition changes) but not the variant names.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            DeltaAction::Add => "ADD",
+            DeltaAction::Update => "UPDATE",
+            DeltaAction::Delete => "DELETE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ADD" => Some(Self::Add),
+            "UPDATE" => Some(Self::Update),
+            "DELETE" => Some(Self::Delete),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod orderbook_aggregator_client {
@@ -90,72 +552,1150 @@ pub mod orderbook_aggregator_client {
             self.inner = self.inner.accept_compressed(encoding);
             self
         }
-        /// Limits the maximum size of a decoded message.
-        ///
-        /// Default: `4MB`
-        #[must_use]
-        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_decoding_message_size(limit);
-            self
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        /// the summaries of the requested pairs, trimmed to the requested depth.
+        pub async fn book_summary(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SummaryRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::Summary>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/BookSummary",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.OrderbookAggregator", "BookSummary"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+        pub async fn get_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> std::result::Result<tonic::Response<super::StatusReport>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/GetStatus",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.OrderbookAggregator", "GetStatus"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn arbitrage_signals(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::ArbitrageSignal>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/ArbitrageSignals",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("orderbook.OrderbookAggregator", "ArbitrageSignals"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// the latest summary of PairRequest.pair, the symbol of the summary. exchange is ignored.
+        pub async fn get_snapshot(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PairRequest>,
+        ) -> std::result::Result<tonic::Response<super::Summary>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/GetSnapshot",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.OrderbookAggregator", "GetSnapshot"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// the levels changed since the previous delta of the pair sent on the stream. Every pair
+        /// starts with a full snapshot, repeated every delta_snapshot_every deltas for resync.
+        pub async fn book_deltas(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::BookDelta>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/BookDeltas",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.OrderbookAggregator", "BookDeltas"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// the last price and 24h volume of every exchange of a pair, published with its summaries.
+        pub async fn ticker_summaries(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::TickerSummary>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/TickerSummaries",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("orderbook.OrderbookAggregator", "TickerSummaries"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// the best bid and ask of the requested pairs, sent as soon as a book update moves them,
+        /// without waiting for the summary. SummaryRequest.depth is ignored.
+        pub async fn best_book_ticker(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SummaryRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::BookTicker>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/BestBookTicker",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("orderbook.OrderbookAggregator", "BestBookTicker"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// the pairs the exchange offers, from its rest api, named as in exchange_pair_map.
+        /// UNIMPLEMENTED for the exchanges not listing them.
+        pub async fn list_supported_pairs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExchangeRequest>,
+        ) -> std::result::Result<tonic::Response<super::PairList>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/ListSupportedPairs",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("orderbook.OrderbookAggregator", "ListSupportedPairs"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// the book of one exchange before the aggregation, as a summary of its levels alone, on each
+        /// of its updates. PairRequest.pair is the symbol or the pair of the exchange, empty => every
+        /// pair of the exchange.
+        pub async fn exchange_book(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PairRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::Summary>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/ExchangeBook",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("orderbook.OrderbookAggregator", "ExchangeBook"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+    }
+}
+/// Generated client implementations.
+pub mod admin_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// operator controls, served only with admin_tokens in the config.
+    #[derive(Debug, Clone)]
+    pub struct AdminClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl AdminClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> AdminClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> AdminClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            AdminClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        /// close the connection of the exchange and leave its books out of the aggregation.
+        pub async fn disable_exchange(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExchangeRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.Admin/DisableExchange",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.Admin", "DisableExchange"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// reconnect a disabled exchange.
+        pub async fn enable_exchange(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExchangeRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.Admin/EnableExchange",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.Admin", "EnableExchange"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// add the pair to the running exchange, or connect the exchange for it. The pair stays
+        /// until Unsubscribe, or a restart.
+        pub async fn subscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PairRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.Admin/Subscribe",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.Admin", "Subscribe"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// drop the pair from the exchange, a reconnection doesn't subscribe it again.
+        pub async fn unsubscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PairRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.Admin/Unsubscribe",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.Admin", "Unsubscribe"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// change the log level of the server until the next change or restart, without dropping
+        /// the subscriptions.
+        pub async fn set_log_level(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LogLevelRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.Admin/SetLogLevel",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("orderbook.Admin", "SetLogLevel"));
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod orderbook_aggregator_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with OrderbookAggregatorServer.
+    #[async_trait]
+    pub trait OrderbookAggregator: Send + Sync + 'static {
+        /// Server streaming response type for the BookSummary method.
+        type BookSummaryStream: futures_core::Stream<
+                Item = std::result::Result<super::Summary, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// the summaries of the requested pairs, trimmed to the requested depth.
+        async fn book_summary(
+            &self,
+            request: tonic::Request<super::SummaryRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::BookSummaryStream>,
+            tonic::Status,
+        >;
+        async fn get_status(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> std::result::Result<tonic::Response<super::StatusReport>, tonic::Status>;
+        /// Server streaming response type for the ArbitrageSignals method.
+        type ArbitrageSignalsStream: futures_core::Stream<
+                Item = std::result::Result<super::ArbitrageSignal, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn arbitrage_signals(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> std::result::Result<
+            tonic::Response<Self::ArbitrageSignalsStream>,
+            tonic::Status,
+        >;
+        /// the latest summary of PairRequest.pair, the symbol of the summary. exchange is ignored.
+        async fn get_snapshot(
+            &self,
+            request: tonic::Request<super::PairRequest>,
+        ) -> std::result::Result<tonic::Response<super::Summary>, tonic::Status>;
+        /// Server streaming response type for the BookDeltas method.
+        type BookDeltasStream: futures_core::Stream<
+                Item = std::result::Result<super::BookDelta, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// the levels changed since the previous delta of the pair sent on the stream. Every pair
+        /// starts with a full snapshot, repeated every delta_snapshot_every deltas for resync.
+        async fn book_deltas(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> std::result::Result<
+            tonic::Response<Self::BookDeltasStream>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the TickerSummaries method.
+        type TickerSummariesStream: futures_core::Stream<
+                Item = std::result::Result<super::TickerSummary, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// the last price and 24h volume of every exchange of a pair, published with its summaries.
+        async fn ticker_summaries(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> std::result::Result<
+            tonic::Response<Self::TickerSummariesStream>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the BestBookTicker method.
+        type BestBookTickerStream: futures_core::Stream<
+                Item = std::result::Result<super::BookTicker, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// the best bid and ask of the requested pairs, sent as soon as a book update moves them,
+        /// without waiting for the summary. SummaryRequest.depth is ignored.
+        async fn best_book_ticker(
+            &self,
+            request: tonic::Request<super::SummaryRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::BestBookTickerStream>,
+            tonic::Status,
+        >;
+        /// the pairs the exchange offers, from its rest api, named as in exchange_pair_map.
+        /// UNIMPLEMENTED for the exchanges not listing them.
+        async fn list_supported_pairs(
+            &self,
+            request: tonic::Request<super::ExchangeRequest>,
+        ) -> std::result::Result<tonic::Response<super::PairList>, tonic::Status>;
+        /// Server streaming response type for the ExchangeBook method.
+        type ExchangeBookStream: futures_core::Stream<
+                Item = std::result::Result<super::Summary, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// the book of one exchange before the aggregation, as a summary of its levels alone, on each
+        /// of its updates. PairRequest.pair is the symbol or the pair of the exchange, empty => every
+        /// pair of the exchange.
+        async fn exchange_book(
+            &self,
+            request: tonic::Request<super::PairRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::ExchangeBookStream>,
+            tonic::Status,
+        >;
+    }
+    #[derive(Debug)]
+    pub struct OrderbookAggregatorServer<T: OrderbookAggregator> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: OrderbookAggregator> OrderbookAggregatorServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for OrderbookAggregatorServer<T>
+    where
+        T: OrderbookAggregator,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/orderbook.OrderbookAggregator/BookSummary" => {
+                    #[allow(non_camel_case_types)]
+                    struct BookSummarySvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::SummaryRequest>
+                    for BookSummarySvc<T> {
+                        type Response = super::Summary;
+                        type ResponseStream = T::BookSummaryStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SummaryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).book_summary(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BookSummarySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/GetStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetStatusSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::Empty> for GetStatusSvc<T> {
+                        type Response = super::StatusReport;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).get_status(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/ArbitrageSignals" => {
+                    #[allow(non_camel_case_types)]
+                    struct ArbitrageSignalsSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::Empty>
+                    for ArbitrageSignalsSvc<T> {
+                        type Response = super::ArbitrageSignal;
+                        type ResponseStream = T::ArbitrageSignalsStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).arbitrage_signals(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ArbitrageSignalsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/GetSnapshot" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetSnapshotSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::PairRequest>
+                    for GetSnapshotSvc<T> {
+                        type Response = super::Summary;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PairRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_snapshot(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetSnapshotSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/BookDeltas" => {
+                    #[allow(non_camel_case_types)]
+                    struct BookDeltasSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::Empty>
+                    for BookDeltasSvc<T> {
+                        type Response = super::BookDelta;
+                        type ResponseStream = T::BookDeltasStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).book_deltas(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BookDeltasSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/TickerSummaries" => {
+                    #[allow(non_camel_case_types)]
+                    struct TickerSummariesSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::Empty>
+                    for TickerSummariesSvc<T> {
+                        type Response = super::TickerSummary;
+                        type ResponseStream = T::TickerSummariesStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).ticker_summaries(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = TickerSummariesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/BestBookTicker" => {
+                    #[allow(non_camel_case_types)]
+                    struct BestBookTickerSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::SummaryRequest>
+                    for BestBookTickerSvc<T> {
+                        type Response = super::BookTicker;
+                        type ResponseStream = T::BestBookTickerStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SummaryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).best_book_ticker(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BestBookTickerSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/ListSupportedPairs" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListSupportedPairsSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::ExchangeRequest>
+                    for ListSupportedPairsSvc<T> {
+                        type Response = super::PairList;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExchangeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).list_supported_pairs(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListSupportedPairsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/ExchangeBook" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExchangeBookSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::PairRequest>
+                    for ExchangeBookSvc<T> {
+                        type Response = super::Summary;
+                        type ResponseStream = T::ExchangeBookStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PairRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).exchange_book(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExchangeBookSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(
+                            http::Response::builder()
+                                .status(200)
+                                .header("grpc-status", "12")
+                                .header("content-type", "application/grpc")
+                                .body(empty_body())
+                                .unwrap(),
+                        )
+                    })
+                }
+            }
+        }
+    }
+    impl<T: OrderbookAggregator> Clone for OrderbookAggregatorServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
         }
-        /// Limits the maximum size of an encoded message.
-        ///
-        /// Default: `usize::MAX`
-        #[must_use]
-        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_encoding_message_size(limit);
-            self
+    }
+    impl<T: OrderbookAggregator> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(Arc::clone(&self.0))
         }
-        pub async fn book_summary(
-            &mut self,
-            request: impl tonic::IntoRequest<super::Empty>,
-        ) -> std::result::Result<
-            tonic::Response<tonic::codec::Streaming<super::Summary>>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
-            let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/orderbook.OrderbookAggregator/BookSummary",
-            );
-            let mut req = request.into_request();
-            req.extensions_mut()
-                .insert(GrpcMethod::new("orderbook.OrderbookAggregator", "BookSummary"));
-            self.inner.server_streaming(req, path, codec).await
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
         }
     }
+    impl<T: OrderbookAggregator> tonic::server::NamedService
+    for OrderbookAggregatorServer<T> {
+        const NAME: &'static str = "orderbook.OrderbookAggregator";
+    }
 }
 /// Generated server implementations.
-pub mod orderbook_aggregator_server {
+pub mod admin_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with OrderbookAggregatorServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with AdminServer.
     #[async_trait]
-    pub trait OrderbookAggregator: Send + Sync + 'static {
-        /// Server streaming response type for the BookSummary method.
-        type BookSummaryStream: futures_core::Stream<
-                Item = std::result::Result<super::Summary, tonic::Status>,
-            >
-            + Send
-            + 'static;
-        async fn book_summary(
+    pub trait Admin: Send + Sync + 'static {
+        /// close the connection of the exchange and leave its books out of the aggregation.
+        async fn disable_exchange(
             &self,
-            request: tonic::Request<super::Empty>,
-        ) -> std::result::Result<
-            tonic::Response<Self::BookSummaryStream>,
-            tonic::Status,
-        >;
+            request: tonic::Request<super::ExchangeRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status>;
+        /// reconnect a disabled exchange.
+        async fn enable_exchange(
+            &self,
+            request: tonic::Request<super::ExchangeRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status>;
+        /// add the pair to the running exchange, or connect the exchange for it. The pair stays
+        /// until Unsubscribe, or a restart.
+        async fn subscribe(
+            &self,
+            request: tonic::Request<super::PairRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status>;
+        /// drop the pair from the exchange, a reconnection doesn't subscribe it again.
+        async fn unsubscribe(
+            &self,
+            request: tonic::Request<super::PairRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status>;
+        /// change the log level of the server until the next change or restart, without dropping
+        /// the subscriptions.
+        async fn set_log_level(
+            &self,
+            request: tonic::Request<super::LogLevelRequest>,
+        ) -> std::result::Result<tonic::Response<super::Empty>, tonic::Status>;
     }
     #[derive(Debug)]
-    pub struct OrderbookAggregatorServer<T: OrderbookAggregator> {
+    pub struct AdminServer<T: Admin> {
         inner: _Inner<T>,
         accept_compression_encodings: EnabledCompressionEncodings,
         send_compression_encodings: EnabledCompressionEncodings,
@@ -163,7 +1703,7 @@ pub mod orderbook_aggregator_server {
         max_encoding_message_size: Option<usize>,
     }
     struct _Inner<T>(Arc<T>);
-    impl<T: OrderbookAggregator> OrderbookAggregatorServer<T> {
+    impl<T: Admin> AdminServer<T> {
         pub fn new(inner: T) -> Self {
             Self::from_arc(Arc::new(inner))
         }
@@ -215,9 +1755,9 @@ pub mod orderbook_aggregator_server {
             self
         }
     }
-    impl<T, B> tonic::codegen::Service<http::Request<B>> for OrderbookAggregatorServer<T>
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for AdminServer<T>
     where
-        T: OrderbookAggregator,
+        T: Admin,
         B: Body + Send + 'static,
         B::Error: Into<StdError> + Send + 'static,
     {
@@ -233,26 +1773,25 @@ pub mod orderbook_aggregator_server {
         fn call(&mut self, req: http::Request<B>) -> Self::Future {
             let inner = self.inner.clone();
             match req.uri().path() {
-                "/orderbook.OrderbookAggregator/BookSummary" => {
+                "/orderbook.Admin/DisableExchange" => {
                     #[allow(non_camel_case_types)]
-                    struct BookSummarySvc<T: OrderbookAggregator>(pub Arc<T>);
+                    struct DisableExchangeSvc<T: Admin>(pub Arc<T>);
                     impl<
-                        T: OrderbookAggregator,
-                    > tonic::server::ServerStreamingService<super::Empty>
-                    for BookSummarySvc<T> {
-                        type Response = super::Summary;
-                        type ResponseStream = T::BookSummaryStream;
+                        T: Admin,
+                    > tonic::server::UnaryService<super::ExchangeRequest>
+                    for DisableExchangeSvc<T> {
+                        type Response = super::Empty;
                         type Future = BoxFuture<
-                            tonic::Response<Self::ResponseStream>,
+                            tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::Empty>,
+                            request: tonic::Request<super::ExchangeRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).book_summary(request).await
+                                (*inner).disable_exchange(request).await
                             };
                             Box::pin(fut)
                         }
@@ -264,7 +1803,7 @@ pub mod orderbook_aggregator_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = BookSummarySvc(inner);
+                        let method = DisableExchangeSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -275,7 +1814,187 @@ pub mod orderbook_aggregator_server {
                                 max_decoding_message_size,
                                 max_encoding_message_size,
                             );
-                        let res = grpc.server_streaming(method, req).await;
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.Admin/EnableExchange" => {
+                    #[allow(non_camel_case_types)]
+                    struct EnableExchangeSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::ExchangeRequest>
+                    for EnableExchangeSvc<T> {
+                        type Response = super::Empty;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExchangeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).enable_exchange(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = EnableExchangeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.Admin/Subscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct SubscribeSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::PairRequest>
+                    for SubscribeSvc<T> {
+                        type Response = super::Empty;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PairRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).subscribe(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.Admin/Unsubscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct UnsubscribeSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::PairRequest>
+                    for UnsubscribeSvc<T> {
+                        type Response = super::Empty;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PairRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).unsubscribe(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UnsubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.Admin/SetLogLevel" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetLogLevelSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::LogLevelRequest>
+                    for SetLogLevelSvc<T> {
+                        type Response = super::Empty;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::LogLevelRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).set_log_level(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SetLogLevelSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
                         Ok(res)
                     };
                     Box::pin(fut)
@@ -295,7 +2014,7 @@ pub mod orderbook_aggregator_server {
             }
         }
     }
-    impl<T: OrderbookAggregator> Clone for OrderbookAggregatorServer<T> {
+    impl<T: Admin> Clone for AdminServer<T> {
         fn clone(&self) -> Self {
             let inner = self.inner.clone();
             Self {
@@ -307,7 +2026,7 @@ pub mod orderbook_aggregator_server {
             }
         }
     }
-    impl<T: OrderbookAggregator> Clone for _Inner<T> {
+    impl<T: Admin> Clone for _Inner<T> {
         fn clone(&self) -> Self {
             Self(Arc::clone(&self.0))
         }
@@ -317,8 +2036,7 @@ pub mod orderbook_aggregator_server {
             write!(f, "{:?}", self.0)
         }
     }
-    impl<T: OrderbookAggregator> tonic::server::NamedService
-    for OrderbookAggregatorServer<T> {
-        const NAME: &'static str = "orderbook.OrderbookAggregator";
+    impl<T: Admin> tonic::server::NamedService for AdminServer<T> {
+        const NAME: &'static str = "orderbook.Admin";
     }
 }
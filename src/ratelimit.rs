@@ -0,0 +1,95 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::time::{sleep, Duration, Instant};
+
+// token bucket limit: up to burst requests at once, refilled at per_sec requests per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_sec: f64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            burst: 10,
+            per_sec: 10.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Bucket {
+        Bucket {
+            tokens: limit.burst as f64,
+            last: now,
+        }
+    }
+
+    // take one token. Returns how long to wait before trying again if the bucket is empty.
+    fn take(&mut self, limit: RateLimit, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_sec).min(limit.burst as f64);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / limit.per_sec))
+        }
+    }
+}
+
+// Exchanges limit the requests per ip, so the buckets are shared by all the connections
+// of the same exchange.
+static BUCKETS: Lazy<Mutex<HashMap<String, Bucket>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// wait until the exchange allows one more request.
+pub async fn acquire(exchange: &str, limit: RateLimit) {
+    loop {
+        let wait = {
+            let mut tmp = BUCKETS.lock().unwrap();
+            let now = Instant::now();
+            tmp.entry(exchange.to_string())
+                .or_insert_with(|| Bucket::new(limit, now))
+                .take(limit, now)
+        };
+        match wait {
+            Some(wait) => sleep(wait).await,
+            None => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let limit = RateLimit {
+            burst: 2,
+            per_sec: 4.0,
+        };
+        let now = Instant::now();
+        let mut bucket = Bucket::new(limit, now);
+        assert_eq!(bucket.take(limit, now), None);
+        assert_eq!(bucket.take(limit, now), None);
+        assert_eq!(bucket.take(limit, now), Some(Duration::from_millis(250)));
+        // refilled one token
+        let now = now + Duration::from_millis(250);
+        assert_eq!(bucket.take(limit, now), None);
+        // never refill above the burst
+        let now = now + Duration::from_secs(10);
+        assert_eq!(bucket.take(limit, now), None);
+        assert_eq!(bucket.take(limit, now), None);
+        assert!(bucket.take(limit, now).is_some());
+    }
+}
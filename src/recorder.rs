@@ -0,0 +1,576 @@
+use crate::config::{RecordFormat, RecorderSetting};
+use crate::orderbook::Orderbook;
+use crate::proto::orderbook::{
+    RecordEntry, RecordedBook, RecordedExchange, RecordedLevel, RecordedRaw, RecordingHeader,
+    Summary,
+};
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Read, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::task;
+
+pub fn get_unixtime() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+// One line in the recorded file. ts is the local unix time in milliseconds.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Summary {
+        ts: u64,
+        summary: Summary,
+    },
+    Book {
+        ts: u64,
+        exchange: String,
+        #[serde(default)]
+        pair: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mark_price: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        funding_rate: Option<String>,
+        // [price, quantity], best price first
+        bids: Vec<[String; 2]>,
+        asks: Vec<[String; 2]>,
+    },
+    // the message as received from the exchange, used by the replay mode.
+    Raw {
+        ts: u64,
+        exchange: String,
+        raw: String,
+    },
+}
+
+impl Record {
+    pub fn summary(summary: &Summary) -> Record {
+        Record::Summary {
+            ts: get_unixtime(),
+            summary: summary.clone(),
+        }
+    }
+    pub fn raw(exchange: &str, raw: &str) -> Record {
+        Record::Raw {
+            ts: get_unixtime(),
+            exchange: exchange.to_string(),
+            raw: raw.to_string(),
+        }
+    }
+    pub fn book(orderbook: &Orderbook) -> Record {
+        Record::Book {
+            ts: get_unixtime(),
+            exchange: orderbook.name.clone(),
+            pair: orderbook.pair.clone(),
+            mark_price: orderbook.mark_price.as_ref().map(|p| p.to_string()),
+            funding_rate: orderbook.funding_rate.as_ref().map(|r| r.to_string()),
+            bids: orderbook
+                .bid
+                .iter()
+                .rev()
+                .map(|(p, q)| [p.to_string(), q.to_string()])
+                .collect(),
+            asks: orderbook
+                .ask
+                .iter()
+                .map(|(p, q)| [p.to_string(), q.to_string()])
+                .collect(),
+        }
+    }
+}
+
+// the first bytes of the protobuf files
+pub const MAGIC: &[u8; 4] = b"MAGR";
+// the version of the protobuf files written, the reader rejects the newer ones
+pub const VERSION: u32 = 1;
+
+fn levels(levels: &[[String; 2]]) -> Vec<RecordedLevel> {
+    levels
+        .iter()
+        .map(|[price, quantity]| RecordedLevel {
+            price: price.clone(),
+            quantity: quantity.clone(),
+        })
+        .collect()
+}
+
+fn pairs(levels: Vec<RecordedLevel>) -> Vec<[String; 2]> {
+    levels
+        .into_iter()
+        .map(|level| [level.price, level.quantity])
+        .collect()
+}
+
+impl From<&Record> for RecordEntry {
+    fn from(record: &Record) -> RecordEntry {
+        match record {
+            Record::Summary { ts, summary } => RecordEntry {
+                ts_ms: *ts,
+                summary: Some(summary.clone()),
+                ..Default::default()
+            },
+            Record::Book {
+                ts,
+                exchange,
+                pair,
+                mark_price,
+                funding_rate,
+                bids,
+                asks,
+            } => RecordEntry {
+                ts_ms: *ts,
+                book: Some(RecordedBook {
+                    exchange: exchange.clone(),
+                    pair: pair.clone(),
+                    mark_price: mark_price.clone(),
+                    funding_rate: funding_rate.clone(),
+                    bids: levels(bids),
+                    asks: levels(asks),
+                }),
+                ..Default::default()
+            },
+            Record::Raw { ts, exchange, raw } => RecordEntry {
+                ts_ms: *ts,
+                raw: Some(RecordedRaw {
+                    exchange: exchange.clone(),
+                    raw: raw.clone(),
+                }),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl TryFrom<RecordEntry> for Record {
+    type Error = anyhow::Error;
+    fn try_from(entry: RecordEntry) -> Result<Record> {
+        let ts = entry.ts_ms;
+        match (entry.summary, entry.book, entry.raw) {
+            (Some(summary), None, None) => Ok(Record::Summary { ts, summary }),
+            (None, Some(book), None) => Ok(Record::Book {
+                ts,
+                exchange: book.exchange,
+                pair: book.pair,
+                mark_price: book.mark_price,
+                funding_rate: book.funding_rate,
+                bids: pairs(book.bids),
+                asks: pairs(book.asks),
+            }),
+            (None, None, Some(raw)) => Ok(Record::Raw {
+                ts,
+                exchange: raw.exchange,
+                raw: raw.raw,
+            }),
+            _ => Err(anyhow!("a record needs one of summary, book and raw")),
+        }
+    }
+}
+
+// the varint length then the message, None at the end of the file
+fn read_delimited(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let (mut length, mut shift) = (0u64, 0);
+    loop {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(anyhow!("truncated record length"));
+        }
+        if shift > 63 {
+            return Err(anyhow!("invalid record length"));
+        }
+        length |= u64::from(byte[0] & 0x7f) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut buf = vec![0u8; length as usize];
+    reader.read_exact(&mut buf).context("truncated record")?;
+    Ok(Some(buf))
+}
+
+enum Source {
+    Json(Lines<BufReader<File>>),
+    Protobuf(BufReader<File>),
+}
+
+// The records of a file written by the recorder, in either format.
+pub struct Records {
+    path: String,
+    // None for the json files
+    pub header: Option<RecordingHeader>,
+    source: Source,
+    // line number of the json files, record number of the protobuf ones
+    index: usize,
+}
+
+impl Records {
+    pub fn open(path: &str) -> Result<Records> {
+        let file = File::open(path).with_context(|| format!("unable to open {}", path))?;
+        let mut reader = BufReader::new(file);
+        if !reader.fill_buf()?.starts_with(MAGIC) {
+            return Ok(Records {
+                path: path.to_string(),
+                header: None,
+                source: Source::Json(reader.lines()),
+                index: 0,
+            });
+        }
+        reader.consume(MAGIC.len());
+        let header = read_delimited(&mut reader)?.ok_or_else(|| anyhow!("{}: no header", path))?;
+        let header = RecordingHeader::decode(&header[..])
+            .with_context(|| format!("{}: invalid header", path))?;
+        if header.version > VERSION {
+            return Err(anyhow!(
+                "{}: version {} is newer than the supported {}",
+                path,
+                header.version,
+                VERSION
+            ));
+        }
+        Ok(Records {
+            path: path.to_string(),
+            header: Some(header),
+            source: Source::Protobuf(reader),
+            index: 0,
+        })
+    }
+
+    // the position of the last record read, for the error messages. ex: summary.jsonl:3
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.path, self.index)
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        loop {
+            self.index += 1;
+            match &mut self.source {
+                Source::Json(lines) => {
+                    let line = match lines.next() {
+                        Some(line) => line?,
+                        None => return Ok(None),
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    return Ok(Some(serde_json::from_str(&line)?));
+                }
+                Source::Protobuf(reader) => {
+                    return match read_delimited(reader)? {
+                        Some(buf) => Ok(Some(RecordEntry::decode(&buf[..])?.try_into()?)),
+                        None => Ok(None),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Records {
+    type Item = Result<Record>;
+    fn next(&mut self) -> Option<Result<Record>> {
+        self.next_record()
+            .with_context(|| format!("{} invalid record", self.location()))
+            .transpose()
+    }
+}
+
+// Append records as json lines or length-prefixed protobuf, rotating the files by size and
+// time.
+pub struct Recorder {
+    setting: RecorderSetting,
+    // exchange => pairs, for the header of the protobuf files
+    exchanges: BTreeMap<String, Vec<String>>,
+    file: Option<BufWriter<File>>,
+    written: u64,
+    opened_at: Instant,
+}
+
+impl Recorder {
+    pub fn new(setting: RecorderSetting, exchanges: BTreeMap<String, Vec<String>>) -> Recorder {
+        Recorder {
+            setting,
+            exchanges,
+            file: None,
+            written: 0,
+            opened_at: Instant::now(),
+        }
+    }
+
+    fn should_rotate(&self) -> bool {
+        if self.file.is_none() {
+            return true;
+        }
+        (self.setting.rotate_bytes > 0 && self.written >= self.setting.rotate_bytes)
+            || (self.setting.rotate_secs > 0
+                && self.opened_at.elapsed() >= Duration::from_secs(self.setting.rotate_secs))
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.flush()?;
+        let start_ts_ms = get_unixtime();
+        let extension = match self.setting.format {
+            RecordFormat::Json => "jsonl",
+            RecordFormat::Protobuf => "pb",
+        };
+        let path = format!("{}.{}.{}", self.setting.path, start_ts_ms, extension);
+        info!("recording to {}", path);
+        let file = File::create(&path).with_context(|| format!("unable to create {}", path))?;
+        let mut file = BufWriter::new(file);
+        self.written = 0;
+        if self.setting.format == RecordFormat::Protobuf {
+            let header = RecordingHeader {
+                version: VERSION,
+                start_ts_ms,
+                exchanges: self
+                    .exchanges
+                    .iter()
+                    .map(|(exchange, pairs)| RecordedExchange {
+                        exchange: exchange.clone(),
+                        pairs: pairs.clone(),
+                    })
+                    .collect(),
+            };
+            let header = header.encode_length_delimited_to_vec();
+            file.write_all(MAGIC)?;
+            file.write_all(&header)?;
+            self.written = (MAGIC.len() + header.len()) as u64;
+        }
+        self.file = Some(file);
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<()> {
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        let buf = match self.setting.format {
+            RecordFormat::Json => {
+                let mut line = serde_json::to_vec(record)?;
+                line.push(b'\n');
+                line
+            }
+            RecordFormat::Protobuf => RecordEntry::from(record).encode_length_delimited_to_vec(),
+        };
+        // file is always set after rotate
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(&buf)?;
+        }
+        self.written += buf.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// consume the records until all the senders are dropped.
+// exchanges goes to the header of the protobuf files. The files are written on a thread of
+// their own, off the runtime.
+pub async fn run(
+    setting: RecorderSetting,
+    exchanges: BTreeMap<String, Vec<String>>,
+    mut rx: UnboundedReceiver<Record>,
+) {
+    let (tx, records) = mpsc::channel();
+    let writer = thread::spawn(move || write_records(setting, exchanges, records));
+    while let Some(record) = rx.recv().await {
+        if tx.send(record).is_err() {
+            break;
+        }
+    }
+    drop(tx);
+    match task::spawn_blocking(move || writer.join()).await {
+        Ok(Ok(())) => {}
+        _ => error!("recorder thread panicked"),
+    }
+}
+
+// write the records until run is done, flushing the file every second
+fn write_records(
+    setting: RecorderSetting,
+    exchanges: BTreeMap<String, Vec<String>>,
+    records: mpsc::Receiver<Record>,
+) {
+    let mut recorder = Recorder::new(setting, exchanges);
+    let mut flushed = Instant::now();
+    loop {
+        match records.recv_timeout(Duration::from_secs(1)) {
+            Ok(record) => {
+                if let Err(e) = recorder.write(&record) {
+                    error!("recorder: {}", e);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if flushed.elapsed() >= Duration::from_secs(1) {
+            if let Err(e) = recorder.flush() {
+                error!("recorder flush: {}", e);
+            }
+            flushed = Instant::now();
+        }
+    }
+    if let Err(e) = recorder.flush() {
+        error!("recorder flush: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = std::env::temp_dir().join(format!("recorder_test_{}", get_unixtime()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut recorder = Recorder::new(
+            RecorderSetting {
+                path: dir.join("summary").to_string_lossy().to_string(),
+                books: false,
+                raw: false,
+                rotate_bytes: 1,
+                rotate_secs: 0,
+                format: RecordFormat::Json,
+            },
+            BTreeMap::new(),
+        );
+        let record = Record::Summary {
+            ts: 1,
+            summary: Summary::default(),
+        };
+        recorder.write(&record).unwrap();
+        // make sure the next file gets a different name
+        std::thread::sleep(Duration::from_millis(2));
+        recorder.write(&record).unwrap();
+        recorder.flush().unwrap();
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 2);
+        for file in files {
+            let content = fs::read_to_string(file.unwrap().path()).unwrap();
+            let parsed: Record = serde_json::from_str(content.trim()).unwrap();
+            assert_eq!(parsed, record);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let dir = std::env::temp_dir().join(format!("recorder_run_test_{}", get_unixtime()));
+        fs::create_dir_all(&dir).unwrap();
+        let setting = RecorderSetting {
+            path: dir.join("summary").to_string_lossy().to_string(),
+            books: false,
+            raw: false,
+            rotate_bytes: 0,
+            rotate_secs: 0,
+            format: RecordFormat::Json,
+        };
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = tokio::spawn(run(setting, BTreeMap::new(), rx));
+        let record = Record::Summary {
+            ts: 1,
+            summary: Summary::default(),
+        };
+        tx.send(record.clone()).unwrap();
+        tx.send(record.clone()).unwrap();
+        // the file is flushed once the senders are gone
+        drop(tx);
+        handle.await.unwrap();
+        let file = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        let records: Vec<Record> = Records::open(&file.to_string_lossy())
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(records, vec![record.clone(), record]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_protobuf() {
+        let dir = std::env::temp_dir().join(format!("recorder_pb_test_{}", get_unixtime()));
+        fs::create_dir_all(&dir).unwrap();
+        let exchanges = BTreeMap::from([("binance".to_string(), vec!["btcusdt".to_string()])]);
+        let mut recorder = Recorder::new(
+            RecorderSetting {
+                path: dir.join("capture").to_string_lossy().to_string(),
+                books: true,
+                raw: true,
+                rotate_bytes: 0,
+                rotate_secs: 0,
+                format: RecordFormat::Protobuf,
+            },
+            exchanges,
+        );
+        let records = vec![
+            Record::Summary {
+                ts: 1,
+                summary: Summary {
+                    pair: "btcusdt".to_string(),
+                    spread: 0.5,
+                    ..Default::default()
+                },
+            },
+            Record::Book {
+                ts: 2,
+                exchange: "binance".to_string(),
+                pair: "btcusdt".to_string(),
+                mark_price: None,
+                funding_rate: Some("0.0001".to_string()),
+                bids: vec![["100.5".to_string(), "0.00000001".to_string()]],
+                asks: vec![],
+            },
+            Record::raw("binance", r#"{"id": 1, "result": null}"#),
+        ];
+        for record in records.iter() {
+            recorder.write(record).unwrap();
+        }
+        recorder.flush().unwrap();
+        let path = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        assert_eq!(path.extension().unwrap(), "pb");
+        let path = path.to_string_lossy().to_string();
+
+        let read = Records::open(&path).unwrap();
+        let header = read.header.clone().unwrap();
+        assert_eq!(header.version, VERSION);
+        assert_eq!(header.exchanges[0].exchange, "binance");
+        assert_eq!(header.exchanges[0].pairs, vec!["btcusdt"]);
+        let read: Vec<Record> = read.map(|r| r.unwrap()).collect();
+        assert_eq!(read, records);
+
+        // a record cut in the middle
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() - 3]).unwrap();
+        let read: Vec<_> = Records::open(&path).unwrap().collect();
+        assert_eq!(read.len(), 3);
+        let e = read[2].as_ref().unwrap_err();
+        assert!(format!("{:#}", e).contains(":3 invalid record: truncated record"));
+
+        // written by a newer version
+        let mut header = header;
+        header.version = VERSION + 1;
+        let mut content = MAGIC.to_vec();
+        content.extend(header.encode_length_delimited_to_vec());
+        fs::write(&path, content).unwrap();
+        assert!(Records::open(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use crate::apitree;
+use crate::apitree::wsapi::{ExchangeAdapter, ParsedEvent};
+use crate::bus::Event;
+use crate::clock::{self, SharedClock, SimulatedClock};
+use crate::config::{contract_of, fee_of, internal_depth_of, quote_of, symbol_of, ExchangeSetting};
+use crate::recorder::{Record, Records};
+use anyhow::Result;
+use log::{debug, error, info};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::mpsc::{channel, Receiver, UnboundedSender};
+use tokio::task;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+// read the raw messages recorded in path, a json lines or a protobuf recording, and push them through the exchange parsers.
+// speed scales the original pace, ex: 2.0 => twice as fast. 0 => as fast as possible.
+// depth trims the books of the pairs without internal_depth like the live exchange connections do,
+// and settings gives the symbols to aggregate the pairs under.
+// clock is set to the recorded time of each message before it is parsed.
+pub async fn run(
+    path: String,
+    speed: f64,
+    depth: u32,
+    settings: HashMap<String, Vec<ExchangeSetting>>,
+    tx: UnboundedSender<Event>,
+    clock: SimulatedClock,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let opened = path.clone();
+    let records = task::spawn_blocking(move || Records::open(&opened)).await??;
+    info!("replay from {} at speed {}", path, speed);
+    if let Some(header) = records.header.as_ref() {
+        info!(
+            "recording version {} started at {}",
+            header.version, header.start_ts_ms
+        );
+    }
+    let mut records = read(records);
+    let started = Instant::now();
+    let mut first_ts = None;
+    let mut count = 0;
+    // one adapter per exchange, like a live connection
+    let mut adapters = HashMap::<String, Box<dyn ExchangeAdapter>>::new();
+    let shared: SharedClock = Arc::new(clock.clone());
+    while let Some((location, record)) = records.recv().await {
+        let Record::Raw { ts, exchange, raw } = record? else {
+            // only the raw messages go through the pipeline again
+            continue;
+        };
+        if speed > 0.0 {
+            let offset = ts.saturating_sub(*first_ts.get_or_insert(ts));
+            let deadline = started + Duration::from_secs_f64(offset as f64 / 1000.0 / speed);
+            select! {
+                _ = sleep_until(deadline) => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+        } else if shutdown.is_cancelled() {
+            return Ok(());
+        }
+        debug!("replay {}: {}", exchange, raw);
+        let api = match adapters.entry(exchange.clone()) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(apitree::ws(&exchange)?),
+        };
+        // there's no connection to send the replies to
+        clock.set(ts);
+        match clock::scope(&shared, || api.parse(&raw)).map(ParsedEvent::book) {
+            Ok(Some(mut orderbook)) => {
+                let pairs = settings.get(&exchange).map_or(&[][..], |s| &s[..]);
+                orderbook.trim(internal_depth_of(pairs, &orderbook.pair, depth));
+                let symbol = symbol_of(pairs, &orderbook.pair);
+                orderbook.fee_bps = fee_of(pairs, &orderbook.pair);
+                orderbook.quote = quote_of(pairs, &orderbook.pair);
+                if let Some(contract) = contract_of(pairs, &orderbook.pair) {
+                    orderbook.convert_contracts(&contract);
+                }
+                tx.send(Event::BookUpdate {
+                    exchange,
+                    symbol,
+                    book: Arc::new(orderbook),
+                })?;
+                count += 1;
+            }
+            Ok(None) => {}
+            Err(e) => error!("replay {} {}", location, e),
+        }
+    }
+    info!("replay finished, {} books from {}", count, path);
+    Ok(())
+}
+
+// the records of the file with their location, read on the blocking pool ahead of the
+// replay. The reading stops once the receiver is dropped.
+fn read(mut records: Records) -> Receiver<(String, Result<Record>)> {
+    let (tx, rx) = channel(1024);
+    task::spawn_blocking(move || {
+        while let Some(record) = records.next() {
+            if tx.blocking_send((records.location(), record)).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Environment;
+    use crate::recorder::get_unixtime;
+    use std::fs;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[cfg(feature = "exchange-binance")]
+    #[tokio::test]
+    async fn test_replay() {
+        let path = std::env::temp_dir().join(format!("replay_test_{}.jsonl", get_unixtime()));
+        let records = [
+            Record::Raw {
+                ts: 1000,
+                exchange: "binance".to_string(),
+                raw: r#"{"id": 1, "result": null}"#.to_string(),
+            },
+            Record::Raw {
+                ts: 1001,
+                exchange: "binance".to_string(),
+                raw: r#"{"stream":"btcusdt@depth20@100ms","data":{"bids":[["0.02", "0.3"]],"asks":[]}}"#
+                    .to_string(),
+            },
+        ];
+        let content: Vec<String> = records
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect();
+        fs::write(&path, content.join("\n")).unwrap();
+
+        let settings = HashMap::from([(
+            "binance".to_string(),
+            vec![ExchangeSetting {
+                pair: "btcusdt".to_string(),
+                ws_api: true,
+                wait_secs: 3,
+                symbol: Some("BTC-USDT".to_string()),
+                fee_bps: 0.0,
+                hybrid: false,
+                incremental: false,
+                min_wait_ms: 0,
+                max_wait_ms: 0,
+                contract: None,
+                internal_depth: None,
+                publish_depth: None,
+                environment: Environment::Production,
+                quote: None,
+            }],
+        )]);
+        let (tx, mut rx) = unbounded_channel();
+        run(
+            path.to_string_lossy().to_string(),
+            0.0,
+            10,
+            settings,
+            tx,
+            SimulatedClock::default(),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // the subscription response is skipped by the parser
+        let Some(Event::BookUpdate {
+            exchange,
+            symbol,
+            book: orderbook,
+        }) = rx.recv().await
+        else {
+            panic!("not a book");
+        };
+        assert_eq!(exchange, "binance");
+        assert_eq!(symbol, "BTC-USDT");
+        assert_eq!(orderbook.bid.len(), 1);
+        // stamped with the recorded time
+        assert_eq!(orderbook.timestamp, 1001);
+        assert_eq!(orderbook.bid_time.values().next(), Some(&1001));
+        assert!(rx.recv().await.is_none());
+    }
+}
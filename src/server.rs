@@ -1,42 +1,167 @@
-#![feature(btree_cursors)]
-
+// a build with a subset of the exchanges leaves the helpers only the others use
+#![cfg_attr(not(feature = "all"), allow(dead_code, unused_imports))]
+mod analytics;
 mod apitree;
+mod arbitrage;
+mod breaker;
+mod bus;
+mod capture;
+mod clock;
 mod config;
+mod error;
+mod export;
+mod fixed;
+#[cfg(test)]
+mod golden;
+mod health;
+mod kafka;
+mod latency;
+mod livecache;
+mod logging;
+#[cfg(test)]
+mod mockws;
+mod net;
 mod orderbook;
+mod poll;
+mod probe;
 mod proto;
+mod ratelimit;
+mod recorder;
+mod replay;
+mod shutdown;
+mod sink;
+mod strategy;
+mod ticker;
+mod tls;
+mod topofbook;
+mod trace;
+mod wsserver;
+use crate::config::ArbitrageSetting;
+use crate::config::CircuitBreakerSetting;
 use crate::config::Config;
+use crate::config::ConversionSetting;
+use crate::config::Environment;
 use crate::config::ExchangeSetting;
-use anyhow::{anyhow, bail, Context, Result};
+use crate::config::NetworkSetting;
+use crate::config::{diff_exchanges, ExchangeChange, InnerConfig};
+use crate::config::{DustSetting, PrecisionSetting};
+use anyhow::{anyhow, Context, Result};
+use apitree::restapi;
+use apitree::wsapi::{supported_depth, ExchangeAdapter, ParsedEvent};
+use breaker::CircuitBreaker;
+use bus::{Event, EventBus};
+use capture::{Capture, CaptureSink};
 use clap::Parser;
+use error::{Error, Rejection};
+use fixed::Fixed;
 use formatx::formatx;
 use futures_util::stream::SplitStream;
-use futures_util::{pin_mut, FutureExt, SinkExt, StreamExt};
-use log::{debug, error, info};
-use orderbook::{AggregatedOrderbook, Orderbook};
-use proto::{AggServer, OrderbookAggregatorServer, Summary};
-use std::collections::HashMap;
+use futures_util::{future, SinkExt, StreamExt};
+use health::HealthRegistry;
+use latency::LatencyRegistry;
+use log::{debug, error, info, warn};
+use notify::{RecursiveMode, Watcher};
+use orderbook::{AggregatedOrderbook, Conversion, Orderbook};
+use poll::AdaptiveInterval;
+use probe::Probe;
+use proto::{
+    AdminServer, AdminService, AggServer, ArbitrageSignal, ConnectionState, Control,
+    ControlRequest, OrderbookAggregatorServer, PairLister, Summary,
+};
+use ratelimit::RateLimit;
+use recorder::Record;
+use sink::Sinks;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
 use std::string::String;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Poll;
 use std::vec::Vec;
-use tokio::net::TcpStream;
+use strategy::SharedStrategy;
+use tokio::net::{TcpListener, TcpStream, UnixListener};
 use tokio::select;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
-use tokio::time::{self, sleep, Duration};
-use tokio_tungstenite::{
-    connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
-};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::{self, JoinHandle};
+use tokio::time::{self, sleep, Duration, MissedTickBehavior};
+use tokio_stream::wrappers::UnixListenerStream;
+use tokio_tungstenite::{tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream};
+use tokio_util::sync::CancellationToken;
+use tonic::server::NamedService;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::server::TcpIncoming;
 use tonic::{transport::Server, Code, Status};
+use trace::FrameTrace;
 use Message::*;
 
+// send the requests to the exchange, keeping under its message rate limit.
+async fn send_requests(
+    name: &str,
+    limit: RateLimit,
+    utx: &UnboundedSender<Message>,
+    requests: Vec<String>,
+) -> error::Result<()> {
+    for request in requests {
+        ratelimit::acquire(name, limit).await;
+        utx.send(Message::Text(request))?;
+    }
+    Ok(())
+}
+
+// one websocket connection of the exchange, and the pairs streamed on it
+struct Shard {
+    pairs: Vec<String>,
+    rx: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    utx: UnboundedSender<Message>,
+    writer: JoinHandle<()>,
+    // None while a frame is parsed on the blocking pool
+    adapter: Option<Box<dyn ExchangeAdapter>>,
+}
+
+// The pairs of an exchange, spread over as many connections as its stream limit needs.
+// Their books come out of next in turn. A connection failing reconnects all of them.
 pub struct Exchange {
     name: String,
     level: u32,
-    rx: Option<SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>>,
-    utx: Option<UnboundedSender<Message>>,
+    shards: Vec<Shard>,
+    // the shard read first by the next call to next, so that none starves the others
+    next_shard: usize,
+    // the pairs a connection takes, 0 => no limit
+    pairs_per_connection: usize,
+    // the streams per connection, instead of those of the adapter
+    max_streams: Option<usize>,
+    // receives the raw messages when the recorder asks for them
+    recorder: Option<UnboundedSender<Record>>,
+    // receives the raw messages with their receive time when the exchange is captured
+    capture: Option<Arc<dyn CaptureSink>>,
+    // keeps the last raw messages, dumped when the feed breaks
+    trace: Option<Arc<FrameTrace>>,
+    // the adapters of the previous connections, and the pairs each resumes
+    resumed: Vec<(Box<dyn ExchangeAdapter>, Vec<String>)>,
+    // the frames parsed on the blocking pool from this size on. 0 => never
+    offload_bytes: usize,
     ws_api: bool,
     pairs: Vec<String>,
-    wait_secs: u64,
+    // rest only. pair => the task polling it
+    polls: HashMap<String, JoinHandle<()>>,
+    // rest only. the books the polls send, and the sender of the next ones
+    polled: Option<(UnboundedSender<Polled>, UnboundedReceiver<Polled>)>,
+    // rest only. how the pairs subscribed later are polled
+    poll_setting: Option<ExchangeSetting>,
+    // the proxy and tls options of the rest requests
+    network: NetworkSetting,
 }
 
+// a book polled over rest, or the error of the request
+type Polled = error::Result<Orderbook>;
+
+// rest only. the random part of the waits between the polls of a pair, of the interval
+const POLL_JITTER: f64 = 0.1;
+
+const NOT_CONNECTED: &str = "Not connect yet. Please run connect first";
+
 impl Exchange {
     pub fn new(name: &str) -> Exchange {
         Exchange {
@@ -44,65 +169,154 @@ impl Exchange {
             level: 10,
             ws_api: true,
             pairs: vec![],
-            wait_secs: 0,
-            rx: None,
-            utx: None,
+            polls: HashMap::new(),
+            polled: None,
+            poll_setting: None,
+            network: NetworkSetting::default(),
+            shards: vec![],
+            next_shard: 0,
+            pairs_per_connection: 0,
+            max_streams: None,
+            recorder: None,
+            capture: None,
+            trace: None,
+            resumed: vec![],
+            offload_bytes: 0,
         }
     }
 
-    pub async fn connect(&mut self, pairs: Vec<ExchangeSetting>) -> Result<()> {
+    pub async fn connect(
+        &mut self,
+        pairs: Vec<ExchangeSetting>,
+        network: &NetworkSetting,
+    ) -> error::Result<()> {
         self.pairs = pairs.iter().map(|e| e.pair.clone()).collect();
         let default_setup = pairs
-            .get(0)
-            .with_context(|| format!("should have at least one pair setting"))?;
-        self.wait_secs = if default_setup.wait_secs > 0 {
-            default_setup.wait_secs
-        } else {
-            1_u64
-        };
+            .first()
+            .context("should have at least one pair setting")?;
         self.ws_api = default_setup.ws_api;
+        self.network = network.clone();
         if !self.ws_api {
+            let api = apitree::rest(&self.name)?;
+            self.poll_setting = Some(default_setup.clone());
+            self.polled = Some(unbounded_channel());
+            for setting in pairs.iter() {
+                self.spawn_poll(api, setting);
+            }
             return Ok(());
         }
         info!("start connecting {}", self.name);
 
         let api = apitree::ws(&self.name)?;
-        let mut url = api.endpoint.to_string();
-        if api.render_url {
-            let p = self.pairs.join(",");
+        let depth = supported_depth(api.supported_depths(), self.level);
+        if depth < self.level {
+            info!(
+                "{} streams {} levels of the {} kept",
+                self.name, depth, self.level
+            );
+        }
+        // every subscribe message of a pair is a stream
+        let streams = self.max_streams.unwrap_or_else(|| api.max_streams());
+        let per_pair = api
+            .subscribe_messages(&default_setup.pair, self.level)?
+            .len()
+            .max(1);
+        self.pairs_per_connection = match streams {
+            0 => 0,
+            streams => (streams / per_pair).max(1),
+        };
+        let chunk = match self.pairs_per_connection {
+            0 => pairs.len(),
+            n => n,
+        };
+        let mut resumed = std::mem::take(&mut self.resumed);
+        for settings in pairs.chunks(chunk) {
+            // the adapter of the previous connection of these pairs
+            let index = resumed.iter().position(|(_, resumed)| {
+                resumed
+                    .iter()
+                    .all(|p| settings.iter().any(|s| s.pair.eq_ignore_ascii_case(p)))
+            });
+            let resumed = index.map(|index| resumed.swap_remove(index));
+            let shard =
+                Exchange::connect_shard(&self.name, self.level, settings, resumed, network).await?;
+            self.shards.push(shard);
+        }
+        if self.shards.len() > 1 {
+            info!(
+                "{} streams {} pairs on {} connections",
+                self.name,
+                self.pairs.len(),
+                self.shards.len()
+            );
+        }
+        Ok(())
+    }
+
+    // open a connection streaming the pairs, with the adapter resuming some of them if any
+    async fn connect_shard(
+        name: &str,
+        level: u32,
+        pairs: &[ExchangeSetting],
+        resumed: Option<(Box<dyn ExchangeAdapter>, Vec<String>)>,
+        network: &NetworkSetting,
+    ) -> error::Result<Shard> {
+        let names = pair_names(pairs);
+        let (mut api, resumed) = match resumed {
+            Some((api, resumed)) => {
+                info!(target: "resume", "{} resumes {:?}", name, resumed);
+                (api, resumed)
+            }
+            None => (apitree::ws(name)?, vec![]),
+        };
+        let is_resumed = |pair: &str| resumed.iter().any(|p| p.eq_ignore_ascii_case(pair));
+        if pairs[0].environment == Environment::Testnet {
+            api.testnet()?;
+        }
+        for setting in pairs.iter().filter(|s| s.incremental) {
+            api.incremental(&setting.pair)?;
+        }
+        let limit = api.rate_limit();
+        ratelimit::acquire(name, limit).await;
+        let mut url = match network.endpoints.get(name) {
+            Some(url) => url.clone(),
+            None => api.prepare(network).await?,
+        };
+        if api.render_url() {
+            let p = names.join(",");
             info!("render Url: {}", p);
             url = formatx!(url, p).map_err(|e| anyhow!("{}", e))?;
         }
         info!("{}", url);
 
-        let (ws_stream, result) = connect_async(url).await?;
+        let (ws_stream, result) = net::connect_ws(&url, network).await?;
         info!("{:?}", result);
         let (mut tx, rx) = ws_stream.split();
-        self.rx = Some(rx);
 
         let (utx, mut urx) = unbounded_channel();
         let utx_hb = utx.clone();
-        self.utx = Some(utx);
 
-        tokio::spawn(async move {
+        let writer = tokio::spawn(async move {
             while let Some(msg) = urx.recv().await {
+                let closing = matches!(msg, Close(_));
                 if let Err(e) = tx.send(msg).await {
                     error!("{}", e);
                 }
+                if closing {
+                    break;
+                }
             }
         });
 
-        let api = apitree::ws(&self.name)?;
-        let (wait_secs, msg) = api.heartbeat.unwrap_or((0, ""));
-        if wait_secs > 0 {
+        if let Some((wait_secs, msg)) = api.heartbeat() {
             let mut interval = time::interval(Duration::from_secs(wait_secs));
-            let name = self.name.clone();
+            let name = name.to_string();
             tokio::spawn(async move {
                 // sending heartbeats
                 loop {
                     interval.tick().await;
                     info!("send heartbeat to {}", name);
-                    if let Err(e) = utx_hb.send(Message::Binary(msg.into())) {
+                    if let Err(e) = utx_hb.send(Message::Text(msg.clone())) {
                         error!("heartbeat: {}", e);
                         break;
                     }
@@ -110,67 +324,275 @@ impl Exchange {
             });
         }
 
-        if !api.render_url {
-            if let Some(utx) = self.utx.clone() {
-                for pair in self.pairs.iter() {
-                    let requests = api.subscribe_text(pair, 20)?;
-                    info!("{:?}", requests);
-                    for request in requests {
-                        utx.send(Message::Text(request))?;
-                    }
+        if !api.render_url() {
+            for pair in names.iter() {
+                let requests = api.subscribe_messages(pair, level)?;
+                info!("{:?}", requests);
+                send_requests(name, limit, &utx, requests).await?;
+            }
+        }
+        for pair in names.iter().filter(|pair| !is_resumed(pair)) {
+            ratelimit::acquire(name, limit).await;
+            api.snapshot(pair, network).await?;
+        }
+        for setting in pairs.iter().filter(|s| s.hybrid && !is_resumed(&s.pair)) {
+            let rest = apitree::rest(name)?;
+            ratelimit::acquire(name, rest.rate_limit).await;
+            let mut ob = (rest.orderbook)(setting.pair.clone(), network.clone()).await?;
+            ob.pair = setting.pair.clone();
+            api.seed(&setting.pair, ob)?;
+        }
+        Ok(Shard {
+            pairs: names,
+            rx,
+            utx,
+            writer,
+            adapter: Some(api),
+        })
+    }
+
+    // rest only. poll the pair on its own pace, until the exchange is dropped
+    fn spawn_poll(&mut self, api: &'static restapi::Api, setting: &ExchangeSetting) {
+        let Some((tx, _)) = self.polled.as_ref() else {
+            return;
+        };
+        let (name, tx, network) = (self.name.clone(), tx.clone(), self.network.clone());
+        let (pair, level) = (setting.pair.clone(), self.level);
+        let mut pace = AdaptiveInterval::of(setting);
+        let task = async move {
+            logging::set_pair(&pair);
+            debug!("polling {} from {}", pair, api.endpoint);
+            // the first polls of the pairs spread over an interval
+            let mut wait = poll::jitter(pace.round(), 1.0);
+            loop {
+                sleep(wait).await;
+                ratelimit::acquire(&name, api.rate_limit).await;
+                let polled = (api.orderbook)(pair.clone(), network.clone())
+                    .await
+                    .map(|mut e| {
+                        e.pair = pair.clone();
+                        e.trim(level);
+                        pace.observe(&e);
+                        e
+                    });
+                if tx.send(polled).is_err() {
+                    return;
                 }
+                let interval = pace.round();
+                wait = interval + poll::jitter(interval, POLL_JITTER);
             }
+        };
+        let handle = tokio::spawn(logging::scope(&self.name, task));
+        if let Some(previous) = self.polls.insert(setting.pair.clone(), handle) {
+            previous.abort();
         }
+    }
+
+    // rest only. stop polling the pair
+    fn stop_poll(&mut self, pair: &str) {
+        if let Some(handle) = self.polls.remove(pair) {
+            handle.abort();
+        }
+    }
+
+    // send a close frame on every connection and wait for them to be flushed to the exchange.
+    pub async fn close(&mut self) {
+        for (_, handle) in self.polls.drain() {
+            handle.abort();
+        }
+        if !self.shards.is_empty() {
+            info!("closing {}", self.name);
+        }
+        let shards = std::mem::take(&mut self.shards);
+        for shard in shards.iter() {
+            if let Err(e) = shard.utx.send(Close(None)) {
+                error!("close {}: {}", self.name, e);
+            }
+        }
+        for shard in shards {
+            if time::timeout(Duration::from_secs(3), shard.writer)
+                .await
+                .is_err()
+            {
+                error!("timeout waiting {} to close", self.name);
+            }
+        }
+    }
+
+    // add a pair to the running connection with the fewest pairs.
+    pub async fn subscribe(&mut self, pair: &str) -> error::Result<()> {
+        if self.pairs.iter().any(|p| p == pair) {
+            return Err(anyhow!("{} is already subscribed on {}", pair, self.name).into());
+        }
+        if self.ws_api {
+            let shard = self
+                .shards
+                .iter_mut()
+                .min_by_key(|shard| shard.pairs.len())
+                .with_context(|| NOT_CONNECTED)?;
+            if self.pairs_per_connection > 0 && shard.pairs.len() >= self.pairs_per_connection {
+                return Err(anyhow!(
+                    "the connections of {} are full, {} pairs each",
+                    self.name,
+                    self.pairs_per_connection
+                )
+                .into());
+            }
+            let api = shard.adapter.as_ref().with_context(|| NOT_CONNECTED)?;
+            if api.render_url() {
+                return Err(Error::Unsupported(format!(
+                    "{} subscriptions are fixed by the url",
+                    self.name
+                )));
+            }
+            let (limit, requests) = (api.rate_limit(), api.subscribe_messages(pair, self.level)?);
+            send_requests(&self.name, limit, &shard.utx, requests).await?;
+            shard.pairs.push(pair.to_string());
+        } else {
+            let mut setting = self.poll_setting.clone().with_context(|| NOT_CONNECTED)?;
+            setting.pair = pair.to_string();
+            self.spawn_poll(apitree::rest(&self.name)?, &setting);
+        }
+        self.pairs.push(pair.to_string());
         Ok(())
     }
 
-    pub fn clear(&self) -> Result<()> {
-        let api = apitree::ws(&self.name)?;
-        (api.clear)();
+    // drop a pair from the running connection streaming it.
+    pub async fn unsubscribe(&mut self, pair: &str) -> error::Result<()> {
+        let index = self
+            .pairs
+            .iter()
+            .position(|p| p == pair)
+            .with_context(|| format!("{} is not subscribed on {}", pair, self.name))?;
+        if self.ws_api {
+            let shard = self
+                .shards
+                .iter_mut()
+                .find(|shard| shard.pairs.iter().any(|p| p == pair))
+                .with_context(|| NOT_CONNECTED)?;
+            let api = shard.adapter.as_ref().with_context(|| NOT_CONNECTED)?;
+            if api.render_url() {
+                return Err(Error::Unsupported(format!(
+                    "{} subscriptions are fixed by the url",
+                    self.name
+                )));
+            }
+            let (limit, requests) = (
+                api.rate_limit(),
+                api.unsubscribe_messages(pair, self.level)?,
+            );
+            send_requests(&self.name, limit, &shard.utx, requests).await?;
+            shard.pairs.retain(|p| p != pair);
+        }
+        self.pairs.remove(index);
+        self.stop_poll(pair);
+        Ok(())
+    }
+
+    // the exchange refused the subscription, nothing to unsubscribe
+    pub fn forget(&mut self, pair: &str) {
+        self.pairs.retain(|p| p != pair);
+        for shard in self.shards.iter_mut() {
+            shard.pairs.retain(|p| p != pair);
+        }
+        self.stop_poll(pair);
+    }
+
+    pub fn clear(&mut self) -> error::Result<()> {
+        for api in self.shards.iter_mut().filter_map(|s| s.adapter.as_mut()) {
+            api.reset();
+        }
         Ok(())
     }
 
-    pub async fn next(&mut self) -> Result<Option<Orderbook>> {
+    // the adapters continuing the books of some pairs on new connections
+    pub fn resumption(&mut self) -> Vec<(Box<dyn ExchangeAdapter>, Vec<String>)> {
+        self.shards
+            .iter_mut()
+            .filter_map(|shard| {
+                let mut api = shard.adapter.take()?;
+                let pairs = api.resume();
+                (!pairs.is_empty()).then_some((api, pairs))
+            })
+            .collect()
+    }
+
+    pub async fn next(&mut self) -> error::Result<Option<Orderbook>> {
         if !self.ws_api {
-            let level = self.level;
-            sleep(Duration::from_secs(self.wait_secs)).await;
-            // only able to handle one pair
-            if let Some(pair) = self.pairs.first() {
-                return (apitree::rest(&self.name)?.orderbook)(pair.clone())
-                    .await
-                    .map(move |mut e| {
-                        e.trim(level);
-                        Some(e)
-                    });
-            } else {
-                bail!("no pair assigned to the exchange");
+            if self.polls.is_empty() {
+                return Err(anyhow!("no pair assigned to the exchange").into());
             }
+            // the senders are kept, the channel stays open
+            let (_, polled) = self.polled.as_mut().with_context(|| NOT_CONNECTED)?;
+            return match polled.recv().await {
+                Some(polled) => polled.map(Some),
+                None => Ok(None),
+            };
+        }
+        if self.shards.is_empty() {
+            return Err(anyhow!(NOT_CONNECTED).into());
         }
-        let result = &mut self
-            .rx
-            .as_mut()
-            .with_context(|| "Not connect yet. Please run connect first")?;
         loop {
-            if let Some(result) = result.next().await {
+            // the first frame of the shards, from the one after the last read
+            let (start, shards) = (self.next_shard, &mut self.shards);
+            let (index, result) = future::poll_fn(|cx| {
+                for offset in 0..shards.len() {
+                    let index = (start + offset) % shards.len();
+                    if let Poll::Ready(result) = shards[index].rx.poll_next_unpin(cx) {
+                        return Poll::Ready((index, result));
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+            self.next_shard = index + 1;
+            let shard = &mut self.shards[index];
+            let api = shard.adapter.as_mut().with_context(|| NOT_CONNECTED)?;
+            if let Some(result) = result {
+                let received_ms = recorder::get_unixtime();
                 let raw = match result? {
                     Text(msg) => msg,
-                    Binary(msg) => std::str::from_utf8(&msg)?.to_string(),
+                    Binary(msg) => api.decode(&msg)?,
                     Ping(_) | Pong(_) => return Ok(None),
                     Close(_) => {
                         error!("stream gets closed: {}", self.name);
-                        return Err(anyhow!("close {}", self.name));
+                        return Err(Error::ConnectionClosed(self.name.clone()));
                     }
                     Frame(_) => {
                         unreachable!();
                     }
                 };
                 debug!("{}: {}", self.name, raw);
+                if let Some(sink) = self.capture.as_ref() {
+                    sink.capture(&self.name, received_ms, &raw);
+                }
+                if let Some(trace) = self.trace.as_ref() {
+                    trace.capture(&self.name, received_ms, &raw);
+                }
+                if let Some(recorder) = self.recorder.as_ref() {
+                    let _ = recorder.send(Record::raw(&self.name, &raw));
+                }
 
-                if let Some(mut e) = (apitree::ws(&self.name)?.parse)(raw)? {
-                    e.trim(self.level);
-                    return Ok(Some(e));
+                // the pair is unknown until parsed
+                logging::set_pair("");
+                let parsed = match self.offload_bytes > 0 && raw.len() >= self.offload_bytes {
+                    true => parse_blocking(&mut shard.adapter, raw).await?,
+                    false => api.parse(&raw)?,
+                };
+                match parsed {
+                    ParsedEvent::Book(mut e) => {
+                        logging::set_pair(&e.pair);
+                        e.received_ts = received_ms as u128;
+                        // a crossed book means the local book is out of sync
+                        e.check_crossed()?;
+                        e.trim(self.level);
+                        return Ok(Some(e));
+                    }
+                    ParsedEvent::Reply(text) => {
+                        shard.utx.send(Message::Text(text))?;
+                    }
+                    ParsedEvent::Ignore => {}
                 }
-                // skip none
             } else {
                 return Ok(None);
             }
@@ -178,127 +600,1516 @@ impl Exchange {
     }
 }
 
-fn setup_logger(
-    log_file: Option<String>,
-    log_level: config::LogLevel,
-) -> Result<(), fern::InitError> {
-    let tmp = fern::Dispatch::new()
-        .format(|out, message, _record| out.finish(format_args!("{}", message)))
-        .level(log_level.to_level_filter())
-        .chain(std::io::stdout());
-    if let Some(path) = log_file {
-        tmp.chain(fern::log_file(path)?).apply()?;
-    } else {
-        tmp.apply()?;
+// the polls of a replaced client stop with it
+impl Drop for Exchange {
+    fn drop(&mut self) {
+        for handle in self.polls.values() {
+            handle.abort();
+        }
+    }
+}
+
+// parse the frame on the blocking pool. The adapter moves there and back, the next frame
+// waits for it.
+async fn parse_blocking(
+    adapter: &mut Option<Box<dyn ExchangeAdapter>>,
+    raw: String,
+) -> error::Result<ParsedEvent> {
+    let mut api = adapter.take().with_context(|| NOT_CONNECTED)?;
+    let (api, parsed) = task::spawn_blocking(move || {
+        let parsed = api.parse(&raw);
+        (api, parsed)
+    })
+    .await
+    .map_err(|e| anyhow!("parse worker: {}", e))?;
+    *adapter = Some(api);
+    parsed
+}
+
+// shared handles every executor needs
+#[derive(Clone)]
+struct ExecutorContext {
+    network: NetworkSetting,
+    // the books and the state changes, to the aggregation task
+    tx: UnboundedSender<Event>,
+    health: HealthRegistry,
+    recorder: Option<UnboundedSender<Record>>,
+    capture: Option<Capture>,
+    trace: Option<Arc<FrameTrace>>,
+    // levels kept per side of the books of the pairs without internal_depth
+    depth: u32,
+    breaker: Option<CircuitBreakerSetting>,
+    // fetch a rest snapshot of the ws pairs before their first ws book
+    backfill: bool,
+    // the frames parsed on the blocking pool from this size on. 0 => never
+    parse_offload_bytes: usize,
+    shutdown: CancellationToken,
+}
+
+impl ExecutorContext {
+    // the health registry answers GetStatus, the aggregation acts on the event
+    fn status(&self, exchange: &str, state: ConnectionState, error: &str) {
+        let _ = self.tx.send(Event::Status {
+            exchange: exchange.to_string(),
+            state,
+            error: error.to_string(),
+        });
+    }
+    fn connecting(&self, exchange: &str, pairs: &[String]) {
+        self.health.connecting(exchange, pairs);
+        self.status(exchange, ConnectionState::Connecting, "");
+    }
+    fn connected(&self, exchange: &str) {
+        self.health.connected(exchange);
+        self.status(exchange, ConnectionState::Connected, "");
+    }
+    fn disconnected(&self, exchange: &str, error: &str) {
+        self.health.disconnected(exchange, error);
+        self.status(exchange, ConnectionState::Disconnected, error);
+    }
+    fn down(&self, exchange: &str, cooldown: Duration) {
+        let until = recorder::get_unixtime() + cooldown.as_millis() as u64;
+        self.health.down(exchange, until);
+        let cause = format!("cooldown {}s", cooldown.as_secs());
+        self.status(exchange, ConnectionState::Down, &cause);
+    }
+    fn disabled(&self, exchange: &str) {
+        self.health.disabled(exchange);
+        self.status(exchange, ConnectionState::Disabled, "disabled");
+    }
+    // dump the last frames of the exchange, on the error of one of them
+    fn traced(&self, exchange: &str, error: &Error) {
+        let Some(trace) = self.trace.as_ref() else {
+            return;
+        };
+        match trace.dump(exchange) {
+            Ok(Some(path)) => error!(target: "trace", "{} {}: frames in {}", exchange, error, path),
+            Ok(None) => {}
+            Err(e) => error!(target: "trace", "{}: {}", exchange, e),
+        }
+    }
+}
+
+// publish a rest snapshot of each ws pair, so the clients get a summary before the ws
+// books arrive. The rest polled pairs don't need it, nor the exchanges without a rest api.
+// The rest api is of production, the testnet pairs are left out.
+async fn backfill(exchange: &str, pairs: &[ExchangeSetting], ctx: &ExecutorContext) {
+    if !ctx.backfill {
+        return;
+    }
+    let Ok(api) = apitree::rest(exchange) else {
+        return;
+    };
+    let backfilled = |e: &&ExchangeSetting| e.ws_api && e.environment == Environment::Production;
+    for setting in pairs.iter().filter(backfilled) {
+        ratelimit::acquire(exchange, api.rate_limit).await;
+        let mut orderbook = select! {
+            result = (api.orderbook)(setting.pair.clone(), ctx.network.clone()) => match result {
+                Ok(orderbook) => orderbook,
+                Err(e) => {
+                    error!(target: "backfill", "{} {}: {}", exchange, setting.pair, e);
+                    continue;
+                }
+            },
+            _ = ctx.shutdown.cancelled() => return,
+        };
+        orderbook.pair = setting.pair.clone();
+        orderbook.trim(setting.internal_depth.unwrap_or(ctx.depth));
+        orderbook.received_ts = recorder::get_unixtime() as u128;
+        orderbook.fee_bps = setting.fee_bps;
+        orderbook.quote = setting.quote.clone();
+        if let Some(contract) = setting.contract.as_ref() {
+            orderbook.convert_contracts(contract);
+        }
+        debug!(target: "backfill", "{} {}", exchange, setting.pair);
+        let _ = ctx.tx.send(Event::BookUpdate {
+            exchange: exchange.to_string(),
+            symbol: setting.symbol().to_string(),
+            book: Arc::new(orderbook),
+        });
+    }
+}
+
+// apply a subscription change on the running connection, and keep the settings in sync
+// so that the change survives reconnection.
+async fn apply_control(
+    client: &mut Exchange,
+    pairs: &mut Vec<ExchangeSetting>,
+    control: Control,
+    ctx: &ExecutorContext,
+) -> Result<()> {
+    match control {
+        Control::Subscribe(request) => {
+            client.subscribe(&request.pair).await?;
+            let mut setting = pairs
+                .first()
+                .cloned()
+                .with_context(|| "should have at least one pair setting")?;
+            setting.pair = request.pair;
+            setting.symbol = None;
+            backfill(&client.name, std::slice::from_ref(&setting), ctx).await;
+            pairs.push(setting);
+        }
+        Control::Unsubscribe(request) => {
+            client.unsubscribe(&request.pair).await?;
+            pairs.retain(|e| e.pair != request.pair);
+        }
+        // handled by the executor
+        Control::Disable(_) | Control::Enable(_) => {}
     }
     Ok(())
 }
 
+// subscribe the pair written as the exchange expects, or give the pair up so that the
+// reconnections don't subscribe it again. Returns false once no pair is left.
+async fn rejected(
+    client: &mut Exchange,
+    exchange: &str,
+    pairs: &mut Vec<ExchangeSetting>,
+    rejection: Rejection,
+    ctx: &ExecutorContext,
+) -> bool {
+    error!(target: "subscription_failed", "{}: {}", exchange, rejection);
+    let Some(index) = pairs
+        .iter()
+        .position(|e| e.pair.eq_ignore_ascii_case(&rejection.pair))
+    else {
+        // unknown, or already given up. ex: kraken rejects the book and the ticker apart
+        return true;
+    };
+    let pair = pairs[index].pair.clone();
+    client.forget(&pair);
+    if let Some(retry) = rejection.retry {
+        // still published under the symbol of the pair
+        let setting = &mut pairs[index];
+        setting.symbol = Some(setting.symbol().to_string());
+        setting.pair = retry.clone();
+        match client.subscribe(&retry).await {
+            Ok(()) => {
+                info!(target: "subscription_failed", "{}: {} subscribed as {}", exchange, pair, retry);
+                ctx.connecting(exchange, &pair_names(pairs));
+                ctx.connected(exchange);
+                return true;
+            }
+            Err(e) => {
+                error!(target: "subscription_failed", "{}: {} {}", exchange, retry, e);
+                pairs[index].pair = pair;
+            }
+        }
+    }
+    let setting = pairs.remove(index);
+    let _ = ctx.tx.send(Event::SubscriptionFailed {
+        exchange: exchange.to_string(),
+        pair: setting.pair.clone(),
+        reason: rejection.reason,
+    });
+    let _ = ctx.tx.send(Event::Removed {
+        exchange: exchange.to_string(),
+        pair: Some(setting.pair),
+    });
+    if pairs.is_empty() {
+        return false;
+    }
+    ctx.connecting(exchange, &pair_names(pairs));
+    ctx.connected(exchange);
+    true
+}
+
+// the exchange asks us to slow down, wait a while before reconnecting.
+const RATE_LIMIT_BACKOFF_SECS: u64 = 30;
+
+// wait before reconnecting if the error asks for it. Returns false on shutdown.
+async fn backoff(e: &Error, shutdown: &CancellationToken) -> bool {
+    let wait_secs = match e {
+        Error::RateLimited(_) => RATE_LIMIT_BACKOFF_SECS,
+        _ => return true,
+    };
+    info!(target: "rate_limited", "wait {} secs before reconnecting", wait_secs);
+    select! {
+        _ = sleep(Duration::from_secs(wait_secs)) => true,
+        _ = shutdown.cancelled() => false,
+    }
+}
+
+// count the failure, and wait the cooldown if the circuit opens. Returns false on shutdown.
+async fn cooldown(
+    breaker: &mut Option<CircuitBreaker>,
+    exchange: &str,
+    ctx: &ExecutorContext,
+) -> bool {
+    let Some(cooldown) = breaker
+        .as_mut()
+        .and_then(|b| b.failure(time::Instant::now()))
+    else {
+        return true;
+    };
+    error!(target: "circuit_open", "{} keeps failing, down for {} secs", exchange, cooldown.as_secs());
+    ctx.down(exchange, cooldown);
+    select! {
+        _ = sleep(cooldown) => true,
+        _ = ctx.shutdown.cancelled() => false,
+    }
+}
+
+// the pairs offered by the exchange, for ListSupportedPairs
+async fn list_pairs(exchange: &str, network: &NetworkSetting) -> Result<Vec<String>, Status> {
+    apitree::pairs(exchange, network)
+        .await
+        .map_err(|e| match e {
+            Error::Unsupported(_) => Status::new(Code::Unimplemented, e.to_string()),
+            _ => Status::new(Code::Unavailable, e.to_string()),
+        })
+}
+
+// check the configured pairs against those their exchange offers. The exchanges not listing
+// their pairs, or failing to, are left unchecked.
+async fn check_pairs(inner: &InnerConfig) -> Result<()> {
+    let mut offered = HashMap::new();
+    for exchange in inner.exchange_pair_map.keys() {
+        match apitree::pairs(exchange, &inner.network).await {
+            Ok(pairs) => {
+                offered.insert(exchange.clone(), pairs);
+            }
+            Err(Error::Unsupported(_)) => {}
+            Err(e) => error!(target: "validate_pairs", "{}: {}, pairs not checked", exchange, e),
+        }
+    }
+    inner.check_pairs(&offered)
+}
+
+// exchange => pairs, for the headers of the recordings
+fn recorded_pairs(inner: &InnerConfig) -> BTreeMap<String, Vec<String>> {
+    inner
+        .exchange_pair_map
+        .iter()
+        .map(|(exchange, settings)| (exchange.clone(), pair_names(settings)))
+        .collect()
+}
+
+// listen on the unix domain socket, replacing the one a previous run left behind.
+fn bind_uds(path: &str) -> Result<UnixListenerStream> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(anyhow!("grpc_uds_path {} exists and isn't a socket", path));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path).with_context(|| format!("bind {}", path))?;
+    info!("grpc server listening on {}", path);
+    Ok(UnixListenerStream::new(listener))
+}
+
+fn pair_names(pairs: &[ExchangeSetting]) -> Vec<String> {
+    pairs.iter().map(|e| e.pair.clone()).collect()
+}
+
+// the client keeps the most levels of the pairs, the executor trims each to its own
+fn new_client(exchange: &str, pairs: &[ExchangeSetting], ctx: &ExecutorContext) -> Exchange {
+    let mut client = Exchange::new(exchange);
+    client.recorder = ctx.recorder.clone();
+    client.capture = ctx.capture.as_ref().and_then(|c| c.sink_of(exchange));
+    client.trace = ctx.trace.clone();
+    client.offload_bytes = ctx.parse_offload_bytes;
+    client.level = pairs
+        .iter()
+        .map(|s| s.internal_depth.unwrap_or(ctx.depth))
+        .max()
+        .unwrap_or(ctx.depth);
+    client
+}
+
+// replace the client with a new connection. Returns false on shutdown.
+// A failed connection is retried by the executor loop. With resume, the books the adapter
+// kept are continued, the others are fetched again.
+async fn reconnect(
+    client: &mut Exchange,
+    exchange: &str,
+    pairs: &[ExchangeSetting],
+    breaker: &mut Option<CircuitBreaker>,
+    ctx: &ExecutorContext,
+    resume: bool,
+) -> Result<bool> {
+    let resumed = match resume {
+        true => client.resumption(),
+        false => vec![],
+    };
+    if let Err(e) = client.clear() {
+        error!("{}, clear error", e);
+    }
+    *client = new_client(exchange, pairs, ctx);
+    client.resumed = resumed;
+    ctx.connecting(exchange, &pair_names(pairs));
+    backfill(exchange, pairs, ctx).await;
+    match client.connect(pairs.to_vec(), &ctx.network).await {
+        Err(e @ Error::Unsupported(_)) => {
+            ctx.disconnected(exchange, &e.to_string());
+            return Err(e.into());
+        }
+        Err(e) => {
+            error!(target: "connect_error", "{} {} connect error", e, exchange);
+            ctx.disconnected(exchange, &e.to_string());
+            if !backoff(&e, &ctx.shutdown).await || !cooldown(breaker, exchange, ctx).await {
+                return Ok(false);
+            }
+        }
+        Ok(()) => ctx.connected(exchange),
+    }
+    error!("connect {}", exchange);
+    Ok(true)
+}
+
+// wait for the exchange to be enabled, the subscriptions are refused meanwhile.
+// Returns false on shutdown, or once the executor is removed.
+async fn disabled(
+    exchange: &str,
+    control: &mut UnboundedReceiver<ControlRequest>,
+    ctx: &ExecutorContext,
+) -> bool {
+    ctx.disabled(exchange);
+    loop {
+        let (command, reply) = select! {
+            request = control.recv() => match request {
+                Some(request) => request,
+                None => return false,
+            },
+            _ = ctx.shutdown.cancelled() => return false,
+        };
+        info!(target: "control", "{}: {:?}", exchange, command);
+        match command {
+            Control::Enable(_) => {
+                let _ = reply.send(Ok(()));
+                return true;
+            }
+            Control::Disable(_) => {
+                let _ = reply.send(Ok(()));
+            }
+            Control::Subscribe(_) | Control::Unsubscribe(_) => {
+                let _ = reply.send(Err(format!("{} is disabled", exchange)));
+            }
+        }
+    }
+}
+
 async fn executor(
     exchange: String,
     pairs: Vec<ExchangeSetting>,
-    tx: UnboundedSender<(String, Orderbook)>,
+    ctx: ExecutorContext,
+    mut control: UnboundedReceiver<ControlRequest>,
 ) -> Result<()> {
-    let mut client = Exchange::new(&exchange);
+    let mut pairs = pairs;
+    let mut breaker = ctx.breaker.as_ref().map(CircuitBreaker::new);
+    let mut client = new_client(&exchange, &pairs, &ctx);
     info!("start executor {}", exchange);
-    client.connect(pairs.clone()).await?;
+    ctx.connecting(&exchange, &pair_names(&pairs));
+    backfill(&exchange, &pairs, &ctx).await;
+    if let Err(e) = client.connect(pairs.clone(), &ctx.network).await {
+        ctx.disconnected(&exchange, &e.to_string());
+        return Err(e.into());
+    }
+    ctx.connected(&exchange);
     info!("connect {}", exchange);
-    // currently we only allow single subscription
     loop {
-        match client.next().await {
-            Ok(Some(orderbook)) => {
-                tx.send((exchange.clone(), orderbook))?;
+        let next = select! {
+            next = client.next() => Some(next),
+            request = control.recv() => match request {
+                Some((Control::Disable(_), reply)) => {
+                    info!(target: "control", "{}: disable", exchange);
+                    client.close().await;
+                    let _ = reply.send(Ok(()));
+                    if !disabled(&exchange, &mut control, &ctx).await {
+                        info!("executor {} stopped", exchange);
+                        return Ok(());
+                    }
+                    info!(target: "control", "{}: enable", exchange);
+                    if !reconnect(&mut client, &exchange, &pairs, &mut breaker, &ctx, false).await? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Some((command, reply)) => {
+                    logging::set_pair(command.pair());
+                    info!(target: "control", "{}: {:?}", exchange, command);
+                    let result = apply_control(&mut client, &mut pairs, command, &ctx).await;
+                    let _ = reply.send(result.map_err(|e| e.to_string()));
+                    if pairs.is_empty() {
+                        info!("no pair left on {}", exchange);
+                        None
+                    } else {
+                        ctx.connecting(&exchange, &pair_names(&pairs));
+                        ctx.connected(&exchange);
+                        continue;
+                    }
+                }
+                // removed by setup_marketdata
+                None => None,
+            },
+            _ = ctx.shutdown.cancelled() => None,
+        };
+        let Some(next) = next else {
+            client.close().await;
+            info!("executor {} stopped", exchange);
+            return Ok(());
+        };
+        // a dropped connection resumes the books the adapter kept
+        let mut resume = true;
+        match next {
+            Ok(Some(mut orderbook)) => {
+                ctx.health.message(&exchange, recorder::get_unixtime());
+                if breaker.as_mut().is_some_and(|b| b.success()) {
+                    info!(target: "circuit_closed", "{} recovered", exchange);
+                }
+                let symbol = config::symbol_of(&pairs, &orderbook.pair);
+                orderbook.trim(config::internal_depth_of(
+                    &pairs,
+                    &orderbook.pair,
+                    ctx.depth,
+                ));
+                orderbook.fee_bps = config::fee_of(&pairs, &orderbook.pair);
+                orderbook.quote = config::quote_of(&pairs, &orderbook.pair);
+                if let Some(contract) = config::contract_of(&pairs, &orderbook.pair) {
+                    orderbook.convert_contracts(&contract);
+                }
+                ctx.tx.send(Event::BookUpdate {
+                    exchange: exchange.clone(),
+                    symbol,
+                    book: Arc::new(orderbook),
+                })?;
                 continue;
             }
             Ok(None) => {
                 error!("shutddown {}", exchange);
+                ctx.disconnected(&exchange, "stream ended");
+                if !cooldown(&mut breaker, &exchange, &ctx).await {
+                    client.close().await;
+                    return Ok(());
+                }
+            }
+            Err(Error::SubscriptionFailed(rejection)) => {
+                if !rejected(&mut client, &exchange, &mut pairs, rejection, &ctx).await {
+                    info!("no pair left on {}", exchange);
+                    client.close().await;
+                    return Ok(());
+                }
+                continue;
+            }
+            // a broken message is dropped, the book is still valid
+            Err(Error::ParseError(e)) => {
+                error!(target: "parse_error", "{}: {}, skip...", exchange, e);
+                ctx.traced(&exchange, &Error::ParseError(e));
+                continue;
+            }
+            Err(e @ Error::Unsupported(_)) => {
+                ctx.disconnected(&exchange, &e.to_string());
+                client.close().await;
+                return Err(e.into());
             }
             Err(e) => {
-                error!("{}, reconnect...", e);
+                // a book with a hole starts over from a snapshot
+                resume = !matches!(e, Error::Desync(_));
+                match &e {
+                    Error::Desync(reason) => {
+                        error!(target: "resync", "{}, resync...", e);
+                        ctx.traced(&exchange, &e);
+                        let _ = ctx.tx.send(Event::Desync {
+                            exchange: exchange.clone(),
+                            reason: reason.clone(),
+                        });
+                    }
+                    _ => error!(target: "reconnect", "{}, reconnect...", e),
+                }
+                ctx.disconnected(&exchange, &e.to_string());
+                if !backoff(&e, &ctx.shutdown).await
+                    || !cooldown(&mut breaker, &exchange, &ctx).await
+                {
+                    client.close().await;
+                    return Ok(());
+                }
             }
         }
-        if let Err(e) = client.clear() {
-            error!("{}, clear error", e);
+        if !reconnect(&mut client, &exchange, &pairs, &mut breaker, &ctx, resume).await? {
+            return Ok(());
         }
-        client = Exchange::new(&exchange);
-        if let Err(e) = client.connect(pairs.clone()).await {
-            error!("{} {} connect error", e, exchange);
+    }
+}
+
+fn spawn_executor(
+    exchange: String,
+    settings: Vec<ExchangeSetting>,
+    ctx: ExecutorContext,
+) -> (UnboundedSender<ControlRequest>, JoinHandle<()>) {
+    info!("loading {}: {:?}", exchange, settings);
+    let name = exchange.clone();
+    let (control_tx, control_rx) = unbounded_channel();
+    let task = async move {
+        let tx = ctx.tx.clone();
+        if let Err(e) = executor(exchange.clone(), settings, ctx, control_rx).await {
+            error!("exchange client spawn error: {}", e);
+        }
+        // the books of a stopped exchange aren't updated anymore
+        let _ = tx.send(Event::Removed {
+            exchange,
+            pair: None,
+        });
+    };
+    let handle = tokio::spawn(logging::scope(&name, task));
+    (control_tx, handle)
+}
+
+// the executors of the running exchanges, by exchange
+struct Executors {
+    ctx: ExecutorContext,
+    running: HashMap<String, (UnboundedSender<ControlRequest>, JoinHandle<()>)>,
+}
+
+impl Executors {
+    fn new(ctx: ExecutorContext) -> Executors {
+        Executors {
+            ctx,
+            running: HashMap::new(),
+        }
+    }
+
+    // the control channel of the exchange, None once its executor stopped
+    fn control(&self, exchange: &str) -> Option<&UnboundedSender<ControlRequest>> {
+        self.running
+            .get(exchange)
+            .map(|(control, _)| control)
+            .filter(|control| !control.is_closed())
+    }
+
+    // start the executor of the exchange, once the previous one is stopped
+    async fn start(&mut self, exchange: String, settings: Vec<ExchangeSetting>) {
+        self.stop(&exchange).await;
+        let (control_tx, handle) = spawn_executor(exchange.clone(), settings, self.ctx.clone());
+        self.running.insert(exchange, (control_tx, handle));
+    }
+
+    // stop the executor of the exchange right away, even in a backoff or cooldown, and
+    // remove its books before any book of the next executor.
+    async fn stop(&mut self, exchange: &str) {
+        let Some((_, handle)) = self.running.remove(exchange) else {
+            return;
+        };
+        handle.abort();
+        match handle.await {
+            Err(e) if !e.is_cancelled() => error!("{:?}", e),
+            _ => {}
+        }
+        let _ = self.ctx.tx.send(Event::Removed {
+            exchange: exchange.to_string(),
+            pair: None,
+        });
+    }
+
+    // wait for all the exchanges to close their connections
+    async fn join(self) {
+        for (_, (_, handle)) in self.running {
+            if let Err(e) = handle.await {
+                error!("{:?}", e);
+            }
         }
-        error!("connect {}", exchange);
     }
 }
 
-async fn setup_marketdata(
-    exchange_pairs: HashMap<String, Vec<ExchangeSetting>>,
+// the config of the file, to be loaded again
+fn file_config(config_path: &str, exchanges: Vec<String>) -> Config {
+    Config {
+        config_path: Some(config_path.to_string()),
+        exchanges,
+        port: None,
+        replay: None,
+        replay_speed: 1.0,
+        render: config::Render::Debug,
+        pairs: vec![],
+        depth: 0,
+        command: None,
+        inner: InnerConfig::default(),
+    }
+}
+
+// the wait for the events of a save to settle before the file is read, ex: an editor
+// writing a temporary file then renaming it
+const RELOAD_SETTLE_MS: u64 = 200;
+
+// reload the config file once it changes, and send the exchange changes to setup_marketdata.
+// The directory is watched rather than the file, the editors often replace the file. The
+// exchanges given on the command line are kept. The log level applies right away.
+async fn watch_config(
+    config_path: String,
+    exchanges: Vec<String>,
+    mut current: HashMap<String, Vec<ExchangeSetting>>,
+    changes: UnboundedSender<ExchangeChange>,
+    shutdown: CancellationToken,
+) {
+    let (path, dir) = match std::fs::canonicalize(&config_path) {
+        Ok(path) => match path.parent() {
+            Some(dir) => (path.clone(), dir.to_path_buf()),
+            None => return,
+        },
+        Err(e) => {
+            error!("config watch {}: {}", config_path, e);
+            return;
+        }
+    };
+    let (tx, mut rx) = unbounded_channel();
+    let watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if !event.kind.is_access() && event.paths.contains(&path) => {
+                let _ = tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => error!("config watch: {}", e),
+        });
+    // dropping the watcher stops it
+    let _watcher = match watcher.and_then(|mut watcher| {
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("config watch {}: {}", config_path, e);
+            return;
+        }
+    };
+    let mut config = file_config(&config_path, exchanges);
+    loop {
+        select! {
+            changed = rx.recv() => if changed.is_none() {
+                return;
+            },
+            _ = shutdown.cancelled() => return,
+        }
+        sleep(Duration::from_millis(RELOAD_SETTLE_MS)).await;
+        while rx.try_recv().is_ok() {}
+        // keep running with the old settings if the new file is broken
+        let (ws, rest) = apitree::supported();
+        if let Err(e) = config
+            .load()
+            .and_then(|_| config.inner.validate(&ws, &rest))
+        {
+            error!("config reload {}: {}", config_path, e);
+            continue;
+        }
+        let inner = std::mem::take(&mut config.inner);
+        info!("config reloaded from {}", config_path);
+        logging::set_level(inner.log_level);
+        for change in diff_exchanges(&current, &inner.exchange_pair_map) {
+            info!("{:?}", change);
+            if changes.send(change).is_err() {
+                return;
+            }
+        }
+        current = inner.exchange_pair_map;
+    }
+}
+
+// merges the cached books of one symbol and publishes the summary
+// (exchange, symbol) => the latest book
+type BookCache = HashMap<(String, String), Arc<Orderbook>>;
+
+struct Publisher {
+    // the strategy of the symbols missing from strategies
+    strategy: SharedStrategy,
+    // symbol => how its composite book is made
+    strategies: HashMap<String, SharedStrategy>,
+    // symbol => tick size of the price buckets
+    tick_sizes: HashMap<String, f64>,
+    // symbol => the minimum size of the levels
+    dust_filters: HashMap<String, DustSetting>,
+    // the offsets of the depth_at of the summaries
+    depth_offsets: Vec<f64>,
+    // the edges of the bands of the summaries
+    band_edges: Vec<f64>,
+    // publish the bands without the levels
+    bands_only: bool,
+    // symbol => the rounding of its prices and amounts
+    precisions: HashMap<String, PrecisionSetting>,
+    decimal_strings: bool,
+    // the levels per side of the summaries of the symbols missing from publish_depths
+    depth: u32,
+    // symbol => the levels per side of its summaries
+    publish_depths: HashMap<String, u32>,
+    analytics_levels: u32,
     tx: UnboundedSender<Result<Summary, Status>>,
+    sinks: Sinks,
+    arbitrage: Option<ArbitrageSetting>,
+    signals: broadcast::Sender<ArbitrageSignal>,
+    // the tickers and every event of the aggregation go there
+    bus: EventBus,
+    // the levels are aged against it. The replay moves it to the recorded times.
+    clock: clock::SharedClock,
+    latency: LatencyRegistry,
+    // fill the exchange, receive and publish times of the summaries
+    summary_timestamps: bool,
+    // the books merged are received within it of the latest one. 0 => every book
+    sync_window_ms: u64,
+    // quote currency => its rate into the quote of the symbols, see ExchangeSetting.quote
+    conversions: HashMap<String, ConversionSetting>,
+    // symbol => the last summary published. An unchanged book isn't published again.
+    last: HashMap<String, Summary>,
+}
+
+impl Publisher {
+    // the rate of quote: the mid of its book in the cache, or the fixed one until it comes
+    fn conversion(&self, quote: &str, exchange_cache: &BookCache) -> Option<Conversion> {
+        let setting = self.conversions.get(quote)?;
+        let mid = match (setting.exchange.as_ref(), setting.pair.as_ref()) {
+            (Some(exchange), Some(pair)) => exchange_cache
+                .values()
+                .find(|ob| ob.name == *exchange && ob.pair.eq_ignore_ascii_case(pair))
+                .and_then(|ob| {
+                    let bid = ob.bid.keys().next_back()?.to_f64()?;
+                    let ask = ob.ask.keys().next()?.to_f64()?;
+                    Some((bid + ask) / 2.0)
+                })
+                .filter(|mid| *mid > 0.0),
+            _ => None,
+        };
+        let rate = match mid {
+            Some(mid) if setting.inverse => 1.0 / mid,
+            Some(mid) => mid,
+            None if setting.rate > 0.0 => setting.rate,
+            None => return None,
+        };
+        Some(Conversion {
+            quote: quote.to_string(),
+            rate: Fixed::from_f64(rate)?,
+        })
+    }
+    // a scheduled summary is published even if unchanged, without the signals, the ticker and
+    // the latencies of an update
+    fn publish(&mut self, symbol: &str, exchange_cache: &mut BookCache, scheduled: bool) {
+        let mut agg = AggregatedOrderbook::new();
+        agg.strategy = self
+            .strategies
+            .get(symbol)
+            .unwrap_or(&self.strategy)
+            .clone();
+        agg.clock = self.clock.clone();
+        if let Some(tick_size) = self.tick_sizes.get(symbol) {
+            agg.set_tick_size(*tick_size);
+        }
+        agg.depth_offsets = self.depth_offsets.clone();
+        agg.band_edges = self.band_edges.clone();
+        agg.precision = self.precisions.get(symbol).copied().unwrap_or_default();
+        agg.decimal_strings = self.decimal_strings;
+        if let Some(dust) = self.dust_filters.get(symbol) {
+            agg.set_dust_filter(dust.min_quantity, dust.quote, dust.fold);
+        }
+        // only the books of the same symbol are merged, those the strategy selects
+        let books: Vec<&Orderbook> = exchange_cache
+            .iter()
+            .filter(|((_, s), _)| s == symbol)
+            .map(|(_, ob)| ob.as_ref())
+            .collect();
+        let (books, stale) = orderbook::synchronized(books, self.sync_window_ms);
+        // a book quoted in another currency waits for its rate
+        let books: Vec<&Orderbook> = books
+            .into_iter()
+            .filter(|ob| match ob.quote.as_ref() {
+                Some(quote) => match self.conversion(quote, exchange_cache) {
+                    Some(conversion) => {
+                        agg.conversions
+                            .insert(ob.name.clone(), Arc::new(conversion));
+                        true
+                    }
+                    None => false,
+                },
+                None => true,
+            })
+            .collect();
+        for ob in agg.strategy.select(books) {
+            agg.merge(ob);
+        }
+        // never publish a desynced venue. Its executor resyncs, the replay waits for the next book
+        if let Err(crossed) = agg.check_crossed() {
+            error!("{} {}, dropped from the aggregation", symbol, crossed);
+            exchange_cache.retain(|(_, s), ob| s != symbol || ob.name != crossed.exchange);
+            return;
+        }
+        let books: Vec<&Orderbook> = exchange_cache
+            .iter()
+            .filter(|((_, s), _)| s == symbol)
+            .map(|(_, ob)| ob.as_ref())
+            .collect();
+        let published_ms = self.clock.now_ms();
+        if !scheduled {
+            if let Some(setting) = self.arbitrage.as_ref() {
+                for signal in arbitrage::signals(symbol, &books, setting) {
+                    // no subscribers
+                    let _ = self.signals.send(signal);
+                }
+            }
+            if let Some(ticker) = ticker::summary(symbol, &books) {
+                self.bus.send(Event::Ticker(ticker));
+            }
+            for ob in books.iter() {
+                self.latency.published(ob, published_ms);
+            }
+        }
+        // the latest of the books
+        let exchange_ts = books.iter().filter_map(|ob| ob.exchange_ts).max();
+        let received_ts = books.iter().map(|ob| ob.received_ts).max();
+        let summary = agg
+            .finalize(
+                self.publish_depths
+                    .get(symbol)
+                    .copied()
+                    .unwrap_or(self.depth),
+            )
+            .map(|mut summary| {
+                summary.pair = symbol.to_string();
+                analytics::apply(&mut summary, self.analytics_levels);
+                if self.summary_timestamps {
+                    summary.exchange_ts_ms = exchange_ts.unwrap_or(0) as u64;
+                    summary.received_ts_ms = received_ts.unwrap_or(0) as u64;
+                    summary.published_ts_ms = published_ms as u64;
+                }
+                summary.scheduled = scheduled;
+                summary.partial = !stale.is_empty();
+                summary.stale_exchanges = stale;
+                if self.bands_only {
+                    summary.bids.clear();
+                    summary.asks.clear();
+                }
+                summary
+            })
+            .map_err(|e| Status::new(Code::InvalidArgument, format!("{:?}", e)));
+        if let Ok(summary) = summary.as_ref() {
+            if !scheduled {
+                // ex: an exchange resending the same snapshot
+                if self
+                    .last
+                    .get(symbol)
+                    .is_some_and(|last| proto::delta::unchanged(last, summary))
+                {
+                    return;
+                }
+                self.last.insert(symbol.to_string(), summary.clone());
+            }
+            self.sinks.summary(summary);
+        }
+        if let Err(e) = self.tx.send(summary) {
+            error!("{:?}", e);
+        }
+    }
+}
+
+// drop the books of the exchange, or of one of its pairs, and republish their symbols
+fn remove_books(
+    publisher: &mut Publisher,
+    exchange_cache: &mut BookCache,
+    exchange: &str,
+    pair: Option<&str>,
+) {
+    let mut symbols = BTreeSet::new();
+    exchange_cache.retain(|(name, symbol), ob| {
+        let removed = name == exchange && pair.is_none_or(|p| ob.pair.eq_ignore_ascii_case(p));
+        if removed {
+            symbols.insert(symbol.clone());
+        }
+        !removed
+    });
+    for symbol in symbols {
+        publisher.publish(&symbol, exchange_cache, false);
+    }
+}
+
+// keep the latest book of every exchange and symbol, and publish the summary of a symbol
+// when one of its books changes, or on the next tick in conflation mode. Every event is
+// passed on to the bus first. With a snapshot interval, every symbol is also published on
+// each wall-clock boundary of it. Runs until every sender of the events is dropped.
+async fn aggregate(
+    mut publisher: Publisher,
+    mut events: UnboundedReceiver<Event>,
+    publish_interval_ms: u64,
+    snapshot_interval_ms: u64,
+) {
+    let mut exchange_cache = BookCache::new();
+    // the symbols updated since the last publish, in conflation mode
+    let mut pending = BTreeSet::<String>::new();
+    // the exchanges disabled or in cooldown, until they connect again. Their books still
+    // in flight are dropped.
+    let mut inactive = BTreeSet::<String>::new();
+    let mut conflation = time::interval(Duration::from_millis(publish_interval_ms.max(1)));
+    conflation.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    // the last boundary published on
+    let mut boundary = 0;
+    loop {
+        // the wall clock, even in a replay: the schedule is of the consumers
+        let now = clock::system().now_ms();
+        let next = clock::next_boundary(now, boundary, snapshot_interval_ms);
+        let event = select! {
+            event = events.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            _ = conflation.tick(), if publish_interval_ms > 0 => {
+                for symbol in std::mem::take(&mut pending) {
+                    publisher.publish(&symbol, &mut exchange_cache, false);
+                }
+                continue;
+            }
+            _ = time::sleep(Duration::from_millis((next - now) as u64)), if snapshot_interval_ms > 0 => {
+                boundary = next;
+                let symbols: BTreeSet<String> =
+                    exchange_cache.keys().map(|(_, symbol)| symbol.clone()).collect();
+                for symbol in symbols {
+                    publisher.publish(&symbol, &mut exchange_cache, true);
+                }
+                continue;
+            }
+        };
+        if let Event::BookUpdate { exchange, .. } = &event {
+            if inactive.contains(exchange) {
+                continue;
+            }
+        }
+        // the consumers of the bus, ex: the top of book, don't wait for the summaries
+        publisher.bus.send(event.clone());
+        match &event {
+            Event::BookUpdate {
+                exchange,
+                symbol,
+                book,
+            } => {
+                publisher.sinks.book(book);
+                exchange_cache.insert((exchange.clone(), symbol.clone()), book.clone());
+                if publish_interval_ms > 0 {
+                    // published with the latest books on the next tick
+                    pending.insert(symbol.clone());
+                } else {
+                    publisher.publish(symbol, &mut exchange_cache, false);
+                }
+            }
+            Event::Status {
+                exchange,
+                state,
+                error,
+            } => match state {
+                ConnectionState::Disabled | ConnectionState::Down => {
+                    info!("{} out of the aggregation: {}", exchange, error);
+                    inactive.insert(exchange.clone());
+                    remove_books(&mut publisher, &mut exchange_cache, exchange, None);
+                }
+                _ => {
+                    inactive.remove(exchange);
+                }
+            },
+            Event::Removed { exchange, pair } => {
+                remove_books(
+                    &mut publisher,
+                    &mut exchange_cache,
+                    exchange,
+                    pair.as_deref(),
+                );
+            }
+            Event::Desync { exchange, reason } => debug!("{} desync: {}", exchange, reason),
+            Event::SubscriptionFailed {
+                exchange,
+                pair,
+                reason,
+            } => info!("{} {} unavailable: {}", exchange, pair, reason),
+            Event::Ticker(_) => {}
+        }
+    }
+    // don't lose the last updates
+    for symbol in pending {
+        publisher.publish(&symbol, &mut exchange_cache, false);
+    }
+}
+
+async fn setup_marketdata(
+    config: Config,
+    mut publisher: Publisher,
+    health: HealthRegistry,
+    mut control: UnboundedReceiver<ControlRequest>,
+    mut changes: UnboundedReceiver<ExchangeChange>,
+    sinks: Sinks,
+    shutdown: CancellationToken,
 ) -> Result<()> {
-    let (itx, mut irx) = unbounded_channel::<(String, Orderbook)>();
-    let mut exchange_cache = HashMap::<String, Orderbook>::new();
+    let inner = config.inner;
+    let (itx, irx) = unbounded_channel::<Event>();
+    let (capture, capture_handle) = match inner.capture.as_ref() {
+        Some(setting) => {
+            let (capture, handle) = capture::file(setting, recorded_pairs(&inner));
+            (Some(capture), Some(handle))
+        }
+        None => (None, None),
+    };
+    let ctx = ExecutorContext {
+        network: inner.network,
+        tx: itx,
+        health: health.clone(),
+        recorder: sinks.raw(),
+        capture,
+        trace: inner
+            .trace
+            .clone()
+            .map(|setting| Arc::new(FrameTrace::new(setting))),
+        depth: inner.depth,
+        breaker: inner.circuit_breaker,
+        backfill: inner.backfill,
+        parse_offload_bytes: inner.parse_offload_bytes,
+        shutdown: shutdown.clone(),
+    };
+    let latency = publisher.latency.clone();
+    let mut executors = Executors::new(ctx.clone());
     let mut threads = vec![];
-    for (exchange, settings) in exchange_pairs {
-        info!("loading {}: {:?}", exchange, settings);
-        let ltx = itx.clone();
+    if let Some(path) = config.replay {
+        // feed the recorded messages instead of connecting to the exchanges
+        let (tx, shutdown) = (ctx.tx.clone(), shutdown.clone());
+        let settings = inner.exchange_pair_map;
+        // the replayed books keep the recorded times, and so are aged against them
+        let clock = clock::SimulatedClock::default();
+        publisher.clock = Arc::new(clock.clone());
         threads.push(tokio::spawn(async move {
-            if let Err(e) = executor(exchange.clone(), settings.clone(), ltx).await {
-                error!("exchange client spawn error: {}", e);
+            let (speed, depth) = (config.replay_speed, inner.depth);
+            if let Err(e) = replay::run(path, speed, depth, settings, tx, clock, shutdown).await {
+                error!("replay: {}", e);
             }
         }));
+    } else {
+        for (exchange, settings) in inner.exchange_pair_map {
+            executors.start(exchange, settings).await;
+        }
     }
-    while let Some((exchange, orderbook)) = irx.recv().await {
-        let mut agg = AggregatedOrderbook::new();
-        exchange_cache.remove(&exchange);
-        exchange_cache.insert(exchange.clone(), orderbook);
-        for (_key, ob) in exchange_cache.iter() {
-            agg.merge(ob);
+    let aggregation = tokio::spawn(aggregate(
+        publisher,
+        irx,
+        inner.publish_interval_ms,
+        inner.snapshot_interval_ms,
+    ));
+    loop {
+        select! {
+            Some((command, reply)) = control.recv() => {
+                let exchange = command.exchange().to_string();
+                match executors.control(&exchange) {
+                    Some(executor) => {
+                        match &command {
+                            Control::Unsubscribe(request) => {
+                                // stop publishing the book of the pair
+                                let _ = ctx.tx.send(Event::Removed {
+                                    exchange: exchange.clone(),
+                                    pair: Some(request.pair.clone()),
+                                });
+                            }
+                            // the executor reports the new state to the aggregation
+                            Control::Disable(_) => info!(target: "control", "disable {}", exchange),
+                            Control::Enable(_) => info!(target: "control", "enable {}", exchange),
+                            Control::Subscribe(_) => {}
+                        }
+                        if let Err(e) = executor.send((command, reply)) {
+                            error!("{} control: {}", exchange, e);
+                        }
+                    }
+                    None => match command {
+                        Control::Subscribe(request) => {
+                            let ws_api = apitree::ws(&exchange).is_ok();
+                            if !ws_api && apitree::rest(&exchange).is_err() {
+                                let _ = reply.send(Err(format!("Exchange {} not supported", exchange)));
+                                continue;
+                            }
+                            let settings = vec![ExchangeSetting {
+                                pair: request.pair,
+                                ws_api,
+                                wait_secs: 3,
+                                symbol: None,
+                                fee_bps: 0.0,
+                                hybrid: false,
+                                incremental: false,
+                                min_wait_ms: 0,
+                                max_wait_ms: 0,
+                                contract: None,
+                                internal_depth: None,
+                                publish_depth: None,
+                                environment: Environment::Production,
+                                quote: None,
+                            }];
+                            executors.start(exchange, settings).await;
+                            let _ = reply.send(Ok(()));
+                        }
+                        Control::Unsubscribe(_) | Control::Disable(_) | Control::Enable(_) => {
+                            let _ = reply.send(Err(format!("{} is not running", exchange)));
+                        }
+                    },
+                }
+            }
+            Some(change) = changes.recv() => {
+                match change {
+                    ExchangeChange::Start(exchange, settings) => {
+                        executors.start(exchange, settings).await;
+                    }
+                    ExchangeChange::Stop(exchange) => {
+                        executors.stop(&exchange).await;
+                        health.remove(&exchange);
+                        latency.remove(&exchange);
+                    }
+                }
+            }
+            _ = shutdown.cancelled() => break,
         }
-        let summary = agg
-            .finalize(10)
-            .map_err(|e| Status::new(Code::InvalidArgument, format!("{:?}", e)));
-        if let Err(e) = tx.send(summary) {
+    }
+    // wait for all the exchanges to close their connections
+    executors.join().await;
+    for thread in threads {
+        if let Err(e) = thread.await {
+            error!("{:?}", e);
+        }
+    }
+    // the aggregation publishes the last updates once the last sender is dropped, and the
+    // capture file is flushed with the last sink
+    drop(ctx);
+    if let Err(e) = aggregation.await {
+        error!("{:?}", e);
+    }
+    if let Some(capture_handle) = capture_handle {
+        if let Err(e) = capture_handle.await {
             error!("{:?}", e);
         }
     }
-    threads.clear();
     Ok(())
 }
 
-#[actix::main]
+// re-read the log level of the config file on SIGHUP, ex: kill -HUP <pid>
+#[cfg(unix)]
+async fn watch_hangup(config_path: String, shutdown: CancellationToken) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("unable to listen to SIGHUP: {}", e);
+            return;
+        }
+    };
+    loop {
+        select! {
+            received = hangup.recv() => if received.is_none() {
+                return;
+            },
+            _ = shutdown.cancelled() => return,
+        }
+        let mut config = file_config(&config_path, vec![]);
+        match config.load() {
+            Ok(()) => logging::set_level(config.inner.log_level),
+            Err(e) => error!("log level reload {}: {}", config_path, e),
+        }
+    }
+}
+
+#[tokio::main]
 async fn main() -> Result<()> {
     let mut config = Config::parse();
-    println!("loading from {}", config.config_path);
+    println!(
+        "loading from {}",
+        config.path().unwrap_or("the command line")
+    );
     config.load()?;
-    setup_logger(config.inner.log_path, config.inner.log_level)?;
+    let (ws, rest) = apitree::supported();
+    config.inner.validate(&ws, &rest)?;
+    logging::setup(
+        config.inner.log_path.clone(),
+        config.inner.log_level,
+        config.inner.log_format,
+    )?;
+    if config.inner.validate_pairs {
+        check_pairs(&config.inner).await?;
+    }
 
     let bind_addr = config
         .inner
         .bind_addr
+        .clone()
         .unwrap_or_else(|| "0.0.0.0".to_string());
     let server_port = config.inner.server_port;
 
-    let aggserver = AggServer::new();
-    let tx = aggserver.tx.clone();
-    let handle = tokio::spawn(async move {
-        Server::builder()
-            .add_service(OrderbookAggregatorServer::new(aggserver))
-            .serve(format!("{}:{}", bind_addr, server_port).parse()?)
-            .await
-            .map_err(|e| anyhow!("{}", e))
-            .map(|_| ())
+    let shutdown = shutdown::listen();
+    let health = HealthRegistry::new();
+    let (control_tx, control_rx) = unbounded_channel();
+    let (changes_tx, changes_rx) = unbounded_channel();
+    let config_path = config.path().map(|p| p.to_string());
+    #[cfg(unix)]
+    if let Some(config_path) = config_path.clone() {
+        tokio::spawn(watch_hangup(config_path, shutdown.clone()));
+    }
+    if let Some(config_path) =
+        config_path.filter(|_| config.inner.reload && config.replay.is_none())
+    {
+        tokio::spawn(watch_config(
+            config_path,
+            config.exchanges.clone(),
+            config.inner.exchange_pair_map.clone(),
+            changes_tx,
+            shutdown.clone(),
+        ));
+    }
+    let mut sinks = Sinks::default();
+    let recorder_handle = match config.inner.recorder.clone() {
+        Some(setting) => {
+            let (recorder_tx, recorder_rx) = unbounded_channel();
+            if setting.raw {
+                sinks.set_raw(recorder_tx.clone());
+            }
+            sinks.add(recorder_tx, setting.books);
+            let exchanges = recorded_pairs(&config.inner);
+            Some(tokio::spawn(recorder::run(setting, exchanges, recorder_rx)))
+        }
+        None => None,
+    };
+    let kafka_handle = match config.inner.kafka.clone() {
+        Some(setting) => {
+            let books = setting.book_topic.is_some();
+            let (kafka_tx, handle) = kafka::start(setting)?;
+            sinks.add(kafka_tx, books);
+            Some(handle)
+        }
+        None => None,
+    };
+    let export_handle = match config.inner.export.clone() {
+        Some(setting) => {
+            let (export_tx, handle) = export::start(setting)?;
+            sinks.add(export_tx, false);
+            Some(handle)
+        }
+        None => None,
+    };
+    let redis_handle = config.inner.redis.clone().map(|setting| {
+        let (redis_tx, handle) = livecache::start(setting);
+        sinks.add(redis_tx, false);
+        handle
+    });
+    let mut aggserver = AggServer::new(
+        shutdown.clone(),
+        health.clone(),
+        config.inner.broadcast_capacity,
+        config.inner.lag_policy,
+        config.inner.delta_snapshot_every,
+    );
+    let network = config.inner.network.clone();
+    aggserver.set_pair_lister(PairLister(Arc::new(move |exchange: String| {
+        let network = network.clone();
+        Box::pin(async move { list_pairs(&exchange, &network).await })
+    })));
+    let latency = LatencyRegistry::new();
+    let bus = EventBus::new();
+    tokio::spawn(bus::forward_tickers(bus.subscribe(), aggserver.tickers()));
+    tokio::spawn(topofbook::run(bus.subscribe(), aggserver.book_tickers()));
+    tokio::spawn(bus::forward_books(
+        bus.subscribe(),
+        aggserver.venue_books(),
+        config.inner.depth,
+    ));
+    let publisher = Publisher {
+        strategy: match config.inner.consolidate {
+            true => Arc::new(strategy::Consolidate),
+            false => Arc::new(strategy::Merge),
+        },
+        strategies: config
+            .inner
+            .strategies
+            .iter()
+            .map(|(symbol, setting)| (symbol.clone(), strategy::of(setting)))
+            .collect(),
+        tick_sizes: config.inner.tick_sizes.clone(),
+        dust_filters: config.inner.dust_filters.clone(),
+        depth_offsets: config.inner.depth_offsets_bps.clone(),
+        band_edges: config.inner.band_edges_bps.clone(),
+        bands_only: config.inner.bands_only,
+        precisions: config.inner.precisions.clone(),
+        decimal_strings: config.inner.decimal_strings,
+        depth: config.inner.depth,
+        publish_depths: config.inner.publish_depths(),
+        analytics_levels: config.inner.analytics_levels,
+        tx: aggserver.tx.clone(),
+        sinks: sinks.clone(),
+        arbitrage: config.inner.arbitrage.clone(),
+        signals: aggserver.signals(),
+        bus: bus.clone(),
+        clock: clock::system(),
+        latency: latency.clone(),
+        summary_timestamps: config.inner.summary_timestamps,
+        sync_window_ms: config.inner.sync_window_ms,
+        conversions: config.inner.conversions.clone(),
+        last: HashMap::new(),
+    };
+    let closed = aggserver.closed.clone();
+    let ws_handle = match config.inner.ws_port {
+        Some(ws_port) => {
+            let listener = TcpListener::bind(format!("{}:{}", bind_addr, ws_port)).await?;
+            let (btx, closed) = (aggserver.broadcaster(), closed.clone());
+            Some(tokio::spawn(wsserver::run(listener, btx, closed)))
+        }
+        None => None,
+    };
+    let mut probe = Probe::new(
+        health.clone(),
+        latency,
+        aggserver.snapshots(),
+        config.inner.live_secs,
+    );
+    probe.ui = config.inner.ui;
+    let grpc_listening = probe.grpc_listening.clone();
+    let probe_handle = match config.inner.probe_port {
+        Some(probe_port) => {
+            let listener = std::net::TcpListener::bind(format!("{}:{}", bind_addr, probe_port))?;
+            let closed = closed.clone();
+            Some(tokio::spawn(probe::run(listener, probe.clone(), closed)))
+        }
+        None => None,
+    };
+    let service = OrderbookAggregatorServer::with_interceptor(
+        aggserver,
+        proto::authenticate(config.inner.auth_tokens.clone()),
+    );
+    if config.inner.admin_tokens().is_empty() {
+        warn!("no admin_tokens nor auth_tokens: anyone can call SetLogLevel or DisableExchange");
+    } else if config.inner.admin_tokens.is_empty() {
+        warn!("no admin_tokens: the subscribers' auth_tokens can call the Admin service");
+    }
+    // separate tokens for the operators, if any
+    let admin = AdminServer::with_interceptor(
+        AdminService::new(control_tx),
+        proto::authenticate(config.inner.admin_tokens().to_vec()),
+    );
+    // grpc.health.v1 is left open for the load balancers, the reflection needs the token
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+    let health_handle = tokio::spawn(probe::report_grpc(
+        probe,
+        reporter,
+        vec!["", OrderbookAggregatorServer::<AggServer>::NAME],
+        closed.clone(),
+    ));
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build()?;
+    let reflection = InterceptedService::new(
+        reflection,
+        proto::authenticate(config.inner.auth_tokens.clone()),
+    );
+    let acceptor = config.inner.tls.as_ref().map(tls::acceptor).transpose()?;
+    let keepalive = Some(config.inner.keepalive_secs)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    let mut builder = Server::builder()
+        .http2_keepalive_interval(keepalive)
+        .http2_keepalive_timeout(Some(Duration::from_secs(
+            config.inner.keepalive_timeout_secs,
+        )));
+    let uds_path = config.inner.grpc_uds_path.clone();
+    let mut handle = tokio::spawn(async move {
+        let addr: SocketAddr = format!("{}:{}", bind_addr, server_port).parse()?;
+        let mut routes = || {
+            builder
+                .add_service(service.clone())
+                .add_service(admin.clone())
+                .add_service(health_service.clone())
+                .add_service(reflection.clone())
+        };
+        // plaintext, the access is given by the permissions of the socket file
+        let uds = match uds_path.as_ref() {
+            Some(path) => {
+                let incoming = bind_uds(path)?;
+                let router = routes();
+                let closed = closed.clone();
+                Some(async move {
+                    router
+                        .serve_with_incoming_shutdown(incoming, closed.cancelled_owned())
+                        .await
+                })
+            }
+            None => None,
+        };
+        let router = routes();
+        let tcp = async {
+            match acceptor {
+                Some(acceptor) => {
+                    let listener = TcpListener::bind(addr).await?;
+                    grpc_listening.store(true, Ordering::Relaxed);
+                    let incoming = tls::incoming(listener, acceptor, closed.clone());
+                    router
+                        .serve_with_incoming_shutdown(incoming, closed.cancelled_owned())
+                        .await?
+                }
+                None => {
+                    let incoming =
+                        TcpIncoming::new(addr, true, None).map_err(|e| anyhow!("{}", e))?;
+                    grpc_listening.store(true, Ordering::Relaxed);
+                    router
+                        .serve_with_incoming_shutdown(incoming, closed.cancelled_owned())
+                        .await?
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        };
+        let uds = async {
+            if let Some(uds) = uds {
+                uds.await?;
+            }
+            Ok(())
+        };
+        let result = tokio::try_join!(tcp, uds).map(|_| ());
+        if let Some(path) = uds_path {
+            if let Err(e) = std::fs::remove_file(&path) {
+                error!("remove {}: {}", path, e);
+            }
+        }
+        result
     });
-    let market_fut = setup_marketdata(config.inner.exchange_pair_map, tx);
-    let fut_1 = handle.fuse();
-    let fut_2 = market_fut.fuse();
-    pin_mut!(fut_1, fut_2);
+    let market_fut = setup_marketdata(
+        config,
+        publisher,
+        health,
+        control_rx,
+        changes_rx,
+        sinks,
+        shutdown.clone(),
+    );
+    let mut market_fut = Box::pin(market_fut);
 
-    select! {
-        agg_killed = fut_1 => {
+    let result = select! {
+        agg_killed = &mut handle => {
             error!("{:?}", agg_killed);
-            agg_killed?
-
+            agg_killed.map_err(|e| anyhow!("{}", e)).and_then(|r| r)
         }
-        market_result = fut_2 => {
-            error!("{:?}", market_result);
+        market_result = &mut market_fut => {
+            if !shutdown.is_cancelled() {
+                error!("{:?}", market_result);
+            }
             market_result
         }
+    };
+    // bring the rest down as well, and let the grpc server drain its streams
+    shutdown.cancel();
+    if !handle.is_finished() {
+        if let Err(e) = handle.await {
+            error!("{:?}", e);
+        }
+    }
+    if let Some(ws_handle) = ws_handle {
+        if let Err(e) = ws_handle.await {
+            error!("{:?}", e);
+        }
+    }
+    if let Some(probe_handle) = probe_handle {
+        if let Err(e) = probe_handle.await {
+            error!("{:?}", e);
+        }
+    }
+    if let Err(e) = health_handle.await {
+        error!("{:?}", e);
+    }
+    // the sinks stop once setup_marketdata drops their senders
+    drop(market_fut);
+    for handle in [recorder_handle, kafka_handle, export_handle, redis_handle]
+        .into_iter()
+        .flatten()
+    {
+        if let Err(e) = handle.await {
+            error!("{:?}", e);
+        }
     }
+    info!("shutdown complete");
+    log::logger().flush();
+    result
 }
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use log::{error, info};
+use tokio_util::sync::CancellationToken;
+
+// wait until SIGINT or SIGTERM is received.
+#[cfg(unix)]
+async fn wait_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut term = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = term.recv() => {}
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_signal() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+// Shutdown controller. Every long running task holds a clone of the token,
+// and stops itself once the token is cancelled by a signal.
+pub fn listen() -> CancellationToken {
+    let token = CancellationToken::new();
+    let signal_token = token.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            result = wait_signal() => match result {
+                Ok(()) => {
+                    info!("signal received, shutting down");
+                    signal_token.cancel();
+                }
+                Err(e) => error!("unable to listen to signals: {}", e),
+            },
+            // shutdown requested from somewhere else
+            _ = signal_token.cancelled() => {}
+        }
+    });
+    token
+}
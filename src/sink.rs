@@ -0,0 +1,74 @@
+use crate::orderbook::Orderbook;
+use crate::proto::Summary;
+use crate::recorder::Record;
+use tokio::sync::mpsc::UnboundedSender;
+
+// the consumers of the published summaries and the per-exchange books, besides the grpc
+// streams. ex: the recorder, kafka. Each one runs in its own task, fed by a channel.
+#[derive(Clone, Default)]
+pub struct Sinks {
+    summaries: Vec<UnboundedSender<Record>>,
+    books: Vec<UnboundedSender<Record>>,
+    // the raw exchange messages, written by the exchange clients themselves
+    raw: Option<UnboundedSender<Record>>,
+}
+
+impl Sinks {
+    // books => also send the books of every exchange, before the aggregation
+    pub fn add(&mut self, tx: UnboundedSender<Record>, books: bool) {
+        if books {
+            self.books.push(tx.clone());
+        }
+        self.summaries.push(tx);
+    }
+
+    pub fn set_raw(&mut self, tx: UnboundedSender<Record>) {
+        self.raw = Some(tx);
+    }
+
+    pub fn raw(&self) -> Option<UnboundedSender<Record>> {
+        self.raw.clone()
+    }
+
+    pub fn summary(&self, summary: &Summary) {
+        send(&self.summaries, || Record::summary(summary));
+    }
+
+    pub fn book(&self, orderbook: &Orderbook) {
+        send(&self.books, || Record::book(orderbook));
+    }
+}
+
+// the record is only built if someone listens
+fn send<F: FnOnce() -> Record>(senders: &[UnboundedSender<Record>], record: F) {
+    let Some((last, others)) = senders.split_last() else {
+        return;
+    };
+    let record = record();
+    for tx in others {
+        // stopped sink
+        let _ = tx.send(record.clone());
+    }
+    let _ = last.send(record);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn test_sinks() {
+        let mut sinks = Sinks::default();
+        let (recorder, mut recorder_rx) = unbounded_channel();
+        let (kafka, mut kafka_rx) = unbounded_channel();
+        sinks.add(recorder, false);
+        sinks.add(kafka, true);
+        sinks.book(&Orderbook::with_pair("binance", "btcusdt"));
+        sinks.summary(&Summary::default());
+        assert!(matches!(recorder_rx.try_recv(), Ok(Record::Summary { .. })));
+        assert!(recorder_rx.try_recv().is_err());
+        assert!(matches!(kafka_rx.try_recv(), Ok(Record::Book { .. })));
+        assert!(matches!(kafka_rx.try_recv(), Ok(Record::Summary { .. })));
+    }
+}
@@ -0,0 +1,397 @@
+use crate::config::{PrecisionSetting, StrategyKind, StrategySetting};
+use crate::fixed::Fixed;
+use crate::orderbook::{round, to_f64, Conversion, Entry, Orderbook};
+use crate::proto::orderbook::Contribution;
+use crate::proto::Level;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+// the merged levels of one side, best price first: price => one entry per exchange
+pub type Entries<'a> = dyn Iterator<Item = (&'a Fixed, &'a Vec<Entry>)> + 'a;
+
+// the rounding, the decimal strings and the ages of the published levels
+pub struct Output<'a> {
+    pub precision: &'a PrecisionSetting,
+    pub decimal_strings: bool,
+    // the levels are aged against it
+    pub now: u128,
+}
+
+impl Output<'_> {
+    fn price(&self, price: &Fixed) -> Fixed {
+        round(
+            *price,
+            self.precision.price_decimals,
+            self.precision.rounding,
+        )
+    }
+    fn amount(&self, amount: &Fixed) -> Fixed {
+        round(
+            *amount,
+            self.precision.amount_decimals,
+            self.precision.rounding,
+        )
+    }
+    fn age(&self, time: u128) -> u64 {
+        self.now.saturating_sub(time) as u64
+    }
+    // a level of a price and an amount before rounding
+    fn level(
+        &self,
+        exchange: String,
+        price: &Fixed,
+        amount: &Fixed,
+        raw_price: &Fixed,
+    ) -> Result<Level> {
+        let (price, amount) = (self.price(price), self.amount(amount));
+        let (decimal_price, decimal_amount) = match self.decimal_strings {
+            true => (
+                decimal(price, self.precision.price_decimals),
+                decimal(amount, self.precision.amount_decimals),
+            ),
+            false => (String::new(), String::new()),
+        };
+        Ok(Level {
+            exchange,
+            price: to_f64(&price, "price")?,
+            amount: to_f64(&amount, "volume")?,
+            raw_price: to_f64(&self.price(raw_price), "price")?,
+            decimal_price,
+            decimal_amount,
+            ..Default::default()
+        })
+    }
+    fn contribution(
+        &self,
+        exchange: &str,
+        amount: &Fixed,
+        time: u128,
+        raw_price: &Fixed,
+        conversion: Option<&Conversion>,
+    ) -> Result<Contribution> {
+        let (quote, quote_rate) = tag(conversion)?;
+        Ok(Contribution {
+            exchange: exchange.to_string(),
+            amount: to_f64(&self.amount(amount), "volume")?,
+            age_ms: self.age(time),
+            raw_price: to_f64(&self.price(raw_price), "price")?,
+            quote,
+            quote_rate,
+        })
+    }
+}
+
+// the quote and the rate of a converted price, empty if not converted
+fn tag(conversion: Option<&Conversion>) -> Result<(String, f64)> {
+    match conversion {
+        Some(conversion) => Ok((conversion.quote.clone(), to_f64(&conversion.rate, "rate")?)),
+        None => Ok((String::new(), 0.0)),
+    }
+}
+
+// a rounded value, padded with zeros to the output precision
+fn decimal(value: Fixed, decimals: Option<u32>) -> String {
+    match decimals {
+        Some(decimals) => format!("{:.*}", decimals as usize, value),
+        None => value.to_string(),
+    }
+}
+
+// How the books of the exchanges make the composite book of a symbol: which of them are
+// merged, and how the merged entries become the published levels.
+pub trait Strategy: fmt::Debug + Send + Sync {
+    // the books merged into the composite, from the books of the symbol
+    fn select<'a>(&self, books: Vec<&'a Orderbook>) -> Vec<&'a Orderbook> {
+        books
+    }
+    // at most level levels of one side, best price first
+    fn levels(&self, entries: &mut Entries, level: u32, output: &Output) -> Result<Vec<Level>>;
+}
+
+pub type SharedStrategy = Arc<dyn Strategy>;
+
+pub fn of(setting: &StrategySetting) -> SharedStrategy {
+    match setting.kind {
+        StrategyKind::Merge => Arc::new(Merge),
+        StrategyKind::Consolidate => Arc::new(Consolidate),
+        StrategyKind::LiquidityWeighted => Arc::new(LiquidityWeighted {
+            step: Fixed::from_f64(setting.step).unwrap_or(Fixed::ZERO),
+        }),
+        StrategyKind::PrimaryVenue => Arc::new(PrimaryVenue {
+            venues: setting.venues.clone(),
+        }),
+    }
+}
+
+// one level per exchange and price
+#[derive(Debug)]
+pub struct Merge;
+
+impl Strategy for Merge {
+    fn levels(&self, entries: &mut Entries, level: u32, output: &Output) -> Result<Vec<Level>> {
+        let mut result = vec![];
+        for (price, v) in entries {
+            for entry in v.iter() {
+                let mut l = output.level(
+                    entry.exchange.clone(),
+                    price,
+                    &entry.volume,
+                    &entry.raw_price,
+                )?;
+                l.age_ms = output.age(entry.time);
+                (l.quote, l.quote_rate) = tag(entry.conversion.as_deref())?;
+                result.push(l);
+                if result.len() == level as usize {
+                    return Ok(result);
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+// one level per price, the amounts of the exchanges summed. The per-exchange breakdown is
+// kept in contributions.
+#[derive(Debug)]
+pub struct Consolidate;
+
+impl Strategy for Consolidate {
+    fn levels(&self, entries: &mut Entries, level: u32, output: &Output) -> Result<Vec<Level>> {
+        let mut result = vec![];
+        for (price, v) in entries.take(level as usize) {
+            let mut amount = Fixed::ZERO;
+            let mut contributions = vec![];
+            for entry in v.iter() {
+                amount += &entry.volume;
+                contributions.push(output.contribution(
+                    &entry.exchange,
+                    &entry.volume,
+                    entry.time,
+                    &entry.raw_price,
+                    entry.conversion.as_deref(),
+                )?);
+            }
+            let exchanges: Vec<&str> = v.iter().map(|e| e.exchange.as_str()).collect();
+            let oldest = v.iter().map(|e| e.time).min().unwrap_or(output.now);
+            let mut l = output.level(exchanges.join(","), price, &amount, price)?;
+            l.raw_price = contributions.first().map_or(l.price, |c| c.raw_price);
+            if let Some(first) = contributions.first() {
+                (l.quote, l.quote_rate) = (first.quote.clone(), first.quote_rate);
+            }
+            l.contributions = contributions;
+            l.age_ms = output.age(oldest);
+            result.push(l);
+        }
+        Ok(result)
+    }
+}
+
+// Synthetic levels of step each, from the best prices: each is priced at the average of
+// the liquidity it takes, what a taker of that much more would pay. The last one may be
+// smaller, the books have no more.
+#[derive(Debug)]
+pub struct LiquidityWeighted {
+    pub step: Fixed,
+}
+
+// the amount, oldest time, best raw price and conversion of an exchange in a slice
+type Taken = (Fixed, u128, Fixed, Option<Arc<Conversion>>);
+
+// the liquidity taken by one synthetic level
+#[derive(Default)]
+struct Slice {
+    amount: Fixed,
+    notional: f64,
+    // the best price taken
+    first: Option<Fixed>,
+    exchanges: BTreeMap<String, Taken>,
+}
+
+impl Slice {
+    fn take(&mut self, price: &Fixed, amount: Fixed, entry: &Entry) {
+        self.amount += &amount;
+        self.notional += price.to_f64().unwrap_or_default() * amount.to_f64().unwrap_or_default();
+        self.first.get_or_insert(*price);
+        let taken = self.exchanges.entry(entry.exchange.clone()).or_insert((
+            Fixed::ZERO,
+            entry.time,
+            entry.raw_price,
+            entry.conversion.clone(),
+        ));
+        taken.0 += &amount;
+        taken.1 = taken.1.min(entry.time);
+    }
+
+    fn level(self, output: &Output) -> Result<Level> {
+        let average = self.notional / self.amount.to_f64().unwrap_or(1.0);
+        let price = Fixed::from_f64(average)
+            .ok_or_else(|| anyhow!("price conversion error: {}", average))?;
+        let first = self.first.unwrap_or(price);
+        let mut contributions = vec![];
+        for (exchange, (amount, time, raw_price, conversion)) in self.exchanges.iter() {
+            contributions.push(output.contribution(
+                exchange,
+                amount,
+                *time,
+                raw_price,
+                conversion.as_deref(),
+            )?);
+        }
+        let exchanges: Vec<&str> = self.exchanges.keys().map(|e| e.as_str()).collect();
+        let oldest = self
+            .exchanges
+            .values()
+            .map(|e| e.1)
+            .min()
+            .unwrap_or(output.now);
+        let mut l = output.level(exchanges.join(","), &price, &self.amount, &first)?;
+        l.contributions = contributions;
+        l.age_ms = output.age(oldest);
+        Ok(l)
+    }
+}
+
+impl Strategy for LiquidityWeighted {
+    fn levels(&self, entries: &mut Entries, level: u32, output: &Output) -> Result<Vec<Level>> {
+        let mut result = vec![];
+        if self.step <= Fixed::ZERO {
+            return Ok(result);
+        }
+        let mut slice = Slice::default();
+        for (price, v) in entries {
+            for entry in v.iter() {
+                let mut left = entry.volume;
+                while left > Fixed::ZERO {
+                    let taken = left.min(self.step - slice.amount);
+                    slice.take(price, taken, entry);
+                    left = left - taken;
+                    if slice.amount < self.step {
+                        continue;
+                    }
+                    result.push(std::mem::take(&mut slice).level(output)?);
+                    if result.len() == level as usize {
+                        return Ok(result);
+                    }
+                }
+            }
+        }
+        if slice.amount > Fixed::ZERO {
+            result.push(slice.level(output)?);
+        }
+        Ok(result)
+    }
+}
+
+// The book of the first of the venues still quoting both sides, ex: the reference
+// exchange of a pair with the others as fallbacks. The exchanges out of the list are left
+// out.
+#[derive(Debug)]
+pub struct PrimaryVenue {
+    pub venues: Vec<String>,
+}
+
+impl Strategy for PrimaryVenue {
+    fn select<'a>(&self, books: Vec<&'a Orderbook>) -> Vec<&'a Orderbook> {
+        let quoting = |venue: &String| {
+            books
+                .iter()
+                .find(|ob| ob.name == *venue && !ob.bid.is_empty() && !ob.ask.is_empty())
+        };
+        self.venues
+            .iter()
+            .find_map(quoting)
+            .into_iter()
+            .copied()
+            .collect()
+    }
+    fn levels(&self, entries: &mut Entries, level: u32, output: &Output) -> Result<Vec<Level>> {
+        Merge.levels(entries, level, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::{AggregatedOrderbook, Side};
+    use std::str::FromStr;
+
+    fn fixed(s: &str) -> Fixed {
+        Fixed::from_str(s).unwrap()
+    }
+
+    fn book(name: &str, levels: &[(&str, &str)]) -> Orderbook {
+        let mut ob = Orderbook::with_pair(name, "btcusd");
+        for (price, amount) in levels {
+            ob.insert(Side::Bid, fixed(price), fixed(amount));
+            // a far ask, so that both sides quote
+            ob.insert(Side::Ask, fixed(price) + fixed("100"), fixed(amount));
+        }
+        ob
+    }
+
+    #[test]
+    fn test_liquidity_weighted() {
+        let mut agg = AggregatedOrderbook::new();
+        agg.strategy = of(&StrategySetting {
+            kind: StrategyKind::LiquidityWeighted,
+            step: 1.0,
+            ..Default::default()
+        });
+        agg.merge(&book("kraken", &[("100", "0.5"), ("99", "2")]));
+        agg.merge(&book("bitstamp", &[("100", "0.25")]));
+        let summary = agg.finalize(10).unwrap();
+        let bids: Vec<_> = summary
+            .bids
+            .iter()
+            .map(|l| (l.exchange.as_str(), l.price, l.amount))
+            .collect();
+        // 0.75 at 100 and 0.25 at 99, 1 at 99, the 0.75 left at 99
+        assert_eq!(
+            bids,
+            vec![
+                ("bitstamp,kraken", 99.75, 1.0),
+                ("kraken", 99.0, 1.0),
+                ("kraken", 99.0, 0.75),
+            ]
+        );
+        let first = &summary.bids[0];
+        assert_eq!(first.raw_price, 100.0);
+        let amounts: Vec<_> = first
+            .contributions
+            .iter()
+            .map(|c| (c.exchange.as_str(), c.amount))
+            .collect();
+        assert_eq!(amounts, vec![("bitstamp", 0.25), ("kraken", 0.75)]);
+
+        assert_eq!(agg.finalize(2).unwrap().bids.len(), 2);
+    }
+
+    #[test]
+    fn test_primary_venue() {
+        let strategy = of(&StrategySetting {
+            kind: StrategyKind::PrimaryVenue,
+            venues: vec!["coinbase".to_string(), "kraken".to_string()],
+            ..Default::default()
+        });
+        let (coinbase, kraken) = (
+            book("coinbase", &[("100", "1")]),
+            book("kraken", &[("101", "1")]),
+        );
+        let binance = book("binance", &[("102", "1")]);
+        let names = |books: Vec<&Orderbook>| -> Vec<String> {
+            strategy
+                .select(books)
+                .iter()
+                .map(|ob| ob.name.clone())
+                .collect()
+        };
+        assert_eq!(names(vec![&binance, &kraken, &coinbase]), vec!["coinbase"]);
+        // the primary lost a side, the fallback takes over
+        let mut one_sided = coinbase.clone();
+        one_sided.ask.clear();
+        assert_eq!(names(vec![&binance, &kraken, &one_sided]), vec!["kraken"]);
+        assert!(names(vec![&binance]).is_empty());
+    }
+}
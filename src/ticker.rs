@@ -0,0 +1,66 @@
+use crate::orderbook::Orderbook;
+use crate::proto::orderbook::ExchangeTicker;
+use crate::proto::TickerSummary;
+
+// the tickers of the books of the same pair. None if no exchange sent a ticker.
+pub fn summary(pair: &str, books: &[&Orderbook]) -> Option<TickerSummary> {
+    let mut tickers: Vec<ExchangeTicker> = books
+        .iter()
+        .filter(|ob| !ob.last_price.is_zero())
+        .map(|ob| ExchangeTicker {
+            exchange: ob.name.clone(),
+            last_price: ob.last_price.to_f64().unwrap_or_default(),
+            volume: ob.volume.to_f64().unwrap_or_default(),
+        })
+        .collect();
+    if tickers.is_empty() {
+        return None;
+    }
+    // stable across the hashmap iteration order
+    tickers.sort_by(|a, b| a.exchange.cmp(&b.exchange));
+    let total_volume: f64 = tickers.iter().map(|t| t.volume).sum();
+    let composite_price = if total_volume > 0.0 {
+        tickers.iter().map(|t| t.last_price * t.volume).sum::<f64>() / total_volume
+    } else {
+        tickers.iter().map(|t| t.last_price).sum::<f64>() / tickers.len() as f64
+    };
+    Some(TickerSummary {
+        pair: pair.to_string(),
+        tickers,
+        composite_price,
+        total_volume,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Fixed;
+    use std::str::FromStr;
+
+    fn book(name: &str, last_price: &str, volume: &str) -> Orderbook {
+        let mut ob = Orderbook::new(name);
+        ob.last_price = Fixed::from_str(last_price).unwrap();
+        ob.volume = Fixed::from_str(volume).unwrap();
+        ob
+    }
+
+    #[test]
+    fn test_summary() {
+        let a = book("kraken", "100", "1");
+        let b = book("binance", "110", "3");
+        let c = Orderbook::new("bitstamp");
+        let result = summary("btcusd", &[&a, &b, &c]).unwrap();
+        assert_eq!(result.pair, "btcusd");
+        let names: Vec<&str> = result.tickers.iter().map(|t| t.exchange.as_str()).collect();
+        assert_eq!(names, vec!["binance", "kraken"]);
+        assert_eq!(result.total_volume, 4.0);
+        assert!((result.composite_price - 107.5).abs() < 1e-9);
+
+        // no volume yet => plain average
+        let a = book("kraken", "100", "0");
+        let b = book("binance", "110", "0");
+        assert_eq!(summary("btcusd", &[&a, &b]).unwrap().composite_price, 105.0);
+        assert!(summary("btcusd", &[&c]).is_none());
+    }
+}
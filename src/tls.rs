@@ -0,0 +1,237 @@
+use crate::config::TlsSetting;
+use anyhow::{anyhow, Context as _, Result};
+use base64::Engine;
+use futures_util::Stream;
+use log::error;
+use std::fs;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::unbounded_channel;
+use tokio_rustls::rustls::{self, Certificate, PrivateKey, ServerName};
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tonic::transport::server::{Connected, TcpConnectInfo};
+
+// the der blocks of a pem file, in order, with their labels
+fn read_pem(path: &str) -> Result<Vec<(String, Vec<u8>)>> {
+    let content = fs::read_to_string(path).with_context(|| format!("read {}", path))?;
+    let mut blocks = vec![];
+    let mut current: Option<(String, String)> = None;
+    for line in content.lines().map(str::trim) {
+        if let Some(label) = line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|l| l.strip_suffix("-----"))
+        {
+            current = Some((label.to_string(), String::new()));
+        } else if line.starts_with("-----END ") {
+            let (label, body) = current
+                .take()
+                .ok_or_else(|| anyhow!("{}: unmatched END", path))?;
+            let der = base64::engine::general_purpose::STANDARD
+                .decode(body)
+                .with_context(|| format!("{}: broken {}", path, label))?;
+            blocks.push((label, der));
+        } else if let Some((_, body)) = current.as_mut() {
+            body.push_str(line);
+        }
+    }
+    Ok(blocks)
+}
+
+fn certificates(path: &str) -> Result<Vec<Certificate>> {
+    let certs: Vec<Certificate> = read_pem(path)?
+        .into_iter()
+        .filter(|(label, _)| label == "CERTIFICATE")
+        .map(|(_, der)| Certificate(der))
+        .collect();
+    if certs.is_empty() {
+        return Err(anyhow!("no certificate in {}", path));
+    }
+    Ok(certs)
+}
+
+// PKCS#8, PKCS#1 (RSA) and SEC1 (EC) keys are taken
+fn private_key(path: &str) -> Result<PrivateKey> {
+    read_pem(path)?
+        .into_iter()
+        .find(|(label, _)| label.ends_with("PRIVATE KEY"))
+        .map(|(_, der)| PrivateKey(der))
+        .ok_or_else(|| anyhow!("no private key in {}", path))
+}
+
+pub fn acceptor(setting: &TlsSetting) -> Result<TlsAcceptor> {
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            certificates(&setting.cert_path)?,
+            private_key(&setting.key_path)?,
+        )?;
+    // grpc clients expect http2 to be negotiated
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+// client only. trusts the certificates of ca_path only
+#[allow(dead_code)]
+pub fn connector(setting: &TlsSetting) -> Result<TlsConnector> {
+    let ca_path = setting
+        .ca_path
+        .as_deref()
+        .ok_or_else(|| anyhow!("tls.ca_path is required to connect"))?;
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in certificates(ca_path)? {
+        roots.add(&cert)?;
+    }
+    let mut config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+// client only
+#[allow(dead_code)]
+pub async fn connect(
+    connector: TlsConnector,
+    host: String,
+    port: u16,
+) -> io::Result<client::TlsStream<TcpStream>> {
+    let domain = ServerName::try_from(host.as_str())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let stream = TcpStream::connect((host.as_str(), port)).await?;
+    connector.connect(domain, stream).await
+}
+
+// a server side tls connection tonic can serve
+pub struct TlsConn(server::TlsStream<TcpStream>);
+
+impl Connected for TlsConn {
+    type ConnectInfo = TcpConnectInfo;
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.0.get_ref().0.connect_info()
+    }
+}
+
+impl AsyncRead for TlsConn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConn {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+// accept the connections and run the handshakes concurrently.
+// A failed handshake only drops that connection, the stream ends on shutdown.
+pub fn incoming(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    closed: CancellationToken,
+) -> impl Stream<Item = io::Result<TlsConn>> {
+    let (tx, rx) = unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let (stream, addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        error!("tls accept: {}", e);
+                        continue;
+                    }
+                },
+                _ = closed.cancelled() => return,
+            };
+            let (acceptor, tx) = (acceptor.clone(), tx.clone());
+            tokio::spawn(async move {
+                match acceptor.accept(stream).await {
+                    Ok(stream) => {
+                        let _ = tx.send(Ok(TlsConn(stream)));
+                    }
+                    Err(e) => error!("tls handshake {}: {}", addr, e),
+                }
+            });
+        }
+    });
+    UnboundedReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn setting() -> TlsSetting {
+        TlsSetting {
+            cert_path: "src/test_resource/tls.crt".to_string(),
+            key_path: "src/test_resource/tls.key".to_string(),
+            ca_path: Some("src/test_resource/ca.crt".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let closed = CancellationToken::new();
+        let mut incoming = Box::pin(incoming(
+            listener,
+            acceptor(&setting()).unwrap(),
+            closed.clone(),
+        ));
+
+        let connector = connector(&setting()).unwrap();
+        let client = tokio::spawn(connect(connector, "localhost".to_string(), port));
+        let mut server = incoming.next().await.unwrap().unwrap();
+        let mut client = client.await.unwrap().unwrap();
+        assert_eq!(client.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        // the server certificate is not a ca
+        let untrusted = TlsSetting {
+            ca_path: Some("src/test_resource/tls.crt".to_string()),
+            ..setting()
+        };
+        let connector = super::connector(&untrusted).unwrap();
+        assert!(connect(connector, "localhost".to_string(), port)
+            .await
+            .is_err());
+
+        closed.cancel();
+        assert!(incoming.next().await.is_none());
+    }
+
+    #[test]
+    fn test_read_pem() {
+        assert_eq!(certificates("src/test_resource/tls.crt").unwrap().len(), 1);
+        assert!(private_key("src/test_resource/tls.key").is_ok());
+        assert!(private_key("src/test_resource/tls.crt").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::bus::Event;
+use crate::fixed::Fixed;
+use crate::orderbook::{adjust, Orderbook, Side};
+use crate::proto::{BookTicker, ConnectionState};
+use crate::recorder::get_unixtime;
+use log::debug;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// the best (price net of the fee, amount) of each side of a book
+#[derive(Debug, Clone, PartialEq)]
+struct Top {
+    pair: String,
+    bid: Option<(Fixed, Fixed)>,
+    ask: Option<(Fixed, Fixed)>,
+}
+
+impl Top {
+    fn of(book: &Orderbook) -> Top {
+        let net =
+            |side, (price, amount): (&Fixed, &Fixed)| (adjust(*price, book.fee_bps, side), *amount);
+        Top {
+            pair: book.pair.clone(),
+            bid: book.bid.iter().next_back().map(|l| net(Side::Bid, l)),
+            ask: book.ask.iter().next().map(|l| net(Side::Ask, l)),
+        }
+    }
+}
+
+// Top of book of every symbol, from the best levels of each exchange only. Much cheaper than
+// the summaries, nothing is merged.
+#[derive(Debug, Default)]
+pub struct TopOfBook {
+    // symbol => exchange => its best levels
+    tops: HashMap<String, BTreeMap<String, Top>>,
+    // symbol => the last ticker sent
+    sent: HashMap<String, BookTicker>,
+}
+
+impl TopOfBook {
+    // the ticker of the symbol if the update moved its best bid or ask
+    pub fn update(&mut self, exchange: &str, symbol: &str, book: &Orderbook) -> Option<BookTicker> {
+        let top = Top::of(book);
+        let tops = self.tops.entry(symbol.to_string()).or_default();
+        if tops.get(exchange) == Some(&top) {
+            return None;
+        }
+        tops.insert(exchange.to_string(), top);
+        self.moved(symbol, book.received_ts as u64)
+    }
+
+    // drop the books of the exchange, or of one of its pairs. The tickers of the symbols moved.
+    pub fn remove(&mut self, exchange: &str, pair: Option<&str>) -> Vec<BookTicker> {
+        let mut symbols = vec![];
+        for (symbol, tops) in self.tops.iter_mut() {
+            let removed = tops
+                .get(exchange)
+                .filter(|top| pair.is_none_or(|p| top.pair.eq_ignore_ascii_case(p)));
+            if removed.is_some() {
+                tops.remove(exchange);
+                symbols.push(symbol.clone());
+            }
+        }
+        symbols
+            .iter()
+            .filter_map(|symbol| self.moved(symbol, get_unixtime()))
+            .collect()
+    }
+
+    // the composite top of the symbol, if it differs from the last one sent
+    fn moved(&mut self, symbol: &str, received_ts_ms: u64) -> Option<BookTicker> {
+        let tops = self.tops.get(symbol)?;
+        // the best price, then the larger amount
+        let best = |side: fn(&Top) -> &Option<(Fixed, Fixed)>, better: Ordering| {
+            tops.iter()
+                .filter_map(|(exchange, top)| side(top).as_ref().map(|l| (exchange, l)))
+                .reduce(
+                    |a, b| match b.1 .0.cmp(&a.1 .0).then_with(|| b.1 .1.cmp(&a.1 .1)) {
+                        ordering if ordering == better => b,
+                        _ => a,
+                    },
+                )
+        };
+        let mut ticker = BookTicker {
+            pair: symbol.to_string(),
+            received_ts_ms,
+            ..Default::default()
+        };
+        if let Some((exchange, (price, amount))) = best(|t| &t.bid, Ordering::Greater) {
+            ticker.bid_price = price.to_f64().unwrap_or_default();
+            ticker.bid_amount = amount.to_f64().unwrap_or_default();
+            ticker.bid_exchange = exchange.clone();
+        }
+        if let Some((exchange, (price, amount))) = best(|t| &t.ask, Ordering::Less) {
+            ticker.ask_price = price.to_f64().unwrap_or_default();
+            ticker.ask_amount = amount.to_f64().unwrap_or_default();
+            ticker.ask_exchange = exchange.clone();
+        }
+        if !ticker.bid_exchange.is_empty() && !ticker.ask_exchange.is_empty() {
+            ticker.spread = ticker.ask_price - ticker.bid_price;
+        }
+        let unchanged = self.sent.get(symbol).is_some_and(|sent| {
+            BookTicker {
+                received_ts_ms,
+                ..sent.clone()
+            } == ticker
+        });
+        if unchanged {
+            return None;
+        }
+        self.sent.insert(symbol.to_string(), ticker.clone());
+        Some(ticker)
+    }
+}
+
+// feed the BestBookTicker streams from the book updates of the bus, until it closes.
+pub async fn run(mut rx: broadcast::Receiver<Event>, tickers: broadcast::Sender<BookTicker>) {
+    let mut top = TopOfBook::default();
+    loop {
+        let moved = match rx.recv().await {
+            Ok(Event::BookUpdate {
+                exchange,
+                symbol,
+                book,
+            }) => top.update(&exchange, &symbol, &book).into_iter().collect(),
+            Ok(Event::Status {
+                exchange,
+                state: ConnectionState::Disabled | ConnectionState::Down,
+                ..
+            }) => top.remove(&exchange, None),
+            Ok(Event::Removed { exchange, pair }) => top.remove(&exchange, pair.as_deref()),
+            Ok(_) => vec![],
+            Err(RecvError::Lagged(n)) => {
+                debug!("top of book skipped {} events", n);
+                vec![]
+            }
+            Err(RecvError::Closed) => return,
+        };
+        for ticker in moved {
+            // no subscribers
+            let _ = tickers.send(ticker);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn book(name: &str, bid: &str, ask: &str, amount: &str) -> Orderbook {
+        let mut ob = Orderbook::with_pair(name, "btcusd");
+        let fixed = |s: &str| Fixed::from_str(s).unwrap();
+        ob.insert(Side::Bid, fixed(bid), fixed(amount));
+        ob.insert(Side::Ask, fixed(ask), fixed(amount));
+        ob.received_ts = 1000;
+        ob
+    }
+
+    #[test]
+    fn test_top_of_book() {
+        let mut top = TopOfBook::default();
+        let ticker = top
+            .update("kraken", "BTC-USD", &book("kraken", "100", "102", "1"))
+            .unwrap();
+        assert_eq!(ticker.bid_exchange, "kraken");
+        assert_eq!(ticker.spread, 2.0);
+        assert_eq!(ticker.received_ts_ms, 1000);
+
+        // a better ask, the same bid with more
+        let ticker = top
+            .update("bitstamp", "BTC-USD", &book("bitstamp", "100", "101", "2"))
+            .unwrap();
+        assert_eq!(
+            (ticker.bid_exchange.as_str(), ticker.bid_amount),
+            ("bitstamp", 2.0)
+        );
+        assert_eq!(
+            (ticker.ask_exchange.as_str(), ticker.ask_price),
+            ("bitstamp", 101.0)
+        );
+        // behind the top, or the same book again
+        assert!(top
+            .update("kraken", "BTC-USD", &book("kraken", "99", "102", "1"))
+            .is_none());
+        assert!(top
+            .update("bitstamp", "BTC-USD", &book("bitstamp", "100", "101", "2"))
+            .is_none());
+
+        // the fee moves the prices against the taker
+        let mut binance = book("binance", "100.05", "101", "1");
+        binance.fee_bps = 10.0;
+        assert!(top.update("binance", "BTC-USD", &binance).is_none());
+
+        let tickers = top.remove("bitstamp", Some("BTCUSD"));
+        assert_eq!(tickers.len(), 1);
+        assert_eq!(tickers[0].bid_exchange, "binance");
+        assert_eq!(tickers[0].bid_price, 99.94995);
+        assert_eq!(tickers[0].ask_exchange, "binance");
+        assert!(top.remove("bitstamp", None).is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::capture::CaptureSink;
+use crate::config::TraceSetting;
+use crate::recorder::{get_unixtime, Record};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+// a feed failing on every message is dumped once per minute
+const DUMP_COOLDOWN_MS: u64 = 60_000;
+
+// The last frames of each exchange in a ring buffer, dumped to a file when its feed breaks:
+// what the exchange sent up to the frame that failed, without capturing everything.
+pub struct FrameTrace {
+    setting: TraceSetting,
+    // exchange => its last frames, the oldest first
+    frames: Mutex<HashMap<String, VecDeque<Record>>>,
+    // exchange => unix millis of its last dump
+    dumped: Mutex<HashMap<String, u64>>,
+}
+
+impl CaptureSink for FrameTrace {
+    fn capture(&self, exchange: &str, received_ms: u64, raw: &str) {
+        let mut frames = self.frames.lock().unwrap();
+        let ring = frames.entry(exchange.to_string()).or_default();
+        if ring.len() >= self.setting.frames {
+            ring.pop_front();
+        }
+        ring.push_back(Record::Raw {
+            ts: received_ms,
+            exchange: exchange.to_string(),
+            raw: raw.to_string(),
+        });
+    }
+}
+
+impl FrameTrace {
+    pub fn new(setting: TraceSetting) -> FrameTrace {
+        FrameTrace {
+            setting,
+            frames: Mutex::new(HashMap::new()),
+            dumped: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // write the frames of the exchange as raw records, readable by --replay, to
+    // <path>.<exchange>.<unix ms>.jsonl. None if it has none, or was dumped less than a
+    // minute ago. The frames are kept.
+    pub fn dump(&self, exchange: &str) -> Result<Option<String>> {
+        let frames: Vec<Record> = match self.frames.lock().unwrap().get(exchange) {
+            Some(ring) if !ring.is_empty() => ring.iter().cloned().collect(),
+            _ => return Ok(None),
+        };
+        let now = get_unixtime();
+        {
+            let mut dumped = self.dumped.lock().unwrap();
+            let last = dumped.entry(exchange.to_string()).or_default();
+            if *last > 0 && now < *last + DUMP_COOLDOWN_MS {
+                return Ok(None);
+            }
+            *last = now;
+        }
+        let path = format!("{}.{}.{}.jsonl", self.setting.path, exchange, now);
+        let file = File::create(&path).with_context(|| format!("unable to create {}", path))?;
+        let mut writer = BufWriter::new(file);
+        for record in frames.iter() {
+            serde_json::to_writer(&mut writer, record)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(Some(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::Records;
+
+    #[test]
+    fn test_dump() {
+        let dir = std::env::temp_dir().join(format!("trace_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let trace = FrameTrace::new(TraceSetting {
+            frames: 2,
+            path: dir.join("trace").to_string_lossy().to_string(),
+        });
+        assert!(trace.dump("kraken").unwrap().is_none());
+        for (i, raw) in ["a", "b", "c"].iter().enumerate() {
+            trace.capture("kraken", i as u64, raw);
+        }
+        trace.capture("bitstamp", 3, "d");
+        let path = trace.dump("kraken").unwrap().unwrap();
+        // in cooldown
+        assert!(trace.dump("kraken").unwrap().is_none());
+        let bitstamp = trace.dump("bitstamp").unwrap().unwrap();
+
+        let raws: Vec<String> = Records::open(&path)
+            .unwrap()
+            .map(|record| match record.unwrap() {
+                Record::Raw { raw, .. } => raw,
+                record => panic!("{:?}", record),
+            })
+            .collect();
+        // the oldest frame left the ring
+        assert_eq!(raws, vec!["b", "c"]);
+        assert_eq!(Records::open(&bitstamp).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use crate::proto::Summary;
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use log::{error, info};
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::select;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_util::sync::CancellationToken;
+use tonic::Status;
+
+type SummaryResult = Result<Summary, Status>;
+
+// Streams the same summaries as the grpc BookSummary call, as json text frames,
+// for the consumers that can't speak grpc.
+pub async fn run(
+    listener: TcpListener,
+    btx: broadcast::Sender<SummaryResult>,
+    closed: CancellationToken,
+) -> Result<()> {
+    info!("websocket server listening on {}", listener.local_addr()?);
+    loop {
+        let (stream, addr) = select! {
+            accepted = listener.accept() => accepted?,
+            _ = closed.cancelled() => return Ok(()),
+        };
+        let (brx, closed) = (btx.subscribe(), closed.clone());
+        tokio::spawn(async move {
+            if let Err(e) = serve(stream, addr, brx, closed).await {
+                error!("websocket {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+async fn serve(
+    stream: TcpStream,
+    addr: SocketAddr,
+    mut brx: broadcast::Receiver<SummaryResult>,
+    closed: CancellationToken,
+) -> Result<()> {
+    let ws_stream = tokio_tungstenite::accept_async(stream).await?;
+    info!("websocket {} connected", addr);
+    let (mut tx, mut rx) = ws_stream.split();
+    loop {
+        select! {
+            // drain the buffered summaries first
+            biased;
+            result = brx.recv() => match result {
+                Ok(Ok(summary)) => tx.send(Message::Text(serde_json::to_string(&summary)?)).await?,
+                Ok(Err(status)) => error!("websocket {}: skip {}", addr, status),
+                Err(RecvError::Lagged(n)) => error!("websocket {} lagged, {} summaries dropped", addr, n),
+                Err(RecvError::Closed) => break,
+            },
+            _ = closed.cancelled() => break,
+            message = rx.next() => match message {
+                // pings are answered by tungstenite while reading
+                Some(Ok(Message::Close(_))) | None => {
+                    info!("websocket {} disconnected", addr);
+                    return Ok(());
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+    }
+    tx.send(Message::Close(None)).await?;
+    info!("websocket {} closed", addr);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stream_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (btx, _brx) = broadcast::channel(20);
+        let closed = CancellationToken::new();
+        let server = tokio::spawn(run(listener, btx.clone(), closed.clone()));
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        // wait for the connection to subscribe
+        while btx.receiver_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let summary = Summary {
+            spread: 1.0,
+            pair: "btcusdt".to_string(),
+            ..Default::default()
+        };
+        btx.send(Ok(summary.clone())).unwrap();
+        let message = client.next().await.unwrap().unwrap();
+        let received: Summary = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(received, summary);
+
+        closed.cancel();
+        assert!(matches!(client.next().await, Some(Ok(Message::Close(_)))));
+        server.await.unwrap().unwrap();
+    }
+}